@@ -0,0 +1,141 @@
+//! Copies and allocations of the image pipeline for one 16-bit frame
+//!
+//! Compares the previous pipeline, which converted every frame to an `ImageArray` on readout
+//! and cloned it for `ImageReady` and `ImageArray`, with the current one, which stores the SDK
+//! frame behind an `Arc` and converts it on request, or encodes ImageBytes straight from it.
+//!
+//! Runs on a QHY600 sized frame by default, another size can be given as
+//! `cargo bench --features bench --bench image_pipeline -- <width> <height>`.
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::hint::black_box;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use ascom_alpaca::api::camera::ImageArray;
+use ndarray::Array3;
+use qhyccd_alpaca::bench;
+
+const ITERATIONS: u32 = 3;
+
+struct Counting;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed);
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        ALLOCATED.fetch_add(new_size, Ordering::Relaxed);
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+fn frame(width: u32, height: u32) -> qhyccd_rs::ImageData {
+    qhyccd_rs::ImageData {
+        data: (0..width as usize * height as usize)
+            .flat_map(|i| (i as u16).to_ne_bytes())
+            .collect(),
+        width,
+        height,
+        bits_per_pixel: 16,
+        channels: 1,
+    }
+}
+
+/// The conversion done on readout before frames were stored as delivered.
+fn previous_transform(image: qhyccd_rs::ImageData) -> ImageArray {
+    let data = image.data[0_usize..image.width as usize * image.height as usize * 2_usize]
+        .to_vec()
+        .chunks_exact(2)
+        .map(|a| u16::from_ne_bytes([a[0], a[1]]))
+        .collect();
+    let mut array =
+        Array3::from_shape_vec((image.height as usize, image.width as usize, 1_usize), data)
+            .unwrap();
+    array.swap_axes(0, 1);
+    array.into()
+}
+
+/// Runs `f` on a fresh copy of `image` and reports allocations and time per run.
+fn measure<T>(name: &str, image: &qhyccd_rs::ImageData, f: impl Fn(qhyccd_rs::ImageData) -> T) {
+    let mut allocations = 0;
+    let mut allocated = 0;
+    let mut elapsed = Duration::ZERO;
+    for _ in 0..ITERATIONS {
+        // the SDK hands over its buffer, copying it here is not part of the pipeline
+        let image = qhyccd_rs::ImageData {
+            data: image.data.clone(),
+            width: image.width,
+            height: image.height,
+            bits_per_pixel: image.bits_per_pixel,
+            channels: image.channels,
+        };
+        let before = (
+            ALLOCATIONS.load(Ordering::Relaxed),
+            ALLOCATED.load(Ordering::Relaxed),
+        );
+        let start = Instant::now();
+        black_box(f(black_box(image)));
+        elapsed += start.elapsed();
+        allocations += ALLOCATIONS.load(Ordering::Relaxed) - before.0;
+        allocated += ALLOCATED.load(Ordering::Relaxed) - before.1;
+    }
+    println!(
+        "{name:<40} {:>8} allocations {:>10.1} MB allocated {:>10.1} ms",
+        allocations / ITERATIONS as usize,
+        allocated as f64 / f64::from(ITERATIONS) / 1_048_576_f64,
+        elapsed.as_secs_f64() * 1_000_f64 / f64::from(ITERATIONS),
+    );
+}
+
+fn main() {
+    let mut size = std::env::args().skip(1).filter_map(|arg| arg.parse().ok());
+    let width = size.next().unwrap_or(9576_u32);
+    let height = size.next().unwrap_or(6388_u32);
+    let image = frame(width, height);
+    println!(
+        "{width} x {height} 16-bit frame, {:.1} MB from the SDK",
+        image.data.len() as f64 / 1_048_576_f64
+    );
+
+    measure("previous: readout", &image, previous_transform);
+    measure(
+        "previous: readout, ImageReady, ImageArray",
+        &image,
+        |image| {
+            let stored = previous_transform(image);
+            let ready = stored.clone();
+            let served = stored.clone();
+            (stored, ready, served)
+        },
+    );
+    measure("current: readout", &image, Arc::new);
+    measure(
+        "current: readout, ImageReady, ImageArray",
+        &image,
+        |image| {
+            let stored = Arc::new(image);
+            let served = bench::image_array(&stored).unwrap();
+            (stored, served)
+        },
+    );
+    measure("current: readout, ImageBytes", &image, |image| {
+        let stored = Arc::new(image);
+        let served = bench::image_bytes(&stored).unwrap();
+        (stored, served)
+    });
+}
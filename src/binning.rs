@@ -0,0 +1,196 @@
+//! Independent horizontal and vertical binning, in hardware and in software
+//!
+//! The SDK takes separate X and Y factors, but does not report whether a camera accepts
+//! different ones. Asymmetric modes such as 1x2 or 1x4 for spectroscopy are therefore only
+//! read in hardware on cameras marked in the configuration, elsewhere `BinX` and `BinY` move
+//! together:
+//!
+//! ```toml
+//! [cameras.QHY16200A-abc123]
+//! asymmetric_binning = true
+//! binning = 1
+//! binning_y = 4
+//! ```
+//!
+//! With `software_binning` every bin up to [`MAX_SOFTWARE_BIN`], asymmetric ones included, is
+//! offered on any camera. The frame is read at the largest hardware bin dividing the requested
+//! one and the remaining factor is binned by the server, summing or averaging the pixels:
+//!
+//! ```toml
+//! [cameras.QHY268M-abc123]
+//! software_binning = "sum"
+//! ```
+use eyre::Result;
+use qhyccd_rs::{CCDChipArea, ImageData};
+use serde::{Deserialize, Serialize};
+
+use crate::image;
+
+/// The largest bin offered with software binning.
+pub(crate) const MAX_SOFTWARE_BIN: u8 = 8;
+
+/// Binning factors along both axes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Binning {
+    pub(crate) x: u8,
+    pub(crate) y: u8,
+}
+
+impl Binning {
+    /// The same factor along both axes.
+    pub(crate) const fn symmetric(bin: u8) -> Self {
+        Self { x: bin, y: bin }
+    }
+
+    pub(crate) const fn is_unbinned(self) -> bool {
+        self.x == 1 && self.y == 1
+    }
+
+    /// The number of pixels combined into one.
+    pub(crate) fn pixels(self) -> u32 {
+        u32::from(self.x) * u32::from(self.y)
+    }
+
+    /// `roi` in pixels binned by `self`, converted to pixels binned by `new`.
+    ///
+    /// Each axis is scaled by its own factors, parts of a pixel are dropped.
+    pub(crate) fn rescale(self, roi: CCDChipArea, new: Self) -> CCDChipArea {
+        let scale = |value: u32, old: u8, new: u8| (value as f32 * old as f32 / new as f32) as u32;
+        CCDChipArea {
+            start_x: scale(roi.start_x, self.x, new.x),
+            start_y: scale(roi.start_y, self.y, new.y),
+            width: scale(roi.width, self.x, new.x),
+            height: scale(roi.height, self.y, new.y),
+        }
+    }
+}
+
+/// How software binning combines pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SoftwareBinning {
+    /// adds the pixels up, the frame gets a larger range and `MaxADU` grows with the bin
+    Sum,
+    /// keeps the range of the camera
+    Average,
+}
+
+/// The bins offered to clients: the hardware bins, with `software` also every bin up to
+/// [`MAX_SOFTWARE_BIN`] that is a multiple of one of them.
+pub(crate) fn offered(hardware: &[u8], software: bool) -> Vec<u8> {
+    let mut bins = hardware.to_vec();
+    if software {
+        bins.extend((1..=MAX_SOFTWARE_BIN).filter(|bin| {
+            hardware
+                .iter()
+                .any(|hardware_bin| *hardware_bin > 0 && bin % hardware_bin == 0)
+        }));
+    }
+    bins.sort_unstable();
+    bins.dedup();
+    bins
+}
+
+/// Splits `binning` into the hardware binning the frame is read with and the factors binned
+/// in software afterwards.
+///
+/// The largest hardware bin is used, along each axis if the camera takes `asymmetric` modes.
+/// `None` if the binning cannot be read, or would need software binning without `software`.
+pub(crate) fn split(
+    binning: Binning,
+    hardware: &[u8],
+    asymmetric: bool,
+    software: bool,
+) -> Option<(Binning, Binning)> {
+    let largest = |divides: &dyn Fn(u8) -> bool| {
+        hardware
+            .iter()
+            .copied()
+            .filter(|bin| *bin > 0 && divides(*bin))
+            .max()
+    };
+    let read = match asymmetric {
+        true => Binning {
+            x: largest(&|bin| binning.x % bin == 0)?,
+            y: largest(&|bin| binning.y % bin == 0)?,
+        },
+        false => Binning::symmetric(largest(&|bin| {
+            binning.x % bin == 0 && binning.y % bin == 0
+        })?),
+    };
+    let factor = Binning {
+        x: binning.x.div_euclid(read.x),
+        y: binning.y.div_euclid(read.y),
+    };
+    (software || factor.is_unbinned()).then_some((read, factor))
+}
+
+/// Bins `image` by `factor`, columns and rows that do not fill a binned pixel are dropped.
+///
+/// Sums grow the bit depth to 16 or 32 bits as needed and saturate, averages are rounded and
+/// keep the bit depth of the frame.
+pub(crate) fn apply(
+    image: &ImageData,
+    factor: Binning,
+    mode: SoftwareBinning,
+) -> Result<ImageData> {
+    let bytes_per_sample = image::bytes_per_sample(image)?;
+    let channels = image.channels as usize;
+    let (factor_x, factor_y) = (usize::from(factor.x.max(1)), usize::from(factor.y.max(1)));
+    let pixels = u64::from(factor.pixels().max(1));
+    let width = (image.width as usize).div_euclid(factor_x);
+    let height = (image.height as usize).div_euclid(factor_y);
+    let bits_per_pixel = match mode {
+        SoftwareBinning::Average => image.bits_per_pixel,
+        SoftwareBinning::Sum => {
+            let max = ((1_u64 << image.bits_per_pixel) - 1) * pixels;
+            if max <= u64::from(u16::MAX) && image.bits_per_pixel <= 16 {
+                16
+            } else {
+                32
+            }
+        }
+    };
+    // 32 bit frames are served and written as signed integers
+    let max = match bits_per_pixel {
+        32 => i32::MAX as u64,
+        bits => (1_u64 << bits) - 1,
+    };
+    let sample = |index: usize| -> u64 {
+        let bytes = &image.data[index * bytes_per_sample..(index + 1) * bytes_per_sample];
+        match bytes_per_sample {
+            1_usize => u64::from(bytes[0]),
+            2_usize => u64::from(u16::from_ne_bytes([bytes[0], bytes[1]])),
+            _ => u64::from(u32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])),
+        }
+    };
+    let mut data = Vec::with_capacity(width * height * channels * (bits_per_pixel as usize >> 3));
+    for y in 0..height {
+        for x in 0..width {
+            for channel in 0..channels {
+                let mut sum = 0_u64;
+                for row in y * factor_y..(y + 1) * factor_y {
+                    for column in x * factor_x..(x + 1) * factor_x {
+                        sum += sample((row * image.width as usize + column) * channels + channel);
+                    }
+                }
+                let value = match mode {
+                    SoftwareBinning::Sum => sum.min(max),
+                    SoftwareBinning::Average => (sum + (pixels >> 1)).div_euclid(pixels),
+                };
+                match bits_per_pixel {
+                    8 => data.push(value as u8),
+                    16 => data.extend_from_slice(&(value as u16).to_ne_bytes()),
+                    _ => data.extend_from_slice(&(value as u32).to_ne_bytes()),
+                }
+            }
+        }
+    }
+    Ok(ImageData {
+        data,
+        width: width as u32,
+        height: height as u32,
+        bits_per_pixel,
+        channels: image.channels,
+    })
+}
@@ -0,0 +1,250 @@
+//! Persistent per-device configuration
+//!
+//! Settings are stored in a TOML file, keyed by the device `unique_id`:
+//!
+//! ```toml
+//! [cameras.QHY600M-abc123]
+//! readout_mode = 1
+//! binning = 2
+//! gain = 26
+//! offset = 30
+//! usb_traffic = 20
+//! ddr_buffer = 1
+//! readout_speed = 2
+//! usb_traffic_backoff = 10
+//! can_stop_exposure = false
+//! target_temperature = -10.0
+//! cool_down_rate = 2.0
+//! warm_up_rate = 1.0
+//! fits_export = true
+//! debayer = "gradient"
+//! include_overscan = true
+//! asymmetric_binning = true
+//! binning_y = 4
+//! software_binning = "average"
+//! transfer_bits = 8
+//! adu_scaling = "native"
+//!
+//! [filter_wheels."CFW=QHY600M-abc123"]
+//! names = ["L", "R", "G", "B", "Ha"]
+//! focus_offsets = [0, 12, 10, 15, -40]
+//! ```
+//!
+//! Sensor characteristics and gain/offset presets can be overridden per model, see
+//! [`crate::sensor`] and [`crate::presets`].
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use eyre::{Result, WrapErr};
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+use tracing::debug;
+
+use crate::DebayerMethod;
+use crate::binning::SoftwareBinning;
+use crate::image::AduScaling;
+use crate::presets::Presets;
+use crate::sensor::SensorCurve;
+
+/// Contents of the configuration file.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub cameras: BTreeMap<String, CameraConfig>,
+    pub filter_wheels: BTreeMap<String, FilterWheelConfig>,
+    /// measured sensor curves keyed by model prefix
+    pub sensors: BTreeMap<String, Vec<SensorCurve>>,
+    /// gain and offset presets keyed by model prefix
+    pub presets: BTreeMap<String, Presets>,
+}
+
+/// Settings applied to a camera when it connects.
+///
+/// Missing values leave the camera default untouched.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CameraConfig {
+    pub readout_mode: Option<u32>,
+    pub binning: Option<u8>,
+    /// vertical binning if it differs from `binning`, needs `asymmetric_binning`
+    pub binning_y: Option<u8>,
+    pub gain: Option<i32>,
+    pub offset: Option<i32>,
+    pub usb_traffic: Option<f64>,
+    /// DDR buffer and readout speed, validated against the range of the control
+    pub ddr_buffer: Option<f64>,
+    pub readout_speed: Option<f64>,
+    /// raise the USB traffic by this amount after a failed frame
+    pub usb_traffic_backoff: Option<f64>,
+    /// offer `StopExposure` on models without a built-in entry, until the SDK refuses a stop
+    pub can_stop_exposure: Option<bool>,
+    pub target_temperature: Option<f64>,
+    /// cooler ramps in °C per minute, override the command line
+    pub cool_down_rate: Option<f64>,
+    pub warm_up_rate: Option<f64>,
+    pub fits_export: Option<bool>,
+    /// use the named gain and offset presets instead of plain values
+    pub presets: Option<bool>,
+    /// debayer colour frames served through `ImageArray`
+    pub debayer: Option<DebayerMethod>,
+    /// read out the whole chip including the overscan
+    pub include_overscan: Option<bool>,
+    /// the camera accepts different horizontal and vertical binning
+    pub asymmetric_binning: Option<bool>,
+    /// offer bins the camera lacks, combining the pixels in software
+    pub software_binning: Option<SoftwareBinning>,
+    /// transfer frames with 8 instead of 16 bits per pixel
+    pub transfer_bits: Option<u8>,
+    /// publish 12 and 14 bit samples shifted down or padded to 16 bits
+    pub adu_scaling: Option<AduScaling>,
+}
+
+/// Filter names and focus offsets of a filter wheel, one entry per slot.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FilterWheelConfig {
+    pub names: Option<Vec<String>>,
+    pub focus_offsets: Option<Vec<i32>>,
+}
+
+/// The configuration shared by all devices, together with the file it came from.
+#[derive(Debug, Default)]
+pub(crate) struct ConfigStore {
+    path: Option<PathBuf>,
+    config: RwLock<Config>,
+}
+
+impl ConfigStore {
+    /// Loads the configuration from `path`, a missing file is treated as empty.
+    pub(crate) fn load(path: PathBuf) -> Result<Self> {
+        let config = match std::fs::read_to_string(&path) {
+            Ok(contents) => toml::from_str(&contents)
+                .wrap_err_with(|| format!("invalid configuration file {}", path.display()))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                debug!(?path, "configuration file not found, starting empty");
+                Config::default()
+            }
+            Err(e) => {
+                return Err(e).wrap_err_with(|| format!("could not read {}", path.display()));
+            }
+        };
+        Ok(Self {
+            path: Some(path),
+            config: RwLock::new(config),
+        })
+    }
+
+    #[cfg(test)]
+    pub(crate) fn new(path: Option<PathBuf>, config: Config) -> Self {
+        Self {
+            path,
+            config: RwLock::new(config),
+        }
+    }
+
+    pub(crate) async fn camera(&self, unique_id: &str) -> CameraConfig {
+        self.config
+            .read()
+            .await
+            .cameras
+            .get(unique_id)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Stores the settings of one camera, the file is only written if there is one.
+    pub(crate) async fn save_camera(&self, unique_id: &str, camera: CameraConfig) -> Result<()> {
+        self.modify(unique_id, |config| {
+            config.cameras.insert(unique_id.to_owned(), camera);
+        })
+        .await
+    }
+
+    /// Updates the settings of one camera, the file is only written if there is one.
+    pub(crate) async fn update_camera(
+        &self,
+        unique_id: &str,
+        update: impl FnOnce(&mut CameraConfig),
+    ) -> Result<()> {
+        self.modify(unique_id, |config| {
+            update(config.cameras.entry(unique_id.to_owned()).or_default());
+        })
+        .await
+    }
+
+    pub(crate) async fn sensors(&self) -> BTreeMap<String, Vec<SensorCurve>> {
+        self.config.read().await.sensors.clone()
+    }
+
+    pub(crate) async fn presets(&self) -> BTreeMap<String, Presets> {
+        self.config.read().await.presets.clone()
+    }
+
+    pub(crate) async fn filter_wheel(&self, unique_id: &str) -> FilterWheelConfig {
+        self.config
+            .read()
+            .await
+            .filter_wheels
+            .get(unique_id)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Updates the settings of one filter wheel, the file is only written if there is one.
+    pub(crate) async fn set_filter_wheel(
+        &self,
+        unique_id: &str,
+        filter_wheel: FilterWheelConfig,
+    ) -> Result<()> {
+        self.modify(unique_id, |config| {
+            config
+                .filter_wheels
+                .insert(unique_id.to_owned(), filter_wheel);
+        })
+        .await
+    }
+
+    /// Applies `change` to a copy of the configuration and keeps it only once it is saved, so
+    /// a failed write leaves the settings in memory as they were.
+    async fn modify(&self, unique_id: &str, change: impl FnOnce(&mut Config)) -> Result<()> {
+        let mut config = self.config.write().await;
+        let mut changed = config.clone();
+        change(&mut changed);
+        self.save(&changed, unique_id).await?;
+        *config = changed;
+        Ok(())
+    }
+
+    /// Writes the whole file off the async runtime, the caller keeps the lock so that writes
+    /// cannot overtake each other.
+    async fn save(&self, config: &Config, unique_id: &str) -> Result<()> {
+        let Some(path) = self.path.clone() else {
+            debug!(
+                unique_id,
+                "no configuration file given, keeping settings in memory"
+            );
+            return Ok(());
+        };
+        let contents = toml::to_string_pretty(config)?;
+        tokio::task::spawn_blocking(move || {
+            std::fs::write(&path, contents)
+                .wrap_err_with(|| format!("could not write {}", path.display()))?;
+            debug!(?path, "configuration saved");
+            Ok(())
+        })
+        .await
+        .wrap_err("writing the configuration file failed")?
+    }
+}
+
+/// The entry whose key is the longest prefix of `model`.
+pub(crate) fn by_model_prefix<'a, T>(
+    model: &str,
+    entries: &'a BTreeMap<String, T>,
+) -> Option<&'a T> {
+    entries
+        .iter()
+        .filter(|(prefix, _)| model.starts_with(prefix.as_str()))
+        .max_by_key(|(prefix, _)| prefix.len())
+        .map(|(_, entry)| entry)
+}
@@ -0,0 +1,259 @@
+//! Cooler regulation with cool-down and warm-up ramps
+//!
+//! The SDK regulates the sensor to whatever set-point it is given, as fast as the TEC allows.
+//! To spare the sensor thermal stress the driver instead moves that set-point towards the
+//! target in small steps from a background task, and warms the sensor up the same way before
+//! the cooler is switched off, so no condensation forms on a cold sensor window.
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::sync::RwLock;
+use tokio::task::JoinHandle;
+use tracing::{debug, error, info, trace};
+
+use crate::QhyCamera;
+
+/// How often the background task moves the set-point.
+pub(crate) const TICK: Duration = Duration::from_secs(5);
+
+/// Where a warm-up ends if the sensor temperature before cooling is unknown.
+const DEFAULT_AMBIENT: f64 = 20_f64;
+
+/// The warm-up ramp in °C per minute the server uses unless told otherwise, slow enough to keep
+/// the sensor free of condensation.
+pub const DEFAULT_WARM_UP_RATE: f64 = 1_f64;
+
+/// Set-point ramps in °C per minute, `None` moves the set-point in one go.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct CoolerRamp {
+    /// used while the set-point is lowered
+    pub cool_down: Option<f64>,
+    /// used while the set-point is raised, including the warm-up before switching off
+    pub warm_up: Option<f64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum CoolerState {
+    Off,
+    /// moving the set-point towards the target
+    Ramping {
+        set_point: f64,
+    },
+    /// holding the target
+    Regulating,
+    /// moving the set-point towards ambient, the cooler is switched off once it gets there
+    WarmingUp {
+        set_point: f64,
+    },
+}
+
+/// What the camera has to be told after the controller changed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum CoolerCommand {
+    None,
+    SetPoint(f64),
+    SwitchOff,
+}
+
+/// The cooler controller of one camera, shared with its background task.
+#[derive(Debug)]
+pub(crate) struct Cooler {
+    pub(crate) state: CoolerState,
+    pub(crate) target: Option<f64>,
+    /// sensor temperature when the cooler was switched on
+    pub(crate) ambient: f64,
+    pub(crate) ramp: CoolerRamp,
+    /// close the camera once the warm-up is done, set by disconnecting
+    pub(crate) close_when_off: bool,
+    pub(crate) task: Option<JoinHandle<()>>,
+}
+
+impl Cooler {
+    pub(crate) fn new(ramp: CoolerRamp) -> Self {
+        Self {
+            state: CoolerState::Off,
+            target: None,
+            ambient: DEFAULT_AMBIENT,
+            ramp,
+            close_when_off: false,
+            task: None,
+        }
+    }
+
+    /// Whether the cooler is on as far as the client is concerned, a warm-up counts as off.
+    pub(crate) fn is_on(&self) -> bool {
+        matches!(
+            self.state,
+            CoolerState::Ramping { .. } | CoolerState::Regulating
+        )
+    }
+
+    /// Starts regulating to the target, or to `temperature` if there is none yet.
+    ///
+    /// `temperature` is the current sensor temperature, a ramp starts there unless a warm-up
+    /// is interrupted.
+    pub(crate) fn switch_on(&mut self, temperature: f64) -> CoolerCommand {
+        let set_point = match self.state {
+            CoolerState::Ramping { .. } | CoolerState::Regulating => return CoolerCommand::None,
+            CoolerState::WarmingUp { set_point } => set_point,
+            CoolerState::Off => {
+                self.ambient = temperature;
+                temperature
+            }
+        };
+        let target = *self.target.get_or_insert(temperature);
+        self.ramp_from(set_point, target)
+    }
+
+    /// Switches off, with a warm-up if a warm-up ramp is configured.
+    pub(crate) fn switch_off(&mut self) -> CoolerCommand {
+        let set_point = match self.state {
+            CoolerState::Off | CoolerState::WarmingUp { .. } => return CoolerCommand::None,
+            CoolerState::Ramping { set_point } => set_point,
+            CoolerState::Regulating => self.target.unwrap_or(self.ambient),
+        };
+        match self.ramp.warm_up {
+            Some(_) if set_point < self.ambient => {
+                self.state = CoolerState::WarmingUp { set_point };
+                CoolerCommand::None
+            }
+            _ => {
+                self.state = CoolerState::Off;
+                CoolerCommand::SwitchOff
+            }
+        }
+    }
+
+    /// Changes the target, a cooler that is on ramps from where it is to the new target.
+    pub(crate) fn set_target(&mut self, target: f64) -> CoolerCommand {
+        let previous = self.target.replace(target);
+        match self.state {
+            CoolerState::Regulating => self.ramp_from(previous.unwrap_or(target), target),
+            // the background task picks up the new target
+            CoolerState::Ramping { .. } | CoolerState::Off | CoolerState::WarmingUp { .. } => {
+                CoolerCommand::None
+            }
+        }
+    }
+
+    /// Advances the ramps by `elapsed`.
+    pub(crate) fn step(&mut self, elapsed: Duration) -> CoolerCommand {
+        match self.state {
+            CoolerState::Ramping { set_point } => {
+                let target = self.target.unwrap_or(set_point);
+                let set_point = self.approach(set_point, target, elapsed);
+                self.state = match set_point == target {
+                    true => CoolerState::Regulating,
+                    false => CoolerState::Ramping { set_point },
+                };
+                CoolerCommand::SetPoint(set_point)
+            }
+            CoolerState::WarmingUp { set_point } => {
+                let set_point = self.approach(set_point, self.ambient, elapsed);
+                match set_point >= self.ambient {
+                    true => {
+                        self.state = CoolerState::Off;
+                        CoolerCommand::SwitchOff
+                    }
+                    false => {
+                        self.state = CoolerState::WarmingUp { set_point };
+                        CoolerCommand::SetPoint(set_point)
+                    }
+                }
+            }
+            CoolerState::Off | CoolerState::Regulating => CoolerCommand::None,
+        }
+    }
+
+    fn ramp_from(&mut self, set_point: f64, target: f64) -> CoolerCommand {
+        match self.rate(set_point, target) {
+            Some(_) if set_point != target => {
+                self.state = CoolerState::Ramping { set_point };
+                CoolerCommand::SetPoint(set_point)
+            }
+            _ => {
+                self.state = CoolerState::Regulating;
+                CoolerCommand::SetPoint(target)
+            }
+        }
+    }
+
+    fn rate(&self, from: f64, to: f64) -> Option<f64> {
+        match to < from {
+            true => self.ramp.cool_down,
+            false => self.ramp.warm_up,
+        }
+    }
+
+    /// Moves `from` towards `to` by at most what the ramp allows in `elapsed`.
+    fn approach(&self, from: f64, to: f64, elapsed: Duration) -> f64 {
+        let Some(rate) = self.rate(from, to) else {
+            return to;
+        };
+        let max_step = rate.abs() * elapsed.as_secs_f64() / 60_f64;
+        match (to - from).abs() <= max_step {
+            true => to,
+            false => from + max_step.copysign(to - from),
+        }
+    }
+}
+
+/// Sends a command of the controller to the camera.
+pub(crate) fn apply(device: &QhyCamera, command: CoolerCommand) -> eyre::Result<()> {
+    match command {
+        CoolerCommand::None => Ok(()),
+        CoolerCommand::SetPoint(set_point) => {
+            trace!(set_point, "cooler set-point");
+            device.set_parameter(qhyccd_rs::Control::Cooler, set_point)
+        }
+        CoolerCommand::SwitchOff => {
+            debug!("switching cooler off");
+            device.set_parameter(qhyccd_rs::Control::ManualPWM, 0_f64)
+        }
+    }
+}
+
+/// Steps the controller every [`TICK`] until the cooler is off.
+///
+/// Closes the camera afterwards if it was disconnected during the warm-up.
+pub(crate) async fn run(device: QhyCamera, cooler: Arc<RwLock<Cooler>>) {
+    loop {
+        tokio::time::sleep(TICK).await;
+        let mut cooler = cooler.write().await;
+        let command = cooler.step(TICK);
+        if let Err(e) = apply(&device, command) {
+            error!(?e, ?command, "could not update cooler");
+        }
+        if cooler.state == CoolerState::Off {
+            if cooler.close_when_off {
+                cooler.close_when_off = false;
+                info!("sensor warmed up, closing camera");
+                if let Err(e) = device.close() {
+                    error!(?e, "close_camera failed");
+                }
+            }
+            return;
+        }
+    }
+}
+
+/// Warms the sensor up and waits until the cooler is off, used when the server shuts down.
+pub(crate) async fn warm_up(device: &QhyCamera, cooler: &RwLock<Cooler>) {
+    let task = {
+        let mut cooler = cooler.write().await;
+        let command = cooler.switch_off();
+        if let Err(e) = apply(device, command) {
+            error!(?e, ?command, "could not switch cooler off");
+        }
+        match cooler.state {
+            CoolerState::Off => None,
+            _ => cooler.task.take(),
+        }
+    };
+    if let Some(task) = task {
+        info!("waiting for the sensor to warm up");
+        if let Err(e) = task.await {
+            error!(?e, "cooler task failed");
+        }
+    }
+}
@@ -0,0 +1,267 @@
+//! Software debayering for one-shot-colour cameras
+//!
+//! Turns a raw colour filter array (CFA) frame into a 3-channel frame, interleaved BGR like
+//! the frames the SDK debayers itself, so it is served the same way. The Bayer pattern must be
+//! the one seen from the frame origin, see `bayer_pattern_at`.
+use eyre::{Result, eyre};
+use serde::{Deserialize, Serialize};
+use tracing::error;
+
+/// How the missing colours of each pixel are interpolated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DebayerMethod {
+    /// Average of the nearest pixels of each colour.
+    Bilinear,
+    /// Interpolates along the smoothest directions only, a simplified take on variable number
+    /// of gradients (VNG) that compares one gradient per direction.
+    Gradient,
+    /// Every 2x2 cell becomes one pixel, halving width and height.
+    Superpixel,
+}
+
+impl DebayerMethod {
+    /// The name used in the configuration file and the `Debayer` action.
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            DebayerMethod::Bilinear => "bilinear",
+            DebayerMethod::Gradient => "gradient",
+            DebayerMethod::Superpixel => "superpixel",
+        }
+    }
+
+    pub(crate) fn from_name(name: &str) -> Option<Self> {
+        [
+            DebayerMethod::Bilinear,
+            DebayerMethod::Gradient,
+            DebayerMethod::Superpixel,
+        ]
+        .into_iter()
+        .find(|method| method.name().eq_ignore_ascii_case(name))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Color {
+    Red = 0,
+    Green = 1,
+    Blue = 2,
+}
+
+/// The eight directions the gradient method looks in.
+const DIRECTIONS: [(isize, isize); 8] = [
+    (0, -1),
+    (0, 1),
+    (1, 0),
+    (-1, 0),
+    (1, -1),
+    (-1, -1),
+    (1, 1),
+    (-1, 1),
+];
+
+/// A single channel frame with its Bayer pattern.
+struct Cfa {
+    samples: Vec<u32>,
+    width: usize,
+    height: usize,
+    /// colours of the 2x2 cell at the origin, row by row
+    cell: [Color; 4],
+}
+
+impl Cfa {
+    fn color(&self, x: usize, y: usize) -> Color {
+        self.cell[(y % 2) * 2 + x % 2]
+    }
+
+    /// The sample at `x`, `y`, mirrored back by whole cells outside the frame, so the colour
+    /// stays the same.
+    fn at(&self, x: isize, y: isize) -> u32 {
+        self.samples[reflect(y, self.height) * self.width + reflect(x, self.width)]
+    }
+
+    /// Average of the samples of `color` in the 3x3 window around `x`, `y`.
+    fn window_average(&self, x: isize, y: isize, color: Color) -> f64 {
+        let mut sum = 0_f64;
+        let mut count = 0_u32;
+        for dy in -1..=1 {
+            for dx in -1..=1 {
+                let (nx, ny) = (x + dx, y + dy);
+                let cx = reflect(nx, self.width);
+                let cy = reflect(ny, self.height);
+                if self.color(cx, cy) == color {
+                    sum += f64::from(self.at(nx, ny));
+                    count += 1;
+                }
+            }
+        }
+        sum / f64::from(count.max(1))
+    }
+
+    fn bilinear(&self, x: usize, y: usize) -> [f64; 3] {
+        let own = self.color(x, y);
+        let mut rgb = [0_f64; 3];
+        for color in [Color::Red, Color::Green, Color::Blue] {
+            rgb[color as usize] = match color == own {
+                true => f64::from(self.samples[y * self.width + x]),
+                false => self.window_average(x as isize, y as isize, color),
+            };
+        }
+        rgb
+    }
+
+    fn gradient(&self, x: usize, y: usize) -> [f64; 3] {
+        let (xi, yi) = (x as isize, y as isize);
+        let own = self.color(x, y);
+        let value = f64::from(self.samples[y * self.width + x]);
+        // both terms compare samples of the same colour
+        let gradients = DIRECTIONS.map(|(dx, dy)| {
+            f64::from(
+                self.at(xi + dx, yi + dy)
+                    .abs_diff(self.at(xi - dx, yi - dy)),
+            ) + f64::from(self.at(xi + 2 * dx, yi + 2 * dy).abs_diff(self.at(xi, yi)))
+        });
+        let min = gradients.iter().copied().fold(f64::INFINITY, f64::min);
+        let max = gradients.iter().copied().fold(0_f64, f64::max);
+        let threshold = 1.5_f64 * min + 0.5_f64 * (max - min);
+
+        let mut sums = [0_f64; 3];
+        let mut selected = 0_u32;
+        for (&(dx, dy), &gradient) in DIRECTIONS.iter().zip(gradients.iter()) {
+            if gradient > threshold {
+                continue;
+            }
+            selected += 1;
+            for color in [Color::Red, Color::Green, Color::Blue] {
+                sums[color as usize] += self.window_average(xi + dx, yi + dy, color);
+            }
+        }
+        if selected == 0 {
+            return self.bilinear(x, y);
+        }
+        let mut rgb = [0_f64; 3];
+        for color in [Color::Red, Color::Green, Color::Blue] {
+            rgb[color as usize] = match color == own {
+                true => value,
+                false => value + (sums[color as usize] - sums[own as usize]) / f64::from(selected),
+            };
+        }
+        rgb
+    }
+
+    /// One pixel per 2x2 cell starting at `x`, `y`, the two greens averaged.
+    fn superpixel(&self, x: usize, y: usize) -> [f64; 3] {
+        let mut rgb = [0_f64; 3];
+        for (dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+            let color = self.color(x + dx, y + dy);
+            let weight = match color {
+                Color::Green => 0.5_f64,
+                Color::Red | Color::Blue => 1_f64,
+            };
+            rgb[color as usize] += weight * f64::from(self.samples[(y + dy) * self.width + x + dx]);
+        }
+        rgb
+    }
+}
+
+/// `i` mirrored back into `0..n` in steps of 2, `n` must be at least 2.
+fn reflect(i: isize, n: usize) -> usize {
+    let n = n as isize;
+    let mut i = i;
+    while i < 0 {
+        i += 2;
+    }
+    while i >= n {
+        i -= 2;
+    }
+    i as usize
+}
+
+fn cell(pattern: qhyccd_rs::BayerMode) -> [Color; 4] {
+    use Color::{Blue as B, Green as G, Red as R};
+    match pattern {
+        qhyccd_rs::BayerMode::RGGB => [R, G, G, B],
+        qhyccd_rs::BayerMode::GRBG => [G, R, B, G],
+        qhyccd_rs::BayerMode::GBRG => [G, B, R, G],
+        qhyccd_rs::BayerMode::BGGR => [B, G, G, R],
+    }
+}
+
+/// Debayers a raw 8- or 16-bit frame whose origin has `pattern`.
+pub(crate) fn debayer(
+    image: &qhyccd_rs::ImageData,
+    pattern: qhyccd_rs::BayerMode,
+    method: DebayerMethod,
+) -> Result<qhyccd_rs::ImageData> {
+    let bytes_per_sample = crate::image::bytes_per_sample(image)?;
+    if image.channels != 1 || bytes_per_sample > 2 {
+        error!(
+            channels = image.channels,
+            bits_per_pixel = image.bits_per_pixel,
+            "cannot debayer frame"
+        );
+        return Err(eyre!(
+            "cannot debayer a frame with {} channels and {} bits per pixel",
+            image.channels,
+            image.bits_per_pixel
+        ));
+    }
+    let width = image.width as usize;
+    let height = image.height as usize;
+    if width < 2 || height < 2 {
+        error!(width, height, "frame too small to debayer");
+        return Err(eyre!(
+            "frame of {}x{} is too small to debayer",
+            width,
+            height
+        ));
+    }
+    let samples = image.data[..width * height * bytes_per_sample]
+        .chunks_exact(bytes_per_sample)
+        .map(|a| match bytes_per_sample {
+            1_usize => u32::from(a[0]),
+            _ => u32::from(u16::from_ne_bytes([a[0], a[1]])),
+        })
+        .collect();
+    let cfa = Cfa {
+        samples,
+        width,
+        height,
+        cell: cell(pattern),
+    };
+
+    let (out_width, out_height, step) = match method {
+        // complete 2x2 cells only
+        DebayerMethod::Superpixel => (width >> 1, height >> 1, 2_usize),
+        DebayerMethod::Bilinear | DebayerMethod::Gradient => (width, height, 1_usize),
+    };
+    let max = match bytes_per_sample {
+        1_usize => f64::from(u8::MAX),
+        _ => f64::from(u16::MAX),
+    };
+    let mut data = Vec::with_capacity(out_width * out_height * 3 * bytes_per_sample);
+    for y in (0..out_height).map(|y| y * step) {
+        for x in (0..out_width).map(|x| x * step) {
+            let rgb = match method {
+                DebayerMethod::Bilinear => cfa.bilinear(x, y),
+                DebayerMethod::Gradient => cfa.gradient(x, y),
+                DebayerMethod::Superpixel => cfa.superpixel(x, y),
+            };
+            // BGR like the frames debayered by the SDK
+            for value in rgb.iter().rev() {
+                let value = value.round().clamp(0_f64, max);
+                match bytes_per_sample {
+                    1_usize => data.push(value as u8),
+                    _ => data.extend_from_slice(&(value as u16).to_ne_bytes()),
+                }
+            }
+        }
+    }
+    Ok(qhyccd_rs::ImageData {
+        data,
+        width: out_width as u32,
+        height: out_height as u32,
+        bits_per_pixel: image.bits_per_pixel,
+        channels: 3,
+    })
+}
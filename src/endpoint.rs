@@ -0,0 +1,239 @@
+//! HTTP endpoint for frames, next to the Alpaca server
+//!
+//! - `GET /live/<camera id>` streams each new live frame as one part of a
+//!   `multipart/x-mixed-replace` response
+//! - `GET /live/<camera id>/latest` returns the newest live frame
+//! - `GET /image/<camera id>` returns the last exposure in the Alpaca ImageBytes format, encoded
+//!   straight from the frame the SDK delivered; `ClientTransactionID` is echoed from the query
+//! - `GET /sequence/<camera id>` returns the progress of the current or last sequence as JSON
+//!
+//! Live frames are sent as the SDK delivers them, native endian, described by the `X-Width`,
+//! `X-Height`, `X-Bits-Per-Pixel`, `X-Channels` and `X-Sequence` headers, `X-Timestamp` is the
+//! time the frame was read in seconds since the Unix epoch.
+//!
+//! Request lines and headers longer than [`MAX_LINE_LENGTH`], or more than [`MAX_HEADERS`]
+//! headers, are refused with `414` or `431`.
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use eyre::Result;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::RwLock;
+use tokio::task;
+use tracing::{debug, error, trace};
+
+use crate::LastImage;
+use crate::image;
+use crate::live::LiveFeed;
+use crate::sequence::Sequencer;
+
+const BOUNDARY: &str = "frame";
+
+/// Longest request line or header the endpoint reads, in bytes.
+const MAX_LINE_LENGTH: u64 = 8 * 1024;
+
+/// Most headers the endpoint reads.
+const MAX_HEADERS: usize = 100;
+
+/// Numbers the ImageBytes responses.
+static SERVER_TRANSACTION_ID: AtomicU32 = AtomicU32::new(0);
+
+/// What the endpoint serves for one camera.
+#[derive(Debug, Clone)]
+pub(crate) struct CameraFrames {
+    pub(crate) live_feed: Arc<LiveFeed>,
+    pub(crate) last_image: Arc<RwLock<Option<LastImage>>>,
+    pub(crate) sequencer: Arc<Sequencer>,
+}
+
+/// The cameras the endpoint serves, keyed by camera id, cameras plugged in later are added.
+pub(crate) type Cameras = Arc<RwLock<HashMap<String, CameraFrames>>>;
+
+/// Serves the frames of the cameras, see the module documentation.
+pub(crate) async fn serve(listener: TcpListener, cameras: Cameras) -> Result<Infallible> {
+    loop {
+        let (stream, addr) = listener.accept().await?;
+        let cameras = cameras.clone();
+        tokio::spawn(async move {
+            if let Err(e) = handle(stream, &cameras).await {
+                debug!(?e, %addr, "endpoint connection closed");
+            }
+        });
+    }
+}
+
+async fn handle(stream: TcpStream, cameras: &RwLock<HashMap<String, CameraFrames>>) -> Result<()> {
+    let mut reader = BufReader::new(stream);
+    let Some(request_line) = read_line(&mut reader).await? else {
+        return respond(reader.get_mut(), "414 URI Too Long").await;
+    };
+    // the headers are not needed
+    let mut headers = 0_usize;
+    loop {
+        let Some(line) = read_line(&mut reader).await? else {
+            return respond(reader.get_mut(), "431 Request Header Fields Too Large").await;
+        };
+        if line.trim().is_empty() {
+            break;
+        }
+        headers += 1;
+        if headers > MAX_HEADERS {
+            return respond(reader.get_mut(), "431 Request Header Fields Too Large").await;
+        }
+    }
+    let mut stream = reader.into_inner();
+    trace!(request = request_line.trim(), "endpoint request");
+    let mut parts = request_line.split_whitespace();
+    if parts.next() != Some("GET") {
+        return respond(&mut stream, "405 Method Not Allowed").await;
+    }
+    let target = parts.next().unwrap_or("/");
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    if let Some(id) = path.strip_prefix("/image/") {
+        let Some(camera) = cameras.read().await.get(id).cloned() else {
+            return respond(&mut stream, "404 Not Found").await;
+        };
+        return last_image(&mut stream, &camera, query).await;
+    }
+    if let Some(id) = path.strip_prefix("/sequence/") {
+        let Some(camera) = cameras.read().await.get(id).cloned() else {
+            return respond(&mut stream, "404 Not Found").await;
+        };
+        let body = serde_json::to_vec(&camera.sequencer.progress().await)?;
+        stream
+            .write_all(
+                format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nCache-Control: no-cache\r\nConnection: close\r\n\r\n",
+                    body.len()
+                )
+                .as_bytes(),
+            )
+            .await?;
+        stream.write_all(&body).await?;
+        return Ok(stream.flush().await?);
+    }
+    let Some(path) = path.strip_prefix("/live/") else {
+        return respond(&mut stream, "404 Not Found").await;
+    };
+    let (id, latest_only) = match path.strip_suffix("/latest") {
+        Some(id) => (id, true),
+        None => (path, false),
+    };
+    let Some(camera) = cameras.read().await.get(id).cloned() else {
+        return respond(&mut stream, "404 Not Found").await;
+    };
+    let feed = &camera.live_feed;
+
+    if latest_only {
+        let Some(frame) = feed.latest().await else {
+            return respond(&mut stream, "204 No Content").await;
+        };
+        stream
+            .write_all(
+                format!(
+                    "HTTP/1.1 200 OK\r\n{}Cache-Control: no-cache\r\nConnection: close\r\n\r\n",
+                    frame.part_headers()
+                )
+                .as_bytes(),
+            )
+            .await?;
+        stream.write_all(&frame.image.data).await?;
+        return Ok(stream.flush().await?);
+    }
+
+    // subscribed before the client sees the response, so it gets every frame from then on
+    let mut sequence = feed.subscribe();
+    stream
+        .write_all(
+            format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: multipart/x-mixed-replace; boundary={BOUNDARY}\r\nCache-Control: no-cache\r\nConnection: close\r\n\r\n"
+            )
+            .as_bytes(),
+        )
+        .await?;
+    loop {
+        sequence.changed().await?;
+        let Some(frame) = feed.latest().await else {
+            continue;
+        };
+        stream
+            .write_all(format!("--{BOUNDARY}\r\n{}\r\n", frame.part_headers()).as_bytes())
+            .await?;
+        stream.write_all(&frame.image.data).await?;
+        stream.write_all(b"\r\n").await?;
+        stream.flush().await?;
+    }
+}
+
+/// Sends the last exposure as ImageBytes.
+async fn last_image(stream: &mut TcpStream, camera: &CameraFrames, query: &str) -> Result<()> {
+    // shared with the camera, the lock is released before the frame is encoded
+    let Some(image) = camera
+        .last_image
+        .read()
+        .await
+        .as_ref()
+        .map(|last_image| last_image.image.clone())
+    else {
+        return respond(stream, "204 No Content").await;
+    };
+    let client_transaction_id = query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(key, _)| key.eq_ignore_ascii_case("ClientTransactionID"))
+        .and_then(|(_, value)| value.parse().ok())
+        .unwrap_or(0_u32);
+    let server_transaction_id = SERVER_TRANSACTION_ID.fetch_add(1, Ordering::Relaxed) + 1;
+    let bytes = match task::spawn_blocking(move || {
+        image::image_bytes(&image, client_transaction_id, server_transaction_id)
+    })
+    .await
+    {
+        Ok(Ok(bytes)) => bytes,
+        Ok(Err(e)) => {
+            error!(?e, "failed to encode image");
+            return respond(stream, "500 Internal Server Error").await;
+        }
+        Err(e) => {
+            error!(?e, "image task failed");
+            return respond(stream, "500 Internal Server Error").await;
+        }
+    };
+    stream
+        .write_all(
+            format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/imagebytes\r\nContent-Length: {}\r\nCache-Control: no-cache\r\nConnection: close\r\n\r\n",
+                bytes.len()
+            )
+            .as_bytes(),
+        )
+        .await?;
+    stream.write_all(&bytes).await?;
+    Ok(stream.flush().await?)
+}
+
+/// Reads one line, `None` if it is longer than [`MAX_LINE_LENGTH`].
+async fn read_line(reader: &mut BufReader<TcpStream>) -> Result<Option<String>> {
+    let mut line = String::new();
+    let len = (&mut *reader)
+        .take(MAX_LINE_LENGTH)
+        .read_line(&mut line)
+        .await?;
+    match len as u64 == MAX_LINE_LENGTH && !line.ends_with('\n') {
+        true => Ok(None),
+        false => Ok(Some(line)),
+    }
+}
+
+async fn respond(stream: &mut TcpStream, status: &str) -> Result<()> {
+    stream
+        .write_all(
+            format!("HTTP/1.1 {status}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n")
+                .as_bytes(),
+        )
+        .await?;
+    Ok(stream.flush().await?)
+}
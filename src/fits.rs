@@ -0,0 +1,339 @@
+//! FITS export of completed exposures
+//!
+//! Writes the frame as it came from the SDK, or after software binning, as a single primary
+//! HDU together with the header keywords only the driver knows about (gain, offset, set-point,
+//! readout mode, ...). Summed software binned and 32-bit frames are written with `BITPIX = 32`,
+//! debayered colour frames as a cube of three planes in RGB order.
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use eyre::Result;
+use qhyccd_rs::CCDChipArea;
+use time::OffsetDateTime;
+
+use crate::FrameType;
+use crate::image::bytes_per_sample;
+use crate::overscan::fits_section;
+
+/// FITS files are made of blocks of this many bytes
+const BLOCK_SIZE: usize = 2880;
+/// every header card is exactly this many characters
+const CARD_SIZE: usize = 80;
+
+/// Default file name template, see [`FitsExport`] for the placeholders.
+pub const DEFAULT_FILENAME_TEMPLATE: &str = "{camera}_{frame_type}_{date_obs}.fits";
+
+/// Where and how completed exposures are written to disk.
+///
+/// The file name template supports the placeholders `{camera}`, `{frame_type}`,
+/// `{date_obs}` and `{exposure}` (in seconds).
+#[derive(Debug, Clone, PartialEq)]
+pub struct FitsExport {
+    pub directory: PathBuf,
+    pub filename_template: String,
+}
+
+impl FitsExport {
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        Self {
+            directory: directory.into(),
+            filename_template: DEFAULT_FILENAME_TEMPLATE.to_owned(),
+        }
+    }
+
+    pub fn with_filename_template(mut self, filename_template: impl Into<String>) -> Self {
+        self.filename_template = filename_template.into();
+        self
+    }
+
+    pub(crate) fn path(&self, metadata: &FitsMetadata) -> PathBuf {
+        let file_name = self
+            .filename_template
+            .replace("{camera}", &metadata.camera)
+            .replace("{frame_type}", metadata.frame_type.name())
+            .replace(
+                "{date_obs}",
+                &format_date(metadata.date_obs).replace(':', "-"),
+            )
+            .replace(
+                "{exposure}",
+                &format!("{}", f64::from(metadata.exposure_us) / 1_000_000_f64),
+            );
+        self.directory.join(file_name)
+    }
+}
+
+impl FrameType {
+    fn name(&self) -> &'static str {
+        match self {
+            FrameType::Light => "Light",
+            FrameType::Dark => "Dark",
+            FrameType::Bias => "Bias",
+        }
+    }
+}
+
+/// Everything the driver knows about an exposure that goes into the FITS header.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct FitsMetadata {
+    pub camera: String,
+    pub frame_type: FrameType,
+    pub date_obs: SystemTime,
+    pub exposure_us: u32,
+    pub gain: Option<f64>,
+    pub offset: Option<f64>,
+    pub bin_x: u8,
+    pub bin_y: u8,
+    pub start_x: u32,
+    pub start_y: u32,
+    pub ccd_temperature: Option<f64>,
+    pub set_temperature: Option<f64>,
+    pub readout_mode: Option<String>,
+    pub bayer_pattern: Option<qhyccd_rs::BayerMode>,
+    pub pixel_width: f64,
+    pub pixel_height: f64,
+    /// overscan and effective area within the frame, if the overscan was read out
+    pub overscan: Option<CCDChipArea>,
+    pub data_area: Option<CCDChipArea>,
+    /// largest sample value, written once the camera has an ADU scaling
+    pub data_max: Option<u32>,
+}
+
+impl FitsMetadata {
+    fn cards(&self, image: &qhyccd_rs::ImageData) -> Vec<String> {
+        let naxis = if image.channels == 1 { "2" } else { "3" };
+        let mut cards = vec![
+            card("SIMPLE", "T", "conforms to FITS standard"),
+            card(
+                "BITPIX",
+                &image.bits_per_pixel.to_string(),
+                "bits per data value",
+            ),
+            card("NAXIS", naxis, "number of data axes"),
+            card("NAXIS1", &image.width.to_string(), "image width"),
+            card("NAXIS2", &image.height.to_string(), "image height"),
+        ];
+        if image.channels != 1 {
+            cards.push(card(
+                "NAXIS3",
+                &image.channels.to_string(),
+                "colour planes, RGB",
+            ));
+        }
+        if image.bits_per_pixel == 16 {
+            cards.push(card("BZERO", "32768", "offset for unsigned 16-bit data"));
+            cards.push(card("BSCALE", "1", "default scaling factor"));
+        }
+        if let Some(data_max) = self.data_max {
+            cards.push(card("DATAMAX", &data_max.to_string(), "maximum data value"));
+        }
+        cards.push(card(
+            "INSTRUME",
+            &quote(&self.camera),
+            "camera used to take the image",
+        ));
+        cards.push(card(
+            "SWCREATE",
+            &quote(concat!("qhyccd-alpaca ", env!("CARGO_PKG_VERSION"))),
+            "software that created the file",
+        ));
+        cards.push(card(
+            "IMAGETYP",
+            &quote(&format!("{} Frame", self.frame_type.name())),
+            "type of image",
+        ));
+        cards.push(card(
+            "DATE-OBS",
+            &quote(&format_date(self.date_obs)),
+            "UTC start of the exposure",
+        ));
+        let exposure = f64::from(self.exposure_us) / 1_000_000_f64;
+        cards.push(card("EXPTIME", &exposure.to_string(), "[s] exposure time"));
+        cards.push(card("EXPOSURE", &exposure.to_string(), "[s] exposure time"));
+        if let Some(gain) = self.gain {
+            cards.push(card("GAIN", &gain.to_string(), "sensor gain"));
+        }
+        if let Some(offset) = self.offset {
+            cards.push(card("OFFSET", &offset.to_string(), "sensor offset"));
+        }
+        cards.push(card(
+            "XBINNING",
+            &self.bin_x.to_string(),
+            "binning factor in width",
+        ));
+        cards.push(card(
+            "YBINNING",
+            &self.bin_y.to_string(),
+            "binning factor in height",
+        ));
+        cards.push(card(
+            "XORGSUBF",
+            &self.start_x.to_string(),
+            "subframe x origin in binned pixels",
+        ));
+        cards.push(card(
+            "YORGSUBF",
+            &self.start_y.to_string(),
+            "subframe y origin in binned pixels",
+        ));
+        if let Some(temperature) = self.ccd_temperature {
+            cards.push(card(
+                "CCD-TEMP",
+                &temperature.to_string(),
+                "[C] sensor temperature at start",
+            ));
+        }
+        if let Some(temperature) = self.set_temperature {
+            cards.push(card(
+                "SET-TEMP",
+                &temperature.to_string(),
+                "[C] cooler set-point",
+            ));
+        }
+        if let Some(readout_mode) = &self.readout_mode {
+            cards.push(card("READOUTM", &quote(readout_mode), "readout mode"));
+        }
+        if let Some(bayer_pattern) = self.bayer_pattern {
+            cards.push(card(
+                "BAYERPAT",
+                &quote(&format!("{:?}", bayer_pattern)),
+                "Bayer pattern at the subframe origin",
+            ));
+            cards.push(card("XBAYROFF", "0", "x offset of the Bayer pattern"));
+            cards.push(card("YBAYROFF", "0", "y offset of the Bayer pattern"));
+        }
+        if let Some(overscan) = self.overscan {
+            cards.push(card(
+                "BIASSEC",
+                &quote(&fits_section(overscan)),
+                "overscan region of the frame",
+            ));
+        }
+        if let Some(data_area) = self.data_area {
+            cards.push(card(
+                "DATASEC",
+                &quote(&fits_section(data_area)),
+                "effective area of the frame",
+            ));
+        }
+        cards.push(card(
+            "XPIXSZ",
+            &(self.pixel_width * f64::from(self.bin_x)).to_string(),
+            "[um] binned pixel width",
+        ));
+        cards.push(card(
+            "YPIXSZ",
+            &(self.pixel_height * f64::from(self.bin_y)).to_string(),
+            "[um] binned pixel height",
+        ));
+        cards.push(format!("{:<CARD_SIZE$}", "END"));
+        cards
+    }
+}
+
+/// Writes `image` with the header built from `metadata` to the path derived from `export`.
+///
+/// The output directory is created if it does not exist yet.
+pub(crate) fn write(
+    export: &FitsExport,
+    metadata: &FitsMetadata,
+    image: &qhyccd_rs::ImageData,
+) -> Result<PathBuf> {
+    std::fs::create_dir_all(&export.directory)?;
+    let path = export.path(metadata);
+    write_to(&path, metadata, image)?;
+    Ok(path)
+}
+
+pub(crate) fn write_to(
+    path: &Path,
+    metadata: &FitsMetadata,
+    image: &qhyccd_rs::ImageData,
+) -> Result<()> {
+    let bytes_per_sample = bytes_per_sample(image)?;
+    let channels = image.channels as usize;
+    let len = image.width as usize * image.height as usize * channels * bytes_per_sample;
+
+    let mut writer = BufWriter::new(File::create(path)?);
+    let header = metadata.cards(image).concat();
+    writer.write_all(header.as_bytes())?;
+    writer.write_all(&vec![b' '; padding(header.len())])?;
+
+    let data = &image.data[0_usize..len];
+    // colour frames are interleaved BGR per pixel, FITS wants one plane after the other in RGB
+    for plane in (0..channels).rev() {
+        let samples = data
+            .chunks_exact(bytes_per_sample)
+            .skip(plane)
+            .step_by(channels);
+        match bytes_per_sample {
+            1_usize => {
+                for sample in samples {
+                    writer.write_all(sample)?;
+                }
+            }
+            4_usize => {
+                // summed software binned and 32-bit frames, served as signed 32-bit integers
+                for sample in samples {
+                    let value = i32::from_ne_bytes([sample[0], sample[1], sample[2], sample[3]]);
+                    writer.write_all(&value.to_be_bytes())?;
+                }
+            }
+            _ => {
+                // FITS only knows signed 16-bit integers, BZERO shifts them back to unsigned
+                for sample in samples {
+                    let value = u16::from_ne_bytes([sample[0], sample[1]]) ^ 0x8000;
+                    writer.write_all(&value.to_be_bytes())?;
+                }
+            }
+        }
+    }
+    writer.write_all(&vec![0_u8; padding(data.len())])?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// number of bytes needed to fill up the last block
+fn padding(len: usize) -> usize {
+    (BLOCK_SIZE - len % BLOCK_SIZE) % BLOCK_SIZE
+}
+
+/// formats a fixed-format header card, values are right aligned in column 30
+fn card(keyword: &str, value: &str, comment: &str) -> String {
+    let card = if value.starts_with('\'') {
+        format!("{:<8}= {:<20} / {}", keyword, value, comment)
+    } else {
+        format!("{:<8}= {:>20} / {}", keyword, value, comment)
+    };
+    let mut card: String = card.chars().take(CARD_SIZE).collect();
+    while card.len() < CARD_SIZE {
+        card.push(' ');
+    }
+    card
+}
+
+/// quotes a string value, quotes inside are escaped by doubling them
+fn quote(value: &str) -> String {
+    let value: String = value
+        .chars()
+        .filter(|c| c.is_ascii() && !c.is_ascii_control())
+        .collect();
+    format!("'{:<8}'", value.replace('\'', "''"))
+}
+
+/// formats a time as used in DATE-OBS, e.g. `2024-01-01T12:00:00.000`
+fn format_date(time: SystemTime) -> String {
+    let date = OffsetDateTime::from(time);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}",
+        date.year(),
+        u8::from(date.month()),
+        date.day(),
+        date.hour(),
+        date.minute(),
+        date.second(),
+        date.millisecond()
+    )
+}
@@ -0,0 +1,412 @@
+//! Devices plugged in or unplugged while the server runs
+//!
+//! The SDK is asked for devices at startup, and again every rescan interval if one is set. Each
+//! rescan compares the cameras and filter wheels the SDK reports with the known ones:
+//!
+//! - a new device is added after the known ones and the server is bound again to serve it, the
+//!   known devices keep their device numbers; if binding fails it is retried, the devices stay
+//!   unserved until it succeeds
+//! - a known device that is missing is stopped, reports not connected and refuses to connect
+//!   until a rescan finds it again
+//! - a filter wheel in the CFW port of a camera comes and goes with its camera; the SDK only
+//!   reports it if it was plugged in at startup, otherwise it is added once the camera connects
+//!   and finds it
+//!
+//! Asking the SDK for devices initialises and releases it, so no rescan runs while a device is
+//! connected, in live mode or cooling: devices plugged in or unplugged meanwhile are noticed
+//! once all are disconnected. Clients have to connect a device again after it was unplugged.
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use ascom_alpaca::api::CargoServerInfo;
+use ascom_alpaca::{BoundServer, Server};
+use tokio::sync::{Notify, RwLock};
+use tokio::task;
+use tracing::{debug, info, warn};
+
+use crate::endpoint::{CameraFrames, Cameras};
+use crate::live::LiveFeed;
+use crate::sequence::Sequencer;
+use crate::shared::Shared;
+use crate::{
+    Binning, ConfigStore, Cooler, CoolerRamp, DarkFramePolicy, FilterWheelDevice, FilterWheels,
+    FitsExport, QhyCamera, QhyFilterWheel, QhyccdCamera, QhyccdFilterWheel, Sdk, State,
+    model_from_id, presets,
+};
+
+/// How long to wait before binding the server again after it failed.
+pub(crate) const REBIND_INTERVAL: Duration = Duration::from_secs(5);
+
+/// The devices of the server, in the order they were found.
+pub(crate) struct DeviceRegistry {
+    config: Arc<ConfigStore>,
+    dark_frame_policy: DarkFramePolicy,
+    fits_export: Option<FitsExport>,
+    cooler_ramp: CoolerRamp,
+    pub(crate) cameras: Vec<Arc<QhyccdCamera>>,
+    /// each wheel with the SDK id of the device driving it, the camera for a CFW port wheel
+    pub(crate) filter_wheels: Vec<(String, Arc<QhyccdFilterWheel>)>,
+    /// the filter wheels by unique id, shared with the cameras
+    filter_wheels_by_id: FilterWheels,
+    frames: Cameras,
+    /// woken by a camera that found a filter wheel in its CFW port
+    cfw_found: Arc<Notify>,
+}
+
+impl DeviceRegistry {
+    pub(crate) fn new(
+        config: Arc<ConfigStore>,
+        dark_frame_policy: DarkFramePolicy,
+        fits_export: Option<FitsExport>,
+        cooler_ramp: CoolerRamp,
+    ) -> Self {
+        Self {
+            config,
+            dark_frame_policy,
+            fits_export,
+            cooler_ramp,
+            cameras: Vec::new(),
+            filter_wheels: Vec::new(),
+            filter_wheels_by_id: Arc::default(),
+            frames: Arc::default(),
+            cfw_found: Arc::default(),
+        }
+    }
+
+    /// What the frame endpoint serves, cameras added later show up there too.
+    pub(crate) fn frames(&self) -> Cameras {
+        self.frames.clone()
+    }
+
+    /// The coolers of the cameras that are plugged in, to warm them up on shutdown.
+    pub(crate) fn coolers(&self) -> Vec<(QhyCamera, Arc<RwLock<Cooler>>)> {
+        self.cameras
+            .iter()
+            .filter(|camera| camera.attached.load(Ordering::Relaxed))
+            .map(|camera| (camera.device.clone(), camera.cooler.clone()))
+            .collect()
+    }
+
+    /// Registers all devices, in the order they were found so their device numbers never change.
+    pub(crate) fn register(&self, server: &mut Server) {
+        for camera in &self.cameras {
+            debug!(?camera, "Registering camera");
+            server
+                .devices
+                .register::<dyn ascom_alpaca::api::Camera>(Shared(camera.clone()));
+        }
+        for (_, filter_wheel) in &self.filter_wheels {
+            debug!(?filter_wheel, "Registering filter wheel");
+            server
+                .devices
+                .register::<dyn ascom_alpaca::api::FilterWheel>(Shared(filter_wheel.clone()));
+        }
+    }
+
+    /// Whether a device handle is open or in use, the SDK is not asked for devices then.
+    ///
+    /// A rescan initialises the SDK again, opens every camera to read its id and releases the
+    /// SDK afterwards, which would pull the handles of connected devices from under them.
+    async fn in_use(&self) -> bool {
+        for camera in self
+            .cameras
+            .iter()
+            .filter(|camera| camera.attached.load(Ordering::Relaxed))
+        {
+            let cooling = camera
+                .cooler
+                .read()
+                .await
+                .task
+                .as_ref()
+                .is_some_and(|task| !task.is_finished());
+            if camera.device.is_open().unwrap_or(true)
+                || camera.live.read().await.is_some()
+                || cooling
+                || matches!(*camera.state.read().await, State::Exposing { .. })
+            {
+                debug!(camera = camera.unique_id, "camera in use");
+                return true;
+            }
+        }
+        for (_, filter_wheel) in self
+            .filter_wheels
+            .iter()
+            .filter(|(_, filter_wheel)| filter_wheel.attached.load(Ordering::Relaxed))
+        {
+            if filter_wheel.device.is_open().unwrap_or(true) {
+                debug!(filter_wheel = filter_wheel.unique_id, "filter wheel in use");
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Adds the devices that are not known yet, returns whether there were any.
+    async fn add(
+        &mut self,
+        cameras: Vec<QhyCamera>,
+        filter_wheels: Vec<(String, QhyFilterWheel)>,
+    ) -> bool {
+        let mut added = false;
+        for c in cameras {
+            if self.cameras.iter().any(|camera| camera.unique_id == c.id()) {
+                continue;
+            }
+            self.add_camera(c).await;
+            added = true;
+        }
+        for (id, c) in filter_wheels {
+            // the SDK also reports a wheel that is driven through the camera CFW port
+            if self.filter_wheels.iter().any(|(known, _)| *known == id) {
+                continue;
+            }
+            self.add_filter_wheel(id, c).await;
+            added = true;
+        }
+        added
+    }
+
+    /// Compares the devices the SDK reports with the known ones, see the module documentation.
+    ///
+    /// Returns whether devices were added, the server has to be bound again to serve them.
+    pub(crate) async fn rescan(&mut self, sdk: &Sdk) -> bool {
+        let cameras: Vec<QhyCamera> = sdk.cameras().collect();
+        let filter_wheels: Vec<(String, QhyFilterWheel)> = sdk
+            .filter_wheels()
+            .map(|c| (c.id().to_owned(), c))
+            .collect();
+        for camera in &self.cameras {
+            let present = cameras.iter().any(|c| c.id() == camera.unique_id);
+            match (camera.attached.load(Ordering::Relaxed), present) {
+                (true, false) => {
+                    warn!("camera {} unplugged", camera.unique_id);
+                    camera.detach().await;
+                }
+                (false, true) => {
+                    info!("camera {} plugged in again", camera.unique_id);
+                    camera.attached.store(true, Ordering::Relaxed);
+                }
+                _ => {}
+            }
+        }
+        for (id, filter_wheel) in &self.filter_wheels {
+            let present = match filter_wheel.device {
+                FilterWheelDevice::Sdk(_) => filter_wheels.iter().any(|(known, _)| known == id),
+                FilterWheelDevice::CameraPort { .. } => cameras.iter().any(|c| c.id() == id),
+            };
+            match (filter_wheel.attached.load(Ordering::Relaxed), present) {
+                (true, false) => {
+                    warn!("filter wheel {} unplugged", filter_wheel.unique_id);
+                    filter_wheel.detach();
+                }
+                (false, true) => {
+                    info!("filter wheel {} plugged in again", filter_wheel.unique_id);
+                    filter_wheel.attached.store(true, Ordering::Relaxed);
+                }
+                _ => {}
+            }
+        }
+        let added = self.add(cameras, filter_wheels).await;
+        self.add_port_wheels().await || added
+    }
+
+    /// Adds the wheels connected cameras found in their CFW port, returns whether there were any.
+    async fn add_port_wheels(&mut self) -> bool {
+        let found: Vec<Arc<QhyccdCamera>> = self
+            .cameras
+            .iter()
+            .filter(|camera| camera.cfw_detected.load(Ordering::Relaxed))
+            .filter(|camera| {
+                !self
+                    .filter_wheels
+                    .iter()
+                    .any(|(id, _)| *id == camera.unique_id)
+            })
+            .cloned()
+            .collect();
+        for camera in &found {
+            info!("filter wheel found on the CFW port of {}", camera.unique_id);
+            let filter_wheel = Arc::new(QhyccdFilterWheel {
+                unique_id: format!("CFW={}", camera.unique_id),
+                name: format!("CFW={}", camera.unique_id),
+                description: "QHYCCD filter wheel on the camera CFW port".to_owned(),
+                number_of_filters: RwLock::new(None),
+                target_position: RwLock::new(None),
+                device: FilterWheelDevice::CameraPort {
+                    camera: camera.device.clone(),
+                    cfw_detected: camera.cfw_detected.clone(),
+                    connected: AtomicBool::new(false),
+                },
+                config: self.config.clone(),
+                camera_state: Some(camera.state.clone()),
+                attached: AtomicBool::new(true),
+            });
+            self.insert_filter_wheel(camera.unique_id.clone(), filter_wheel)
+                .await;
+        }
+        !found.is_empty()
+    }
+
+    async fn add_camera(&mut self, c: QhyCamera) {
+        let state = Arc::new(RwLock::new(State::Idle));
+        let camera_config = self.config.camera(c.id()).await;
+        let fits_export_enabled =
+            self.fits_export.is_some() && camera_config.fits_export.unwrap_or(true);
+        let camera_presets = match (camera_config.presets, model_from_id(c.id())) {
+            (Some(true), Some(model)) => {
+                let camera_presets = presets::for_model(model, &self.config.presets().await);
+                if camera_presets.is_none() {
+                    warn!(
+                        model,
+                        "presets enabled, but none are defined for this model"
+                    );
+                }
+                camera_presets
+            }
+            _ => None,
+        };
+        let cooler = Arc::new(RwLock::new(Cooler::new(CoolerRamp {
+            cool_down: camera_config.cool_down_rate.or(self.cooler_ramp.cool_down),
+            warm_up: camera_config.warm_up_rate.or(self.cooler_ramp.warm_up),
+        })));
+        let live_feed = Arc::new(LiveFeed::default());
+        let last_image = Arc::new(RwLock::new(None));
+        let sequencer = Arc::new(Sequencer::default());
+        self.frames.write().await.insert(
+            c.id().to_owned(),
+            CameraFrames {
+                live_feed: live_feed.clone(),
+                last_image: last_image.clone(),
+                sequencer: sequencer.clone(),
+            },
+        );
+        let camera = Arc::new_cyclic(|this| QhyccdCamera {
+            this: this.clone(),
+            unique_id: c.id().to_owned(),
+            name: c.id().to_owned(),
+            description: "QHYCCD camera".to_owned(),
+            device: c.clone(),
+            binning: RwLock::new(Binning::symmetric(1)),
+            valid_bins: RwLock::new(None),
+            cooler,
+            ccd_info: RwLock::new(None),
+            intended_roi: RwLock::new(None),
+            effective_area: RwLock::new(None),
+            overscan_area: RwLock::new(None),
+            include_overscan: RwLock::new(false),
+            transfer_bits: RwLock::new(16),
+            readout_speed_min_max_step: RwLock::new(None),
+            exposure_min_max_step: RwLock::new(None),
+            last_exposure_start_time: RwLock::new(None),
+            last_exposure_duration_us: Arc::new(RwLock::new(None)),
+            last_image,
+            state: state.clone(),
+            gain_min_max: RwLock::new(None),
+            offset_min_max: RwLock::new(None),
+            dark_frame_policy: self.dark_frame_policy,
+            shutter_closed: RwLock::new(false),
+            fits_export: self.fits_export.clone(),
+            fits_export_enabled: RwLock::new(fits_export_enabled),
+            debayer: RwLock::new(camera_config.debayer),
+            config: self.config.clone(),
+            cfw_detected: Arc::new(AtomicBool::new(false)),
+            cfw_found: self.cfw_found.clone(),
+            presets: camera_presets,
+            live_feed,
+            live: RwLock::new(None),
+            sequencer,
+            stop_supported: Arc::new(AtomicBool::new(true)),
+            sensors: RwLock::new(Vec::new()),
+            usb_traffic_range: RwLock::new(None),
+            ddr_range: RwLock::new(None),
+            filter_wheels: self.filter_wheels_by_id.clone(),
+            attached: AtomicBool::new(true),
+        });
+        self.cameras.push(camera);
+    }
+
+    async fn add_filter_wheel(&mut self, id: String, c: QhyFilterWheel) {
+        let camera_state = self
+            .cameras
+            .iter()
+            .find(|camera| camera.unique_id == id)
+            .map(|camera| camera.state.clone());
+        let filter_wheel = Arc::new(QhyccdFilterWheel {
+            unique_id: format!("CFW={}", id),
+            name: format!("CFW={}", id),
+            description: "QHYCCD filter wheel".to_owned(),
+            number_of_filters: RwLock::new(None),
+            target_position: RwLock::new(None),
+            device: FilterWheelDevice::Sdk(c),
+            config: self.config.clone(),
+            camera_state,
+            attached: AtomicBool::new(true),
+        });
+        self.insert_filter_wheel(id, filter_wheel).await;
+    }
+
+    async fn insert_filter_wheel(&mut self, id: String, filter_wheel: Arc<QhyccdFilterWheel>) {
+        self.filter_wheels_by_id
+            .write()
+            .await
+            .insert(filter_wheel.unique_id.clone(), filter_wheel.clone());
+        self.filter_wheels.push((id, filter_wheel));
+    }
+}
+
+/// Binds a server for all devices of `registry`.
+pub(crate) async fn bind(
+    registry: &DeviceRegistry,
+    listen_addr: SocketAddr,
+) -> eyre::Result<BoundServer> {
+    let mut server = Server::new(CargoServerInfo!());
+    server.listen_addr = listen_addr;
+    registry.register(&mut server);
+    Ok(server.bind().await?)
+}
+
+/// Rescans every `interval`, and adds the wheels cameras find in their CFW port as they connect,
+/// until new devices are found.
+///
+/// A rescan only runs while no device is in use, see [`DeviceRegistry::in_use`].
+pub(crate) async fn watch(registry: &mut DeviceRegistry, interval: Option<Duration>) {
+    let cfw_found = registry.cfw_found.clone();
+    loop {
+        let rescan = async {
+            match interval {
+                Some(interval) => tokio::time::sleep(interval).await,
+                None => std::future::pending().await,
+            }
+        };
+        tokio::select! {
+            () = rescan => {}
+            () = cfw_found.notified() => {
+                if registry.add_port_wheels().await {
+                    return;
+                }
+                continue;
+            }
+        }
+        if registry.in_use().await {
+            debug!("devices in use, skipping the rescan");
+            continue;
+        }
+        let sdk = match task::spawn_blocking(Sdk::new).await {
+            Ok(Ok(sdk)) => sdk,
+            Ok(Err(e)) => {
+                warn!(?e, "rescan failed");
+                continue;
+            }
+            Err(e) => {
+                warn!(?e, "rescan task failed");
+                continue;
+            }
+        };
+        if registry.rescan(&sdk).await {
+            return;
+        }
+    }
+}
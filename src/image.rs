@@ -0,0 +1,159 @@
+//! Raw frames and their Alpaca ImageBytes encoding
+//!
+//! Frames are kept as the SDK delivers them and only converted when a client asks for them.
+//! The ImageBytes format is a 44 byte header of little endian `i32` values followed by the
+//! pixels, serialized like a .NET `[x, y]` array, so the last index changes fastest. As the
+//! SDK delivers frames row by row, the encoding is a single transposing copy of the raw buffer
+//! into the response, without an intermediate `ImageArray`.
+//!
+//! Cameras with 12 or 14 bit ADCs deliver their samples MSB-aligned in 16 bit words. The
+//! `adu_scaling` of a camera decides whether clients get these padded values with `MaxADU`
+//! reporting 65535, or the native values shifted down by `16 - OutputDataActualBits`:
+//!
+//! ```toml
+//! [cameras.QHY268M-abc123]
+//! adu_scaling = "native"
+//! ```
+use eyre::{Result, eyre};
+use serde::{Deserialize, Serialize};
+use tracing::error;
+
+/// Length of the ImageBytes header, also where the pixel data starts.
+pub(crate) const HEADER_LEN: usize = 44;
+
+const METADATA_VERSION: i32 = 1;
+
+// ImageArrayElementTypes from the Alpaca API
+const ELEMENT_TYPE_INT32: i32 = 2;
+const ELEMENT_TYPE_BYTE: i32 = 6;
+const ELEMENT_TYPE_UINT16: i32 = 8;
+
+/// How the samples of cameras with less than 16 bits per pixel are published.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AduScaling {
+    /// shifts the samples down to the range of the ADC, `MaxADU` is its largest value
+    Native,
+    /// keeps the samples as delivered, `MaxADU` is the largest value of the frame depth
+    Padded,
+}
+
+/// Shifts the 16 bit samples of `image` down to a range of `actual_bits`.
+///
+/// Frames of other depths, or already using all 16 bits, are left untouched.
+pub(crate) fn to_native(image: &mut qhyccd_rs::ImageData, actual_bits: u32) {
+    if image.bits_per_pixel != 16 || actual_bits == 0 || actual_bits >= 16 {
+        return;
+    }
+    let shift = 16 - actual_bits;
+    for sample in image.data.chunks_exact_mut(2) {
+        let value = u16::from_ne_bytes([sample[0], sample[1]]) >> shift;
+        sample.copy_from_slice(&value.to_ne_bytes());
+    }
+}
+
+/// Checks that a frame can be served and returns the bytes per sample.
+///
+/// Frames have one channel, or three for debayered colour frames, which the SDK delivers
+/// interleaved per pixel in BGR order.
+pub(crate) fn bytes_per_sample(image: &qhyccd_rs::ImageData) -> Result<usize> {
+    let bytes_per_sample = match image.channels {
+        1_u32 | 3_u32 => match image.bits_per_pixel {
+            8_u32 => 1_usize,
+            16_u32 => 2_usize,
+            32_u32 => 4_usize,
+            other => {
+                error!("unsupported bits_per_pixel {:?}", other);
+                return Err(eyre!("unsupported bits_per_pixel {:?}", other));
+            }
+        },
+        other => {
+            error!("unsupported number of channels {:?}", other);
+            return Err(eyre!("unsupported number of channels {:?}", other));
+        }
+    };
+    let bytes_per_pixel = bytes_per_sample * image.channels as usize;
+    if image.width as usize * image.height as usize * bytes_per_pixel > image.data.len() {
+        error!(
+            "image data length ({}) does not match width ({}) * height ({}) * {}",
+            image.data.len(),
+            image.width,
+            image.height,
+            bytes_per_pixel
+        );
+        return Err(eyre!(
+            "image data length ({}) does not match width ({}) * height ({}) * {}",
+            image.data.len(),
+            image.width,
+            image.height,
+            bytes_per_pixel
+        ));
+    }
+    Ok(bytes_per_sample)
+}
+
+/// Encodes a frame in the ImageBytes format.
+///
+/// The image element type is `Int32` as for every `ImageArray`, the pixels are transmitted as
+/// `Byte`, `UInt16` or `Int32` depending on the bit depth of the frame. Colour frames have rank
+/// 3 with the planes in RGB order.
+pub(crate) fn image_bytes(
+    image: &qhyccd_rs::ImageData,
+    client_transaction_id: u32,
+    server_transaction_id: u32,
+) -> Result<Vec<u8>> {
+    let bytes_per_sample = bytes_per_sample(image)?;
+    let transmission_element_type = match bytes_per_sample {
+        1_usize => ELEMENT_TYPE_BYTE,
+        2_usize => ELEMENT_TYPE_UINT16,
+        _ => ELEMENT_TYPE_INT32,
+    };
+    let (rank, planes) = match image.channels {
+        1_u32 => (2_i32, 0_i32),
+        channels => (3_i32, channels as i32),
+    };
+    let channels = image.channels as usize;
+    let bytes_per_pixel = bytes_per_sample * channels;
+    let width = image.width as usize;
+    let len = width * image.height as usize * bytes_per_pixel;
+
+    let mut bytes = Vec::with_capacity(HEADER_LEN + len);
+    for value in [
+        METADATA_VERSION,
+        0_i32, // error number
+        client_transaction_id as i32,
+        server_transaction_id as i32,
+        HEADER_LEN as i32,
+        ELEMENT_TYPE_INT32,
+        transmission_element_type,
+        rank,
+        image.width as i32,
+        image.height as i32,
+        planes,
+    ] {
+        bytes.extend_from_slice(&value.to_le_bytes());
+    }
+    if len == 0 {
+        return Ok(bytes);
+    }
+    let rows = image.data[..len].chunks_exact(width * bytes_per_pixel);
+    for x in 0..width {
+        for row in rows.clone() {
+            let pixel = &row[x * bytes_per_pixel..(x + 1) * bytes_per_pixel];
+            // BGR from the SDK, RGB for the client
+            for sample in pixel.chunks_exact(bytes_per_sample).rev() {
+                match bytes_per_sample {
+                    1_usize => bytes.push(sample[0]),
+                    2_usize => bytes.extend_from_slice(
+                        &u16::from_ne_bytes([sample[0], sample[1]]).to_le_bytes(),
+                    ),
+                    _ => bytes.extend_from_slice(
+                        &i32::from_ne_bytes([sample[0], sample[1], sample[2], sample[3]])
+                            .to_le_bytes(),
+                    ),
+                }
+            }
+        }
+    }
+    Ok(bytes)
+}
@@ -1,7 +1,10 @@
 #![warn(clippy::integer_division)]
 use core::f64;
 use qhyccd_rs::CCDChipInfo;
-use std::sync::Arc;
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Weak};
 use std::time::{Duration, SystemTime};
 use tokio::sync::RwLock;
 
@@ -10,8 +13,8 @@ use ascom_alpaca::api::{Camera, CargoServerInfo, Device, FilterWheel};
 use ascom_alpaca::{ASCOMError, ASCOMResult, Server};
 use async_trait::async_trait;
 
-use eyre::{Result, eyre};
-use ndarray::Array3;
+use eyre::{Result, WrapErr, eyre};
+use ndarray::{Array3, Axis};
 
 #[macro_use]
 extern crate educe;
@@ -29,232 +32,2399 @@ cfg_if! {
     }
 }
 
-use tokio::sync::{oneshot, watch};
+use tokio::sync::{Notify, oneshot, watch};
 use tokio::task;
-use tracing::{debug, error, instrument, trace};
+use tracing::{debug, error, instrument, trace, warn};
+
+mod binning;
+mod config;
+mod cooler;
+mod debayer;
+mod endpoint;
+mod fits;
+mod hotplug;
+mod image;
+mod live;
+mod overscan;
+mod presets;
+mod sensor;
+mod sequence;
+mod shared;
+mod telemetry;
+mod tuning;
+use binning::Binning;
+pub use binning::SoftwareBinning;
+use config::{CameraConfig, ConfigStore, FilterWheelConfig};
+use cooler::{Cooler, CoolerCommand, CoolerState};
+pub use cooler::{CoolerRamp, DEFAULT_WARM_UP_RATE};
+pub use debayer::DebayerMethod;
+pub use fits::{DEFAULT_FILENAME_TEMPLATE as DEFAULT_FITS_FILENAME_TEMPLATE, FitsExport};
+use hotplug::DeviceRegistry;
+pub use image::AduScaling;
+use live::{LiveFeed, LiveSession};
+use sequence::Sequencer;
+use telemetry::Sensor;
+use tuning::{ControlRange, Tuning, UsbBackoff};
+
+macro_rules! ensure_connected {
+    ($self:ident) => {
+        if !$self.connected().await.is_ok_and(|connected| connected) {
+            error!("camera not connected");
+            return Err(ASCOMError::NOT_CONNECTED);
+        }
+    };
+}
 
 /// Builder for the ASCOM Alpaca server.
 ///
 /// Discovers QHYCCD cameras and filter wheels via the SDK,
 /// registers them with the Alpaca server, and binds to the specified port.
-#[derive(Default)]
+/// Devices plugged in later are found if a rescan interval is set.
 pub struct ServerBuilder {
     port: u16,
+    dark_frame_policy: DarkFramePolicy,
+    fits_export: Option<FitsExport>,
+    config_file: Option<PathBuf>,
+    cooler_ramp: CoolerRamp,
+    live_port: Option<u16>,
+    rescan_interval: Option<Duration>,
 }
 
 impl ServerBuilder {
     pub fn new() -> Self {
-        Self { port: 0 }
+        Self {
+            port: 0,
+            dark_frame_policy: DarkFramePolicy::default(),
+            fits_export: None,
+            config_file: None,
+            cooler_ramp: CoolerRamp {
+                cool_down: None,
+                warm_up: Some(DEFAULT_WARM_UP_RATE),
+            },
+            live_port: None,
+            rescan_interval: None,
+        }
+    }
+
+    pub fn with_port(mut self, port: u16) -> Self {
+        self.port = port;
+        self
+    }
+
+    /// Sets how dark and bias frames are handled on cameras without a mechanical shutter.
+    pub fn with_dark_frame_policy(mut self, dark_frame_policy: DarkFramePolicy) -> Self {
+        self.dark_frame_policy = dark_frame_policy;
+        self
+    }
+
+    /// Writes every completed exposure of every camera to a FITS file.
+    ///
+    /// Writing can be switched off per camera with the `FitsExport` action.
+    pub fn with_fits_export(mut self, fits_export: FitsExport) -> Self {
+        self.fits_export = Some(fits_export);
+        self
+    }
+
+    /// Loads per-device settings from a TOML file, which is created when settings are saved.
+    pub fn with_config_file(mut self, config_file: impl Into<PathBuf>) -> Self {
+        self.config_file = Some(config_file.into());
+        self
+    }
+
+    /// Ramps the cooler set-point of every camera, overridable per camera in the configuration file.
+    ///
+    /// A warm-up ramp also warms the sensors up on disconnect and on shutdown. Without this call
+    /// the set-point is lowered in one go and raised by [`DEFAULT_WARM_UP_RATE`].
+    pub fn with_cooler_ramp(mut self, cooler_ramp: CoolerRamp) -> Self {
+        self.cooler_ramp = cooler_ramp;
+        self
+    }
+
+    /// Serves live frames, the last exposures as ImageBytes and sequence progress over HTTP on this port.
+    pub fn with_live_port(mut self, live_port: u16) -> Self {
+        self.live_port = Some(live_port);
+        self
+    }
+
+    /// Asks the SDK for devices plugged in or unplugged while the server runs at this interval.
+    ///
+    /// New devices are served after the known ones, which keep their device numbers.
+    pub fn with_rescan_interval(mut self, rescan_interval: Duration) -> Self {
+        self.rescan_interval = Some(rescan_interval);
+        self
+    }
+
+    /// Finds the devices and binds the server.
+    ///
+    /// Returns this crate's [`BoundServer`] instead of `ascom_alpaca::BoundServer`,
+    /// which also serves the frame endpoint, rescans for devices and warms the sensors up on
+    /// shutdown. It offers the same `listen_addr` and `start`.
+    pub async fn build(self) -> eyre::Result<BoundServer> {
+        let mut server = Server::new(CargoServerInfo!());
+        server.listen_addr.set_port(self.port);
+
+        let config = Arc::new(match self.config_file.clone() {
+            Some(path) => ConfigStore::load(path)?,
+            None => ConfigStore::default(),
+        });
+
+        let sdk = Sdk::new()?;
+        let sdk_version = sdk.version()?;
+        trace!(sdk_version = ?sdk_version);
+
+        let mut registry = DeviceRegistry::new(
+            config,
+            self.dark_frame_policy,
+            self.fits_export,
+            self.cooler_ramp,
+        );
+        registry.rescan(&sdk).await;
+        registry.register(&mut server);
+
+        let live = match self.live_port {
+            Some(live_port) => {
+                let mut addr = server.listen_addr;
+                addr.set_port(live_port);
+                let listener = tokio::net::TcpListener::bind(addr).await?;
+                tracing::info!(addr = %listener.local_addr()?, "Frame endpoint bound");
+                Some((listener, registry.frames()))
+            }
+            None => None,
+        };
+
+        let bound = server.bind().await?;
+        tracing::info!(addr = %bound.listen_addr(), "Server bound");
+        Ok(BoundServer {
+            server: bound,
+            registry,
+            live,
+            rescan_interval: self.rescan_interval,
+        })
+    }
+}
+
+impl Default for ServerBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A server ready to accept requests, see [`ServerBuilder::build`].
+pub struct BoundServer {
+    server: ascom_alpaca::BoundServer,
+    registry: DeviceRegistry,
+    live: Option<(tokio::net::TcpListener, endpoint::Cameras)>,
+    rescan_interval: Option<Duration>,
+}
+
+impl BoundServer {
+    pub fn listen_addr(&self) -> std::net::SocketAddr {
+        self.server.listen_addr()
+    }
+
+    /// Serves requests until Ctrl-C, then warms up the sensors of all cameras before returning.
+    ///
+    /// A second Ctrl-C skips the warm-up. The server is bound again on the same address whenever
+    /// a rescan, or a camera connecting with a wheel in its CFW port, adds devices. A failed bind
+    /// is logged and retried, the server keeps running for Ctrl-C.
+    pub async fn start(self) -> eyre::Result<()> {
+        let Self {
+            server,
+            mut registry,
+            live,
+            rescan_interval,
+        } = self;
+        let listen_addr = server.listen_addr();
+        let mut server = Some(server);
+        let live = async {
+            match live {
+                Some((listener, cameras)) => endpoint::serve(listener, cameras).await,
+                None => std::future::pending().await,
+            }
+        };
+        tokio::pin!(live);
+        let ctrl_c = tokio::signal::ctrl_c();
+        tokio::pin!(ctrl_c);
+        loop {
+            let bound = server.is_some();
+            let serve = async {
+                match server.take() {
+                    Some(server) => server.start().await,
+                    None => std::future::pending().await,
+                }
+            };
+            let rescan = hotplug::watch(&mut registry, rescan_interval);
+            tokio::select! {
+                res = serve => return res.map(|never| match never {}),
+                res = &mut live => return res.map(|never| match never {}),
+                // the old server keeps serving during the rescan, it only stops now because the
+                // new one needs its address
+                () = rescan, if bound => {}
+                () = tokio::time::sleep(hotplug::REBIND_INTERVAL), if !bound => {}
+                res = &mut ctrl_c => {
+                    res?;
+                    tracing::info!("shutting down, press Ctrl-C again to skip warming up the sensors");
+                    let warm_up = async {
+                        let tasks: Vec<_> = registry
+                            .coolers()
+                            .into_iter()
+                            .map(|(device, cooler)| {
+                                tokio::spawn(async move { cooler::warm_up(&device, &cooler).await })
+                            })
+                            .collect();
+                        for task in tasks {
+                            if let Err(e) = task.await {
+                                error!(?e, "warm-up failed");
+                            }
+                        }
+                    };
+                    tokio::select! {
+                        () = warm_up => {}
+                        _ = tokio::signal::ctrl_c() => warn!("skipped warming up the sensors"),
+                    }
+                    return Ok(());
+                }
+            }
+            match hotplug::bind(&registry, listen_addr).await {
+                Ok(bound) => {
+                    tracing::info!(addr = %bound.listen_addr(), "Server bound again for new devices");
+                    server = Some(bound);
+                }
+                Err(e) => error!(
+                    ?e,
+                    "binding the server again failed, retrying in {:?}",
+                    hotplug::REBIND_INTERVAL
+                ),
+            }
+        }
+    }
+}
+
+/// Camera ids are MODEL-SerialNumber
+fn model_from_id(id: &str) -> Option<&str> {
+    id.split('-').next().filter(|model| !model.is_empty())
+}
+
+/// Whether the SDK reads out the partial image when an exposure of the model is stopped, keyed
+/// by model prefix.
+const STOP_EXPOSURE: &[(&str, bool)] = &[
+    ("QHY5III", false),
+    ("QHY268", true),
+    ("QHY294", true),
+    ("QHY411", true),
+    ("QHY461", true),
+    ("QHY600", true),
+];
+
+/// `STOP_EXPOSURE` for `model`, `None` for models that are not listed.
+fn stop_exposure_supported(model: &str) -> Option<bool> {
+    let table: BTreeMap<String, bool> = STOP_EXPOSURE
+        .iter()
+        .map(|&(prefix, supported)| (prefix.to_owned(), supported))
+        .collect();
+    config::by_model_prefix(model, &table).copied()
+}
+
+/// What to do with dark and bias frames on cameras without a mechanical shutter.
+///
+/// The driver cannot tell whether the sensor is covered, so the user decides.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum DarkFramePolicy {
+    /// Take the frame without complaint.
+    Allow,
+    /// Take the frame, but log a warning that the sensor must be covered.
+    #[default]
+    Warn,
+    /// Refuse the frame.
+    Reject,
+}
+
+/// The kind of frame an exposure produced.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FrameType {
+    Light,
+    Dark,
+    Bias,
+}
+
+/// The last image read from the camera together with the kind of frame it is.
+///
+/// The frame is kept as the SDK delivered it and shared with the readers instead of being
+/// copied for every request.
+#[derive(Debug, Clone)]
+struct LastImage {
+    image: Arc<qhyccd_rs::ImageData>,
+    frame_type: FrameType,
+    /// start of the ROI the frame was read with, decides the Bayer pattern of the frame
+    origin: (u32, u32),
+    binning: Binning,
+    /// overscan within the frame, if it was read out
+    overscan: Option<CCDChipArea>,
+    /// the frame converted for `ImageArray`, shared by all requests for it
+    ///
+    /// `ImageArray` shares its data, so serving the same frame again, as JSON or as ImageBytes,
+    /// does not copy it again.
+    image_array: Arc<std::sync::Mutex<Option<ImageArray>>>,
+}
+
+// values for the CamMechanicalShutter control, see ControlQHYCCDShutter in the SDK
+const SHUTTER_OPEN: f64 = 0_f64;
+const SHUTTER_CLOSED: f64 = 1_f64;
+
+/// The Bayer pattern seen from a subframe starting at `start_x`, `start_y`.
+///
+/// An odd origin shifts the pattern by one column or row.
+fn bayer_pattern_at(
+    pattern: qhyccd_rs::BayerMode,
+    start_x: u32,
+    start_y: u32,
+) -> qhyccd_rs::BayerMode {
+    use qhyccd_rs::BayerMode::{BGGR, GBRG, GRBG, RGGB};
+    let pattern = match start_x % 2 {
+        0 => pattern,
+        _ => match pattern {
+            RGGB => GRBG,
+            GRBG => RGGB,
+            GBRG => BGGR,
+            BGGR => GBRG,
+        },
+    };
+    match start_y % 2 {
+        0 => pattern,
+        _ => match pattern {
+            RGGB => GBRG,
+            GBRG => RGGB,
+            GRBG => BGGR,
+            BGGR => GRBG,
+        },
+    }
+}
+
+#[derive(Debug)]
+struct StopExposure {
+    want_image: bool,
+}
+
+#[derive(Educe)]
+#[educe(Debug, PartialEq)]
+enum State {
+    Idle,
+    Exposing {
+        start: SystemTime,
+        expected_duration_us: u32,
+        #[educe(PartialEq(ignore))]
+        stop_tx: Option<oneshot::Sender<StopExposure>>,
+        #[educe(PartialEq(ignore))]
+        done_rx: watch::Receiver<bool>,
+    },
+}
+
+/// The filter wheels of the server by unique id, shared with the cameras for their sequences.
+type FilterWheels = Arc<RwLock<HashMap<String, Arc<QhyccdFilterWheel>>>>;
+
+#[derive(Debug)]
+struct QhyccdCamera {
+    /// the camera itself, for sequences that outlive the request starting them
+    this: Weak<QhyccdCamera>,
+    unique_id: String,
+    name: String,
+    description: String,
+    device: QhyCamera,
+    binning: RwLock<Binning>,
+    valid_bins: RwLock<Option<Vec<u8>>>,
+    /// cooler controller, shared with its background task
+    cooler: Arc<RwLock<Cooler>>,
+    ccd_info: RwLock<Option<CCDChipInfo>>,
+    intended_roi: RwLock<Option<qhyccd_rs::CCDChipArea>>,
+    /// chip areas without and with overscan as reported on connect, in unbinned pixels
+    effective_area: RwLock<Option<CCDChipArea>>,
+    overscan_area: RwLock<Option<CCDChipArea>>,
+    /// read out the whole chip including the overscan instead of the effective area
+    include_overscan: RwLock<bool>,
+    /// bits per pixel the frames are transferred with, 8 or 16
+    transfer_bits: RwLock<u8>,
+    readout_speed_min_max_step: RwLock<Option<(f64, f64, f64)>>,
+    exposure_min_max_step: RwLock<Option<(f64, f64, f64)>>,
+    last_exposure_start_time: RwLock<Option<SystemTime>>,
+    last_exposure_duration_us: Arc<RwLock<Option<u32>>>,
+    last_image: Arc<RwLock<Option<LastImage>>>,
+    state: Arc<RwLock<State>>,
+    gain_min_max: RwLock<Option<(f64, f64)>>,
+    offset_min_max: RwLock<Option<(f64, f64)>>,
+    dark_frame_policy: DarkFramePolicy,
+    shutter_closed: RwLock<bool>,
+    fits_export: Option<FitsExport>,
+    fits_export_enabled: RwLock<bool>,
+    /// software debayering of colour frames served through `ImageArray`
+    debayer: RwLock<Option<DebayerMethod>>,
+    config: Arc<ConfigStore>,
+    /// set on connect if a filter wheel is plugged into the CFW port, shared with that wheel
+    cfw_detected: Arc<AtomicBool>,
+    /// woken when `cfw_detected` is set, so that the server publishes the wheel
+    cfw_found: Arc<Notify>,
+    /// named gain and offset presets, switches `Gains`/`Offsets` to list mode
+    presets: Option<presets::Presets>,
+    /// frames read in live mode, shared with the live endpoint
+    live_feed: Arc<LiveFeed>,
+    /// set while the camera is in live mode
+    live: RwLock<Option<LiveSession>>,
+    /// runs exposure sequences, shared with the frame endpoint
+    sequencer: Arc<Sequencer>,
+    /// cleared once the SDK refuses to stop an exposure early, see `can_stop_exposure`
+    stop_supported: Arc<AtomicBool>,
+    /// the environment sensors found on connect
+    sensors: RwLock<Vec<Sensor>>,
+    /// ranges of the USB traffic and DDR buffer controls found on connect
+    usb_traffic_range: RwLock<Option<ControlRange>>,
+    ddr_range: RwLock<Option<ControlRange>>,
+    filter_wheels: FilterWheels,
+    /// cleared while the camera is unplugged, see [`hotplug`]
+    attached: AtomicBool,
+}
+
+impl QhyccdCamera {
+    /// The camera model, parsed from the id, which is MODEL-SerialNumber.
+    fn model(&self) -> Option<&str> {
+        model_from_id(&self.unique_id)
+    }
+
+    /// The gain presets, if the camera is in list mode for gain.
+    fn gain_presets(&self) -> Option<&[presets::GainPreset]> {
+        self.presets
+            .as_ref()
+            .map(|presets| presets.gains.as_slice())
+            .filter(|gains| !gains.is_empty())
+    }
+
+    /// The offset presets, if the camera is in list mode for offset.
+    fn offset_presets(&self) -> Option<&[presets::OffsetPreset]> {
+        self.presets
+            .as_ref()
+            .map(|presets| presets.offsets.as_slice())
+            .filter(|offsets| !offsets.is_empty())
+    }
+
+    /// The gain as set on the camera, regardless of presets.
+    async fn gain_value(&self) -> ASCOMResult<i32> {
+        ensure_connected!(self);
+        self.device
+            .is_control_available(qhyccd_rs::Control::Gain)
+            .ok_or_else(|| {
+                debug!("gain control not available");
+                ASCOMError::NOT_IMPLEMENTED
+            })?;
+        self.device
+            .get_parameter(qhyccd_rs::Control::Gain)
+            .map_or_else(
+                |e| {
+                    error!(?e, "failed to set gain");
+                    Err(ASCOMError::INVALID_OPERATION)
+                },
+                |gain| Ok(gain as i32),
+            )
+    }
+
+    /// Sets a plain gain value, regardless of presets.
+    async fn set_gain_value(&self, gain: i32) -> ASCOMResult {
+        ensure_connected!(self);
+        self.device
+            .is_control_available(qhyccd_rs::Control::Gain)
+            .ok_or_else(|| {
+                debug!("gain control not available");
+                ASCOMError::NOT_IMPLEMENTED
+            })?;
+        let (min, max) = self
+                        .gain_min_max
+                        .read()
+                        .await
+                        .ok_or(ASCOMError::invalid_operation("camera reports gain control available, but min, max values are not set after initialization"))?;
+        if !(min as i32..=max as i32).contains(&gain) {
+            return Err(ASCOMError::INVALID_VALUE);
+        }
+        self.device
+            .set_parameter(qhyccd_rs::Control::Gain, gain as f64)
+            .map_err(|e| {
+                error!(?e, "failed to set gain");
+                ASCOMError::INVALID_OPERATION
+            })
+    }
+
+    /// The offset as set on the camera, regardless of presets.
+    async fn offset_value(&self) -> ASCOMResult<i32> {
+        ensure_connected!(self);
+        self.device
+            .is_control_available(qhyccd_rs::Control::Offset)
+            .ok_or_else(|| {
+                debug!("offset control not available");
+                ASCOMError::NOT_IMPLEMENTED
+            })?;
+        self.device
+            .get_parameter(qhyccd_rs::Control::Offset)
+            .map_or_else(
+                |e| {
+                    error!(?e, "failed to get offset");
+                    Err(ASCOMError::INVALID_OPERATION)
+                },
+                |offset| Ok(offset as i32),
+            )
+    }
+
+    /// Sets a plain offset value, regardless of presets.
+    async fn set_offset_value(&self, offset: i32) -> ASCOMResult {
+        ensure_connected!(self);
+        self.device
+            .is_control_available(qhyccd_rs::Control::Offset)
+            .ok_or_else(|| {
+                debug!("offset control not available");
+                ASCOMError::NOT_IMPLEMENTED
+            })?;
+        let (min, max) = self
+                        .offset_min_max
+                        .read()
+                        .await
+                        .ok_or(ASCOMError::invalid_operation("camera reports offset control available, but min, max values are not set after initialization"))?;
+        if !(min as i32..=max as i32).contains(&offset) {
+            return Err(ASCOMError::INVALID_VALUE);
+        }
+        self.device
+            .set_parameter(qhyccd_rs::Control::Offset, offset as f64)
+            .map_err(|e| {
+                error!(?e, "failed to set offset");
+                ASCOMError::INVALID_OPERATION
+            })
+    }
+
+    /// The e-/ADU and full well of the sensor at the current gain and readout mode.
+    async fn sensor_point(&self) -> ASCOMResult<sensor::SensorPoint> {
+        ensure_connected!(self);
+        let Some(model) = self.model() else {
+            error!("camera id should be MODEL-SerialNumber, but split failed");
+            return Err(ASCOMError::NOT_IMPLEMENTED);
+        };
+        let Some(curves) = sensor::curves(model, &self.config.sensors().await) else {
+            debug!(model, "no sensor characteristics known");
+            return Err(ASCOMError::NOT_IMPLEMENTED);
+        };
+        let readout_mode = self.device.get_readout_mode().ok();
+        let gain = self
+            .available_parameter(qhyccd_rs::Control::Gain)
+            .unwrap_or_default();
+        let point = sensor::interpolate(&curves, readout_mode, gain).ok_or_else(|| {
+            debug!(model, ?readout_mode, "no sensor curve for readout mode");
+            ASCOMError::NOT_IMPLEMENTED
+        })?;
+        Ok(point.shifted(self.output_shift().await))
+    }
+
+    /// How many bits the frames read with the current settings are shifted down from the
+    /// 16 bit output the sensor characteristics are given for.
+    async fn output_shift(&self) -> u32 {
+        match self.native_bits().await {
+            Some(bits) => 16_u32.saturating_sub(bits),
+            None => 16 - u32::from(*self.transfer_bits.read().await),
+        }
+    }
+
+    /// Ends the integration early, returning when it ended so the real duration can be reported.
+    ///
+    /// A camera that refuses to stop no longer offers `StopExposure`.
+    fn stop_integration_static(
+        device: &QhyCamera,
+        stop_supported: &AtomicBool,
+    ) -> Option<SystemTime> {
+        match device.stop_exposure() {
+            Ok(()) => {
+                debug!("exposure stopped, reading out");
+                Some(SystemTime::now())
+            }
+            Err(e) => {
+                error!(?e, "failed to stop exposure, reading out full exposure");
+                stop_supported.store(false, Ordering::Relaxed);
+                None
+            }
+        }
+    }
+
+    fn get_valid_binning_modes(&self) -> Vec<u8> {
+        let mut valid_binning_modes = Vec::with_capacity(6);
+        self.device
+            .is_control_available(qhyccd_rs::Control::CamBin1x1mode)
+            .is_some()
+            .then(|| valid_binning_modes.push(1_u8));
+        self.device
+            .is_control_available(qhyccd_rs::Control::CamBin2x2mode)
+            .is_some()
+            .then(|| valid_binning_modes.push(2_u8));
+        self.device
+            .is_control_available(qhyccd_rs::Control::CamBin3x3mode)
+            .is_some()
+            .then(|| valid_binning_modes.push(3_u8));
+        self.device
+            .is_control_available(qhyccd_rs::Control::CamBin4x4mode)
+            .is_some()
+            .then(|| valid_binning_modes.push(4_u8));
+        self.device
+            .is_control_available(qhyccd_rs::Control::CamBin6x6mode)
+            .is_some()
+            .then(|| valid_binning_modes.push(6_u8));
+        self.device
+            .is_control_available(qhyccd_rs::Control::CamBin8x8mode)
+            .is_some()
+            .then(|| valid_binning_modes.push(8_u8));
+        valid_binning_modes
+    }
+
+    /// Converts a frame to the `ImageArray` served to clients.
+    ///
+    /// The pixels are copied once from the SDK buffer into the array, `ImageArray` converts
+    /// them from there. Colour frames become three planes in RGB order, 32-bit samples are
+    /// mapped to `i32`.
+    fn transform_image_static(image: &qhyccd_rs::ImageData) -> Result<ImageArray> {
+        let bytes_per_sample = image::bytes_per_sample(image)?;
+        let shape = (
+            image.height as usize,
+            image.width as usize,
+            image.channels as usize,
+        );
+        let samples = &image.data[0_usize..shape.0 * shape.1 * shape.2 * bytes_per_sample];
+        match bytes_per_sample {
+            1_usize => Self::to_image_array(shape, samples.to_vec()),
+            2_usize => Self::to_image_array(
+                shape,
+                samples
+                    .chunks_exact(2)
+                    .map(|a| u16::from_ne_bytes([a[0], a[1]]))
+                    .collect(),
+            ),
+            _ => Self::to_image_array(
+                shape,
+                samples
+                    .chunks_exact(4)
+                    .map(|a| i32::from_ne_bytes([a[0], a[1], a[2], a[3]]))
+                    .collect(),
+            ),
+        }
+    }
+
+    /// Arranges samples read row by row, BGR for colour frames, as `[x, y, plane]`.
+    fn to_image_array<T>(shape: (usize, usize, usize), data: Vec<T>) -> Result<ImageArray>
+    where
+        Array3<T>: Into<ImageArray>,
+    {
+        let mut array = Array3::from_shape_vec(shape, data).map_err(|e| {
+            error!(?e, "could not transform image");
+            eyre!(e)
+        })?;
+        array.swap_axes(0, 1);
+        array.invert_axis(Axis(2));
+        Ok(array.into())
+    }
+
+    /// Gets the shutter and the dark frame policy ready for the requested frame type.
+    ///
+    /// Frames no longer than the minimum exposure time are treated as bias frames.
+    async fn prepare_frame(&self, duration: Duration, light: bool) -> ASCOMResult<FrameType> {
+        if light {
+            // the shutter can only have been closed by a previous dark frame
+            let mut shutter_closed = self.shutter_closed.write().await;
+            if *shutter_closed {
+                self.device
+                    .set_parameter(qhyccd_rs::Control::CamMechanicalShutter, SHUTTER_OPEN)
+                    .map_err(|e| {
+                        error!(?e, "failed to open shutter");
+                        ASCOMError::INVALID_OPERATION
+                    })?;
+                *shutter_closed = false;
+            }
+            return Ok(FrameType::Light);
+        }
+        let frame_type = match *self.exposure_min_max_step.read().await {
+            Some((min, _max, _step)) if duration.as_secs_f64() * 1_000_000_f64 <= min => {
+                FrameType::Bias
+            }
+            _ => FrameType::Dark,
+        };
+        if self
+            .device
+            .is_control_available(qhyccd_rs::Control::CamMechanicalShutter)
+            .is_some()
+        {
+            self.device
+                .set_parameter(qhyccd_rs::Control::CamMechanicalShutter, SHUTTER_CLOSED)
+                .map_err(|e| {
+                    error!(?e, "failed to close shutter");
+                    ASCOMError::INVALID_OPERATION
+                })?;
+            *self.shutter_closed.write().await = true;
+            return Ok(frame_type);
+        }
+        match self.dark_frame_policy {
+            DarkFramePolicy::Allow => {
+                debug!(
+                    ?frame_type,
+                    "no mechanical shutter, taking frame as requested"
+                );
+            }
+            DarkFramePolicy::Warn => {
+                warn!(
+                    ?frame_type,
+                    "no mechanical shutter, make sure the sensor is covered"
+                );
+            }
+            DarkFramePolicy::Reject => {
+                error!(?frame_type, "no mechanical shutter, rejecting frame");
+                return Err(ASCOMError::invalid_operation(
+                    "dark frames not supported without a mechanical shutter",
+                ));
+            }
+        }
+        Ok(frame_type)
+    }
+
+    /// Starts an exposure, returning a receiver that turns `true` once the image is stored.
+    ///
+    /// The receiver closes without turning `true` if the exposure fails or is aborted. With
+    /// `export` the frame is also written to a FITS file if FITS export is on, sequences write
+    /// their frames themselves.
+    async fn begin_exposure(
+        &self,
+        duration: Duration,
+        light: bool,
+        export: bool,
+    ) -> ASCOMResult<watch::Receiver<bool>> {
+        ensure_connected!(self);
+        if self.live.read().await.is_some() {
+            error!("cannot start an exposure in live mode");
+            return Err(ASCOMError::invalid_operation(
+                "camera is in live mode, stop it first",
+            ));
+        }
+        if self.start_x().await? > self.num_x().await? {
+            return Err(ASCOMError::invalid_value("StartX > NumX"));
+        }
+        if self.start_y().await? > self.num_y().await? {
+            return Err(ASCOMError::invalid_value("StartY > NumY"));
+        }
+        if self.num_x().await?
+            > (self.camera_x_size().await? as f32 / self.bin_x().await? as f32) as u32
+        {
+            return Err(ASCOMError::invalid_value("NumX > CameraXSize"));
+        }
+        if self.num_y().await?
+            > (self.camera_y_size().await? as f32 / self.bin_y().await? as f32) as u32
+        {
+            return Err(ASCOMError::invalid_value("NumY > CameraYSize"));
+        }
+        let Some(roi) = *self.intended_roi.read().await else {
+            debug!("no roi defined, but trying to start exposure");
+            return Err(ASCOMError::invalid_value("no ROI defined for camera"));
+        };
+        if let Some(readable) = self.readable_area().await {
+            if !overscan::contains(readable, roi) {
+                debug!(?roi, ?readable, "ROI outside the readable area");
+                return Err(ASCOMError::invalid_value(
+                    "ROI exceeds the readable area of the chip",
+                ));
+            }
+        }
+        let (_, factor, software_binning) = self.binning_parts().await;
+        // the camera reads the ROI at the hardware binning
+        self.device
+            .set_roi(factor.rescale(roi, Binning::symmetric(1)))
+            .map_err(|e| {
+                debug!(?e, "failed to set ROI");
+                ASCOMError::invalid_value("failed to set ROI")
+            })?;
+        let software_binning = software_binning.map(|mode| (factor, mode));
+        let native_bits = self.native_bits().await;
+        let usb_backoff = self.usb_backoff().await;
+        let frame_type = self.prepare_frame(duration, light).await?;
+        let exposure_us = (duration.as_secs_f64() * 1_000_000_f64) as u32;
+        let (stop_tx, mut stop_rx) = oneshot::channel::<StopExposure>();
+        let (done_tx, done_rx) = watch::channel(false);
+
+        let start = SystemTime::now();
+        let mut lock = self.state.write().await;
+        *lock = match *lock {
+            State::Idle => State::Exposing {
+                start,
+                expected_duration_us: exposure_us,
+                stop_tx: Some(stop_tx),
+                done_rx: done_rx.clone(),
+            },
+            State::Exposing { .. } => {
+                error!("camera already exposing");
+                return Err(ASCOMError::INVALID_OPERATION);
+            }
+        };
+        drop(lock);
+
+        *self.last_exposure_start_time.write().await = Some(start);
+        *self.last_exposure_duration_us.write().await = Some(exposure_us);
+
+        let fits = match (
+            &self.fits_export,
+            export && *self.fits_export_enabled.read().await,
+        ) {
+            (Some(export), true) => Some((
+                export.clone(),
+                self.fits_metadata(frame_type, start, exposure_us).await,
+            )),
+            _ => None,
+        };
+
+        self.device
+            .set_parameter(qhyccd_rs::Control::Exposure, exposure_us as f64)
+            .map_err(|e| {
+                error!(?e, "failed to set exposure time: {:?}", e);
+                ASCOMError::INVALID_OPERATION
+            })?;
+
+        let device = self.device.clone();
+        // Create separate device instance for abort to ensure proper SDK synchronization
+        // According to SDK docs, after successful abort we must complete data exchange
+        let device_for_abort = self.device.clone();
+        let state = self.state.clone();
+        let last_image = self.last_image.clone();
+        let origin = (roi.start_x, roi.start_y);
+        let binning = *self.binning.read().await;
+        let debayering = self.frame_debayering(binning, origin).await;
+        let (overscan, _data_area) = self.frame_areas(roi, binning).await;
+        let last_exposure_duration_us = self.last_exposure_duration_us.clone();
+        let stop_supported = self.stop_supported.clone();
+
+        tokio::spawn(async move {
+            // set when the exposure was stopped early, the image is still read out
+            let mut stopped_at = None;
+            // the receiver must not be polled again once it yielded a value or closed
+            let mut listening = true;
+            debug!("DEBUG: New implementation started");
+            // raises the USB traffic after a failed readout, if configured
+            let back_off = || async {
+                if let Some(usb_backoff) = &usb_backoff {
+                    usb_backoff.apply(&device_for_abort).await;
+                }
+            };
+            // Helper function to handle abort and data exchange
+            let handle_abort = || async {
+                debug!("DEBUG: Handling abort");
+                match device_for_abort.abort_exposure_and_readout() {
+                    Ok(()) => {
+                        debug!("abort succeeded, completing data exchange for sync");
+                        if let Ok(buffer_size) = device_for_abort.get_image_size() {
+                            let frame = device_for_abort.get_single_frame(buffer_size);
+                            if let Err(e) = &frame {
+                                error!(?e, "reading the aborted frame failed");
+                                back_off().await;
+                            }
+                            if let Ok(mut image) = frame {
+                                if let Some(bits) = native_bits {
+                                    image::to_native(&mut image, bits);
+                                }
+                                let image = match software_binning {
+                                    Some((factor, mode)) => binning::apply(&image, factor, mode),
+                                    None => Ok(image),
+                                };
+                                let image = match debayering {
+                                    Some((pattern, method)) => image.and_then(|image| {
+                                        debayer::debayer(&image, pattern, method)
+                                    }),
+                                    None => image,
+                                };
+                                match image.and_then(|image| {
+                                    image::bytes_per_sample(&image).map(|_| image)
+                                }) {
+                                    Ok(image) => {
+                                        *last_image.write().await = Some(LastImage {
+                                            image: Arc::new(image),
+                                            frame_type,
+                                            origin,
+                                            binning,
+                                            overscan,
+                                            image_array: Arc::default(),
+                                        });
+                                        debug!("aborted exposure data stored");
+                                    }
+                                    Err(e) => error!(?e, "unsupported aborted image"),
+                                }
+                            }
+                        }
+                    }
+                    Err(e) => error!(?e, "failed to abort exposure"),
+                }
+                debug!("exposure aborted");
+            };
+
+            // Execute start_single_frame_exposure
+            let start_task = task::spawn_blocking({
+                let device = device.clone();
+                move || {
+                    device.start_single_frame_exposure().map_err(|e| {
+                        error!(?e, "failed to start exposure: {:?}", e);
+                        ASCOMError::INVALID_OPERATION
+                    })
+                }
+            });
+
+            match start_task.await {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => {
+                    error!(?e, "start exposure failed");
+                    *state.write().await = State::Idle;
+                    return;
+                }
+                Err(e) => {
+                    error!(?e, "start task failed");
+                    *state.write().await = State::Idle;
+                    return;
+                }
+            }
+
+            // Check for abort after start_single_frame_exposure
+            debug!("DEBUG: Checking for abort after start_single_frame_exposure");
+            match stop_rx.try_recv() {
+                Ok(StopExposure { want_image: false }) => {
+                    debug!("DEBUG: Abort detected after start_single_frame_exposure!");
+                    handle_abort().await;
+                    *state.write().await = State::Idle;
+                    return;
+                }
+                Ok(StopExposure { want_image: true }) => {
+                    listening = false;
+                    stopped_at =
+                        QhyccdCamera::stop_integration_static(&device_for_abort, &stop_supported);
+                }
+                Err(oneshot::error::TryRecvError::Empty) => {}
+                Err(e) => {
+                    listening = false;
+                    debug!("DEBUG: No abort signal: {:?}", e);
+                }
+            }
+
+            // Execute get_image_size
+            let size_task = task::spawn_blocking({
+                let device = device.clone();
+                move || {
+                    device.get_image_size().map_err(|e| {
+                        error!(?e, "get_image_size failed");
+                        ASCOMError::INVALID_OPERATION
+                    })
+                }
+            });
+
+            let buffer_size = match size_task.await {
+                Ok(Ok(size)) => {
+                    debug!(?size);
+                    size
+                }
+                Ok(Err(e)) => {
+                    error!(?e, "get image size failed");
+                    *state.write().await = State::Idle;
+                    return;
+                }
+                Err(e) => {
+                    error!(?e, "size task failed");
+                    *state.write().await = State::Idle;
+                    return;
+                }
+            };
+
+            // Check for abort after get_image_size
+            if listening {
+                match stop_rx.try_recv() {
+                    Ok(StopExposure { want_image: false }) => {
+                        handle_abort().await;
+                        *state.write().await = State::Idle;
+                        return;
+                    }
+                    Ok(StopExposure { want_image: true }) => {
+                        listening = false;
+                        stopped_at = QhyccdCamera::stop_integration_static(
+                            &device_for_abort,
+                            &stop_supported,
+                        );
+                    }
+                    Err(oneshot::error::TryRecvError::Empty) => {}
+                    Err(_) => listening = false,
+                }
+            }
+
+            // Execute get_single_frame
+            let mut image_task = task::spawn_blocking({
+                move || {
+                    device.get_single_frame(buffer_size).map_err(|e| {
+                        error!(?e, "get_single_frame failed");
+                        ASCOMError::INVALID_OPERATION
+                    })
+                }
+            });
+
+            // get_single_frame blocks until the exposure is over, so stop and abort requests
+            // have to be served while it is running
+            let image_result = tokio::select! {
+                result = &mut image_task => result,
+                Ok(stop) = &mut stop_rx, if listening => {
+                    if !stop.want_image {
+                        if let Err(e) = device_for_abort.abort_exposure_and_readout() {
+                            error!(?e, "failed to abort exposure");
+                        }
+                        // the pending readout completes the data exchange the SDK expects
+                        if let Ok(Err(e)) = image_task.await {
+                            error!(?e, "reading the aborted frame failed");
+                            back_off().await;
+                        }
+                        *state.write().await = State::Idle;
+                        debug!("exposure aborted");
+                        return;
+                    }
+                    stopped_at = QhyccdCamera::stop_integration_static(&device_for_abort, &stop_supported);
+                    image_task.await
+                }
+            };
+
+            let image = match image_result {
+                Ok(Ok(image)) => image,
+                Ok(Err(e)) => {
+                    error!(?e, "get single frame failed");
+                    back_off().await;
+                    *state.write().await = State::Idle;
+                    return;
+                }
+                Err(e) => {
+                    error!(?e, "image task failed");
+                    *state.write().await = State::Idle;
+                    return;
+                }
+            };
+
+            let image = match native_bits {
+                Some(bits) => {
+                    let scale_task = task::spawn_blocking(move || {
+                        let mut image = image;
+                        image::to_native(&mut image, bits);
+                        image
+                    });
+                    match scale_task.await {
+                        Ok(image) => image,
+                        Err(e) => {
+                            error!(?e, "scaling task failed");
+                            *state.write().await = State::Idle;
+                            return;
+                        }
+                    }
+                }
+                None => image,
+            };
+
+            let image = match software_binning {
+                Some((factor, mode)) => {
+                    match task::spawn_blocking(move || binning::apply(&image, factor, mode)).await {
+                        Ok(Ok(image)) => image,
+                        Ok(Err(e)) => {
+                            error!(?e, "software binning failed");
+                            *state.write().await = State::Idle;
+                            return;
+                        }
+                        Err(e) => {
+                            error!(?e, "binning task failed");
+                            *state.write().await = State::Idle;
+                            return;
+                        }
+                    }
+                }
+                None => image,
+            };
+
+            // debayered on readout, so ImageArray, ImageBytes and FITS files get the same frame
+            let image = match debayering {
+                Some((pattern, method)) => {
+                    match task::spawn_blocking(move || debayer::debayer(&image, pattern, method))
+                        .await
+                    {
+                        Ok(Ok(image)) => image,
+                        Ok(Err(e)) => {
+                            error!(?e, "debayering failed");
+                            *state.write().await = State::Idle;
+                            return;
+                        }
+                        Err(e) => {
+                            error!(?e, "debayer task failed");
+                            *state.write().await = State::Idle;
+                            return;
+                        }
+                    }
+                }
+                None => image,
+            };
+
+            let elapsed_us = stopped_at
+                .and_then(|stopped_at| stopped_at.duration_since(start).ok())
+                .map(|elapsed| u32::try_from(elapsed.as_micros()).unwrap_or(u32::MAX));
+
+            // Write the frame to disk before it is stored
+            let image = match fits {
+                Some((export, mut metadata)) => {
+                    metadata.exposure_us = elapsed_us.unwrap_or(exposure_us);
+                    if debayering.is_some() {
+                        // the colour planes no longer have a Bayer pattern
+                        metadata.bayer_pattern = None;
+                    }
+                    let fits_task = task::spawn_blocking(move || {
+                        let res = fits::write(&export, &metadata, &image);
+                        (image, res)
+                    });
+                    match fits_task.await {
+                        Ok((image, Ok(path))) => {
+                            debug!(?path, "image written");
+                            image
+                        }
+                        Ok((image, Err(e))) => {
+                            error!(?e, "failed to write FITS file");
+                            image
+                        }
+                        Err(e) => {
+                            error!(?e, "fits task failed");
+                            *state.write().await = State::Idle;
+                            return;
+                        }
+                    }
+                }
+                None => image,
+            };
+
+            // Store the image, it is only converted when a client asks for it
+            match image::bytes_per_sample(&image) {
+                Ok(_) => {
+                    if let Some(elapsed_us) = elapsed_us {
+                        debug!(?elapsed_us, "exposure was stopped early");
+                        *last_exposure_duration_us.write().await = Some(elapsed_us);
+                    }
+                    *last_image.write().await = Some(LastImage {
+                        image: Arc::new(image),
+                        frame_type,
+                        origin,
+                        binning,
+                        overscan,
+                        image_array: Arc::default(),
+                    });
+                    let _ = done_tx.send(true);
+                    debug!("exposure completed successfully");
+                }
+                Err(e) => error!(?e, "unsupported image"),
+            }
+
+            *state.write().await = State::Idle;
+        });
+
+        Ok(done_rx)
+    }
+
+    /// The ADC depth 16 bit frames are shifted down to, if the camera publishes native values.
+    async fn native_bits(&self) -> Option<u32> {
+        let config = self.config.camera(&self.unique_id).await;
+        if config.adu_scaling != Some(AduScaling::Native) || *self.transfer_bits.read().await != 16
+        {
+            return None;
+        }
+        match self
+            .device
+            .get_parameter(qhyccd_rs::Control::OutputDataActualBits)
+        {
+            Ok(bits) => Some(bits as u32),
+            Err(e) => {
+                warn!(?e, "could not get OutputDataActualBits, frames stay padded");
+                None
+            }
+        }
+    }
+
+    /// The largest sample value of the frames read with the current settings.
+    ///
+    /// Without an `adu_scaling` 16 bit frames report `2^OutputDataActualBits`, as before the
+    /// option existed.
+    async fn adu_max(&self) -> ASCOMResult<u32> {
+        let transfer_bits = u32::from(*self.transfer_bits.read().await);
+        let scaling = self.config.camera(&self.unique_id).await.adu_scaling;
+        let max_adu = match (transfer_bits, scaling) {
+            (8, _) => Ok(2_u32.pow(8) - 1),
+            (bits, Some(AduScaling::Padded)) => Ok(2_u32.pow(bits) - 1),
+            (_, scaling) => self
+                .device
+                .get_parameter(qhyccd_rs::Control::OutputDataActualBits)
+                .map_or_else(
+                    |e| {
+                        error!(?e, "could not get OutputDataActualBits");
+                        Err(ASCOMError::VALUE_NOT_SET)
+                    },
+                    |bits| {
+                        debug!(?bits, "ADU");
+                        match scaling {
+                            Some(_) => Ok(2_u32.pow(bits as u32) - 1),
+                            None => Ok(2_u32.pow(bits as u32)),
+                        }
+                    },
+                ),
+        }?;
+        // summed pixels reach a multiple of the range of the camera
+        match self.binning_parts().await {
+            (_, factor, Some(SoftwareBinning::Sum)) => Ok(max_adu.saturating_mul(factor.pixels())),
+            _ => Ok(max_adu),
+        }
+    }
+
+    /// The current value of `control`, if the camera has it.
+    fn available_parameter(&self, control: qhyccd_rs::Control) -> Option<f64> {
+        self.device
+            .is_control_available(control)
+            .and_then(|_| self.device.get_parameter(control).ok())
+    }
+
+    /// The Bayer pattern of a frame read with `binning` from a ROI starting at `origin`.
+    ///
+    /// `None` for monochrome cameras and binned frames, which no longer have a Bayer pattern.
+    fn frame_bayer_pattern(
+        &self,
+        binning: Binning,
+        origin: (u32, u32),
+    ) -> Option<qhyccd_rs::BayerMode> {
+        if !binning.is_unbinned() {
+            return None;
+        }
+        self.device
+            .is_control_available(qhyccd_rs::Control::CamIsColor)
+            .and_then(|_| {
+                self.device
+                    .is_control_available(qhyccd_rs::Control::CamColor)
+            })
+            .and_then(|bayer_id| qhyccd_rs::BayerMode::try_from(bayer_id).ok())
+            .map(|pattern| bayer_pattern_at(pattern, origin.0, origin.1))
+    }
+
+    /// The pattern and method a frame read with `binning` from a ROI starting at `origin` is
+    /// debayered with, `None` if it stays raw.
+    async fn frame_debayering(
+        &self,
+        binning: Binning,
+        origin: (u32, u32),
+    ) -> Option<(qhyccd_rs::BayerMode, DebayerMethod)> {
+        let method = (*self.debayer.read().await)?;
+        self.frame_bayer_pattern(binning, origin)
+            .map(|pattern| (pattern, method))
+    }
+
+    /// Converts a frame for `ImageArray`, debayering it first if `debayering` is set.
+    ///
+    /// Converting a large frame takes a while, so it runs off the runtime.
+    async fn convert_image(
+        image: Arc<qhyccd_rs::ImageData>,
+        debayering: Option<(qhyccd_rs::BayerMode, DebayerMethod)>,
+    ) -> ASCOMResult<ImageArray> {
+        task::spawn_blocking(move || match debayering {
+            Some((pattern, method)) => {
+                QhyccdCamera::transform_image_static(&debayer::debayer(&image, pattern, method)?)
+            }
+            None => QhyccdCamera::transform_image_static(&image),
+        })
+        .await
+        .map_err(|e| {
+            error!(?e, "image task failed");
+            ASCOMError::INVALID_OPERATION
+        })?
+        .map_err(|e| {
+            error!(?e, "failed to transform image");
+            ASCOMError::INVALID_OPERATION
+        })
+    }
+
+    /// Whether frames read with the current binning are debayered.
+    async fn debayers_frames(&self) -> bool {
+        self.debayer.read().await.is_some() && self.binning.read().await.is_unbinned()
+    }
+
+    /// Queries or switches software debayering for this camera.
+    ///
+    /// An empty parameter returns the current method, `off` switches debayering off.
+    async fn debayer_action(&self, parameters: &str) -> ASCOMResult<String> {
+        let mut debayer = self.debayer.write().await;
+        match parameters.trim().to_lowercase().as_str() {
+            "" => {}
+            "off" => *debayer = None,
+            other => match DebayerMethod::from_name(other) {
+                Some(method) => *debayer = Some(method),
+                None => {
+                    error!("invalid Debayer parameter: {}", other);
+                    return Err(ASCOMError::invalid_value(
+                        "Debayer parameter must be off, bilinear, gradient, superpixel or empty",
+                    ));
+                }
+            },
+        }
+        Ok(debayer.map_or("off", DebayerMethod::name).to_owned())
+    }
+
+    /// Collects the FITS header values for an exposure that is about to start.
+    ///
+    /// Values the camera cannot report are left out of the header.
+    async fn fits_metadata(
+        &self,
+        frame_type: FrameType,
+        start: SystemTime,
+        exposure_us: u32,
+    ) -> fits::FitsMetadata {
+        let bin = *self.binning.read().await;
+        let roi = *self.intended_roi.read().await;
+        let ccd_info = *self.ccd_info.read().await;
+        let (start_x, start_y) = roi.map_or((0, 0), |roi| (roi.start_x, roi.start_y));
+        let bayer_pattern = self.frame_bayer_pattern(bin, (start_x, start_y));
+        let (overscan, data_area) = match roi {
+            Some(roi) => self.frame_areas(roi, bin).await,
+            None => (None, None),
+        };
+        fits::FitsMetadata {
+            camera: self.model().unwrap_or(&self.unique_id).to_owned(),
+            frame_type,
+            date_obs: start,
+            exposure_us,
+            gain: self.available_parameter(qhyccd_rs::Control::Gain),
+            offset: self.available_parameter(qhyccd_rs::Control::Offset),
+            bin_x: bin.x,
+            bin_y: bin.y,
+            start_x,
+            start_y,
+            ccd_temperature: self
+                .device
+                .is_control_available(qhyccd_rs::Control::Cooler)
+                .and_then(|_| self.device.get_parameter(qhyccd_rs::Control::CurTemp).ok()),
+            set_temperature: self.cooler.read().await.target,
+            readout_mode: self
+                .device
+                .get_readout_mode()
+                .and_then(|mode| self.device.get_readout_mode_name(mode))
+                .ok(),
+            bayer_pattern,
+            pixel_width: ccd_info.map_or(0_f64, |ccd_info| ccd_info.pixel_width),
+            pixel_height: ccd_info.map_or(0_f64, |ccd_info| ccd_info.pixel_height),
+            overscan,
+            data_area,
+            data_max: match self.config.camera(&self.unique_id).await.adu_scaling {
+                Some(_) => self.adu_max().await.ok(),
+                None => None,
+            },
+        }
+    }
+
+    /// Whether `BinX` and `BinY` are set independently, see `can_asymmetric_bin`.
+    async fn asymmetric_binning(&self) -> bool {
+        let config = self.config.camera(&self.unique_id).await;
+        config.asymmetric_binning.unwrap_or(false) || config.software_binning.is_some()
+    }
+
+    /// Switches to the binning `change` makes of the current one after checking `bin` against
+    /// the offered bins, and rescales the ROI axis by axis.
+    ///
+    /// The camera is set to the hardware part of the binning, see [`binning::split`].
+    async fn set_binning(&self, bin: u8, change: impl FnOnce(Binning) -> Binning) -> ASCOMResult {
+        let valid_bins = self.valid_bins.read().await.clone().ok_or_else(|| {
+            error!("valid_bins not set");
+            ASCOMError::NOT_CONNECTED
+        })?;
+        let config = self.config.camera(&self.unique_id).await;
+        let software = config.software_binning.is_some();
+        let invalid = || {
+            error!("trying to set invalid bin value: {}", bin);
+            ASCOMError::invalid_value("bin value must be one of the valid bins")
+        };
+        binning::offered(&valid_bins, software)
+            .iter()
+            .find(|valid| **valid == bin)
+            .ok_or_else(invalid)?;
+        let mut lock = self.binning.write().await;
+        let old = *lock;
+        let new = change(old);
+        if old == new {
+            return Ok(());
+        };
+        let (read, _) = binning::split(
+            new,
+            &valid_bins,
+            config.asymmetric_binning.unwrap_or(false),
+            software,
+        )
+        .ok_or_else(invalid)?;
+        self.device
+            .set_bin_mode(read.x as u32, read.y as u32)
+            .map_err(|e| {
+                error!(?e, "set_bin_mode failed");
+                ASCOMError::VALUE_NOT_SET
+            })?;
+        *lock = new;
+        //adjust start and num values
+        let mut roi_lock = self.intended_roi.write().await;
+        *roi_lock = roi_lock.map(|roi| old.rescale(roi, new));
+        debug!(?new, "binning changed");
+        Ok(())
+    }
+
+    /// The hardware binning the current binning is read with and the factors binned in
+    /// software, with the mode combining them if software binning is needed.
+    async fn binning_parts(&self) -> (Binning, Binning, Option<SoftwareBinning>) {
+        let binning = *self.binning.read().await;
+        let config = self.config.camera(&self.unique_id).await;
+        let split = self
+            .valid_bins
+            .read()
+            .await
+            .as_deref()
+            .and_then(|valid_bins| {
+                binning::split(
+                    binning,
+                    valid_bins,
+                    config.asymmetric_binning.unwrap_or(false),
+                    config.software_binning.is_some(),
+                )
+            });
+        match split {
+            Some((read, factor)) if !factor.is_unbinned() => {
+                (read, factor, config.software_binning)
+            }
+            _ => (binning, Binning::symmetric(1), None),
+        }
+    }
+
+    /// Reloads the mode dependent state after switching to `readout_mode` with a frame of
+    /// `resolution`.
+    ///
+    /// The chip areas are kept within the new frame, a binning the mode lacks falls back to
+    /// 1x1. A ROI that covered `full_frame`, the whole readable area of the previous mode,
+    /// covers the new one, other ROIs are clamped to it, or reset if nothing is left.
+    async fn switch_mode_state(
+        &self,
+        readout_mode: u32,
+        resolution: (u32, u32),
+        full_frame: Option<CCDChipArea>,
+    ) -> Result<()> {
+        self.load_mode_state().await?;
+        let frame = CCDChipArea {
+            start_x: 0,
+            start_y: 0,
+            width: resolution.0,
+            height: resolution.1,
+        };
+        let mut ccd_info = self.ccd_info.write().await;
+        *ccd_info = ccd_info.map(|ccd_info| CCDChipInfo {
+            image_width: resolution.0,
+            image_height: resolution.1,
+            ..ccd_info
+        });
+        drop(ccd_info);
+        let mut effective_area = self.effective_area.write().await;
+        *effective_area = effective_area.map(|area| overscan::clamp(area, frame).unwrap_or(frame));
+        drop(effective_area);
+        let mut overscan_area = self.overscan_area.write().await;
+        *overscan_area = overscan_area.and_then(|area| overscan::clamp(area, frame));
+        if overscan_area.is_none() {
+            *self.include_overscan.write().await = false;
+        }
+        drop(overscan_area);
+
+        let valid_bins = self.valid_bins.read().await.clone().unwrap_or_default();
+        let config = self.config.camera(&self.unique_id).await;
+        let mut binning = self.binning.write().await;
+        let old = *binning;
+        let split = binning::split(
+            old,
+            &valid_bins,
+            config.asymmetric_binning.unwrap_or(false),
+            config.software_binning.is_some(),
+        );
+        let (new, (read, _)) = match split {
+            Some(parts) => (old, parts),
+            None => {
+                warn!(
+                    ?old,
+                    readout_mode, "binning not available in this mode, using 1x1"
+                );
+                let unbinned = Binning::symmetric(1);
+                (unbinned, (unbinned, unbinned))
+            }
+        };
+        self.device
+            .set_bin_mode(read.x as u32, read.y as u32)
+            .wrap_err("set_bin_mode failed")?;
+        *binning = new;
+        drop(binning);
+
+        let readable = self.readable_area().await;
+        let mut roi = self.intended_roi.write().await;
+        *roi = match *roi {
+            Some(current) if Some(current) != full_frame => readable.map(|readable| {
+                overscan::clamp(old.rescale(current, new), readable).unwrap_or(readable)
+            }),
+            _ => readable,
+        };
+        debug!(readout_mode, ?resolution, ?new, roi = ?*roi, "readout mode switched");
+        Ok(())
+    }
+
+    /// Switches back to `previous` after the state of a new readout mode could not be loaded.
+    ///
+    /// If that fails too, or the previous mode is unknown, the cached state matches no mode
+    /// and the camera is disconnected so that connecting again reloads it. The disconnect runs
+    /// in its own task, a sequence switching modes through a gain preset waits for it to end.
+    async fn roll_back_readout_mode(&self, previous: Option<u32>, full_frame: Option<CCDChipArea>) {
+        let rolled_back = match previous {
+            Some(previous) => self.restore_readout_mode(previous, full_frame).await,
+            None => Err(eyre!("previous readout mode unknown")),
+        };
+        match rolled_back {
+            Ok(()) => warn!(previous, "switched back to the previous readout mode"),
+            Err(e) => {
+                error!(
+                    ?e,
+                    previous, "rolling back the readout mode failed, disconnecting"
+                );
+                if let Some(camera) = self.this.upgrade() {
+                    tokio::spawn(async move {
+                        if let Err(e) = camera.set_connected(false).await {
+                            error!(?e, "disconnecting failed");
+                        }
+                    });
+                }
+            }
+        }
+    }
+
+    async fn restore_readout_mode(
+        &self,
+        readout_mode: u32,
+        full_frame: Option<CCDChipArea>,
+    ) -> Result<()> {
+        let resolution = self
+            .device
+            .get_readout_mode_resolution(readout_mode)
+            .wrap_err("get_readout_mode_resolution failed")?;
+        self.device
+            .set_readout_mode(readout_mode)
+            .wrap_err("set_readout_mode failed")?;
+        self.switch_mode_state(readout_mode, resolution, full_frame)
+            .await
+    }
+
+    /// The full frame at the current binning, the whole chip if the overscan is read out.
+    ///
+    /// `None` until the chip areas are known on connect.
+    async fn readable_area(&self) -> Option<CCDChipArea> {
+        let effective_area = (*self.effective_area.read().await)?;
+        let full_frame = overscan::full_frame(
+            *self.include_overscan.read().await,
+            effective_area,
+            *self.ccd_info.read().await,
+        );
+        Some(overscan::binned(full_frame, *self.binning.read().await))
+    }
+
+    /// The overscan and the effective area within a frame read with `roi` at `bin`, both
+    /// `None` unless the overscan is read out.
+    async fn frame_areas(
+        &self,
+        roi: CCDChipArea,
+        bin: Binning,
+    ) -> (Option<CCDChipArea>, Option<CCDChipArea>) {
+        if !*self.include_overscan.read().await {
+            return (None, None);
+        }
+        let in_frame =
+            |area: Option<CCDChipArea>| area.and_then(|area| overscan::in_frame(area, roi, bin));
+        (
+            in_frame(*self.overscan_area.read().await),
+            in_frame(*self.effective_area.read().await),
+        )
+    }
+
+    /// Queries (empty `parameters`) or switches the overscan readout, returns the chip areas
+    /// and the overscan within the last image as JSON.
+    async fn overscan_action(&self, parameters: &str) -> ASCOMResult<String> {
+        ensure_connected!(self);
+        match parameters.trim().to_lowercase().as_str() {
+            "" => {}
+            "true" => self.set_include_overscan(true).await?,
+            "false" => self.set_include_overscan(false).await?,
+            other => {
+                error!("invalid Overscan parameter: {}", other);
+                return Err(ASCOMError::invalid_value(
+                    "Overscan parameter must be true, false or empty",
+                ));
+            }
+        }
+        Ok(serde_json::json!({
+            "include_overscan": *self.include_overscan.read().await,
+            "effective_area": overscan::to_json(*self.effective_area.read().await),
+            "overscan_area": overscan::to_json(*self.overscan_area.read().await),
+            "image_overscan": overscan::to_json(
+                self.last_image
+                    .read()
+                    .await
+                    .as_ref()
+                    .and_then(|last_image| last_image.overscan)
+            ),
+        })
+        .to_string())
+    }
+
+    /// Switches the overscan readout and resets the ROI to the new full frame.
+    async fn set_include_overscan(&self, include_overscan: bool) -> ASCOMResult {
+        if *self.state.read().await != State::Idle {
+            error!("cannot switch the overscan readout during an exposure");
+            return Err(ASCOMError::invalid_operation(
+                "cannot switch the overscan readout during an exposure",
+            ));
+        }
+        if self.live.read().await.is_some() {
+            error!("cannot switch the overscan readout in live mode");
+            return Err(ASCOMError::invalid_operation(
+                "camera is in live mode, stop it first",
+            ));
+        }
+        let Some(effective_area) = *self.effective_area.read().await else {
+            error!("effective area not set, but camera connected");
+            return Err(ASCOMError::NOT_CONNECTED);
+        };
+        if include_overscan && self.overscan_area.read().await.is_none() {
+            error!("camera reports no overscan area");
+            return Err(ASCOMError::invalid_operation(
+                "camera reports no overscan area",
+            ));
+        }
+        *self.include_overscan.write().await = include_overscan;
+        let full_frame = overscan::full_frame(
+            include_overscan,
+            effective_area,
+            *self.ccd_info.read().await,
+        );
+        *self.intended_roi.write().await =
+            Some(overscan::binned(full_frame, *self.binning.read().await));
+        debug!(include_overscan, ?full_frame, "overscan readout switched");
+        Ok(())
+    }
+
+    /// Queries (empty `parameters`) or switches the bits per pixel frames are transferred with.
+    async fn transfer_bits_action(&self, parameters: &str) -> ASCOMResult<String> {
+        ensure_connected!(self);
+        match parameters.trim() {
+            "" => {}
+            "8" => self.set_transfer_bits(8).await?,
+            "16" => self.set_transfer_bits(16).await?,
+            other => {
+                error!("invalid TransferBits parameter: {}", other);
+                return Err(ASCOMError::invalid_value(
+                    "TransferBits parameter must be 8, 16 or empty",
+                ));
+            }
+        }
+        Ok(self.transfer_bits.read().await.to_string())
+    }
+
+    /// Switches between 8 and 16 bit transfer, `MaxADU` and the frames follow the new depth.
+    async fn set_transfer_bits(&self, transfer_bits: u8) -> ASCOMResult {
+        if transfer_bits != 8 && transfer_bits != 16 {
+            error!(transfer_bits, "transfer bits must be 8 or 16");
+            return Err(ASCOMError::invalid_value("transfer bits must be 8 or 16"));
+        }
+        if *self.state.read().await != State::Idle {
+            error!("cannot switch the transfer bits during an exposure");
+            return Err(ASCOMError::invalid_operation(
+                "cannot switch the transfer bits during an exposure",
+            ));
+        }
+        if self.live.read().await.is_some() {
+            error!("cannot switch the transfer bits in live mode");
+            return Err(ASCOMError::invalid_operation(
+                "camera is in live mode, stop it first",
+            ));
+        }
+        if transfer_bits == 8
+            && self
+                .device
+                .is_control_available(qhyccd_rs::Control::Cam8bits)
+                .is_none()
+        {
+            error!("camera does not transfer 8 bit frames");
+            return Err(ASCOMError::invalid_operation(
+                "camera does not transfer 8 bit frames",
+            ));
+        }
+        self.device
+            .set_if_available(qhyccd_rs::Control::TransferBit, f64::from(transfer_bits))
+            .map_err(|e| {
+                error!(?e, transfer_bits, "setting transfer bits failed");
+                ASCOMError::INVALID_OPERATION
+            })?;
+        *self.transfer_bits.write().await = transfer_bits;
+        debug!(transfer_bits, "transfer bits switched");
+        Ok(())
+    }
+
+    /// Queries or switches FITS export for this camera.
+    ///
+    /// An empty parameter returns the current state, `true` or `false` switches it.
+    async fn fits_export_action(&self, parameters: &str) -> ASCOMResult<String> {
+        let mut enabled = self.fits_export_enabled.write().await;
+        match parameters.trim().to_lowercase().as_str() {
+            "" => {}
+            "true" => {
+                if self.fits_export.is_none() {
+                    error!("no FITS output directory configured");
+                    return Err(ASCOMError::invalid_operation(
+                        "no FITS output directory configured",
+                    ));
+                }
+                *enabled = true;
+            }
+            "false" => *enabled = false,
+            other => {
+                error!("invalid FitsExport parameter: {}", other);
+                return Err(ASCOMError::invalid_value(
+                    "FitsExport parameter must be true, false or empty",
+                ));
+            }
+        }
+        Ok(enabled.to_string())
+    }
+
+    /// Applies the stored settings after connecting.
+    ///
+    /// Settings the camera rejects are logged and skipped, so a stale file cannot prevent
+    /// connecting.
+    async fn apply_config(&self) {
+        let config = self.config.camera(&self.unique_id).await;
+        debug!(?config, "applying configuration");
+        if let Some(readout_mode) = config.readout_mode {
+            if let Err(e) = self.set_readout_mode(readout_mode as usize).await {
+                warn!(?e, readout_mode, "could not apply configured readout mode");
+            }
+        }
+        if let Some(transfer_bits) = config.transfer_bits {
+            if let Err(e) = self.set_transfer_bits(transfer_bits).await {
+                warn!(
+                    ?e,
+                    transfer_bits, "could not apply configured transfer bits"
+                );
+            }
+        }
+        if let Some(include_overscan) = config.include_overscan {
+            if let Err(e) = self.set_include_overscan(include_overscan).await {
+                warn!(
+                    ?e,
+                    include_overscan, "could not apply configured overscan readout"
+                );
+            }
+        }
+        if let Some(binning) = config.binning {
+            if let Err(e) = self.set_bin_x(binning).await {
+                warn!(?e, binning, "could not apply configured binning");
+            }
+        }
+        if let Some(binning_y) = config.binning_y {
+            if let Err(e) = self.set_bin_y(binning_y).await {
+                warn!(?e, binning_y, "could not apply configured vertical binning");
+            }
+        }
+        if let Some(gain) = config.gain {
+            if let Err(e) = self.set_gain_value(gain).await {
+                warn!(?e, gain, "could not apply configured gain");
+            }
+        }
+        if let Some(offset) = config.offset {
+            if let Err(e) = self.set_offset_value(offset).await {
+                warn!(?e, offset, "could not apply configured offset");
+            }
+        }
+        for (tuning, value) in [
+            (Tuning::UsbTraffic, config.usb_traffic),
+            (Tuning::DdrBuffer, config.ddr_buffer),
+            (Tuning::ReadoutSpeed, config.readout_speed),
+        ] {
+            if let Some(value) = value {
+                if let Err(e) = self.set_tuning(tuning, value).await {
+                    warn!(?e, ?tuning, value, "could not apply configured tuning");
+                }
+            }
+        }
+        if let Some(target_temperature) = config.target_temperature {
+            if let Err(e) = self.set_set_ccd_temperature(target_temperature).await {
+                warn!(
+                    ?e,
+                    target_temperature, "could not apply configured cooler set-point"
+                );
+            }
+        }
+    }
+
+    /// Reads the live settings of the camera, keeping stored values it does not report.
+    async fn live_config(&self) -> CameraConfig {
+        let stored = self.config.camera(&self.unique_id).await;
+        let binning = *self.binning.read().await;
+        CameraConfig {
+            readout_mode: self.device.get_readout_mode().ok(),
+            binning: Some(binning.x),
+            binning_y: (binning.y != binning.x).then_some(binning.y),
+            asymmetric_binning: stored.asymmetric_binning,
+            software_binning: stored.software_binning,
+            adu_scaling: stored.adu_scaling,
+            transfer_bits: {
+                let transfer_bits = *self.transfer_bits.read().await;
+                (transfer_bits != 16).then_some(transfer_bits)
+            },
+            gain: self
+                .available_parameter(qhyccd_rs::Control::Gain)
+                .map(|gain| gain as i32),
+            offset: self
+                .available_parameter(qhyccd_rs::Control::Offset)
+                .map(|offset| offset as i32),
+            usb_traffic: self.available_parameter(qhyccd_rs::Control::UsbTraffic),
+            ddr_buffer: match *self.ddr_range.read().await {
+                Some(_) => self.device.get_parameter(qhyccd_rs::Control::DDR).ok(),
+                None => stored.ddr_buffer,
+            },
+            readout_speed: match *self.readout_speed_min_max_step.read().await {
+                Some(_) => self.device.get_parameter(qhyccd_rs::Control::Speed).ok(),
+                None => stored.readout_speed,
+            },
+            usb_traffic_backoff: stored.usb_traffic_backoff,
+            can_stop_exposure: stored.can_stop_exposure,
+            target_temperature: self.cooler.read().await.target,
+            cool_down_rate: stored.cool_down_rate,
+            warm_up_rate: stored.warm_up_rate,
+            fits_export: match self.fits_export {
+                Some(_) => Some(*self.fits_export_enabled.read().await),
+                None => stored.fits_export,
+            },
+            presets: stored.presets,
+            debayer: *self.debayer.read().await,
+            include_overscan: match *self.overscan_area.read().await {
+                Some(_) => Some(*self.include_overscan.read().await),
+                None => stored.include_overscan,
+            },
+        }
+    }
+
+    /// The range of `control`, if the camera has it.
+    fn control_range(&self, control: qhyccd_rs::Control) -> Option<ControlRange> {
+        self.device.is_control_available(control)?;
+        match self.device.get_parameter_min_max_step(control) {
+            Ok(range) => Some(range.into()),
+            Err(e) => {
+                warn!(?e, ?control, "could not read the range of the control");
+                None
+            }
+        }
+    }
+
+    async fn tuning_range(&self, tuning: Tuning) -> Option<ControlRange> {
+        match tuning {
+            Tuning::UsbTraffic => *self.usb_traffic_range.read().await,
+            Tuning::DdrBuffer => *self.ddr_range.read().await,
+            Tuning::ReadoutSpeed => self
+                .readout_speed_min_max_step
+                .read()
+                .await
+                .map(ControlRange::from),
+        }
+    }
+
+    /// Queries (empty `parameters`) or changes a tuning control and stores it, returns the
+    /// value and the range of the control as JSON.
+    async fn tuning_action(&self, tuning: Tuning, parameters: &str) -> ASCOMResult<String> {
+        ensure_connected!(self);
+        let Some(range) = self.tuning_range(tuning).await else {
+            debug!(?tuning, "control not available");
+            return Err(ASCOMError::NOT_IMPLEMENTED);
+        };
+        if !parameters.trim().is_empty() {
+            let value = parameters.trim().parse::<f64>().map_err(|_| {
+                error!("invalid {} parameter: {}", tuning.action(), parameters);
+                ASCOMError::invalid_value(format!(
+                    "{} parameter must be a number or empty",
+                    tuning.action()
+                ))
+            })?;
+            self.set_tuning(tuning, value).await?;
+            self.config
+                .update_camera(&self.unique_id, |config| {
+                    let stored = match tuning {
+                        Tuning::UsbTraffic => &mut config.usb_traffic,
+                        Tuning::DdrBuffer => &mut config.ddr_buffer,
+                        Tuning::ReadoutSpeed => &mut config.readout_speed,
+                    };
+                    *stored = Some(value);
+                })
+                .await
+                .map_err(|e| {
+                    error!(?e, ?tuning, "saving tuning failed");
+                    ASCOMError::invalid_operation("could not save configuration")
+                })?;
+        }
+        Ok(serde_json::json!({
+            "value": self.device.get_parameter(tuning.control()).ok(),
+            "min": range.min,
+            "max": range.max,
+            "step": range.step,
+        })
+        .to_string())
+    }
+
+    /// Sets a tuning control after checking `value` against its range.
+    async fn set_tuning(&self, tuning: Tuning, value: f64) -> ASCOMResult {
+        let Some(range) = self.tuning_range(tuning).await else {
+            debug!(?tuning, "control not available");
+            return Err(ASCOMError::NOT_IMPLEMENTED);
+        };
+        if !range.contains(value) {
+            error!(
+                ?tuning,
+                value,
+                ?range,
+                "value outside the range of the control"
+            );
+            return Err(ASCOMError::invalid_value(format!(
+                "{} must be between {} and {} in steps of {}",
+                tuning.action(),
+                range.min,
+                range.max,
+                range.step
+            )));
+        }
+        if *self.state.read().await != State::Idle {
+            error!(?tuning, "cannot change a tuning control during an exposure");
+            return Err(ASCOMError::invalid_operation(format!(
+                "cannot change {} during an exposure",
+                tuning.action()
+            )));
+        }
+        self.device
+            .set_parameter(tuning.control(), value)
+            .map_err(|e| {
+                error!(?e, ?tuning, value, "setting tuning control failed");
+                ASCOMError::INVALID_OPERATION
+            })?;
+        debug!(?tuning, value, "tuning control set");
+        Ok(())
+    }
+
+    /// The USB traffic back-off of the camera, if configured and the camera has the control.
+    async fn usb_backoff(&self) -> Option<UsbBackoff> {
+        let by = self
+            .config
+            .camera(&self.unique_id)
+            .await
+            .usb_traffic_backoff?;
+        Some(UsbBackoff {
+            config: self.config.clone(),
+            unique_id: self.unique_id.clone(),
+            by,
+            range: (*self.usb_traffic_range.read().await)?,
+        })
+    }
+
+    /// Reads one environment sensor, or all found on connect, as a JSON object.
+    async fn telemetry_action(&self, sensor: Option<Sensor>) -> ASCOMResult<String> {
+        ensure_connected!(self);
+        let sensors = match sensor {
+            Some(sensor) if !self.sensors.read().await.contains(&sensor) => {
+                debug!(?sensor, "sensor not available");
+                return Err(ASCOMError::ACTION_NOT_IMPLEMENTED);
+            }
+            Some(sensor) => vec![sensor],
+            None => self.sensors.read().await.clone(),
+        };
+        let mut readings = serde_json::Map::new();
+        for sensor in sensors {
+            let value = self.device.get_parameter(sensor.control()).map_err(|e| {
+                error!(?e, ?sensor, "failed to read sensor");
+                ASCOMError::INVALID_OPERATION
+            })?;
+            readings.insert(sensor.key().to_owned(), serde_json::json!(value));
+        }
+        Ok(serde_json::Value::Object(readings).to_string())
+    }
+
+    /// Stores the live settings of the camera, written to the configuration file if there is one.
+    async fn save_settings_action(&self) -> ASCOMResult<String> {
+        ensure_connected!(self);
+        let live_config = self.live_config().await;
+        debug!(?live_config, "saving configuration");
+        self.config
+            .save_camera(&self.unique_id, live_config)
+            .await
+            .map_err(|e| {
+                error!(?e, "saving configuration failed");
+                ASCOMError::invalid_operation("could not save configuration")
+            })?;
+        Ok(String::new())
+    }
+
+    /// Changes the cooler controller and tells the camera, the controller is left as it was if
+    /// the camera rejects the change.
+    ///
+    /// Starts the background task if the cooler is on and the task is not running.
+    async fn update_cooler(
+        &self,
+        update: impl FnOnce(&mut Cooler) -> CoolerCommand,
+    ) -> ASCOMResult {
+        let mut cooler = self.cooler.write().await;
+        let (state, target) = (cooler.state, cooler.target);
+        let command = update(&mut cooler);
+        if let Err(e) = cooler::apply(&self.device, command) {
+            error!(?e, ?command, "could not update cooler");
+            cooler.state = state;
+            cooler.target = target;
+            return Err(ASCOMError::INVALID_OPERATION);
+        }
+        debug!(state = ?cooler.state, target = ?cooler.target, "cooler updated");
+        if cooler.state != CoolerState::Off
+            && cooler.task.as_ref().is_none_or(|task| task.is_finished())
+        {
+            cooler.task = Some(tokio::spawn(cooler::run(
+                self.device.clone(),
+                self.cooler.clone(),
+            )));
+        }
+        Ok(())
+    }
+
+    /// Marks the camera as unplugged and stops everything running on it, see [`hotplug`].
+    ///
+    /// Unlike disconnecting, nothing is sent to the camera, it is gone.
+    async fn detach(&self) {
+        self.attached.store(false, Ordering::Relaxed);
+        self.sequencer.abort().await;
+        self.cfw_detected.store(false, Ordering::Relaxed);
+        if let Some(session) = self.live.write().await.take() {
+            session.stop().await;
+        }
+        {
+            let mut cooler = self.cooler.write().await;
+            if let Some(task) = cooler.task.take() {
+                task.abort();
+            }
+            cooler.state = CoolerState::Off;
+            cooler.close_when_off = false;
+        }
+        if let Err(e) = self.device.close() {
+            debug!(?e, "closing the unplugged camera failed");
+        }
+    }
+
+    /// Switches between single frame and live mode.
+    ///
+    /// The stream mode only takes effect with `init`, which resets the camera, so the readout
+    /// mode, transfer bits, binning, gain, offset, USB traffic and cooler set-point are carried
+    /// over.
+    async fn switch_stream_mode(&self, mode: qhyccd_rs::StreamMode) -> ASCOMResult {
+        let readout_mode = self.device.get_readout_mode().ok();
+        let parameters = [
+            qhyccd_rs::Control::Gain,
+            qhyccd_rs::Control::Offset,
+            qhyccd_rs::Control::UsbTraffic,
+        ]
+        .map(|control| (control, self.available_parameter(control)));
+        debug!(?mode, "switching stream mode");
+        self.device.set_stream_mode(mode).map_err(|e| {
+            error!(?e, "setting StreamMode failed");
+            ASCOMError::INVALID_OPERATION
+        })?;
+        if let Some(readout_mode) = readout_mode {
+            self.device.set_readout_mode(readout_mode).map_err(|e| {
+                error!(?e, readout_mode, "restoring readout mode failed");
+                ASCOMError::INVALID_OPERATION
+            })?;
+        }
+        self.device.init().map_err(|e| {
+            error!(?e, "camera init failed");
+            ASCOMError::INVALID_OPERATION
+        })?;
+        let transfer_bits = *self.transfer_bits.read().await;
+        self.device
+            .set_if_available(qhyccd_rs::Control::TransferBit, f64::from(transfer_bits))
+            .map_err(|e| {
+                error!(?e, transfer_bits, "restoring transfer bits failed");
+                ASCOMError::INVALID_OPERATION
+            })?;
+        let (bin, _, _) = self.binning_parts().await;
+        self.device
+            .set_bin_mode(bin.x as u32, bin.y as u32)
+            .map_err(|e| {
+                error!(?e, "restoring bin mode failed");
+                ASCOMError::INVALID_OPERATION
+            })?;
+        for (control, value) in parameters {
+            if let Some(value) = value {
+                if let Err(e) = self.device.set_parameter(control, value) {
+                    warn!(?e, ?control, value, "could not restore parameter");
+                }
+            }
+        }
+        let cooler = self.cooler.read().await;
+        if let (CoolerState::Regulating, Some(target)) = (cooler.state, cooler.target) {
+            if let Err(e) = cooler::apply(&self.device, CoolerCommand::SetPoint(target)) {
+                warn!(?e, target, "could not restore cooler set-point");
+            }
+        }
+        Ok(())
     }
 
-    pub fn with_port(mut self, port: u16) -> Self {
-        self.port = port;
-        self
+    /// Puts the camera into live mode and starts reading frames into the live feed.
+    async fn start_live(&self, exposure: Option<Duration>) -> ASCOMResult {
+        ensure_connected!(self);
+        let mut live = self.live.write().await;
+        if live.is_some() {
+            return Ok(());
+        }
+        if self.sequencer.is_running().await {
+            error!("cannot start live mode while a sequence is running");
+            return Err(ASCOMError::invalid_operation(
+                "a sequence is running, abort it first",
+            ));
+        }
+        if self.binning_parts().await.2.is_some() {
+            error!("cannot start live mode with software binning");
+            return Err(ASCOMError::invalid_operation(
+                "live mode needs a binning the camera reads in hardware",
+            ));
+        }
+        self.device
+            .is_control_available(qhyccd_rs::Control::CamLiveVideoMode)
+            .ok_or_else(|| {
+                error!("LiveVideoMode is not available");
+                ASCOMError::invalid_operation("camera has no live mode")
+            })?;
+        // held until the session is set, so no exposure can start in between
+        let state = self.state.read().await;
+        if matches!(*state, State::Exposing { .. }) {
+            error!("cannot start live mode during an exposure");
+            return Err(ASCOMError::invalid_operation(
+                "cannot start live mode during an exposure",
+            ));
+        }
+        self.switch_stream_mode(qhyccd_rs::StreamMode::LiveMode)
+            .await?;
+        let buffer_size = match self.begin_live(exposure).await {
+            Ok(buffer_size) => buffer_size,
+            Err(e) => {
+                // leave the camera usable for single frames
+                if let Err(e) = self
+                    .switch_stream_mode(qhyccd_rs::StreamMode::SingleFrameMode)
+                    .await
+                {
+                    error!(?e, "could not return to single frame mode");
+                }
+                return Err(e);
+            }
+        };
+        self.live_feed.clear().await;
+        let native_bits = self.native_bits().await;
+        *live = Some(live::start(
+            self.device.clone(),
+            self.live_feed.clone(),
+            buffer_size,
+            native_bits,
+            self.usb_backoff().await,
+        ));
+        drop(state);
+        debug!(buffer_size, "live mode started");
+        Ok(())
     }
 
-    pub async fn build(self) -> eyre::Result<ascom_alpaca::BoundServer> {
-        let mut server = Server::new(CargoServerInfo!());
-        server.listen_addr.set_port(self.port);
-
-        let sdk = Sdk::new()?;
-        let sdk_version = sdk.version()?;
-        trace!(sdk_version = ?sdk_version);
-
-        sdk.cameras().for_each(|c| {
-            let camera = QhyccdCamera {
-                unique_id: c.id().to_owned(),
-                name: c.id().to_owned(),
-                description: "QHYCCD camera".to_owned(),
-                device: c.clone(),
-                binning: RwLock::new(1_u8),
-                valid_bins: RwLock::new(None),
-                target_temperature: RwLock::new(None),
-                ccd_info: RwLock::new(None),
-                intended_roi: RwLock::new(None),
-                readout_speed_min_max_step: RwLock::new(None),
-                exposure_min_max_step: RwLock::new(None),
-                last_exposure_start_time: RwLock::new(None),
-                last_exposure_duration_us: RwLock::new(None),
-                last_image: Arc::new(RwLock::new(None)),
-                state: Arc::new(RwLock::new(State::Idle)),
-                gain_min_max: RwLock::new(None),
-                offset_min_max: RwLock::new(None),
-            };
-            debug!(?camera, "Registering camera");
-            server
-                .devices
-                .register::<dyn ascom_alpaca::api::Camera>(camera);
-        });
-
-        sdk.filter_wheels().for_each(|c| {
-            let filter_wheel = QhyccdFilterWheel {
-                unique_id: format!("CFW={}", c.id()),
-                name: format!("CFW={}", c.id()),
-                description: "QHYCCD filter wheel".to_owned(),
-                number_of_filters: RwLock::new(None),
-                target_position: RwLock::new(None),
-                device: c.clone(),
-            };
-            debug!(?filter_wheel, "Registering filter wheel");
-            server
-                .devices
-                .register::<dyn ascom_alpaca::api::FilterWheel>(filter_wheel);
-        });
+    /// Sets the ROI and exposure of a camera in live mode and starts streaming.
+    ///
+    /// Returns the size of a frame.
+    async fn begin_live(&self, exposure: Option<Duration>) -> ASCOMResult<usize> {
+        if let Some(roi) = *self.intended_roi.read().await {
+            self.device.set_roi(roi).map_err(|e| {
+                error!(?e, "failed to set ROI");
+                ASCOMError::invalid_value("failed to set ROI")
+            })?;
+        }
+        if let Some(exposure) = exposure {
+            self.device
+                .set_parameter(
+                    qhyccd_rs::Control::Exposure,
+                    exposure.as_secs_f64() * 1_000_000_f64,
+                )
+                .map_err(|e| {
+                    error!(?e, "failed to set exposure time");
+                    ASCOMError::INVALID_OPERATION
+                })?;
+        }
+        self.device.begin_live().map_err(|e| {
+            error!(?e, "begin_live failed");
+            ASCOMError::INVALID_OPERATION
+        })?;
+        self.device.get_image_size().map_err(|e| {
+            error!(?e, "get_image_size failed");
+            ASCOMError::INVALID_OPERATION
+        })
+    }
 
-        let bound = server.bind().await?;
-        tracing::info!(addr = %bound.listen_addr(), "Server bound");
-        Ok(bound)
+    /// Stops live mode and returns the camera to single frame mode.
+    async fn stop_live(&self) -> ASCOMResult {
+        let Some(session) = self.live.write().await.take() else {
+            return Ok(());
+        };
+        session.stop().await;
+        self.device.end_live().map_err(|e| {
+            error!(?e, "end_live failed");
+            ASCOMError::INVALID_OPERATION
+        })?;
+        self.switch_stream_mode(qhyccd_rs::StreamMode::SingleFrameMode)
+            .await?;
+        debug!("live mode stopped");
+        Ok(())
     }
-}
 
-#[derive(Debug)]
-struct StopExposure {
-    _want_image: bool,
-}
+    /// Starts live mode, the parameter is the exposure time in seconds or empty to keep the
+    /// current one.
+    async fn start_live_action(&self, parameters: &str) -> ASCOMResult<String> {
+        let exposure = match parameters.trim() {
+            "" => None,
+            seconds => Some(
+                seconds
+                    .parse::<f64>()
+                    .ok()
+                    .and_then(|seconds| Duration::try_from_secs_f64(seconds).ok())
+                    .ok_or_else(|| {
+                        error!("invalid StartLive parameter: {}", seconds);
+                        ASCOMError::invalid_value(
+                            "StartLive parameter must be the exposure time in seconds or empty",
+                        )
+                    })?,
+            ),
+        };
+        self.start_live(exposure).await?;
+        Ok(String::new())
+    }
 
-#[derive(Educe)]
-#[educe(Debug, PartialEq)]
-enum State {
-    Idle,
-    Exposing {
-        start: SystemTime,
-        expected_duration_us: u32,
-        #[educe(PartialEq(ignore))]
-        stop_tx: Option<oneshot::Sender<StopExposure>>,
-        #[educe(PartialEq(ignore))]
-        done_rx: watch::Receiver<bool>,
-    },
-}
+    /// Whether live mode is on and frames arrive, the sequence number of the newest frame and the
+    /// number of buffered frames, as JSON.
+    async fn live_status_action(&self) -> ASCOMResult<String> {
+        ensure_connected!(self);
+        Ok(serde_json::json!({
+            "active": self
+                .live
+                .read()
+                .await
+                .as_ref()
+                .is_some_and(|session| session.is_running()),
+            "sequence": self.live_feed.sequence(),
+            "buffered": self.live_feed.len().await,
+        })
+        .to_string())
+    }
 
-#[derive(Debug)]
-struct QhyccdCamera {
-    unique_id: String,
-    name: String,
-    description: String,
-    device: QhyCamera,
-    binning: RwLock<u8>,
-    valid_bins: RwLock<Option<Vec<u8>>>,
-    target_temperature: RwLock<Option<f64>>,
-    ccd_info: RwLock<Option<CCDChipInfo>>,
-    intended_roi: RwLock<Option<qhyccd_rs::CCDChipArea>>,
-    readout_speed_min_max_step: RwLock<Option<(f64, f64, f64)>>,
-    exposure_min_max_step: RwLock<Option<(f64, f64, f64)>>,
-    last_exposure_start_time: RwLock<Option<SystemTime>>,
-    last_exposure_duration_us: RwLock<Option<u32>>,
-    last_image: Arc<RwLock<Option<ImageArray>>>,
-    state: Arc<RwLock<State>>,
-    gain_min_max: RwLock<Option<(f64, f64)>>,
-    offset_min_max: RwLock<Option<(f64, f64)>>,
-}
+    /// Validates the sequence given as JSON and runs it in the background.
+    async fn start_sequence_action(&self, parameters: &str) -> ASCOMResult<String> {
+        ensure_connected!(self);
+        let sequence: sequence::Sequence = serde_json::from_str(parameters).map_err(|e| {
+            error!(?e, "invalid sequence");
+            ASCOMError::invalid_value(format!(
+                "StartSequence parameter is not a valid sequence: {e}"
+            ))
+        })?;
+        sequence::validate(self, &sequence).await?;
+        if self.live.read().await.is_some() {
+            error!("cannot start a sequence in live mode");
+            return Err(ASCOMError::invalid_operation(
+                "camera is in live mode, stop it first",
+            ));
+        }
+        let Some(camera) = self.this.upgrade() else {
+            error!("camera is not shared, cannot run a sequence");
+            return Err(ASCOMError::invalid_operation("camera cannot run sequences"));
+        };
+        self.sequencer.start(camera, sequence).await?;
+        Ok(String::new())
+    }
 
-impl QhyccdCamera {
-    fn get_valid_binning_modes(&self) -> Vec<u8> {
-        let mut valid_binning_modes = Vec::with_capacity(6);
-        self.device
-            .is_control_available(qhyccd_rs::Control::CamBin1x1mode)
-            .is_some()
-            .then(|| valid_binning_modes.push(1_u8));
-        self.device
-            .is_control_available(qhyccd_rs::Control::CamBin2x2mode)
-            .is_some()
-            .then(|| valid_binning_modes.push(2_u8));
-        self.device
-            .is_control_available(qhyccd_rs::Control::CamBin3x3mode)
-            .is_some()
-            .then(|| valid_binning_modes.push(3_u8));
-        self.device
-            .is_control_available(qhyccd_rs::Control::CamBin4x4mode)
-            .is_some()
-            .then(|| valid_binning_modes.push(4_u8));
-        self.device
-            .is_control_available(qhyccd_rs::Control::CamBin6x6mode)
-            .is_some()
-            .then(|| valid_binning_modes.push(6_u8));
-        self.device
-            .is_control_available(qhyccd_rs::Control::CamBin8x8mode)
-            .is_some()
-            .then(|| valid_binning_modes.push(8_u8));
-        valid_binning_modes
+    /// The progress of the current or last sequence as JSON.
+    async fn sequence_status_action(&self) -> ASCOMResult<String> {
+        serde_json::to_string(&self.sequencer.progress().await).map_err(|e| {
+            error!(?e, "could not serialize sequence progress");
+            ASCOMError::INVALID_OPERATION
+        })
     }
 
-    fn transform_image_static(image: qhyccd_rs::ImageData) -> Result<ImageArray> {
-        match image.channels {
-            1_u32 => match image.bits_per_pixel {
-                8_u32 => {
-                    if image.width as usize * image.height as usize > image.data.len() {
-                        error!(
-                            "image data length ({}) does not match width ({}) * height ({})",
-                            image.data.len(),
-                            image.width,
-                            image.height
-                        );
-                        return Err(eyre!(
-                            "image data length ({}) does not match width ({}) * height ({})",
-                            image.data.len(),
-                            image.width,
-                            image.height
-                        ));
-                    }
-                    let data: Vec<u8> =
-                        image.data[0_usize..image.width as usize * image.height as usize].to_vec();
-                    let array_base = Array3::from_shape_vec(
-                        (image.height as usize, image.width as usize, 1_usize),
-                        data,
-                    )
-                    .map_err(|e| {
-                        error!(?e, "could not transform image");
-                        eyre!(e)
-                    })?;
-                    let mut swapped = array_base;
-                    swapped.swap_axes(0, 1);
-                    Ok(swapped.into())
+    /// Reads everything that depends on the readout mode from the SDK: the chip geometry, the
+    /// valid bins and the ranges of readout speed, exposure, gain and offset.
+    async fn load_mode_state(&self) -> Result<()> {
+        let info = self.device.get_ccd_info().wrap_err("get_ccd_info failed")?;
+        *self.ccd_info.write().await = Some(info);
+        let area = self
+            .device
+            .get_effective_area()
+            .wrap_err("get_effective_area failed")?;
+        *self.effective_area.write().await = Some(area);
+        *self.overscan_area.write().await = match self.device.get_overscan_area() {
+            Ok(overscan_area) if overscan_area.width > 0 && overscan_area.height > 0 => {
+                Some(overscan_area)
+            }
+            Ok(_) => None,
+            Err(e) => {
+                debug!(?e, "no overscan area");
+                None
+            }
+        };
+        *self.valid_bins.write().await = Some(self.get_valid_binning_modes());
+        *self.readout_speed_min_max_step.write().await =
+            match self.device.is_control_available(qhyccd_rs::Control::Speed) {
+                Some(_) => Some(
+                    self.device
+                        .get_parameter_min_max_step(qhyccd_rs::Control::Speed)
+                        .wrap_err("get_readout_speed_min_max_step failed")?,
+                ),
+                None => {
+                    debug!("readout_speed control not available");
+                    None
                 }
-                16_u32 => {
-                    if image.width as usize * image.height as usize * 2 > image.data.len() {
-                        error!(
-                            "image data length ({}) does not match width ({}) * height ({}) * 2",
-                            image.data.len(),
-                            image.width,
-                            image.height
-                        );
-                        return Err(eyre!(
-                            "image data length ({}) does not match width ({}) * height ({}) * 2",
-                            image.data.len(),
-                            image.width,
-                            image.height
-                        ));
-                    }
-                    let data = image.data
-                        [0_usize..image.width as usize * image.height as usize * 2_usize]
-                        .to_vec()
-                        .chunks_exact(2)
-                        .map(|a| u16::from_ne_bytes([a[0], a[1]]))
-                        .collect();
-                    let array_base = Array3::from_shape_vec(
-                        (image.height as usize, image.width as usize, 1_usize),
-                        data,
-                    )
-                    .map_err(|e| {
-                        error!(?e, "could not transform image");
-                        eyre!(e)
-                    })?;
-                    let mut swapped = array_base;
-                    swapped.swap_axes(0, 1);
-                    Ok(swapped.into())
+            };
+        *self.exposure_min_max_step.write().await = Some(
+            self.device
+                .get_parameter_min_max_step(qhyccd_rs::Control::Exposure)
+                .wrap_err("get_exposure_min_max_step failed")?,
+        );
+        *self.gain_min_max.write().await =
+            match self.device.is_control_available(qhyccd_rs::Control::Gain) {
+                Some(_) => {
+                    let (min, max, _step) = self
+                        .device
+                        .get_parameter_min_max_step(qhyccd_rs::Control::Gain)
+                        .wrap_err("get_gain_min_max failed")?;
+                    Some((min, max))
                 }
-                other => {
-                    error!("unsupported bits_per_pixel {:?}", other);
-                    Err(eyre!("unsupported bits_per_pixel {:?}", other))
+                None => {
+                    debug!("gain control not available");
+                    None
                 }
-            },
-            other => {
-                error!("unsupported number of channels {:?}", other);
-                Err(eyre!("unsupported number of channels {:?}", other))
-            }
-        }
+            };
+        *self.offset_min_max.write().await =
+            match self.device.is_control_available(qhyccd_rs::Control::Offset) {
+                Some(_) => {
+                    let (min, max, _step) = self
+                        .device
+                        .get_parameter_min_max_step(qhyccd_rs::Control::Offset)
+                        .wrap_err("get_offset_min_max failed")?;
+                    Some((min, max))
+                }
+                None => {
+                    debug!("offset control not available");
+                    None
+                }
+            };
+        Ok(())
     }
 
     async fn connect(&self) -> ASCOMResult {
+        // reconnecting during a warm-up keeps the camera open
+        self.cooler.write().await.close_when_off = false;
         self.device.open().map_err(|e| {
             error!(?e, "open failed");
             ASCOMError::NOT_CONNECTED
@@ -286,78 +2456,30 @@ impl QhyccdCamera {
                 ASCOMError::NOT_CONNECTED
             })?;
         trace!(cam_transfer_bit = 16.0);
-        let mut lock = self.ccd_info.write().await;
-        let info = self.device.get_ccd_info().map_err(|e| {
-            error!(?e, "get_ccd_info failed");
+        *self.transfer_bits.write().await = 16;
+        self.load_mode_state().await.map_err(|e| {
+            error!(?e, "reading the camera capabilities failed");
             ASCOMError::NOT_CONNECTED
         })?;
-        *lock = Some(info);
-        let mut lock = self.intended_roi.write().await;
-        let area = self.device.get_effective_area().map_err(|e| {
-            error!(?e, "get_effective_area failed");
-            ASCOMError::NOT_CONNECTED
-        })?;
-        *lock = Some(area);
-        *self.valid_bins.write().await = Some(self.get_valid_binning_modes());
-        match self.device.is_control_available(qhyccd_rs::Control::Speed) {
-            Some(_) => {
-                let mut lock = self.readout_speed_min_max_step.write().await;
-                let readout_speed_min_max_step = self
-                    .device
-                    .get_parameter_min_max_step(qhyccd_rs::Control::Speed)
-                    .map_err(|e| {
-                        error!(?e, "get_readout_speed_min_max_step failed");
-                        ASCOMError::NOT_CONNECTED
-                    })?;
-                *lock = Some(readout_speed_min_max_step);
-            }
-            None => debug!("readout_speed control not available"),
-        }
-        let mut lock = self.exposure_min_max_step.write().await;
-        let exposure_min_max = self
-            .device
-            .get_parameter_min_max_step(qhyccd_rs::Control::Exposure)
-            .map_err(|e| {
-                error!(?e, "get_exposure_min_max_step failed");
-                ASCOMError::NOT_CONNECTED
-            })?;
-        *lock = Some(exposure_min_max);
-        match self.device.is_control_available(qhyccd_rs::Control::Gain) {
-            Some(_) => {
-                let mut lock = self.gain_min_max.write().await;
-                *lock = match self
-                    .device
-                    .get_parameter_min_max_step(qhyccd_rs::Control::Gain)
-                {
-                    Ok((min, max, _step)) => Some((min, max)),
-                    Err(e) => {
-                        error!(?e, "get_gain_min_max failed");
-                        return Err(ASCOMError::NOT_CONNECTED);
-                    }
-                };
-            }
-            None => {
-                debug!("gain control not available");
-            }
-        }
-        match self.device.is_control_available(qhyccd_rs::Control::Offset) {
-            Some(_) => {
-                let mut lock = self.offset_min_max.write().await;
-                *lock = match self
-                    .device
-                    .get_parameter_min_max_step(qhyccd_rs::Control::Offset)
-                {
-                    Ok((min, max, _step)) => Some((min, max)),
-                    Err(e) => {
-                        error!(?e, "get_offset_min_max failed");
-                        return Err(ASCOMError::NOT_CONNECTED);
-                    }
-                };
-            }
-            None => {
-                debug!("offset control not available");
-            }
+        *self.intended_roi.write().await = self.readable_area().await;
+        let cfw_detected = self.device.is_cfw_plugged_in().unwrap_or_else(|e| {
+            warn!(?e, "is_cfw_plugged_in failed");
+            false
+        });
+        debug!(cfw_detected, "filter wheel on CFW port");
+        self.cfw_detected.store(cfw_detected, Ordering::Relaxed);
+        if cfw_detected {
+            self.cfw_found.notify_one();
         }
+        let sensors: Vec<Sensor> = Sensor::ALL
+            .into_iter()
+            .filter(|sensor| self.device.is_control_available(sensor.control()).is_some())
+            .collect();
+        debug!(?sensors, "environment sensors");
+        *self.sensors.write().await = sensors;
+        *self.usb_traffic_range.write().await = self.control_range(qhyccd_rs::Control::UsbTraffic);
+        *self.ddr_range.write().await = self.control_range(qhyccd_rs::Control::DDR);
+        self.apply_config().await;
         Ok(())
     }
 }
@@ -373,22 +2495,110 @@ impl Device for QhyccdCamera {
     }
 
     async fn connected(&self) -> ASCOMResult<bool> {
+        if !self.attached.load(Ordering::Relaxed) {
+            return Ok(false);
+        }
+        // disconnected, the camera is only kept open to finish the warm-up
+        if self.cooler.read().await.close_when_off {
+            return Ok(false);
+        }
         self.device.is_open().map_err(|e| {
             error!(?e, "is_open failed");
             ASCOMError::NOT_CONNECTED
         })
     }
 
+    async fn supported_actions(&self) -> ASCOMResult<Vec<String>> {
+        let mut actions = vec![
+            "FitsExport".to_owned(),
+            "Debayer".to_owned(),
+            "SaveSettings".to_owned(),
+            "StartLive".to_owned(),
+            "StopLive".to_owned(),
+            "LiveStatus".to_owned(),
+            "StartSequence".to_owned(),
+            "AbortSequence".to_owned(),
+            "SequenceStatus".to_owned(),
+            "Telemetry".to_owned(),
+            "Overscan".to_owned(),
+            "TransferBits".to_owned(),
+        ];
+        for tuning in Tuning::ALL {
+            if self.tuning_range(tuning).await.is_some() {
+                actions.push(tuning.action().to_owned());
+            }
+        }
+        actions.extend(
+            self.sensors
+                .read()
+                .await
+                .iter()
+                .map(|sensor| sensor.action().to_owned()),
+        );
+        Ok(actions)
+    }
+
+    async fn action(&self, action: String, parameters: String) -> ASCOMResult<String> {
+        match action.to_lowercase().as_str() {
+            "fitsexport" => self.fits_export_action(&parameters).await,
+            "debayer" => self.debayer_action(&parameters).await,
+            "savesettings" => self.save_settings_action().await,
+            "startlive" => self.start_live_action(&parameters).await,
+            "stoplive" => self.stop_live().await.map(|()| String::new()),
+            "livestatus" => self.live_status_action().await,
+            "startsequence" => self.start_sequence_action(&parameters).await,
+            "abortsequence" => {
+                self.sequencer.abort().await;
+                Ok(String::new())
+            }
+            "sequencestatus" => self.sequence_status_action().await,
+            "telemetry" => self.telemetry_action(None).await,
+            "overscan" => self.overscan_action(&parameters).await,
+            "transferbits" => self.transfer_bits_action(&parameters).await,
+            "usbtraffic" => self.tuning_action(Tuning::UsbTraffic, &parameters).await,
+            "ddrbuffer" => self.tuning_action(Tuning::DdrBuffer, &parameters).await,
+            "readoutspeed" => self.tuning_action(Tuning::ReadoutSpeed, &parameters).await,
+            other if Sensor::from_action(other).is_some() => {
+                self.telemetry_action(Sensor::from_action(other)).await
+            }
+            _ => {
+                debug!("unsupported action: {}", action);
+                Err(ASCOMError::ACTION_NOT_IMPLEMENTED)
+            }
+        }
+    }
+
     async fn set_connected(&self, connected: bool) -> ASCOMResult {
         if self.connected().await? == connected {
             return Ok(());
         };
         match connected {
+            true if !self.attached.load(Ordering::Relaxed) => {
+                error!("camera is not attached");
+                Err(ASCOMError::NOT_CONNECTED)
+            }
             true => self.connect().await,
-            false => self.device.close().map_err(|e| {
-                error!(?e, "close_camera failed");
-                ASCOMError::NOT_CONNECTED
-            }),
+            false => {
+                self.sequencer.abort().await;
+                self.cfw_detected.store(false, Ordering::Relaxed);
+                if let Some(session) = self.live.write().await.take() {
+                    session.stop().await;
+                    if let Err(e) = self.device.end_live() {
+                        warn!(?e, "end_live failed");
+                    }
+                }
+                self.update_cooler(Cooler::switch_off).await?;
+                let mut cooler = self.cooler.write().await;
+                if cooler.state != CoolerState::Off {
+                    debug!("closing the camera once the sensor has warmed up");
+                    cooler.close_when_off = true;
+                    return Ok(());
+                }
+                self.device.close().map_err(|e| {
+                    error!(?e, "close_camera failed");
+                    ASCOMError::NOT_CONNECTED
+                })
+            }
         }
     }
 
@@ -405,19 +2615,14 @@ impl Device for QhyccdCamera {
     }
 }
 
-macro_rules! ensure_connected {
-    ($self:ident) => {
-        if !$self.connected().await.is_ok_and(|connected| connected) {
-            error!("camera not connected");
-            return Err(ASCOMError::NOT_CONNECTED);
-        }
-    };
-}
-
 #[async_trait]
 impl Camera for QhyccdCamera {
     async fn bayer_offset_x(&self) -> ASCOMResult<u8> {
         ensure_connected!(self);
+        if self.debayers_frames().await {
+            debug!("frames are debayered, there is no Bayer offset");
+            return Err(ASCOMError::NOT_IMPLEMENTED);
+        }
         self.device
             .is_control_available(qhyccd_rs::Control::CamIsColor)
             .ok_or_else(|| {
@@ -446,6 +2651,10 @@ impl Camera for QhyccdCamera {
 
     async fn bayer_offset_y(&self) -> ASCOMResult<u8> {
         ensure_connected!(self);
+        if self.debayers_frames().await {
+            debug!("frames are debayered, there is no Bayer offset");
+            return Err(ASCOMError::NOT_IMPLEMENTED);
+        }
         self.device
             .is_control_available(qhyccd_rs::Control::CamIsColor)
             .ok_or_else(|| {
@@ -472,71 +2681,71 @@ impl Camera for QhyccdCamera {
         }
     }
 
-    async fn sensor_name(&self) -> ASCOMResult<String> {
-        //ideally we would use getModel, but that returns an error for all the cameras I have, so
-        //parsing the model from the ID
-        ensure_connected!(self);
-        match self.unique_id().split('-').next() {
-            Some(model) => Ok(model.to_string()),
-            None => {
-                error!("camera id should be MODEL-SerialNumber, but split failed");
-                Err(ASCOMError::INVALID_OPERATION)
-            }
-        }
-    }
-
-    async fn bin_x(&self) -> ASCOMResult<u8> {
-        ensure_connected!(self);
-        Ok(*self.binning.read().await)
-    }
-
-    async fn set_bin_x(&self, bin_x: u8) -> ASCOMResult {
-        ensure_connected!(self);
-        let valid_bins = self.valid_bins.read().await.clone().ok_or_else(|| {
-            error!("valid_bins not set");
-            ASCOMError::NOT_CONNECTED
-        })?;
-        valid_bins
-            .iter()
-            .find(|bin| **bin == bin_x)
-            .ok_or_else(|| {
-                error!("trying to set invalid bin value: {}", bin_x);
-                ASCOMError::invalid_value("bin value must be one of the valid bins")
-            })?;
-        let mut lock = self.binning.write().await;
-        if (*lock) == bin_x {
-            return Ok(());
-        };
-        self.device
-            .set_bin_mode(bin_x as u32, bin_x as u32)
-            .map_err(|e| {
-                error!(?e, "set_bin_mode failed");
-                ASCOMError::VALUE_NOT_SET
-            })?;
-        //adjust start and num values
-        let old = *lock;
-        *lock = bin_x;
-        let mut roi_lock = self.intended_roi.write().await;
-        *roi_lock = roi_lock.map(|roi| CCDChipArea {
-            start_x: (roi.start_x as f32 * old as f32 / bin_x as f32) as u32,
-            start_y: (roi.start_y as f32 * old as f32 / bin_x as f32) as u32,
-            width: (roi.width as f32 * old as f32 / bin_x as f32) as u32,
-            height: (roi.height as f32 * old as f32 / bin_x as f32) as u32,
-        });
-        Ok(())
+    async fn sensor_name(&self) -> ASCOMResult<String> {
+        //ideally we would use getModel, but that returns an error for all the cameras I have, so
+        //parsing the model from the ID
+        ensure_connected!(self);
+        match self.model() {
+            Some(model) => Ok(model.to_string()),
+            None => {
+                error!("camera id should be MODEL-SerialNumber, but split failed");
+                Err(ASCOMError::INVALID_OPERATION)
+            }
+        }
+    }
+
+    async fn bin_x(&self) -> ASCOMResult<u8> {
+        ensure_connected!(self);
+        Ok(self.binning.read().await.x)
+    }
+
+    async fn set_bin_x(&self, bin_x: u8) -> ASCOMResult {
+        ensure_connected!(self);
+        let asymmetric = self.asymmetric_binning().await;
+        self.set_binning(bin_x, |binning| match asymmetric {
+            true => Binning {
+                x: bin_x,
+                ..binning
+            },
+            false => Binning::symmetric(bin_x),
+        })
+        .await
     }
 
     async fn bin_y(&self) -> ASCOMResult<u8> {
-        self.bin_x().await
+        ensure_connected!(self);
+        Ok(self.binning.read().await.y)
     }
 
     async fn set_bin_y(&self, bin_y: u8) -> ASCOMResult {
-        self.set_bin_x(bin_y).await
+        ensure_connected!(self);
+        let asymmetric = self.asymmetric_binning().await;
+        self.set_binning(bin_y, |binning| match asymmetric {
+            true => Binning {
+                y: bin_y,
+                ..binning
+            },
+            false => Binning::symmetric(bin_y),
+        })
+        .await
+    }
+
+    /// Taken from the configuration, the SDK does not tell whether a camera accepts different
+    /// horizontal and vertical bins. Binning in software always can.
+    async fn can_asymmetric_bin(&self) -> ASCOMResult<bool> {
+        ensure_connected!(self);
+        Ok(self.asymmetric_binning().await)
     }
 
     async fn max_bin_x(&self) -> ASCOMResult<u8> {
         ensure_connected!(self);
-        self.get_valid_binning_modes()
+        let software = self
+            .config
+            .camera(&self.unique_id)
+            .await
+            .software_binning
+            .is_some();
+        binning::offered(&self.get_valid_binning_modes(), software)
             .iter()
             .max()
             .copied()
@@ -559,7 +2768,9 @@ impl Camera for QhyccdCamera {
     }
 
     async fn electrons_per_adu(&self) -> ASCOMResult<f64> {
-        Err(ASCOMError::NOT_IMPLEMENTED)
+        self.sensor_point()
+            .await
+            .map(|point| point.electrons_per_adu)
     }
 
     async fn exposure_max(&self) -> ASCOMResult<Duration> {
@@ -596,8 +2807,9 @@ impl Camera for QhyccdCamera {
     }
 
     async fn full_well_capacity(&self) -> ASCOMResult<f64> {
-        debug!("full_well_capacity not implemented");
-        Err(ASCOMError::NOT_IMPLEMENTED)
+        self.sensor_point()
+            .await
+            .map(|point| point.full_well_capacity)
     }
 
     async fn has_shutter(&self) -> ASCOMResult<bool> {
@@ -615,21 +2827,46 @@ impl Camera for QhyccdCamera {
 
     async fn image_array(&self) -> ASCOMResult<ImageArray> {
         ensure_connected!(self);
-        match (*self.last_image.read().await).clone() {
-            Some(image) => Ok(image),
-            None => Err(ASCOMError::VALUE_NOT_SET),
+        if self.live.read().await.is_some() {
+            let Some(frame) = self.live_feed.latest().await else {
+                return Err(ASCOMError::VALUE_NOT_SET);
+            };
+            trace!(sequence = frame.sequence, "live frame");
+            let origin = self
+                .intended_roi
+                .read()
+                .await
+                .map_or((0, 0), |roi| (roi.start_x, roi.start_y));
+            // live frames are kept raw, they are debayered when a client asks for one
+            let debayering = self
+                .frame_debayering(*self.binning.read().await, origin)
+                .await;
+            return Self::convert_image(frame.image.clone(), debayering).await;
+        }
+        // stored frames were debayered on readout already
+        let (image, converted) = match &*self.last_image.read().await {
+            Some(last_image) => {
+                trace!(frame_type = ?last_image.frame_type);
+                (last_image.image.clone(), last_image.image_array.clone())
+            }
+            None => return Err(ASCOMError::VALUE_NOT_SET),
+        };
+        if let Some(cached) = converted.lock().unwrap_or_else(|e| e.into_inner()).clone() {
+            trace!("serving the converted frame again");
+            return Ok(cached);
         }
+        let image_array = Self::convert_image(image, None).await?;
+        *converted.lock().unwrap_or_else(|e| e.into_inner()) = Some(image_array.clone());
+        Ok(image_array)
     }
 
     async fn image_ready(&self) -> ASCOMResult<bool> {
         ensure_connected!(self);
+        if self.live.read().await.is_some() {
+            return Ok(self.live_feed.latest().await.is_some());
+        }
         match *self.state.read().await {
-            State::Idle => self
-                .last_image
-                .read()
-                .await
-                .clone()
-                .map_or_else(|| Ok(false), |_| Ok(true)),
+            State::Idle => Ok(self.last_image.read().await.is_some()),
             State::Exposing { .. } => Ok(false),
         }
     }
@@ -652,18 +2889,7 @@ impl Camera for QhyccdCamera {
 
     async fn max_adu(&self) -> ASCOMResult<u32> {
         ensure_connected!(self);
-        self.device
-            .get_parameter(qhyccd_rs::Control::OutputDataActualBits)
-            .map_or_else(
-                |e| {
-                    error!(?e, "could not get OutputDataActualBits");
-                    Err(ASCOMError::VALUE_NOT_SET)
-                },
-                |bits| {
-                    debug!(?bits, "ADU");
-                    Ok(2_u32.pow(bits as u32))
-                },
-            )
+        self.adu_max().await
     }
 
     async fn camera_x_size(&self) -> ASCOMResult<u32> {
@@ -829,17 +3055,39 @@ impl Camera for QhyccdCamera {
                 error!(?e, "get_readout_mode_resolution failed");
                 ASCOMError::INVALID_VALUE
             })?;
+        if matches!(*self.state.read().await, State::Exposing { .. }) {
+            error!("cannot switch the readout mode during an exposure");
+            return Err(ASCOMError::invalid_operation(
+                "cannot switch the readout mode during an exposure",
+            ));
+        }
+        if self.live.read().await.is_some() {
+            error!("cannot switch the readout mode in live mode");
+            return Err(ASCOMError::invalid_operation(
+                "camera is in live mode, stop it first",
+            ));
+        }
+        let full_frame = self.readable_area().await;
+        let previous = self
+            .device
+            .get_readout_mode()
+            .inspect_err(|e| debug!(?e, "get_readout_mode failed"))
+            .ok();
         self.device.set_readout_mode(readout_mode).map_err(|e| {
             error!(?e, "set_readout_mode failed");
             ASCOMError::VALUE_NOT_SET
         })?;
-        let mut lock = self.ccd_info.write().await;
-        *lock = lock.map(|ccd_info| CCDChipInfo {
-            image_width: width,
-            image_height: height,
-            ..ccd_info
-        });
-        Ok(())
+        match self
+            .switch_mode_state(readout_mode, (width, height), full_frame)
+            .await
+        {
+            Ok(()) => Ok(()),
+            Err(e) => {
+                error!(?e, readout_mode, "reloading the readout mode state failed");
+                self.roll_back_readout_mode(previous, full_frame).await;
+                Err(ASCOMError::INVALID_OPERATION)
+            }
+        }
     }
 
     async fn readout_modes(&self) -> ASCOMResult<Vec<String>> {
@@ -857,238 +3105,52 @@ impl Camera for QhyccdCamera {
             readout_modes.push(readout_mode);
         }
         Ok(readout_modes)
-    }
-
-    async fn sensor_type(&self) -> ASCOMResult<SensorType> {
-        //see here: https://ascom-standards.org/api/#/Camera%20Specific%20Methods/get_camera__device_number__imagearray
-        ensure_connected!(self);
-        if self
-            .device
-            .is_control_available(qhyccd_rs::Control::CamIsColor)
-            .is_none()
-        {
-            error!("CamIsColor not available");
-            return Ok(SensorType::Monochrome);
-        };
-        self.device
-            .is_control_available(qhyccd_rs::Control::CamColor)
-            .map_or_else(
-                || {
-                    error!("invalid bayer_id from camera");
-                    Err(ASCOMError::INVALID_VALUE)
-                },
-                |_| Ok(SensorType::RGGB),
-            )
-    }
-
-    #[instrument(level = "trace")]
-    async fn start_exposure(&self, duration: Duration, light: bool) -> ASCOMResult {
-        if !light {
-            return Err(ASCOMError::invalid_operation("dark frames not supported"));
-        }
-        ensure_connected!(self);
-        if self.start_x().await? > self.num_x().await? {
-            return Err(ASCOMError::invalid_value("StartX > NumX"));
-        }
-        if self.start_y().await? > self.num_y().await? {
-            return Err(ASCOMError::invalid_value("StartY > NumY"));
-        }
-        if self.num_x().await?
-            > (self.camera_x_size().await? as f32 / self.bin_x().await? as f32) as u32
-        {
-            return Err(ASCOMError::invalid_value("NumX > CameraXSize"));
-        }
-        if self.num_y().await?
-            > (self.camera_y_size().await? as f32 / self.bin_y().await? as f32) as u32
-        {
-            return Err(ASCOMError::invalid_value("NumY > CameraYSize"));
-        }
-        let Some(roi) = *self.intended_roi.read().await else {
-            debug!("no roi defined, but trying to start exposure");
-            return Err(ASCOMError::invalid_value("no ROI defined for camera"));
-        };
-        self.device.set_roi(roi).map_err(|e| {
-            debug!(?e, "failed to set ROI");
-            ASCOMError::invalid_value("failed to set ROI")
-        })?;
-        let exposure_us = (duration.as_secs_f64() * 1_000_000_f64) as u32;
-        let (stop_tx, mut stop_rx) = oneshot::channel::<StopExposure>();
-        let (done_tx, done_rx) = watch::channel(false);
-
-        let mut lock = self.state.write().await;
-        *lock = match *lock {
-            State::Idle => State::Exposing {
-                start: SystemTime::now(),
-                expected_duration_us: exposure_us,
-                stop_tx: Some(stop_tx),
-                done_rx,
-            },
-            State::Exposing { .. } => {
-                error!("camera already exposing");
-                return Err(ASCOMError::INVALID_OPERATION);
-            }
-        };
-        drop(lock);
-
-        *self.last_exposure_start_time.write().await = Some(SystemTime::now());
-        *self.last_exposure_duration_us.write().await = Some(exposure_us);
-
-        self.device
-            .set_parameter(qhyccd_rs::Control::Exposure, exposure_us as f64)
-            .map_err(|e| {
-                error!(?e, "failed to set exposure time: {:?}", e);
-                ASCOMError::INVALID_OPERATION
-            })?;
-
-        let device = self.device.clone();
-        // Create separate device instance for abort to ensure proper SDK synchronization
-        // According to SDK docs, after successful abort we must complete data exchange
-        let device_for_abort = self.device.clone();
-        let state = self.state.clone();
-        let last_image = self.last_image.clone();
-
-        tokio::spawn(async move {
-            debug!("DEBUG: New implementation started");
-            // Helper function to handle abort and data exchange
-            let handle_abort = || async {
-                debug!("DEBUG: Handling abort");
-                match device_for_abort.abort_exposure_and_readout() {
-                    Ok(()) => {
-                        debug!("abort succeeded, completing data exchange for sync");
-                        if let Ok(buffer_size) = device_for_abort.get_image_size() {
-                            if let Ok(image) = device_for_abort.get_single_frame(buffer_size) {
-                                match QhyccdCamera::transform_image_static(image) {
-                                    Ok(transformed) => {
-                                        *last_image.write().await = Some(transformed);
-                                        debug!("aborted exposure data stored");
-                                    }
-                                    Err(e) => error!(?e, "failed to transform aborted image"),
-                                }
-                            }
-                        }
-                    }
-                    Err(e) => error!(?e, "failed to abort exposure"),
-                }
-                debug!("exposure aborted");
-            };
-
-            // Execute start_single_frame_exposure
-            let start_task = task::spawn_blocking({
-                let device = device.clone();
-                move || {
-                    device.start_single_frame_exposure().map_err(|e| {
-                        error!(?e, "failed to start exposure: {:?}", e);
-                        ASCOMError::INVALID_OPERATION
-                    })
-                }
-            });
-
-            match start_task.await {
-                Ok(Ok(())) => {}
-                Ok(Err(e)) => {
-                    error!(?e, "start exposure failed");
-                    *state.write().await = State::Idle;
-                    return;
-                }
-                Err(e) => {
-                    error!(?e, "start task failed");
-                    *state.write().await = State::Idle;
-                    return;
-                }
-            }
-
-            // Check for abort after start_single_frame_exposure
-            debug!("DEBUG: Checking for abort after start_single_frame_exposure");
-            match stop_rx.try_recv() {
-                Ok(_) => {
-                    debug!("DEBUG: Abort detected after start_single_frame_exposure!");
-                    handle_abort().await;
-                    *state.write().await = State::Idle;
-                    return;
-                }
-                Err(e) => {
-                    debug!("DEBUG: No abort signal: {:?}", e);
-                }
-            }
-
-            // Execute get_image_size
-            let size_task = task::spawn_blocking({
-                let device = device.clone();
-                move || {
-                    device.get_image_size().map_err(|e| {
-                        error!(?e, "get_image_size failed");
-                        ASCOMError::INVALID_OPERATION
-                    })
-                }
-            });
-
-            let buffer_size = match size_task.await {
-                Ok(Ok(size)) => {
-                    debug!(?size);
-                    size
-                }
-                Ok(Err(e)) => {
-                    error!(?e, "get image size failed");
-                    *state.write().await = State::Idle;
-                    return;
-                }
-                Err(e) => {
-                    error!(?e, "size task failed");
-                    *state.write().await = State::Idle;
-                    return;
-                }
-            };
-
-            // Check for abort after get_image_size
-            if stop_rx.try_recv().is_ok() {
-                handle_abort().await;
-                *state.write().await = State::Idle;
-                return;
-            }
-
-            // Execute get_single_frame
-            let image_task = task::spawn_blocking({
-                move || {
-                    device.get_single_frame(buffer_size).map_err(|e| {
-                        error!(?e, "get_single_frame failed");
-                        ASCOMError::INVALID_OPERATION
-                    })
-                }
-            });
-
-            let image = match image_task.await {
-                Ok(Ok(image)) => image,
-                Ok(Err(e)) => {
-                    error!(?e, "get single frame failed");
-                    *state.write().await = State::Idle;
-                    return;
-                }
-                Err(e) => {
-                    error!(?e, "image task failed");
-                    *state.write().await = State::Idle;
-                    return;
-                }
-            };
-
-            // Transform and store the image
-            match QhyccdCamera::transform_image_static(image) {
-                Ok(transformed) => {
-                    *last_image.write().await = Some(transformed);
-                    let _ = done_tx.send(true);
-                    debug!("exposure completed successfully");
-                }
-                Err(e) => error!(?e, "failed to transform image"),
-            }
+    }
 
-            *state.write().await = State::Idle;
-        });
+    async fn sensor_type(&self) -> ASCOMResult<SensorType> {
+        //see here: https://ascom-standards.org/api/#/Camera%20Specific%20Methods/get_camera__device_number__imagearray
+        ensure_connected!(self);
+        if self
+            .device
+            .is_control_available(qhyccd_rs::Control::CamIsColor)
+            .is_none()
+        {
+            error!("CamIsColor not available");
+            return Ok(SensorType::Monochrome);
+        };
+        self.device
+            .is_control_available(qhyccd_rs::Control::CamColor)
+            .ok_or_else(|| {
+                error!("invalid bayer_id from camera");
+                ASCOMError::INVALID_VALUE
+            })?;
+        // debayered frames are delivered as colour planes, binned frames stay raw
+        match self.debayers_frames().await {
+            true => Ok(SensorType::Color),
+            false => Ok(SensorType::RGGB),
+        }
+    }
 
-        Ok(())
+    #[instrument(level = "trace")]
+    async fn start_exposure(&self, duration: Duration, light: bool) -> ASCOMResult {
+        if self.sequencer.is_running().await {
+            error!("cannot start an exposure while a sequence is running");
+            return Err(ASCOMError::invalid_operation(
+                "a sequence is running, abort it first",
+            ));
+        }
+        self.begin_exposure(duration, light, true).await.map(|_| ())
     }
 
+    /// Taken from `STOP_EXPOSURE` for the listed models. Other models offer it only when
+    /// `can_stop_exposure` in the configuration file enables it, and until the SDK refuses to
+    /// stop an exposure, which then reads out the full exposure.
     async fn can_stop_exposure(&self) -> ASCOMResult<bool> {
-        //this is not true for every camera, but better to say no here
-        Ok(false)
+        if let Some(supported) = self.model().and_then(stop_exposure_supported) {
+            return Ok(supported);
+        }
+        let configured = self.config.camera(&self.unique_id).await.can_stop_exposure;
+        Ok(configured.unwrap_or(false) && self.stop_supported.load(Ordering::Relaxed))
     }
 
     async fn can_abort_exposure(&self) -> ASCOMResult<bool> {
@@ -1096,21 +3158,28 @@ impl Camera for QhyccdCamera {
     }
 
     async fn stop_exposure(&self) -> ASCOMResult {
-        Err(ASCOMError::NOT_IMPLEMENTED)
-        /*
-        match self.connected().await {
-            Ok(true) => match self.device.stop_exposure() {
-                Ok(_) => Ok(()),
-                Err(e) => {
-                    error!(?e, "stop_exposure failed");
+        if !self.can_stop_exposure().await? {
+            debug!("stop_exposure not supported by this camera");
+            return Err(ASCOMError::NOT_IMPLEMENTED);
+        }
+        ensure_connected!(self);
+
+        let mut state_lock = self.state.write().await;
+        match &mut *state_lock {
+            State::Exposing { stop_tx, .. } => {
+                if let Some(tx) = stop_tx.take() {
+                    let _ = tx.send(StopExposure { want_image: true });
+                    Ok(())
+                } else {
+                    // Channel already used
                     Err(ASCOMError::INVALID_OPERATION)
                 }
-            },
-            _ => {
-                error!("camera not connected");
-                return Err(ASCOMError::NOT_CONNECTED);
             }
-        } */
+            State::Idle => {
+                // Nothing to stop
+                Ok(())
+            }
+        }
     }
 
     async fn abort_exposure(&self) -> ASCOMResult {
@@ -1120,7 +3189,7 @@ impl Camera for QhyccdCamera {
         match &mut *state_lock {
             State::Exposing { stop_tx, .. } => {
                 if let Some(tx) = stop_tx.take() {
-                    let _ = tx.send(StopExposure { _want_image: false });
+                    let _ = tx.send(StopExposure { want_image: false });
                     Ok(())
                 } else {
                     // Channel already used
@@ -1191,7 +3260,8 @@ impl Camera for QhyccdCamera {
                 debug!("no cooler");
                 ASCOMError::NOT_IMPLEMENTED
             })?;
-        match *self.target_temperature.read().await {
+        let target = self.cooler.read().await.target;
+        match target {
             Some(temperature) => Ok(temperature),
             None => self.ccd_temperature().await,
         }
@@ -1209,19 +3279,8 @@ impl Camera for QhyccdCamera {
                 debug!("no cooler");
                 ASCOMError::NOT_IMPLEMENTED
             })?;
-        match self
-            .device
-            .set_parameter(qhyccd_rs::Control::Cooler, set_ccd_temperature)
-        {
-            Ok(_) => {
-                *self.target_temperature.write().await = Some(set_ccd_temperature);
-                Ok(())
-            }
-            Err(e) => {
-                error!(?e, "could not set target temperature");
-                Err(ASCOMError::INVALID_OPERATION)
-            }
-        }
+        self.update_cooler(|cooler| cooler.set_target(set_ccd_temperature))
+            .await
     }
 
     async fn cooler_on(&self) -> ASCOMResult<bool> {
@@ -1232,14 +3291,7 @@ impl Camera for QhyccdCamera {
                 debug!("no cooler");
                 ASCOMError::NOT_IMPLEMENTED
             })?;
-        let cooler_power = self
-            .device
-            .get_parameter(qhyccd_rs::Control::CurPWM)
-            .map_err(|e| {
-                error!(?e, "could not get current power");
-                ASCOMError::INVALID_VALUE
-            })?;
-        Ok(cooler_power > 0_f64)
+        Ok(self.cooler.read().await.is_on())
     }
 
     async fn set_cooler_on(&self, cooler_on: bool) -> ASCOMResult {
@@ -1247,20 +3299,18 @@ impl Camera for QhyccdCamera {
             return Ok(());
         }
         match cooler_on {
-            true => self
-                .device
-                .set_parameter(qhyccd_rs::Control::ManualPWM, 1_f64 / 100_f64 * 255_f64)
-                .map_err(|e| {
-                    error!(?e, "error setting cooler power to 1");
-                    ASCOMError::INVALID_OPERATION
-                }),
-            false => self
-                .device
-                .set_parameter(qhyccd_rs::Control::ManualPWM, 0_f64)
-                .map_err(|e| {
-                    error!(?e, "error setting cooler power to 0");
-                    ASCOMError::INVALID_OPERATION
-                }),
+            true => {
+                let temperature = self
+                    .device
+                    .get_parameter(qhyccd_rs::Control::CurTemp)
+                    .map_err(|e| {
+                        error!(?e, "could not get current temperature");
+                        ASCOMError::INVALID_VALUE
+                    })?;
+                self.update_cooler(|cooler| cooler.switch_on(temperature))
+                    .await
+            }
+            false => self.update_cooler(Cooler::switch_off).await,
         }
     }
 
@@ -1283,51 +3333,60 @@ impl Camera for QhyccdCamera {
             )
     }
 
-    async fn gain(&self) -> ASCOMResult<i32> {
+    async fn gains(&self) -> ASCOMResult<Vec<String>> {
         ensure_connected!(self);
-        self.device
-            .is_control_available(qhyccd_rs::Control::Gain)
+        match self.gain_presets() {
+            Some(presets) => Ok(presets.iter().map(|preset| preset.name.clone()).collect()),
+            None => {
+                debug!("gain presets not enabled");
+                Err(ASCOMError::NOT_IMPLEMENTED)
+            }
+        }
+    }
+
+    async fn gain(&self) -> ASCOMResult<i32> {
+        let gain = self.gain_value().await?;
+        let Some(presets) = self.gain_presets() else {
+            return Ok(gain);
+        };
+        let readout_mode = self.device.get_readout_mode().ok();
+        presets
+            .iter()
+            .position(|preset| {
+                preset.gain == gain
+                    && preset
+                        .readout_mode
+                        .is_none_or(|mode| Some(mode) == readout_mode)
+            })
+            .map(|index| index as i32)
             .ok_or_else(|| {
-                debug!("gain control not available");
-                ASCOMError::NOT_IMPLEMENTED
-            })?;
-        self.device
-            .get_parameter(qhyccd_rs::Control::Gain)
-            .map_or_else(
-                |e| {
-                    error!(?e, "failed to set gain");
-                    Err(ASCOMError::INVALID_OPERATION)
-                },
-                |gain| Ok(gain as i32),
-            )
+                debug!(gain, ?readout_mode, "camera settings match no gain preset");
+                ASCOMError::VALUE_NOT_SET
+            })
     }
 
     async fn set_gain(&self, gain: i32) -> ASCOMResult {
+        let Some(presets) = self.gain_presets() else {
+            return self.set_gain_value(gain).await;
+        };
         ensure_connected!(self);
-        self.device
-            .is_control_available(qhyccd_rs::Control::Gain)
-            .ok_or_else(|| {
-                debug!("gain control not available");
-                ASCOMError::NOT_IMPLEMENTED
-            })?;
-        let (min, max) = self
-                        .gain_min_max
-                        .read()
-                        .await
-                        .ok_or(ASCOMError::invalid_operation("camera reports gain control available, but min, max values are not set after initialization"))?;
-        if !(min as i32..=max as i32).contains(&gain) {
-            return Err(ASCOMError::INVALID_VALUE);
+        let preset = usize::try_from(gain)
+            .ok()
+            .and_then(|index| presets.get(index))
+            .ok_or(ASCOMError::INVALID_VALUE)?;
+        debug!(?preset, "applying gain preset");
+        if let Some(readout_mode) = preset.readout_mode {
+            self.set_readout_mode(readout_mode as usize).await?;
         }
-        self.device
-            .set_parameter(qhyccd_rs::Control::Gain, gain as f64)
-            .map_err(|e| {
-                error!(?e, "failed to set gain");
-                ASCOMError::INVALID_OPERATION
-            })
+        self.set_gain_value(preset.gain).await
     }
 
     async fn gain_max(&self) -> ASCOMResult<i32> {
         ensure_connected!(self);
+        if self.gain_presets().is_some() {
+            debug!("gain_max not available with gain presets");
+            return Err(ASCOMError::NOT_IMPLEMENTED);
+        }
         self.gain_min_max
             .read()
             .await
@@ -1337,6 +3396,10 @@ impl Camera for QhyccdCamera {
 
     async fn gain_min(&self) -> ASCOMResult<i32> {
         ensure_connected!(self);
+        if self.gain_presets().is_some() {
+            debug!("gain_min not available with gain presets");
+            return Err(ASCOMError::NOT_IMPLEMENTED);
+        }
         self.gain_min_max
             .read()
             .await
@@ -1344,51 +3407,51 @@ impl Camera for QhyccdCamera {
             .ok_or(ASCOMError::NOT_IMPLEMENTED)
     }
 
-    async fn offset(&self) -> ASCOMResult<i32> {
+    async fn offsets(&self) -> ASCOMResult<Vec<String>> {
         ensure_connected!(self);
-        self.device
-            .is_control_available(qhyccd_rs::Control::Offset)
+        match self.offset_presets() {
+            Some(presets) => Ok(presets.iter().map(|preset| preset.name.clone()).collect()),
+            None => {
+                debug!("offset presets not enabled");
+                Err(ASCOMError::NOT_IMPLEMENTED)
+            }
+        }
+    }
+
+    async fn offset(&self) -> ASCOMResult<i32> {
+        let offset = self.offset_value().await?;
+        let Some(presets) = self.offset_presets() else {
+            return Ok(offset);
+        };
+        presets
+            .iter()
+            .position(|preset| preset.offset == offset)
+            .map(|index| index as i32)
             .ok_or_else(|| {
-                debug!("offset control not available");
-                ASCOMError::NOT_IMPLEMENTED
-            })?;
-        self.device
-            .get_parameter(qhyccd_rs::Control::Offset)
-            .map_or_else(
-                |e| {
-                    error!(?e, "failed to get offset");
-                    Err(ASCOMError::INVALID_OPERATION)
-                },
-                |offset| Ok(offset as i32),
-            )
+                debug!(offset, "camera settings match no offset preset");
+                ASCOMError::VALUE_NOT_SET
+            })
     }
 
     async fn set_offset(&self, offset: i32) -> ASCOMResult {
+        let Some(presets) = self.offset_presets() else {
+            return self.set_offset_value(offset).await;
+        };
         ensure_connected!(self);
-        self.device
-            .is_control_available(qhyccd_rs::Control::Offset)
-            .ok_or_else(|| {
-                debug!("offset control not available");
-                ASCOMError::NOT_IMPLEMENTED
-            })?;
-        let (min, max) = self
-                        .offset_min_max
-                        .read()
-                        .await
-                        .ok_or(ASCOMError::invalid_operation("camera reports offset control available, but min, max values are not set after initialization"))?;
-        if !(min as i32..=max as i32).contains(&offset) {
-            return Err(ASCOMError::INVALID_VALUE);
-        }
-        self.device
-            .set_parameter(qhyccd_rs::Control::Offset, offset as f64)
-            .map_err(|e| {
-                error!(?e, "failed to set offset");
-                ASCOMError::INVALID_OPERATION
-            })
+        let preset = usize::try_from(offset)
+            .ok()
+            .and_then(|index| presets.get(index))
+            .ok_or(ASCOMError::INVALID_VALUE)?;
+        debug!(?preset, "applying offset preset");
+        self.set_offset_value(preset.offset).await
     }
 
     async fn offset_max(&self) -> ASCOMResult<i32> {
         ensure_connected!(self);
+        if self.offset_presets().is_some() {
+            debug!("offset_max not available with offset presets");
+            return Err(ASCOMError::NOT_IMPLEMENTED);
+        }
         self.offset_min_max
             .read()
             .await
@@ -1398,6 +3461,10 @@ impl Camera for QhyccdCamera {
 
     async fn offset_min(&self) -> ASCOMResult<i32> {
         ensure_connected!(self);
+        if self.offset_presets().is_some() {
+            debug!("offset_min not available with offset presets");
+            return Err(ASCOMError::NOT_IMPLEMENTED);
+        }
         self.offset_min_max
             .read()
             .await
@@ -1477,7 +3544,194 @@ struct QhyccdFilterWheel {
     description: String,
     number_of_filters: RwLock<Option<u32>>,
     target_position: RwLock<Option<u32>>,
-    device: QhyFilterWheel,
+    device: FilterWheelDevice,
+    config: Arc<ConfigStore>,
+    /// state of the camera the wheel is attached to, the wheel does not move while it exposes
+    camera_state: Option<Arc<RwLock<State>>>,
+    /// cleared while the wheel, or the camera it is plugged into, is unplugged
+    attached: AtomicBool,
+}
+
+/// the CFW port reports and takes positions as ASCII digits
+const CFW_PORT_POSITION_OFFSET: f64 = 48_f64;
+
+/// How a filter wheel is driven.
+#[derive(Debug)]
+enum FilterWheelDevice {
+    /// a filter wheel found by the SDK at startup
+    Sdk(QhyFilterWheel),
+    /// a filter wheel in the CFW port of a camera, driven through the camera handle
+    ///
+    /// The handle belongs to the camera device, so the wheel never opens or closes it, and is
+    /// only used while the camera does not expose, see `camera_state`.
+    CameraPort {
+        camera: QhyCamera,
+        cfw_detected: Arc<AtomicBool>,
+        connected: AtomicBool,
+    },
+}
+
+impl FilterWheelDevice {
+    fn open(&self) -> Result<()> {
+        match self {
+            FilterWheelDevice::Sdk(device) => device.open(),
+            FilterWheelDevice::CameraPort {
+                camera,
+                cfw_detected,
+                connected,
+            } => {
+                if !camera.is_open()? {
+                    return Err(eyre!("camera not connected"));
+                }
+                if !cfw_detected.load(Ordering::Relaxed) {
+                    return Err(eyre!("no filter wheel detected on the camera CFW port"));
+                }
+                connected.store(true, Ordering::Relaxed);
+                Ok(())
+            }
+        }
+    }
+
+    fn close(&self) -> Result<()> {
+        match self {
+            FilterWheelDevice::Sdk(device) => device.close(),
+            FilterWheelDevice::CameraPort { connected, .. } => {
+                connected.store(false, Ordering::Relaxed);
+                Ok(())
+            }
+        }
+    }
+
+    fn is_open(&self) -> Result<bool> {
+        match self {
+            FilterWheelDevice::Sdk(device) => device.is_open(),
+            FilterWheelDevice::CameraPort {
+                camera,
+                cfw_detected,
+                connected,
+            } => Ok(connected.load(Ordering::Relaxed)
+                && cfw_detected.load(Ordering::Relaxed)
+                && camera.is_open()?),
+        }
+    }
+
+    fn get_number_of_filters(&self) -> Result<u32> {
+        match self {
+            FilterWheelDevice::Sdk(device) => device.get_number_of_filters(),
+            FilterWheelDevice::CameraPort { camera, .. } => {
+                camera
+                    .is_control_available(qhyccd_rs::Control::CfwSlotsNum)
+                    .ok_or_else(|| eyre!("no filter wheel on the camera CFW port"))?;
+                Ok(camera.get_parameter(qhyccd_rs::Control::CfwSlotsNum)? as u32)
+            }
+        }
+    }
+
+    fn get_fw_position(&self) -> Result<u32> {
+        match self {
+            FilterWheelDevice::Sdk(device) => device.get_fw_position(),
+            FilterWheelDevice::CameraPort { camera, .. } => {
+                camera
+                    .is_control_available(qhyccd_rs::Control::CfwPort)
+                    .ok_or_else(|| eyre!("no filter wheel on the camera CFW port"))?;
+                let position = camera.get_parameter(qhyccd_rs::Control::CfwPort)?;
+                Ok((position - CFW_PORT_POSITION_OFFSET) as u32)
+            }
+        }
+    }
+
+    fn set_fw_position(&self, position: u32) -> Result<()> {
+        match self {
+            FilterWheelDevice::Sdk(device) => device.set_fw_position(position),
+            FilterWheelDevice::CameraPort { camera, .. } => {
+                camera
+                    .is_control_available(qhyccd_rs::Control::CfwPort)
+                    .ok_or_else(|| eyre!("no filter wheel on the camera CFW port"))?;
+                camera.set_parameter(
+                    qhyccd_rs::Control::CfwPort,
+                    f64::from(position) + CFW_PORT_POSITION_OFFSET,
+                )
+            }
+        }
+    }
+}
+
+impl QhyccdFilterWheel {
+    /// Marks the wheel as unplugged, see [`hotplug`].
+    fn detach(&self) {
+        self.attached.store(false, Ordering::Relaxed);
+        if let Err(e) = self.device.close() {
+            debug!(?e, "closing the unplugged filter wheel failed");
+        }
+    }
+
+    /// Checks that configured names and focus offsets have one entry per filter.
+    fn validate_config(config: &FilterWheelConfig, number_of_filters: u32) -> ASCOMResult {
+        if let Some(names) = &config.names {
+            if names.len() != number_of_filters as usize {
+                return Err(ASCOMError::invalid_value(format!(
+                    "{} filter names configured, but the filter wheel has {} filters",
+                    names.len(),
+                    number_of_filters
+                )));
+            }
+        }
+        if let Some(focus_offsets) = &config.focus_offsets {
+            if focus_offsets.len() != number_of_filters as usize {
+                return Err(ASCOMError::invalid_value(format!(
+                    "{} focus offsets configured, but the filter wheel has {} filters",
+                    focus_offsets.len(),
+                    number_of_filters
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Queries (empty `parameters`) or replaces the filter names, given as a JSON array.
+    async fn filter_names_action(&self, parameters: &str) -> ASCOMResult<String> {
+        if !parameters.trim().is_empty() {
+            let names: Vec<String> = serde_json::from_str(parameters).map_err(|e| {
+                debug!(?e, "invalid filter names");
+                ASCOMError::invalid_value("FilterNames parameter must be a JSON array of strings")
+            })?;
+            self.update_config(|config| config.names = Some(names))
+                .await?;
+        }
+        Ok(serde_json::Value::from(self.names().await?).to_string())
+    }
+
+    /// Queries (empty `parameters`) or replaces the focus offsets, given as a JSON array.
+    async fn focus_offsets_action(&self, parameters: &str) -> ASCOMResult<String> {
+        if !parameters.trim().is_empty() {
+            let focus_offsets: Vec<i32> = serde_json::from_str(parameters).map_err(|e| {
+                debug!(?e, "invalid focus offsets");
+                ASCOMError::invalid_value("FocusOffsets parameter must be a JSON array of integers")
+            })?;
+            self.update_config(|config| config.focus_offsets = Some(focus_offsets))
+                .await?;
+        }
+        Ok(serde_json::Value::from(self.focus_offsets().await?).to_string())
+    }
+
+    /// Applies `update` to the stored settings of this wheel after validating the result.
+    async fn update_config(&self, update: impl FnOnce(&mut FilterWheelConfig)) -> ASCOMResult {
+        ensure_connected!(self);
+        let Some(number_of_filters) = *self.number_of_filters.read().await else {
+            error!("number of filters not set, but filter wheel connected");
+            return Err(ASCOMError::NOT_CONNECTED);
+        };
+        let mut config = self.config.filter_wheel(&self.unique_id).await;
+        update(&mut config);
+        Self::validate_config(&config, number_of_filters)?;
+        self.config
+            .set_filter_wheel(&self.unique_id, config)
+            .await
+            .map_err(|e| {
+                error!(?e, "saving filter wheel configuration failed");
+                ASCOMError::invalid_operation("could not save configuration")
+            })
+    }
 }
 
 #[async_trait]
@@ -1491,17 +3745,39 @@ impl Device for QhyccdFilterWheel {
     }
 
     async fn connected(&self) -> ASCOMResult<bool> {
+        if !self.attached.load(Ordering::Relaxed) {
+            return Ok(false);
+        }
         self.device.is_open().map_err(|e| {
             error!(?e, "is_open failed");
             ASCOMError::NOT_CONNECTED
         })
     }
 
+    async fn supported_actions(&self) -> ASCOMResult<Vec<String>> {
+        Ok(vec!["FilterNames".to_owned(), "FocusOffsets".to_owned()])
+    }
+
+    async fn action(&self, action: String, parameters: String) -> ASCOMResult<String> {
+        match action.to_lowercase().as_str() {
+            "filternames" => self.filter_names_action(&parameters).await,
+            "focusoffsets" => self.focus_offsets_action(&parameters).await,
+            _ => {
+                debug!("unsupported action: {}", action);
+                Err(ASCOMError::ACTION_NOT_IMPLEMENTED)
+            }
+        }
+    }
+
     async fn set_connected(&self, connected: bool) -> ASCOMResult {
         if self.connected().await? == connected {
             return Ok(());
         };
         match connected {
+            true if !self.attached.load(Ordering::Relaxed) => {
+                error!("filter wheel is not attached");
+                Err(ASCOMError::NOT_CONNECTED)
+            }
             true => {
                 self.device.open().map_err(|e| {
                     error!(?e, "open failed");
@@ -1512,6 +3788,17 @@ impl Device for QhyccdFilterWheel {
                     error!(?e, "get_number_of_filters failed");
                     ASCOMError::NOT_CONNECTED
                 })?;
+                let config = self.config.filter_wheel(&self.unique_id).await;
+                if let Err(e) = Self::validate_config(&config, number_of_filters) {
+                    error!(
+                        ?e,
+                        "filter wheel configuration does not match the filter wheel"
+                    );
+                    if let Err(e) = self.device.close() {
+                        error!(?e, "close failed");
+                    }
+                    return Err(e);
+                }
                 *lock = Some(number_of_filters);
                 let mut lock = self.target_position.write().await;
                 let target_position = self.device.get_fw_position().map_err(|e| {
@@ -1550,7 +3837,12 @@ impl FilterWheel for QhyccdFilterWheel {
             error!("number of filters not set, but filter wheel connected");
             return Err(ASCOMError::NOT_CONNECTED);
         };
-        Ok(vec![0; number_of_filters as usize])
+        Ok(self
+            .config
+            .filter_wheel(&self.unique_id)
+            .await
+            .focus_offsets
+            .unwrap_or_else(|| vec![0; number_of_filters as usize]))
     }
 
     /// The names of the filters
@@ -1560,6 +3852,9 @@ impl FilterWheel for QhyccdFilterWheel {
             error!("number of filters not set, but filter wheel connected");
             return Err(ASCOMError::NOT_CONNECTED);
         };
+        if let Some(names) = self.config.filter_wheel(&self.unique_id).await.names {
+            return Ok(names);
+        }
         let mut names = Vec::with_capacity(number_of_filters as usize);
         for i in 0..number_of_filters {
             names.push(format!("Filter{}", i));
@@ -1573,6 +3868,19 @@ impl FilterWheel for QhyccdFilterWheel {
             error!("target_position not set, but filter wheel connected");
             return Err(ASCOMError::NOT_CONNECTED);
         };
+        // held while the wheel is asked, so an exposure cannot start in between
+        let camera_state = match &self.camera_state {
+            Some(camera_state) => Some(camera_state.read().await),
+            None => None,
+        };
+        if camera_state
+            .as_ref()
+            .is_some_and(|state| matches!(**state, State::Exposing { .. }))
+        {
+            // the wheel does not move during an exposure, and the camera is busy reading out
+            trace!("camera is exposing, not asking the filter wheel");
+            return Ok(Some(target_position as usize));
+        }
         let actual = self.device.get_fw_position().map_err(|e| {
             error!(?e, "get_fw_position failed");
             ASCOMError::INVALID_OPERATION
@@ -1603,6 +3911,20 @@ impl FilterWheel for QhyccdFilterWheel {
         if lock.is_some_and(|target_position| target_position == position as u32) {
             return Ok(());
         }
+        // held until the move is issued, so an exposure cannot start in between
+        let camera_state = match &self.camera_state {
+            Some(camera_state) => Some(camera_state.read().await),
+            None => None,
+        };
+        if camera_state
+            .as_ref()
+            .is_some_and(|state| matches!(**state, State::Exposing { .. }))
+        {
+            debug!("camera is exposing, not moving the filter wheel");
+            return Err(ASCOMError::invalid_operation(
+                "cannot move the filter wheel during an exposure",
+            ));
+        }
         self.device.set_fw_position(position as u32).map_or_else(
             |e| {
                 error!(?e, "set_fw_position failed");
@@ -1616,5 +3938,22 @@ impl FilterWheel for QhyccdFilterWheel {
     }
 }
 
+/// The image pipeline, for the benchmarks in `benches/`, not a stable API.
+#[cfg(feature = "bench")]
+#[doc(hidden)]
+pub mod bench {
+    use ascom_alpaca::api::camera::ImageArray;
+
+    /// What `ImageArray` returns for a stored frame.
+    pub fn image_array(image: &qhyccd_rs::ImageData) -> eyre::Result<ImageArray> {
+        crate::QhyccdCamera::transform_image_static(image)
+    }
+
+    /// What the frame endpoint sends for a stored frame.
+    pub fn image_bytes(image: &qhyccd_rs::ImageData) -> eyre::Result<Vec<u8>> {
+        crate::image::image_bytes(image, 0, 0)
+    }
+}
+
 #[cfg(test)]
 mod tests;
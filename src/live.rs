@@ -0,0 +1,219 @@
+//! Live video mode
+//!
+//! In live mode the camera streams frames continuously. A background task reads them into a
+//! small ring buffer, from where Alpaca clients get the newest one through `ImageArray` and the
+//! optional HTTP endpoint streams every frame, see [`crate::endpoint`].
+//!
+//! The SDK reports an error until the next frame is ready, the task asks again with a growing
+//! interval and gives up if no frame arrives for [`FRAME_TIMEOUT`]. With a USB traffic back-off
+//! every warning about missing frames raises the USB traffic, see [`crate::tuning`].
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use tokio::sync::{RwLock, oneshot, watch};
+use tokio::task::{self, JoinHandle};
+use tokio::time::Instant;
+use tracing::{debug, error, trace, warn};
+
+use crate::tuning::UsbBackoff;
+use crate::{QhyCamera, image};
+
+/// Number of frames kept in the ring buffer.
+pub(crate) const RING_SIZE: usize = 8;
+
+/// How long to wait before asking the SDK again if no frame was ready, doubled up to
+/// [`MAX_POLL_INTERVAL`] while none is.
+const POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+const MAX_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// The task stops if the SDK delivers no frame for this long.
+pub(crate) const FRAME_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Failed reads between two warnings.
+const FAILURES_PER_WARNING: u32 = 100;
+
+/// A frame read in live mode.
+#[derive(Debug)]
+pub(crate) struct LiveFrame {
+    /// counts up from 1 for every frame of a feed
+    pub(crate) sequence: u64,
+    pub(crate) timestamp: SystemTime,
+    pub(crate) image: Arc<qhyccd_rs::ImageData>,
+}
+
+impl LiveFrame {
+    /// Headers describing the frame, each terminated by CRLF.
+    pub(crate) fn part_headers(&self) -> String {
+        format!(
+            "Content-Type: application/octet-stream\r\nContent-Length: {}\r\nX-Sequence: {}\r\nX-Timestamp: {:.6}\r\nX-Width: {}\r\nX-Height: {}\r\nX-Bits-Per-Pixel: {}\r\nX-Channels: {}\r\n",
+            self.image.data.len(),
+            self.sequence,
+            self.timestamp
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs_f64(),
+            self.image.width,
+            self.image.height,
+            self.image.bits_per_pixel,
+            self.image.channels,
+        )
+    }
+}
+
+/// The ring buffer of one camera, shared by the capture task and the readers.
+#[derive(Debug)]
+pub(crate) struct LiveFeed {
+    frames: RwLock<VecDeque<Arc<LiveFrame>>>,
+    /// sequence of the newest frame, 0 before the first one
+    sequence: watch::Sender<u64>,
+}
+
+impl Default for LiveFeed {
+    fn default() -> Self {
+        Self {
+            frames: RwLock::new(VecDeque::with_capacity(RING_SIZE)),
+            sequence: watch::channel(0).0,
+        }
+    }
+}
+
+impl LiveFeed {
+    /// Adds a frame, dropping the oldest one if the buffer is full.
+    pub(crate) async fn push(&self, image: qhyccd_rs::ImageData) -> u64 {
+        let sequence = *self.sequence.borrow() + 1;
+        let mut frames = self.frames.write().await;
+        if frames.len() == RING_SIZE {
+            frames.pop_front();
+        }
+        frames.push_back(Arc::new(LiveFrame {
+            sequence,
+            timestamp: SystemTime::now(),
+            image: Arc::new(image),
+        }));
+        drop(frames);
+        self.sequence.send_replace(sequence);
+        sequence
+    }
+
+    pub(crate) async fn latest(&self) -> Option<Arc<LiveFrame>> {
+        self.frames.read().await.back().cloned()
+    }
+
+    /// Number of frames in the buffer.
+    pub(crate) async fn len(&self) -> usize {
+        self.frames.read().await.len()
+    }
+
+    /// Drops the frames of a previous session, the sequence keeps counting.
+    pub(crate) async fn clear(&self) {
+        self.frames.write().await.clear();
+    }
+
+    pub(crate) fn sequence(&self) -> u64 {
+        *self.sequence.borrow()
+    }
+
+    pub(crate) fn subscribe(&self) -> watch::Receiver<u64> {
+        self.sequence.subscribe()
+    }
+}
+
+/// The capture task of a camera in live mode.
+#[derive(Debug)]
+pub(crate) struct LiveSession {
+    stop_tx: oneshot::Sender<()>,
+    task: JoinHandle<()>,
+}
+
+impl LiveSession {
+    /// Whether the task still reads frames, see [`FRAME_TIMEOUT`].
+    pub(crate) fn is_running(&self) -> bool {
+        !self.task.is_finished()
+    }
+
+    /// Stops capturing and waits for the frame being read.
+    pub(crate) async fn stop(self) {
+        let _ = self.stop_tx.send(());
+        if let Err(e) = self.task.await {
+            error!(?e, "live task failed");
+        }
+    }
+}
+
+/// Reads frames of `buffer_size` bytes into `feed` until the session is stopped, shifting
+/// 16 bit samples down to `native_bits` if set and backing off the USB traffic with
+/// `usb_backoff` while frames go missing.
+///
+/// The camera must already be in live mode.
+pub(crate) fn start(
+    device: QhyCamera,
+    feed: Arc<LiveFeed>,
+    buffer_size: usize,
+    native_bits: Option<u32>,
+    usb_backoff: Option<UsbBackoff>,
+) -> LiveSession {
+    let (stop_tx, mut stop_rx) = oneshot::channel::<()>();
+    let task = tokio::spawn(async move {
+        let mut device = device;
+        let mut poll_interval = POLL_INTERVAL;
+        let mut last_frame = Instant::now();
+        let mut failures = 0_u32;
+        loop {
+            let frame_task = task::spawn_blocking(move || {
+                let mut frame = device.get_live_frame(buffer_size);
+                if let (Ok(image), Some(bits)) = (&mut frame, native_bits) {
+                    image::to_native(image, bits);
+                }
+                (device, frame)
+            });
+            let frame = match frame_task.await {
+                Ok((returned, frame)) => {
+                    device = returned;
+                    frame
+                }
+                Err(e) => {
+                    error!(?e, "live frame task failed");
+                    return;
+                }
+            };
+            match frame {
+                Ok(image) => {
+                    let sequence = feed.push(image).await;
+                    trace!(sequence, "live frame");
+                    poll_interval = POLL_INTERVAL;
+                    last_frame = Instant::now();
+                    failures = 0;
+                }
+                Err(e) => {
+                    failures += 1;
+                    trace!(?e, failures, "no live frame");
+                    if last_frame.elapsed() >= FRAME_TIMEOUT {
+                        error!(
+                            ?e,
+                            failures, "no live frame for {FRAME_TIMEOUT:?}, giving up"
+                        );
+                        return;
+                    }
+                    if failures % FAILURES_PER_WARNING == 0 {
+                        warn!(?e, failures, "still no live frame");
+                        if let Some(usb_backoff) = &usb_backoff {
+                            usb_backoff.apply(&device).await;
+                        }
+                    }
+                    tokio::time::sleep(poll_interval).await;
+                    poll_interval = (poll_interval * 2).min(MAX_POLL_INTERVAL);
+                }
+            }
+            match stop_rx.try_recv() {
+                Err(oneshot::error::TryRecvError::Empty) => {}
+                _ => {
+                    debug!("live mode stopped");
+                    return;
+                }
+            }
+        }
+    });
+    LiveSession { stop_tx, task }
+}
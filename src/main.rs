@@ -1,5 +1,5 @@
 use clap::Parser;
-use qhyccd_alpaca::ServerBuilder;
+use qhyccd_alpaca::{CoolerRamp, DEFAULT_WARM_UP_RATE, DarkFramePolicy, FitsExport, ServerBuilder};
 
 /// ASCOM Alpaca server for QHYCCD cameras and filter wheels
 #[derive(Parser, Debug)]
@@ -12,6 +12,38 @@ struct Args {
     /// valid values: trace, debug, info, warn, error
     #[arg(short, long, default_value = "info")]
     log_level: Option<String>,
+
+    /// How to handle dark and bias frames on cameras without a mechanical shutter
+    #[arg(long, value_enum, default_value = "warn")]
+    dark_frame_policy: DarkFramePolicy,
+
+    /// Write every completed exposure as a FITS file to this directory
+    #[arg(long)]
+    fits_dir: Option<std::path::PathBuf>,
+
+    /// File name template for FITS files, placeholders: {camera}, {frame_type}, {date_obs}, {exposure}
+    #[arg(long, default_value = qhyccd_alpaca::DEFAULT_FITS_FILENAME_TEMPLATE)]
+    fits_template: String,
+
+    /// Cool-down ramp of the cooler set-point in °C per minute, unlimited if not given
+    #[arg(long)]
+    cool_down_rate: Option<f64>,
+
+    /// Warm-up ramp in °C per minute, also used to warm the sensor up on disconnect and shutdown
+    #[arg(long, default_value_t = DEFAULT_WARM_UP_RATE)]
+    warm_up_rate: f64,
+
+    /// Serve live frames, the last exposures as ImageBytes and sequence progress over HTTP on this port
+    #[arg(long)]
+    live_port: Option<u16>,
+
+    /// Per-device settings file, applied on connect and written by the SaveSettings, FilterNames and FocusOffsets actions
+    #[arg(long)]
+    config: Option<std::path::PathBuf>,
+
+    /// Look for cameras and filter wheels plugged in or unplugged every this many seconds, off if not given
+    #[arg(long)]
+    rescan_interval: Option<u64>,
 }
 
 fn resolve_log_level(cli_arg: Option<String>) -> eyre::Result<tracing::Level> {
@@ -22,7 +54,7 @@ fn resolve_log_level(cli_arg: Option<String>) -> eyre::Result<tracing::Level> {
 }
 
 #[tokio::main]
-async fn main() -> eyre::Result<std::convert::Infallible> {
+async fn main() -> eyre::Result<()> {
     let args = Args::parse();
     let log_level = resolve_log_level(args.log_level)?;
 
@@ -30,12 +62,28 @@ async fn main() -> eyre::Result<std::convert::Infallible> {
         tracing_subscriber::fmt().with_max_level(log_level).finish(),
     )?;
 
-    ServerBuilder::new()
+    let mut builder = ServerBuilder::new()
         .with_port(args.port)
-        .build()
-        .await?
-        .start()
-        .await
+        .with_dark_frame_policy(args.dark_frame_policy)
+        .with_cooler_ramp(CoolerRamp {
+            cool_down: args.cool_down_rate,
+            warm_up: Some(args.warm_up_rate),
+        });
+    if let Some(fits_dir) = args.fits_dir {
+        builder = builder
+            .with_fits_export(FitsExport::new(fits_dir).with_filename_template(args.fits_template));
+    }
+    if let Some(config) = args.config {
+        builder = builder.with_config_file(config);
+    }
+    if let Some(live_port) = args.live_port {
+        builder = builder.with_live_port(live_port);
+    }
+    if let Some(rescan_interval) = args.rescan_interval {
+        builder = builder.with_rescan_interval(std::time::Duration::from_secs(rescan_interval));
+    }
+
+    builder.build().await?.start().await
 }
 
 #[cfg(test)]
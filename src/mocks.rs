@@ -51,9 +51,6 @@ mock! {
         pub fn open(&self) -> Result<()>;
         pub fn close(&self) -> Result<()>;
         pub fn is_open(&self) -> Result<bool>;
-        pub fn get_number_of_filters(&self) -> Option<u32>;
-        pub fn get_fw_position(&self) -> Option<u32>;
-        pub fn set_fw_position(&self, position: u32) -> Result<()>;
     }
     impl Clone for Camera {
         fn clone(&self) -> Self;
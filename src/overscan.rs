@@ -0,0 +1,106 @@
+//! Overscan geometry and overscan-inclusive readout
+//!
+//! On connect the SDK reports the effective area of the chip, which the driver normally uses
+//! as the full frame, and the overscan area next to it. With `include_overscan` the full frame
+//! becomes the whole chip, so the overscan is read out with every image for bias-level
+//! tracking:
+//!
+//! ```toml
+//! [cameras.QHY268M-abc123]
+//! include_overscan = true
+//! ```
+//!
+//! The chip areas are in unbinned pixels, the ROI and the areas reported for a frame are in
+//! binned pixels like the rest of the ASCOM interface.
+use qhyccd_rs::{CCDChipArea, CCDChipInfo};
+
+use crate::binning::Binning;
+
+/// The frame read at bin 1: the whole chip with the overscan, the effective area without.
+pub(crate) fn full_frame(
+    include_overscan: bool,
+    effective_area: CCDChipArea,
+    ccd_info: Option<CCDChipInfo>,
+) -> CCDChipArea {
+    match (include_overscan, ccd_info) {
+        (true, Some(ccd_info)) => CCDChipArea {
+            start_x: 0,
+            start_y: 0,
+            width: ccd_info.image_width,
+            height: ccd_info.image_height,
+        },
+        _ => effective_area,
+    }
+}
+
+/// `area` in pixels binned by `bin`, parts of a binned pixel are dropped.
+pub(crate) fn binned(area: CCDChipArea, bin: Binning) -> CCDChipArea {
+    let bin_x = u32::from(bin.x.max(1));
+    let bin_y = u32::from(bin.y.max(1));
+    let start_x = area.start_x.div_ceil(bin_x);
+    let start_y = area.start_y.div_ceil(bin_y);
+    CCDChipArea {
+        start_x,
+        start_y,
+        width: (area.start_x + area.width)
+            .div_euclid(bin_x)
+            .saturating_sub(start_x),
+        height: (area.start_y + area.height)
+            .div_euclid(bin_y)
+            .saturating_sub(start_y),
+    }
+}
+
+/// Whether `roi` lies within `area`, both in the same pixels.
+pub(crate) fn contains(area: CCDChipArea, roi: CCDChipArea) -> bool {
+    roi.start_x >= area.start_x
+        && roi.start_y >= area.start_y
+        && roi.start_x + roi.width <= area.start_x + area.width
+        && roi.start_y + roi.height <= area.start_y + area.height
+}
+
+/// The part of `area` within `frame`, `None` if they do not overlap.
+pub(crate) fn clamp(area: CCDChipArea, frame: CCDChipArea) -> Option<CCDChipArea> {
+    let start_x = area.start_x.max(frame.start_x);
+    let start_y = area.start_y.max(frame.start_y);
+    let end_x = (area.start_x + area.width).min(frame.start_x + frame.width);
+    let end_y = (area.start_y + area.height).min(frame.start_y + frame.height);
+    (start_x < end_x && start_y < end_y).then(|| CCDChipArea {
+        start_x,
+        start_y,
+        width: end_x - start_x,
+        height: end_y - start_y,
+    })
+}
+
+/// The part of the chip area `area` read with `roi` at `bin`, relative to the frame origin.
+pub(crate) fn in_frame(area: CCDChipArea, roi: CCDChipArea, bin: Binning) -> Option<CCDChipArea> {
+    clamp(binned(area, bin), roi).map(|area| CCDChipArea {
+        start_x: area.start_x - roi.start_x,
+        start_y: area.start_y - roi.start_y,
+        ..area
+    })
+}
+
+/// `area` as a JSON object.
+pub(crate) fn to_json(area: Option<CCDChipArea>) -> serde_json::Value {
+    area.map_or(serde_json::Value::Null, |area| {
+        serde_json::json!({
+            "start_x": area.start_x,
+            "start_y": area.start_y,
+            "width": area.width,
+            "height": area.height,
+        })
+    })
+}
+
+/// `area` as a FITS section such as `[1:24,1:6388]`, 1-based and inclusive.
+pub(crate) fn fits_section(area: CCDChipArea) -> String {
+    format!(
+        "[{}:{},{}:{}]",
+        area.start_x + 1,
+        area.start_x + area.width,
+        area.start_y + 1,
+        area.start_y + area.height
+    )
+}
@@ -0,0 +1,111 @@
+//! Named gain and offset presets for the ASCOM `Gains`/`Offsets` list mode
+//!
+//! Presets are defined per model prefix and can be replaced in the configuration file. The
+//! list mode is switched on per camera:
+//!
+//! ```toml
+//! [presets.QHY600]
+//! gains = [
+//!     { name = "Unity", gain = 26, readout_mode = 0 },
+//!     { name = "HCG", gain = 56, readout_mode = 1 },
+//! ]
+//! offsets = [{ name = "Standard", offset = 30 }]
+//!
+//! [cameras.QHY600M-abc123]
+//! presets = true
+//! ```
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::by_model_prefix;
+
+/// A named gain, optionally tied to the readout mode it was chosen for.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GainPreset {
+    pub name: String,
+    pub gain: i32,
+    #[serde(default)]
+    pub readout_mode: Option<u32>,
+}
+
+/// A named offset.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OffsetPreset {
+    pub name: String,
+    pub offset: i32,
+}
+
+/// The preset lists of a model, an empty list leaves that setting in value mode.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Presets {
+    pub gains: Vec<GainPreset>,
+    pub offsets: Vec<OffsetPreset>,
+}
+
+/// (name, gain, readout mode)
+type Gains = &'static [(&'static str, i32, Option<u32>)];
+/// (name, offset)
+type Offsets = &'static [(&'static str, i32)];
+
+/// Built-in presets keyed by model prefix.
+const BUILTIN: &[(&str, Gains, Offsets)] = &[
+    (
+        "QHY600",
+        &[
+            ("HighDynamicRange", 0, Some(0)),
+            ("Unity", 26, Some(0)),
+            ("HCG", 56, Some(1)),
+            ("LowNoise", 100, Some(1)),
+        ],
+        &[("Standard", 30), ("Low", 10)],
+    ),
+    (
+        "QHY268",
+        &[
+            ("HighDynamicRange", 0, Some(0)),
+            ("Unity", 30, Some(0)),
+            ("HCG", 56, Some(1)),
+            ("LowNoise", 100, Some(1)),
+        ],
+        &[("Standard", 30), ("Low", 10)],
+    ),
+    (
+        "QHY533",
+        &[
+            ("HighDynamicRange", 0, None),
+            ("HCG", 60, None),
+            ("LowNoise", 100, None),
+        ],
+        &[("Standard", 30), ("Low", 10)],
+    ),
+];
+
+/// The presets for `model`, overrides win over the built-in table.
+pub(crate) fn for_model(model: &str, overrides: &BTreeMap<String, Presets>) -> Option<Presets> {
+    if let Some(presets) = by_model_prefix(model, overrides) {
+        return Some(presets.clone());
+    }
+    let (_, gains, offsets) = BUILTIN
+        .iter()
+        .filter(|(prefix, _, _)| model.starts_with(prefix))
+        .max_by_key(|(prefix, _, _)| prefix.len())?;
+    Some(Presets {
+        gains: gains
+            .iter()
+            .map(|&(name, gain, readout_mode)| GainPreset {
+                name: name.to_owned(),
+                gain,
+                readout_mode,
+            })
+            .collect(),
+        offsets: offsets
+            .iter()
+            .map(|&(name, offset)| OffsetPreset {
+                name: name.to_owned(),
+                offset,
+            })
+            .collect(),
+    })
+}
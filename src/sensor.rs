@@ -0,0 +1,209 @@
+//! Sensor characteristics behind ElectronsPerADU and FullWellCapacity
+//!
+//! The built-in values are approximations read off the manufacturer's published gain curves,
+//! the conversion gain in ADU of the 16-bit output and the full well in electrons. Users with their own measurements can override a model in the
+//! configuration file:
+//!
+//! ```toml
+//! [[sensors.QHY600]]
+//! readout_mode = 1
+//! points = [
+//!     { gain = 0, electrons_per_adu = 0.33, full_well_capacity = 21500 },
+//!     { gain = 60, electrons_per_adu = 0.12, full_well_capacity = 7800 },
+//! ]
+//! ```
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::by_model_prefix;
+
+/// Conversion gain and full well at one gain setting.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SensorPoint {
+    pub gain: f64,
+    pub electrons_per_adu: f64,
+    pub full_well_capacity: f64,
+}
+
+impl SensorPoint {
+    /// The point for frames shifted down by `bits` from the 16-bit output, each ADU of them
+    /// holds `2^bits` times the electrons.
+    pub(crate) fn shifted(self, bits: u32) -> Self {
+        Self {
+            electrons_per_adu: self.electrons_per_adu * f64::from(1_u32 << bits),
+            ..self
+        }
+    }
+}
+
+/// Gain curve of a sensor, either for one readout mode or for all of them.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SensorCurve {
+    #[serde(default)]
+    pub readout_mode: Option<u32>,
+    pub points: Vec<SensorPoint>,
+}
+
+/// (gain, e-/ADU, full well) at a few gain settings
+type Points = &'static [(f64, f64, f64)];
+
+/// Built-in curves keyed by model prefix, `None` applies to every readout mode.
+const BUILTIN: &[(&str, Option<u32>, Points)] = &[
+    (
+        "QHY600",
+        Some(0),
+        &[
+            (0_f64, 0.80_f64, 51_000_f64),
+            (26_f64, 0.50_f64, 32_000_f64),
+            (60_f64, 0.22_f64, 14_000_f64),
+            (100_f64, 0.08_f64, 5_000_f64),
+        ],
+    ),
+    (
+        "QHY600",
+        Some(1),
+        &[
+            (0_f64, 0.33_f64, 21_500_f64),
+            (56_f64, 0.13_f64, 8_500_f64),
+            (100_f64, 0.05_f64, 3_300_f64),
+        ],
+    ),
+    (
+        "QHY268",
+        Some(0),
+        &[
+            (0_f64, 0.78_f64, 51_000_f64),
+            (30_f64, 0.45_f64, 29_500_f64),
+            (60_f64, 0.21_f64, 13_700_f64),
+            (100_f64, 0.08_f64, 5_200_f64),
+        ],
+    ),
+    (
+        "QHY268",
+        Some(1),
+        &[
+            (0_f64, 0.34_f64, 22_000_f64),
+            (56_f64, 0.13_f64, 8_500_f64),
+            (100_f64, 0.05_f64, 3_300_f64),
+        ],
+    ),
+    (
+        "QHY294",
+        None,
+        &[
+            (0_f64, 1.00_f64, 66_000_f64),
+            (1600_f64, 0.40_f64, 26_000_f64),
+            (3000_f64, 0.10_f64, 6_500_f64),
+        ],
+    ),
+    (
+        "QHY533",
+        None,
+        &[
+            (0_f64, 0.76_f64, 50_000_f64),
+            (60_f64, 0.25_f64, 16_000_f64),
+            (100_f64, 0.10_f64, 6_500_f64),
+        ],
+    ),
+    (
+        "QHY183",
+        None,
+        &[
+            (0_f64, 0.24_f64, 15_500_f64),
+            (20_f64, 0.12_f64, 7_800_f64),
+            (30_f64, 0.06_f64, 3_900_f64),
+        ],
+    ),
+    (
+        "QHY178",
+        None,
+        &[
+            (0_f64, 0.23_f64, 15_000_f64),
+            (20_f64, 0.11_f64, 7_200_f64),
+            (30_f64, 0.05_f64, 3_300_f64),
+        ],
+    ),
+    (
+        "QHY5III290",
+        None,
+        &[
+            (0_f64, 0.25_f64, 16_000_f64),
+            (20_f64, 0.12_f64, 7_900_f64),
+            (50_f64, 0.03_f64, 2_000_f64),
+        ],
+    ),
+];
+
+/// The curves for `model`, overrides win over the built-in table.
+///
+/// Keys are model prefixes, the longest matching prefix is used.
+pub(crate) fn curves(
+    model: &str,
+    overrides: &BTreeMap<String, Vec<SensorCurve>>,
+) -> Option<Vec<SensorCurve>> {
+    if let Some(curves) = by_model_prefix(model, overrides) {
+        return Some(curves.clone());
+    }
+    let prefix = BUILTIN
+        .iter()
+        .map(|(prefix, _, _)| *prefix)
+        .filter(|prefix| model.starts_with(prefix))
+        .max_by_key(|prefix| prefix.len())?;
+    Some(
+        BUILTIN
+            .iter()
+            .filter(|(p, _, _)| *p == prefix)
+            .map(|(_, readout_mode, points)| SensorCurve {
+                readout_mode: *readout_mode,
+                points: points
+                    .iter()
+                    .map(
+                        |&(gain, electrons_per_adu, full_well_capacity)| SensorPoint {
+                            gain,
+                            electrons_per_adu,
+                            full_well_capacity,
+                        },
+                    )
+                    .collect(),
+            })
+            .collect(),
+    )
+}
+
+/// Picks the curve for `readout_mode` (falling back to one for all modes) and interpolates it
+/// linearly at `gain`, gains outside the curve are clamped to its ends.
+pub(crate) fn interpolate(
+    curves: &[SensorCurve],
+    readout_mode: Option<u32>,
+    gain: f64,
+) -> Option<SensorPoint> {
+    let curve = curves
+        .iter()
+        .find(|curve| readout_mode.is_some() && curve.readout_mode == readout_mode)
+        .or_else(|| curves.iter().find(|curve| curve.readout_mode.is_none()))?;
+    let mut points = curve.points.clone();
+    points.sort_by(|a, b| a.gain.total_cmp(&b.gain));
+    let first = *points.first()?;
+    let last = *points.last()?;
+    if gain <= first.gain {
+        return Some(SensorPoint { gain, ..first });
+    }
+    if gain >= last.gain {
+        return Some(SensorPoint { gain, ..last });
+    }
+    points.windows(2).find_map(|pair| {
+        let (low, high) = (pair[0], pair[1]);
+        if !(low.gain..=high.gain).contains(&gain) || high.gain == low.gain {
+            return None;
+        }
+        let t = (gain - low.gain) / (high.gain - low.gain);
+        Some(SensorPoint {
+            gain,
+            electrons_per_adu: low.electrons_per_adu
+                + t * (high.electrons_per_adu - low.electrons_per_adu),
+            full_well_capacity: low.full_well_capacity
+                + t * (high.full_well_capacity - low.full_well_capacity),
+        })
+    })
+}
@@ -0,0 +1,404 @@
+//! Exposure sequences run by the server
+//!
+//! A sequence is a list of entries, each taking `count` frames with the same settings. The
+//! runner applies the settings of an entry, moves the filter wheel and takes the frames
+//! back-to-back, writing every frame to a FITS file, so a sequence keeps going when the client
+//! that started it goes away. Progress is reported by the `SequenceStatus` action and served by
+//! the frame endpoint at `GET /sequence/<camera id>`.
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use ascom_alpaca::api::{Camera, FilterWheel};
+use ascom_alpaca::{ASCOMError, ASCOMResult};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{Mutex, RwLock, watch};
+use tokio::task::{self, JoinHandle};
+use tracing::{debug, error, info, warn};
+
+use crate::{FrameType, QhyccdCamera, fits};
+
+/// How often a moving filter wheel is polled.
+const WHEEL_POLL: Duration = Duration::from_millis(250);
+
+/// How long a filter wheel may take to reach its position.
+const WHEEL_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// A sequence as given to the `StartSequence` action.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Sequence {
+    pub(crate) entries: Vec<SequenceEntry>,
+}
+
+/// Frames taken with the same settings, settings left out keep the current value.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct SequenceEntry {
+    pub(crate) count: u32,
+    /// exposure time in seconds, bias frames use the shortest exposure of the camera
+    #[serde(default)]
+    pub(crate) duration: f64,
+    #[serde(default)]
+    pub(crate) frame_type: SequenceFrameType,
+    /// gain and offset as set through `Gain` and `Offset`, preset indices with presets
+    pub(crate) gain: Option<i32>,
+    pub(crate) offset: Option<i32>,
+    pub(crate) bin: Option<u8>,
+    pub(crate) roi: Option<SequenceRoi>,
+    pub(crate) filter: Option<FilterSlot>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum SequenceFrameType {
+    #[default]
+    Light,
+    Dark,
+    Bias,
+}
+
+/// A subframe in binned pixels, like `StartX`, `StartY`, `NumX` and `NumY`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct SequenceRoi {
+    pub(crate) start_x: u32,
+    pub(crate) start_y: u32,
+    pub(crate) num_x: u32,
+    pub(crate) num_y: u32,
+}
+
+/// A position on a filter wheel, which is named by its unique id, e.g. `CFW=QHY600M-1234`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct FilterSlot {
+    pub(crate) wheel: String,
+    pub(crate) position: usize,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum SequenceState {
+    #[default]
+    Idle,
+    Running,
+    Completed,
+    Aborted,
+    Failed,
+}
+
+/// Where a sequence is, as served to clients.
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+pub(crate) struct Progress {
+    pub(crate) state: SequenceState,
+    /// index of the entry being run
+    pub(crate) entry: Option<usize>,
+    /// frames of that entry written so far
+    pub(crate) frame: u32,
+    pub(crate) frames_done: u32,
+    pub(crate) frames_total: u32,
+    pub(crate) last_file: Option<PathBuf>,
+    pub(crate) error: Option<String>,
+}
+
+#[derive(Debug)]
+struct Run {
+    stop_tx: watch::Sender<bool>,
+    task: JoinHandle<()>,
+}
+
+/// The sequence runner of one camera, shared with the frame endpoint.
+#[derive(Debug, Default)]
+pub(crate) struct Sequencer {
+    pub(crate) progress: RwLock<Progress>,
+    run: Mutex<Option<Run>>,
+}
+
+impl Sequencer {
+    pub(crate) async fn progress(&self) -> Progress {
+        self.progress.read().await.clone()
+    }
+
+    pub(crate) async fn is_running(&self) -> bool {
+        self.progress.read().await.state == SequenceState::Running
+    }
+
+    /// Runs `sequence` on `camera` in the background, which must not run a sequence already.
+    pub(crate) async fn start(&self, camera: Arc<QhyccdCamera>, sequence: Sequence) -> ASCOMResult {
+        let mut run = self.run.lock().await;
+        let mut progress = self.progress.write().await;
+        if progress.state == SequenceState::Running {
+            error!("a sequence is already running");
+            return Err(ASCOMError::invalid_operation(
+                "a sequence is already running",
+            ));
+        }
+        *progress = Progress {
+            state: SequenceState::Running,
+            frames_total: sequence.entries.iter().map(|entry| entry.count).sum(),
+            ..Progress::default()
+        };
+        let (stop_tx, stop_rx) = watch::channel(false);
+        *run = Some(Run {
+            stop_tx,
+            task: tokio::spawn(run_sequence(camera, sequence, stop_rx)),
+        });
+        Ok(())
+    }
+
+    /// Stops the running sequence, aborting its exposure, and waits until it has ended.
+    pub(crate) async fn abort(&self) {
+        let Some(run) = self.run.lock().await.take() else {
+            return;
+        };
+        let _ = run.stop_tx.send(true);
+        if let Err(e) = run.task.await {
+            error!(?e, "sequence task failed");
+        }
+    }
+
+    async fn update(&self, update: impl FnOnce(&mut Progress)) {
+        update(&mut *self.progress.write().await);
+    }
+}
+
+/// Checks a sequence before it is started, so a typo does not show up hours into a run.
+pub(crate) async fn validate(camera: &QhyccdCamera, sequence: &Sequence) -> ASCOMResult {
+    if sequence.entries.is_empty() {
+        return Err(ASCOMError::invalid_value("sequence has no entries"));
+    }
+    if camera.fits_export.is_none() {
+        error!("no FITS output directory configured");
+        return Err(ASCOMError::invalid_operation(
+            "no FITS output directory configured",
+        ));
+    }
+    let filter_wheels = camera.filter_wheels.read().await;
+    for (index, entry) in sequence.entries.iter().enumerate() {
+        if entry.count == 0 {
+            return Err(ASCOMError::invalid_value(format!(
+                "entry {index}: count must be at least 1"
+            )));
+        }
+        if Duration::try_from_secs_f64(entry.duration).is_err() {
+            return Err(ASCOMError::invalid_value(format!(
+                "entry {index}: duration must be a number of seconds"
+            )));
+        }
+        if let Some(filter) = &entry.filter {
+            if !filter_wheels.contains_key(&filter.wheel) {
+                return Err(ASCOMError::invalid_value(format!(
+                    "entry {index}: unknown filter wheel {}",
+                    filter.wheel
+                )));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Why a sequence ended early.
+enum Interrupted {
+    Aborted,
+    Failed(String),
+}
+
+async fn run_sequence(
+    camera: Arc<QhyccdCamera>,
+    sequence: Sequence,
+    mut stop_rx: watch::Receiver<bool>,
+) {
+    info!(entries = sequence.entries.len(), "sequence started");
+    let mut result = Ok(());
+    for (index, entry) in sequence.entries.iter().enumerate() {
+        camera
+            .sequencer
+            .update(|progress| {
+                progress.entry = Some(index);
+                progress.frame = 0;
+            })
+            .await;
+        result = run_entry(&camera, entry, &mut stop_rx)
+            .await
+            .map_err(|interrupted| match interrupted {
+                Interrupted::Failed(e) => Interrupted::Failed(format!("entry {index}: {e}")),
+                aborted => aborted,
+            });
+        if result.is_err() {
+            break;
+        }
+    }
+    let (state, error) = match result {
+        Ok(()) => {
+            info!("sequence completed");
+            (SequenceState::Completed, None)
+        }
+        Err(Interrupted::Aborted) => {
+            info!("sequence aborted");
+            (SequenceState::Aborted, None)
+        }
+        Err(Interrupted::Failed(e)) => {
+            error!(%e, "sequence failed");
+            (SequenceState::Failed, Some(e))
+        }
+    };
+    camera
+        .sequencer
+        .update(|progress| {
+            progress.state = state;
+            progress.error = error;
+        })
+        .await;
+}
+
+async fn run_entry(
+    camera: &QhyccdCamera,
+    entry: &SequenceEntry,
+    stop_rx: &mut watch::Receiver<bool>,
+) -> Result<(), Interrupted> {
+    debug!(?entry, "running sequence entry");
+    apply(camera, entry, stop_rx).await?;
+    let (duration, light) = match entry.frame_type {
+        SequenceFrameType::Light => (Duration::from_secs_f64(entry.duration), true),
+        SequenceFrameType::Dark => (Duration::from_secs_f64(entry.duration), false),
+        SequenceFrameType::Bias => (camera.exposure_min().await.map_err(failed)?, false),
+    };
+    for _ in 0..entry.count {
+        if *stop_rx.borrow() {
+            return Err(Interrupted::Aborted);
+        }
+        let path = expose(camera, duration, light, stop_rx).await?;
+        camera
+            .sequencer
+            .update(|progress| {
+                progress.frame += 1;
+                progress.frames_done += 1;
+                progress.last_file = Some(path);
+            })
+            .await;
+    }
+    Ok(())
+}
+
+/// Applies the settings of an entry, the filter wheel first as it takes longest.
+async fn apply(
+    camera: &QhyccdCamera,
+    entry: &SequenceEntry,
+    stop_rx: &mut watch::Receiver<bool>,
+) -> Result<(), Interrupted> {
+    if let Some(filter) = &entry.filter {
+        let Some(wheel) = camera
+            .filter_wheels
+            .read()
+            .await
+            .get(&filter.wheel)
+            .cloned()
+        else {
+            return Err(Interrupted::Failed(format!(
+                "unknown filter wheel {}",
+                filter.wheel
+            )));
+        };
+        wheel.set_position(filter.position).await.map_err(failed)?;
+        let started = tokio::time::Instant::now();
+        while wheel.position().await.map_err(failed)? != Some(filter.position) {
+            if started.elapsed() > WHEEL_TIMEOUT {
+                return Err(Interrupted::Failed(format!(
+                    "filter wheel {} did not reach position {}",
+                    filter.wheel, filter.position
+                )));
+            }
+            tokio::select! {
+                () = tokio::time::sleep(WHEEL_POLL) => {}
+                _ = stop_rx.changed() => return Err(Interrupted::Aborted),
+            }
+        }
+    }
+    if let Some(bin) = entry.bin {
+        camera.set_bin_x(bin).await.map_err(failed)?;
+        // entries bin square, `BinX` alone does not move `BinY` on asymmetric cameras
+        if camera.can_asymmetric_bin().await.map_err(failed)? {
+            camera.set_bin_y(bin).await.map_err(failed)?;
+        }
+    }
+    if let Some(roi) = entry.roi {
+        camera.set_start_x(roi.start_x).await.map_err(failed)?;
+        camera.set_start_y(roi.start_y).await.map_err(failed)?;
+        camera.set_num_x(roi.num_x).await.map_err(failed)?;
+        camera.set_num_y(roi.num_y).await.map_err(failed)?;
+    }
+    // like `Gain` and `Offset`, so with presets the entry picks a preset by its index
+    if let Some(gain) = entry.gain {
+        camera.set_gain(gain).await.map_err(failed)?;
+    }
+    if let Some(offset) = entry.offset {
+        camera.set_offset(offset).await.map_err(failed)?;
+    }
+    Ok(())
+}
+
+/// Takes one frame and writes it to a FITS file, returning the path.
+async fn expose(
+    camera: &QhyccdCamera,
+    duration: Duration,
+    light: bool,
+    stop_rx: &mut watch::Receiver<bool>,
+) -> Result<PathBuf, Interrupted> {
+    // the header describes the camera as it was when the exposure started, the frame type and
+    // timing are only known once it ran
+    let mut metadata = camera
+        .fits_metadata(FrameType::Light, SystemTime::now(), 0)
+        .await;
+    let mut done_rx = camera
+        .begin_exposure(duration, light, false)
+        .await
+        .map_err(failed)?;
+    let stopped = tokio::select! {
+        _ = done_rx.wait_for(|done| *done) => false,
+        _ = stop_rx.changed() => true,
+    };
+    if stopped {
+        if let Err(e) = camera.abort_exposure().await {
+            warn!(?e, "could not abort the exposure");
+        }
+        // the exposure task ends once the readout completed
+        let _ = done_rx.wait_for(|done| *done).await;
+        return Err(Interrupted::Aborted);
+    }
+    // false if the exposure task ended without an image
+    let done = *done_rx.borrow();
+    let Some(last_image) = camera.last_image.read().await.clone().filter(|_| done) else {
+        return Err(Interrupted::Failed("exposure failed".to_owned()));
+    };
+    let start = camera
+        .last_exposure_start_time
+        .read()
+        .await
+        .unwrap_or_else(SystemTime::now);
+    metadata.frame_type = last_image.frame_type;
+    metadata.date_obs = start;
+    metadata.exposure_us = camera.last_exposure_duration_us.read().await.unwrap_or(0);
+    if last_image.image.channels == 3 {
+        // debayered on readout, the colour planes no longer have a Bayer pattern
+        metadata.bayer_pattern = None;
+    }
+    let Some(export) = camera.fits_export.clone() else {
+        return Err(Interrupted::Failed(
+            "no FITS output directory configured".to_owned(),
+        ));
+    };
+    match task::spawn_blocking(move || fits::write(&export, &metadata, &last_image.image)).await {
+        Ok(Ok(path)) => {
+            debug!(?path, "sequence frame written");
+            Ok(path)
+        }
+        Ok(Err(e)) => Err(Interrupted::Failed(format!(
+            "failed to write FITS file: {e}"
+        ))),
+        Err(e) => Err(Interrupted::Failed(format!("FITS task failed: {e}"))),
+    }
+}
+
+fn failed(e: ASCOMError) -> Interrupted {
+    Interrupted::Failed(e.to_string())
+}
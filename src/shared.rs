@@ -0,0 +1,342 @@
+//! Devices shared between the Alpaca server and background tasks
+//!
+//! The server takes ownership of every registered device, but a sequence has to keep driving
+//! its camera and filter wheels after the request that started it returned. Devices are
+//! therefore registered behind an `Arc`, and [`Shared`] forwards the ASCOM traits to it.
+//!
+//! Every method a device implements has to be forwarded here, or the server falls back to the
+//! default of the trait.
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use ascom_alpaca::ASCOMResult;
+use ascom_alpaca::api::camera::{CameraState, ImageArray, SensorType};
+use ascom_alpaca::api::{Camera, Device, FilterWheel};
+use async_trait::async_trait;
+
+use crate::{QhyccdCamera, QhyccdFilterWheel};
+
+/// A device registered with the server and shared with background tasks.
+#[derive(Debug)]
+pub(crate) struct Shared<T>(pub(crate) Arc<T>);
+
+#[async_trait]
+impl<T: Device> Device for Shared<T> {
+    fn static_name(&self) -> &str {
+        self.0.static_name()
+    }
+
+    fn unique_id(&self) -> &str {
+        self.0.unique_id()
+    }
+
+    async fn connected(&self) -> ASCOMResult<bool> {
+        self.0.connected().await
+    }
+
+    async fn supported_actions(&self) -> ASCOMResult<Vec<String>> {
+        self.0.supported_actions().await
+    }
+
+    async fn action(&self, action: String, parameters: String) -> ASCOMResult<String> {
+        self.0.action(action, parameters).await
+    }
+
+    async fn set_connected(&self, connected: bool) -> ASCOMResult {
+        self.0.set_connected(connected).await
+    }
+
+    async fn description(&self) -> ASCOMResult<String> {
+        self.0.description().await
+    }
+
+    async fn driver_info(&self) -> ASCOMResult<String> {
+        self.0.driver_info().await
+    }
+
+    async fn driver_version(&self) -> ASCOMResult<String> {
+        self.0.driver_version().await
+    }
+}
+
+#[async_trait]
+impl Camera for Shared<QhyccdCamera> {
+    async fn bayer_offset_x(&self) -> ASCOMResult<u8> {
+        self.0.bayer_offset_x().await
+    }
+
+    async fn bayer_offset_y(&self) -> ASCOMResult<u8> {
+        self.0.bayer_offset_y().await
+    }
+
+    async fn sensor_name(&self) -> ASCOMResult<String> {
+        self.0.sensor_name().await
+    }
+
+    async fn bin_x(&self) -> ASCOMResult<u8> {
+        self.0.bin_x().await
+    }
+
+    async fn set_bin_x(&self, bin_x: u8) -> ASCOMResult {
+        self.0.set_bin_x(bin_x).await
+    }
+
+    async fn bin_y(&self) -> ASCOMResult<u8> {
+        self.0.bin_y().await
+    }
+
+    async fn set_bin_y(&self, bin_y: u8) -> ASCOMResult {
+        self.0.set_bin_y(bin_y).await
+    }
+
+    async fn can_asymmetric_bin(&self) -> ASCOMResult<bool> {
+        self.0.can_asymmetric_bin().await
+    }
+
+    async fn max_bin_x(&self) -> ASCOMResult<u8> {
+        self.0.max_bin_x().await
+    }
+
+    async fn max_bin_y(&self) -> ASCOMResult<u8> {
+        self.0.max_bin_y().await
+    }
+
+    async fn camera_state(&self) -> ASCOMResult<CameraState> {
+        self.0.camera_state().await
+    }
+
+    async fn electrons_per_adu(&self) -> ASCOMResult<f64> {
+        self.0.electrons_per_adu().await
+    }
+
+    async fn exposure_max(&self) -> ASCOMResult<Duration> {
+        self.0.exposure_max().await
+    }
+
+    async fn exposure_min(&self) -> ASCOMResult<Duration> {
+        self.0.exposure_min().await
+    }
+
+    async fn exposure_resolution(&self) -> ASCOMResult<Duration> {
+        self.0.exposure_resolution().await
+    }
+
+    async fn full_well_capacity(&self) -> ASCOMResult<f64> {
+        self.0.full_well_capacity().await
+    }
+
+    async fn has_shutter(&self) -> ASCOMResult<bool> {
+        self.0.has_shutter().await
+    }
+
+    async fn image_array(&self) -> ASCOMResult<ImageArray> {
+        self.0.image_array().await
+    }
+
+    async fn image_ready(&self) -> ASCOMResult<bool> {
+        self.0.image_ready().await
+    }
+
+    async fn last_exposure_start_time(&self) -> ASCOMResult<SystemTime> {
+        self.0.last_exposure_start_time().await
+    }
+
+    async fn last_exposure_duration(&self) -> ASCOMResult<Duration> {
+        self.0.last_exposure_duration().await
+    }
+
+    async fn max_adu(&self) -> ASCOMResult<u32> {
+        self.0.max_adu().await
+    }
+
+    async fn camera_x_size(&self) -> ASCOMResult<u32> {
+        self.0.camera_x_size().await
+    }
+
+    async fn camera_y_size(&self) -> ASCOMResult<u32> {
+        self.0.camera_y_size().await
+    }
+
+    async fn start_x(&self) -> ASCOMResult<u32> {
+        self.0.start_x().await
+    }
+
+    async fn set_start_x(&self, start_x: u32) -> ASCOMResult {
+        self.0.set_start_x(start_x).await
+    }
+
+    async fn start_y(&self) -> ASCOMResult<u32> {
+        self.0.start_y().await
+    }
+
+    async fn set_start_y(&self, start_y: u32) -> ASCOMResult {
+        self.0.set_start_y(start_y).await
+    }
+
+    async fn num_x(&self) -> ASCOMResult<u32> {
+        self.0.num_x().await
+    }
+
+    async fn set_num_x(&self, num_x: u32) -> ASCOMResult {
+        self.0.set_num_x(num_x).await
+    }
+
+    async fn num_y(&self) -> ASCOMResult<u32> {
+        self.0.num_y().await
+    }
+
+    async fn set_num_y(&self, num_y: u32) -> ASCOMResult {
+        self.0.set_num_y(num_y).await
+    }
+
+    async fn percent_completed(&self) -> ASCOMResult<u8> {
+        self.0.percent_completed().await
+    }
+
+    async fn readout_mode(&self) -> ASCOMResult<usize> {
+        self.0.readout_mode().await
+    }
+
+    async fn set_readout_mode(&self, readout_mode: usize) -> ASCOMResult {
+        self.0.set_readout_mode(readout_mode).await
+    }
+
+    async fn readout_modes(&self) -> ASCOMResult<Vec<String>> {
+        self.0.readout_modes().await
+    }
+
+    async fn sensor_type(&self) -> ASCOMResult<SensorType> {
+        self.0.sensor_type().await
+    }
+
+    async fn start_exposure(&self, duration: Duration, light: bool) -> ASCOMResult {
+        self.0.start_exposure(duration, light).await
+    }
+
+    async fn can_stop_exposure(&self) -> ASCOMResult<bool> {
+        self.0.can_stop_exposure().await
+    }
+
+    async fn can_abort_exposure(&self) -> ASCOMResult<bool> {
+        self.0.can_abort_exposure().await
+    }
+
+    async fn stop_exposure(&self) -> ASCOMResult {
+        self.0.stop_exposure().await
+    }
+
+    async fn abort_exposure(&self) -> ASCOMResult {
+        self.0.abort_exposure().await
+    }
+
+    async fn pixel_size_x(&self) -> ASCOMResult<f64> {
+        self.0.pixel_size_x().await
+    }
+
+    async fn pixel_size_y(&self) -> ASCOMResult<f64> {
+        self.0.pixel_size_y().await
+    }
+
+    async fn can_get_cooler_power(&self) -> ASCOMResult<bool> {
+        self.0.can_get_cooler_power().await
+    }
+
+    async fn can_set_ccd_temperature(&self) -> ASCOMResult<bool> {
+        self.0.can_set_ccd_temperature().await
+    }
+
+    async fn ccd_temperature(&self) -> ASCOMResult<f64> {
+        self.0.ccd_temperature().await
+    }
+
+    async fn set_ccd_temperature(&self) -> ASCOMResult<f64> {
+        self.0.set_ccd_temperature().await
+    }
+
+    async fn set_set_ccd_temperature(&self, set_ccd_temperature: f64) -> ASCOMResult {
+        self.0.set_set_ccd_temperature(set_ccd_temperature).await
+    }
+
+    async fn cooler_on(&self) -> ASCOMResult<bool> {
+        self.0.cooler_on().await
+    }
+
+    async fn set_cooler_on(&self, cooler_on: bool) -> ASCOMResult {
+        self.0.set_cooler_on(cooler_on).await
+    }
+
+    async fn cooler_power(&self) -> ASCOMResult<f64> {
+        self.0.cooler_power().await
+    }
+
+    async fn gains(&self) -> ASCOMResult<Vec<String>> {
+        self.0.gains().await
+    }
+
+    async fn gain(&self) -> ASCOMResult<i32> {
+        self.0.gain().await
+    }
+
+    async fn set_gain(&self, gain: i32) -> ASCOMResult {
+        self.0.set_gain(gain).await
+    }
+
+    async fn gain_max(&self) -> ASCOMResult<i32> {
+        self.0.gain_max().await
+    }
+
+    async fn gain_min(&self) -> ASCOMResult<i32> {
+        self.0.gain_min().await
+    }
+
+    async fn offsets(&self) -> ASCOMResult<Vec<String>> {
+        self.0.offsets().await
+    }
+
+    async fn offset(&self) -> ASCOMResult<i32> {
+        self.0.offset().await
+    }
+
+    async fn set_offset(&self, offset: i32) -> ASCOMResult {
+        self.0.set_offset(offset).await
+    }
+
+    async fn offset_max(&self) -> ASCOMResult<i32> {
+        self.0.offset_max().await
+    }
+
+    async fn offset_min(&self) -> ASCOMResult<i32> {
+        self.0.offset_min().await
+    }
+
+    async fn can_fast_readout(&self) -> ASCOMResult<bool> {
+        self.0.can_fast_readout().await
+    }
+
+    async fn fast_readout(&self) -> ASCOMResult<bool> {
+        self.0.fast_readout().await
+    }
+
+    async fn set_fast_readout(&self, fast_readout: bool) -> ASCOMResult {
+        self.0.set_fast_readout(fast_readout).await
+    }
+}
+
+#[async_trait]
+impl FilterWheel for Shared<QhyccdFilterWheel> {
+    async fn focus_offsets(&self) -> ASCOMResult<Vec<i32>> {
+        self.0.focus_offsets().await
+    }
+
+    async fn names(&self) -> ASCOMResult<Vec<String>> {
+        self.0.names().await
+    }
+
+    async fn position(&self) -> ASCOMResult<Option<usize>> {
+        self.0.position().await
+    }
+
+    async fn set_position(&self, position: usize) -> ASCOMResult {
+        self.0.set_position(position).await
+    }
+}
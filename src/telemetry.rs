@@ -0,0 +1,57 @@
+//! Environment sensors some QHY cameras carry next to the cooler
+//!
+//! The sensors a camera has are found on connect through `is_control_available` and read
+//! through Alpaca actions, each returning a JSON object keyed by sensor:
+//!
+//! ```text
+//! Humidity   {"humidity": 41.5}
+//! Pressure   {"pressure": 1012.8}
+//! Telemetry  {"humidity": 41.5, "pressure": 1012.8}
+//! ```
+//!
+//! The SDK has no control for the chamber air temperature, `CamChipTemperatureSensorInterface`
+//! only tells whether the chip temperature sensor can be read.
+use qhyccd_rs::Control;
+
+/// An environment sensor, read like any other SDK control.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Sensor {
+    /// relative humidity in the sensor chamber, in percent
+    Humidity,
+    /// pressure in the sensor chamber, in hPa
+    Pressure,
+}
+
+impl Sensor {
+    pub(crate) const ALL: [Sensor; 2] = [Sensor::Humidity, Sensor::Pressure];
+
+    pub(crate) fn control(self) -> Control {
+        match self {
+            Sensor::Humidity => Control::CamHumidity,
+            Sensor::Pressure => Control::CamPressure,
+        }
+    }
+
+    /// The Alpaca action reading this sensor.
+    pub(crate) fn action(self) -> &'static str {
+        match self {
+            Sensor::Humidity => "Humidity",
+            Sensor::Pressure => "Pressure",
+        }
+    }
+
+    /// The key of the reading in the JSON answer.
+    pub(crate) fn key(self) -> &'static str {
+        match self {
+            Sensor::Humidity => "humidity",
+            Sensor::Pressure => "pressure",
+        }
+    }
+
+    /// The sensor read by `action`, case-insensitive like the action dispatch.
+    pub(crate) fn from_action(action: &str) -> Option<Sensor> {
+        Sensor::ALL
+            .into_iter()
+            .find(|sensor| sensor.action().eq_ignore_ascii_case(action))
+    }
+}
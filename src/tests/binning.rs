@@ -0,0 +1,135 @@
+//! Binning geometry and software binning tests
+
+use qhyccd_rs::{CCDChipArea, ImageData};
+use rstest::*;
+
+use crate::binning::*;
+
+fn image(values: &[u16], width: u32, height: u32, bits_per_pixel: u32) -> ImageData {
+    ImageData {
+        data: match bits_per_pixel {
+            8 => values.iter().map(|value| *value as u8).collect(),
+            _ => values
+                .iter()
+                .flat_map(|value| value.to_ne_bytes())
+                .collect(),
+        },
+        width,
+        height,
+        bits_per_pixel,
+        channels: 1,
+    }
+}
+
+#[test]
+fn rescale_per_axis() {
+    //given
+    let roi = CCDChipArea {
+        start_x: 10,
+        start_y: 21,
+        width: 100,
+        height: 101,
+    };
+    //when
+    let res = Binning::symmetric(1).rescale(roi, Binning { x: 2, y: 4 });
+    //then
+    assert_eq!(
+        res,
+        CCDChipArea {
+            start_x: 5,
+            start_y: 5,
+            width: 50,
+            height: 25,
+        }
+    );
+}
+
+#[rstest]
+#[case(&[1, 2, 4], false, vec![1, 2, 4])]
+#[case(&[1, 2, 4], true, vec![1, 2, 3, 4, 5, 6, 7, 8])]
+#[case(&[2, 4], true, vec![2, 4, 6, 8])]
+#[case(&[], true, vec![])]
+fn offered_bins(#[case] hardware: &[u8], #[case] software: bool, #[case] expected: Vec<u8>) {
+    assert_eq!(offered(hardware, software), expected);
+}
+
+#[rstest]
+#[case::hardware(Binning::symmetric(2), false, true, Some((Binning::symmetric(2), Binning::symmetric(1))))]
+#[case::three(Binning::symmetric(3), false, true, Some((Binning::symmetric(1), Binning::symmetric(3))))]
+#[case::six(Binning::symmetric(6), false, true, Some((Binning::symmetric(2), Binning::symmetric(3))))]
+#[case::eight(Binning::symmetric(8), false, true, Some((Binning::symmetric(4), Binning::symmetric(2))))]
+#[case::asymmetric_in_software(Binning { x: 2, y: 4 }, false, true, Some((Binning::symmetric(2), Binning { x: 1, y: 2 })))]
+#[case::asymmetric_in_hardware(Binning { x: 2, y: 4 }, true, true, Some((Binning { x: 2, y: 4 }, Binning::symmetric(1))))]
+#[case::mixed(Binning { x: 1, y: 6 }, true, true, Some((Binning { x: 1, y: 2 }, Binning { x: 1, y: 3 })))]
+#[case::without_software(Binning::symmetric(3), false, false, None)]
+#[case::asymmetric_without_software(Binning { x: 2, y: 4 }, false, false, None)]
+fn split_binning(
+    #[case] binning: Binning,
+    #[case] asymmetric: bool,
+    #[case] software: bool,
+    #[case] expected: Option<(Binning, Binning)>,
+) {
+    assert_eq!(split(binning, &[1, 2, 4], asymmetric, software), expected);
+}
+
+#[rstest]
+#[case::sum_8bit(SoftwareBinning::Sum, 8, 16, vec![1 + 2 + 5 + 6, 3 + 4 + 7 + 8])]
+#[case::average_8bit(SoftwareBinning::Average, 8, 8, vec![4, 6])]
+#[case::average_16bit(SoftwareBinning::Average, 16, 16, vec![4, 6])]
+fn apply_2x2(
+    #[case] mode: SoftwareBinning,
+    #[case] bits_per_pixel: u32,
+    #[case] expected_bits: u32,
+    #[case] expected: Vec<u16>,
+) {
+    //given a 4x3 frame, the last row does not fill a binned pixel
+    let frame = image(
+        &[1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12],
+        4,
+        3,
+        bits_per_pixel,
+    );
+    //when
+    let res = apply(&frame, Binning::symmetric(2), mode).unwrap();
+    //then
+    assert_eq!((res.width, res.height), (2, 1));
+    assert_eq!(res.bits_per_pixel, expected_bits);
+    assert_eq!(res, image(&expected, 2, 1, expected_bits));
+}
+
+#[test]
+fn apply_sum_16bit_grows_to_32bit() {
+    //given
+    let frame = image(&[65535, 65535, 1, 2, 3, 4], 1, 6, 16);
+    //when
+    let res = apply(&frame, Binning { x: 1, y: 3 }, SoftwareBinning::Sum).unwrap();
+    //then
+    assert_eq!((res.width, res.height, res.bits_per_pixel), (1, 2, 32));
+    let values: Vec<u32> = res
+        .data
+        .chunks_exact(4)
+        .map(|bytes| u32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        .collect();
+    assert_eq!(values, vec![65535 + 65535 + 1, 2 + 3 + 4]);
+}
+
+#[test]
+fn apply_rejects_short_frames() {
+    //given
+    let mut frame = image(&[1, 2, 3, 4], 2, 2, 16);
+    frame.data.truncate(3);
+    //when
+    let res = apply(&frame, Binning::symmetric(2), SoftwareBinning::Sum);
+    //then
+    assert!(res.is_err());
+}
+
+#[test]
+fn config_names() {
+    assert_eq!(
+        toml::from_str::<crate::config::CameraConfig>(r#"software_binning = "average""#)
+            .unwrap()
+            .software_binning,
+        Some(SoftwareBinning::Average)
+    );
+}
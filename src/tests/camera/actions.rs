@@ -0,0 +1,193 @@
+//! Alpaca action tests
+
+use super::*;
+
+#[tokio::test]
+async fn supported_actions() {
+    //given
+    let camera = new_camera(MockCamera::new(), MockCameraType::Untouched);
+    //when
+    let res = camera.supported_actions().await;
+    //then
+    assert!(res.unwrap().contains(&"FitsExport".to_owned()));
+}
+
+#[tokio::test]
+async fn action_not_implemented() {
+    //given
+    let camera = new_camera(MockCamera::new(), MockCameraType::Untouched);
+    //when
+    let res = camera
+        .action("NoSuchAction".to_owned(), String::new())
+        .await;
+    //then
+    assert_eq!(
+        res.err().unwrap().to_string(),
+        ASCOMError::ACTION_NOT_IMPLEMENTED.to_string()
+    );
+}
+
+#[rstest]
+#[case(true, true, "", Ok("true".to_owned()), true)]
+#[case(true, true, "false", Ok("false".to_owned()), false)]
+#[case(true, false, "TRUE", Ok("true".to_owned()), true)]
+#[case(false, false, "", Ok("false".to_owned()), false)]
+#[case(
+    false,
+    false,
+    "true",
+    Err(ASCOMError::invalid_operation("no FITS output directory configured")),
+    false
+)]
+#[case(
+    true,
+    true,
+    "maybe",
+    Err(ASCOMError::invalid_value("FitsExport parameter must be true, false or empty")),
+    true
+)]
+#[tokio::test]
+async fn fits_export_action(
+    #[case] configured: bool,
+    #[case] enabled_before: bool,
+    #[case] parameters: &str,
+    #[case] expected: ASCOMResult<String>,
+    #[case] enabled_after: bool,
+) {
+    //given
+    let mut camera = new_camera(MockCamera::new(), MockCameraType::Untouched);
+    camera.fits_export = configured.then(|| FitsExport::new(std::env::temp_dir()));
+    camera.fits_export_enabled = RwLock::new(enabled_before);
+    //when
+    let res = camera
+        .action("FitsExport".to_owned(), parameters.to_owned())
+        .await;
+    //then
+    if expected.is_ok() {
+        assert_eq!(res.unwrap(), expected.unwrap());
+    } else {
+        assert_eq!(
+            res.unwrap_err().to_string(),
+            expected.unwrap_err().to_string()
+        );
+    }
+    assert_eq!(*camera.fits_export_enabled.read().await, enabled_after);
+}
+
+#[rstest]
+#[case(None, "", Ok("off".to_owned()), None)]
+#[case(None, "Gradient", Ok("gradient".to_owned()), Some(DebayerMethod::Gradient))]
+#[case(Some(DebayerMethod::Gradient), "superpixel", Ok("superpixel".to_owned()), Some(DebayerMethod::Superpixel))]
+#[case(Some(DebayerMethod::Bilinear), "", Ok("bilinear".to_owned()), Some(DebayerMethod::Bilinear))]
+#[case(Some(DebayerMethod::Bilinear), "off", Ok("off".to_owned()), None)]
+#[case(
+    Some(DebayerMethod::Bilinear),
+    "ahd",
+    Err(ASCOMError::invalid_value(
+        "Debayer parameter must be off, bilinear, gradient, superpixel or empty"
+    )),
+    Some(DebayerMethod::Bilinear)
+)]
+#[tokio::test]
+async fn debayer_action(
+    #[case] before: Option<DebayerMethod>,
+    #[case] parameters: &str,
+    #[case] expected: ASCOMResult<String>,
+    #[case] after: Option<DebayerMethod>,
+) {
+    //given
+    let mut camera = new_camera(MockCamera::new(), MockCameraType::Untouched);
+    camera.debayer = RwLock::new(before);
+    //when
+    let res = camera
+        .action("Debayer".to_owned(), parameters.to_owned())
+        .await;
+    //then
+    if expected.is_ok() {
+        assert_eq!(res.unwrap(), expected.unwrap());
+    } else {
+        assert_eq!(
+            res.unwrap_err().to_string(),
+            expected.unwrap_err().to_string()
+        );
+    }
+    assert_eq!(*camera.debayer.read().await, after);
+}
+
+#[tokio::test]
+async fn save_settings_action() {
+    //given
+    let path = std::env::temp_dir().join("qhyccd-alpaca-save-settings.toml");
+    let _ = std::fs::remove_file(&path);
+    let mut mock = MockCamera::new();
+    mock.expect_get_readout_mode().once().returning(|| Ok(1));
+    mock.expect_is_control_available()
+        .times(3)
+        .returning(|control| match control {
+            qhyccd_rs::Control::Gain => Some(0),
+            _ => None,
+        });
+    mock.expect_get_parameter()
+        .once()
+        .withf(|control| *control == qhyccd_rs::Control::Gain)
+        .returning(|_| Ok(26_f64));
+    let mut camera = new_camera(mock, MockCameraType::IsOpenTrue { times: 1 });
+    camera.config = Arc::new(ConfigStore::load(path.clone()).unwrap());
+    //when
+    let res = camera
+        .action("SaveSettings".to_owned(), String::new())
+        .await;
+    //then
+    assert_eq!(res.unwrap(), "");
+    let saved = ConfigStore::load(path.clone()).unwrap();
+    assert_eq!(
+        saved.camera("test-camera").await,
+        CameraConfig {
+            readout_mode: Some(1),
+            binning: Some(1),
+            gain: Some(26),
+            ..Default::default()
+        }
+    );
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[tokio::test]
+async fn save_settings_action_without_file() {
+    //given
+    let mut mock = MockCamera::new();
+    mock.expect_get_readout_mode().once().returning(|| Ok(0));
+    mock.expect_is_control_available()
+        .times(3)
+        .returning(|_| None);
+    let camera = new_camera(mock, MockCameraType::IsOpenTrue { times: 1 });
+    //when
+    let res = camera
+        .action("SaveSettings".to_owned(), String::new())
+        .await;
+    //then
+    assert_eq!(res.unwrap(), "");
+    assert_eq!(
+        camera.config.camera("test-camera").await,
+        CameraConfig {
+            readout_mode: Some(0),
+            binning: Some(1),
+            ..Default::default()
+        }
+    );
+}
+
+#[tokio::test]
+async fn save_settings_action_not_connected() {
+    //given
+    let camera = new_camera(MockCamera::new(), MockCameraType::IsOpenFalse { times: 1 });
+    //when
+    let res = camera
+        .action("SaveSettings".to_owned(), String::new())
+        .await;
+    //then
+    assert_eq!(
+        res.unwrap_err().to_string(),
+        ASCOMError::NOT_CONNECTED.to_string()
+    );
+}
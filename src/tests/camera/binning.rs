@@ -221,3 +221,341 @@ async fn set_bin_x_fail_no_valid_bins() {
         ASCOMError::NOT_CONNECTED.to_string()
     );
 }
+
+/// A connected 1920x1080 camera at `camera_binning` with the ROI `camera_roi`, hardware bins
+/// 1, 2 and 4, and the configuration changed by `configure`.
+async fn roi_camera(
+    mock: MockCamera,
+    times: usize,
+    camera_roi: CCDChipArea,
+    camera_binning: Binning,
+    configure: impl FnOnce(&mut CameraConfig),
+) -> QhyccdCamera {
+    let camera = new_camera(
+        mock,
+        MockCameraType::WithBinningAndValidBinsAndRoiAndCCDInfo {
+            times,
+            camera_roi,
+            camera_ccd_info: CCDChipInfo {
+                chip_width: 7_f64,
+                chip_height: 5_f64,
+                image_width: 1920,
+                image_height: 1080,
+                pixel_width: 2.9_f64,
+                pixel_height: 2.9_f64,
+                bits_per_pixel: 16,
+            },
+            camera_binning: camera_binning.x,
+            camera_valid_bins: vec![1_u8, 2_u8, 4_u8],
+        },
+    );
+    *camera.binning.write().await = camera_binning;
+    camera
+        .config
+        .update_camera("test-camera", configure)
+        .await
+        .unwrap();
+    camera
+}
+
+fn asymmetric(config: &mut CameraConfig) {
+    config.asymmetric_binning = Some(true);
+}
+
+fn symmetric(config: &mut CameraConfig) {
+    config.asymmetric_binning = Some(false);
+}
+
+fn software(mode: SoftwareBinning) -> impl FnOnce(&mut CameraConfig) {
+    move |config| config.software_binning = Some(mode)
+}
+
+fn roi(start_x: u32, start_y: u32, width: u32, height: u32) -> CCDChipArea {
+    CCDChipArea {
+        start_x,
+        start_y,
+        width,
+        height,
+    }
+}
+
+#[rstest]
+#[case::y_only(false, 4, Binning { x: 1, y: 1 }, Binning { x: 1, y: 4 }, roi(10, 20, 1920, 1080), roi(10, 5, 1920, 270))]
+#[case::x_only(true, 2, Binning { x: 1, y: 4 }, Binning { x: 2, y: 4 }, roi(10, 5, 1920, 270), roi(5, 5, 960, 270))]
+#[case::back_to_unbinned(false, 1, Binning { x: 2, y: 4 }, Binning { x: 2, y: 1 }, roi(5, 5, 960, 270), roi(5, 20, 960, 1080))]
+#[case::odd_start(false, 2, Binning { x: 1, y: 1 }, Binning { x: 1, y: 2 }, roi(11, 11, 100, 101), roi(11, 5, 100, 50))]
+#[tokio::test]
+async fn set_bin_asymmetric_rescales_one_axis(
+    #[case] x: bool,
+    #[case] bin: u8,
+    #[case] binning: Binning,
+    #[case] expected_binning: Binning,
+    #[case] camera_roi: CCDChipArea,
+    #[case] expected_roi: CCDChipArea,
+) {
+    //given
+    let mut mock = MockCamera::new();
+    mock.expect_set_bin_mode()
+        .once()
+        .withf(move |bin_x: &u32, bin_y: &u32| {
+            *bin_x == u32::from(expected_binning.x) && *bin_y == u32::from(expected_binning.y)
+        })
+        .returning(|_, _| Ok(()));
+    let camera = roi_camera(mock, 3, camera_roi, binning, asymmetric).await;
+    //when
+    let res = match x {
+        true => camera.set_bin_x(bin).await,
+        false => camera.set_bin_y(bin).await,
+    };
+    //then
+    assert!(res.is_ok());
+    assert_eq!(camera.bin_x().await.unwrap(), expected_binning.x);
+    assert_eq!(camera.bin_y().await.unwrap(), expected_binning.y);
+    assert_eq!(*camera.intended_roi.read().await, Some(expected_roi));
+}
+
+#[tokio::test]
+async fn set_bin_y_symmetric_moves_both_axes() {
+    //given a camera that is not marked for asymmetric binning
+    let mut mock = MockCamera::new();
+    mock.expect_set_bin_mode()
+        .once()
+        .withf(|bin_x: &u32, bin_y: &u32| *bin_x == 2 && *bin_y == 2)
+        .returning(|_, _| Ok(()));
+    let camera = roi_camera(
+        mock,
+        1,
+        roi(10, 20, 1920, 1080),
+        Binning::symmetric(1),
+        symmetric,
+    )
+    .await;
+    //when
+    let res = camera.set_bin_y(2).await;
+    //then
+    assert!(res.is_ok());
+    assert_eq!(*camera.binning.read().await, Binning::symmetric(2));
+    assert_eq!(
+        *camera.intended_roi.read().await,
+        Some(roi(5, 10, 960, 540))
+    );
+}
+
+#[tokio::test]
+async fn set_bin_asymmetric_failure_keeps_binning_and_roi() {
+    //given
+    let mut mock = MockCamera::new();
+    mock.expect_set_bin_mode()
+        .once()
+        .returning(|_, _| Err(eyre!("error")));
+    let camera = roi_camera(
+        mock,
+        1,
+        roi(10, 20, 1920, 1080),
+        Binning::symmetric(1),
+        asymmetric,
+    )
+    .await;
+    //when
+    let res = camera.set_bin_y(4).await;
+    //then
+    assert_eq!(
+        res.unwrap_err().to_string(),
+        ASCOMError::VALUE_NOT_SET.to_string()
+    );
+    assert_eq!(*camera.binning.read().await, Binning::symmetric(1));
+    assert_eq!(
+        *camera.intended_roi.read().await,
+        Some(roi(10, 20, 1920, 1080))
+    );
+}
+
+#[rstest]
+#[case(None, false)]
+#[case(Some(false), false)]
+#[case(Some(true), true)]
+#[tokio::test]
+async fn can_asymmetric_bin(#[case] configured: Option<bool>, #[case] expected: bool) {
+    //given
+    let camera = new_camera(MockCamera::new(), MockCameraType::IsOpenTrue { times: 1 });
+    camera
+        .config
+        .update_camera("test-camera", |config| {
+            config.asymmetric_binning = configured
+        })
+        .await
+        .unwrap();
+    //when
+    let res = camera.can_asymmetric_bin().await;
+    //then
+    assert_eq!(res.unwrap(), expected);
+}
+
+#[rstest]
+#[case::three(3, Binning::symmetric(1), roi(0, 0, 640, 360))]
+#[case::six(6, Binning::symmetric(2), roi(0, 0, 320, 180))]
+#[case::eight(8, Binning::symmetric(4), roi(0, 0, 240, 135))]
+#[tokio::test]
+async fn set_bin_in_software(
+    #[case] bin: u8,
+    #[case] read: Binning,
+    #[case] expected_roi: CCDChipArea,
+) {
+    //given
+    let mut mock = MockCamera::new();
+    mock.expect_set_bin_mode()
+        .once()
+        .withf(move |bin_x: &u32, bin_y: &u32| {
+            *bin_x == u32::from(read.x) && *bin_y == u32::from(read.y)
+        })
+        .returning(|_, _| Ok(()));
+    let camera = roi_camera(
+        mock,
+        1,
+        roi(0, 0, 1920, 1080),
+        Binning::symmetric(1),
+        software(SoftwareBinning::Average),
+    )
+    .await;
+    //when
+    let res = camera.set_bin_x(bin).await;
+    //then
+    assert!(res.is_ok());
+    assert_eq!(*camera.binning.read().await, Binning::symmetric(bin));
+    assert_eq!(*camera.intended_roi.read().await, Some(expected_roi));
+}
+
+#[tokio::test]
+async fn set_bin_beyond_hardware_without_software_binning() {
+    //given
+    let camera = roi_camera(
+        MockCamera::new(),
+        1,
+        roi(0, 0, 1920, 1080),
+        Binning::symmetric(1),
+        symmetric,
+    )
+    .await;
+    //when
+    let res = camera.set_bin_x(3).await;
+    //then
+    assert_eq!(
+        res.unwrap_err().to_string(),
+        ASCOMError::invalid_value("bin value must be one of the valid bins").to_string()
+    );
+}
+
+#[tokio::test]
+async fn max_bin_with_software_binning() {
+    //given
+    let mut mock = MockCamera::new();
+    mock.expect_is_control_available()
+        .times(6)
+        .returning(|control| match control {
+            Control::CamBin1x1mode | Control::CamBin2x2mode => Some(0_u32),
+            _ => None,
+        });
+    let camera = new_camera(mock, MockCameraType::IsOpenTrue { times: 2 });
+    camera
+        .config
+        .update_camera("test-camera", software(SoftwareBinning::Sum))
+        .await
+        .unwrap();
+    //when
+    let res = camera.max_bin_x().await;
+    //then
+    assert_eq!(res.unwrap(), crate::binning::MAX_SOFTWARE_BIN);
+    assert!(camera.can_asymmetric_bin().await.unwrap());
+}
+
+#[rstest]
+#[case(SoftwareBinning::Sum, 4096 * 9)]
+#[case(SoftwareBinning::Average, 4096)]
+#[tokio::test]
+async fn max_adu_with_software_binning(#[case] mode: SoftwareBinning, #[case] expected: u32) {
+    //given
+    let mut mock = MockCamera::new();
+    mock.expect_get_parameter()
+        .once()
+        .withf(|control| *control == Control::OutputDataActualBits)
+        .returning(|_| Ok(12_f64));
+    let camera = roi_camera(
+        mock,
+        1,
+        roi(0, 0, 640, 360),
+        Binning::symmetric(3),
+        software(mode),
+    )
+    .await;
+    //when
+    let res = camera.max_adu().await;
+    //then
+    assert_eq!(res.unwrap(), expected);
+}
+
+#[tokio::test]
+async fn start_exposure_reads_the_hardware_roi() {
+    //given a camera that fails right after the ROI was set
+    let mut mock = MockCamera::new();
+    mock.expect_set_roi()
+        .once()
+        .withf(|roi| {
+            *roi == CCDChipArea {
+                start_x: 30,
+                start_y: 60,
+                width: 1800,
+                height: 900,
+            }
+        })
+        .returning(|_| Err(eyre!("error")));
+    let camera = new_camera(
+        mock,
+        MockCameraType::WithBinningAndRoiAndCCDInfoUnlimited {
+            camera_roi: roi(10, 20, 600, 300),
+            camera_ccd_info: CCDChipInfo {
+                chip_width: 7_f64,
+                chip_height: 5_f64,
+                image_width: 1920,
+                image_height: 1080,
+                pixel_width: 2.9_f64,
+                pixel_height: 2.9_f64,
+                bits_per_pixel: 16,
+            },
+            camera_binning: 3,
+        },
+    );
+    *camera.valid_bins.write().await = Some(vec![1_u8, 2_u8, 4_u8]);
+    camera
+        .config
+        .update_camera("test-camera", software(SoftwareBinning::Sum))
+        .await
+        .unwrap();
+    //when
+    let res = camera.start_exposure(Duration::from_secs(1), true).await;
+    //then
+    assert_eq!(
+        res.unwrap_err().to_string(),
+        ASCOMError::invalid_value("failed to set ROI").to_string()
+    );
+}
+
+#[tokio::test]
+async fn start_live_with_software_binning() {
+    //given
+    let camera = roi_camera(
+        MockCamera::new(),
+        1,
+        roi(0, 0, 640, 360),
+        Binning::symmetric(3),
+        software(SoftwareBinning::Average),
+    )
+    .await;
+    //when
+    let res = camera.action("StartLive".to_owned(), String::new()).await;
+    //then
+    assert_eq!(
+        res.unwrap_err().to_string(),
+        ASCOMError::invalid_operation("live mode needs a binning the camera reads in hardware")
+            .to_string()
+    );
+}
@@ -7,7 +7,10 @@ async fn not_connected_asyncs() {
     not_connected! {sensor_type()}
     not_connected! {max_bin_x()}
     not_connected! {max_bin_y()}
+    not_connected! {can_asymmetric_bin()}
     not_connected! {sensor_name()}
+    not_connected! {electrons_per_adu()}
+    not_connected! {full_well_capacity()}
     not_connected! {camera_state()}
     not_connected! {bin_x()}
     not_connected! {bin_y()}
@@ -73,35 +76,60 @@ async fn qhyccd_camera() {
     mock.expect_clone().returning(MockCamera::new);
     //when
     let camera = QhyccdCamera {
+        this: Weak::new(),
         unique_id: mock.id().to_owned(),
         name: format!("QHYCCD-{}", mock.id()),
         description: "QHYCCD camera".to_owned(),
         device: mock.clone(),
-        binning: RwLock::new(1_u8),
+        binning: RwLock::new(Binning::symmetric(1)),
         valid_bins: RwLock::new(None),
-        target_temperature: RwLock::new(None),
+        cooler: Arc::new(RwLock::new(Cooler::new(CoolerRamp::default()))),
         ccd_info: RwLock::new(None),
         intended_roi: RwLock::new(None),
+        effective_area: RwLock::new(None),
+        overscan_area: RwLock::new(None),
+        include_overscan: RwLock::new(false),
+        transfer_bits: RwLock::new(16),
         readout_speed_min_max_step: RwLock::new(None),
         exposure_min_max_step: RwLock::new(None),
         last_exposure_start_time: RwLock::new(None),
-        last_exposure_duration_us: RwLock::new(None),
+        last_exposure_duration_us: Arc::new(RwLock::new(None)),
         last_image: Arc::new(RwLock::new(None)),
         state: Arc::new(RwLock::new(State::Idle)),
         gain_min_max: RwLock::new(None),
         offset_min_max: RwLock::new(None),
+        dark_frame_policy: DarkFramePolicy::default(),
+        shutter_closed: RwLock::new(false),
+        fits_export: None,
+        fits_export_enabled: RwLock::new(false),
+        debayer: RwLock::new(None),
+        config: Arc::new(ConfigStore::default()),
+        cfw_detected: Arc::new(AtomicBool::new(false)),
+        cfw_found: Arc::default(),
+        presets: None,
+        live_feed: Arc::new(LiveFeed::default()),
+        live: RwLock::new(None),
+        sequencer: Arc::new(Sequencer::default()),
+        stop_supported: Arc::new(AtomicBool::new(true)),
+        sensors: RwLock::new(Vec::new()),
+        usb_traffic_range: RwLock::new(None),
+        ddr_range: RwLock::new(None),
+        filter_wheels: Arc::default(),
+        attached: AtomicBool::new(true),
     };
     //then
     assert_eq!(camera.unique_id, "test_camera");
     assert_eq!(camera.name, "QHYCCD-test_camera");
     assert_eq!(camera.description, "QHYCCD camera");
-    assert_eq!(*camera.binning.read().await, 1);
+    assert_eq!(*camera.binning.read().await, Binning::symmetric(1));
     assert!(camera.valid_bins.read().await.is_none());
     assert!(camera.intended_roi.read().await.is_none());
     assert!(camera.last_exposure_start_time.read().await.is_none());
     assert!(camera.last_exposure_duration_us.read().await.is_none());
     assert!(camera.last_image.read().await.is_none());
     assert_eq!(*camera.state.read().await, State::Idle);
+    assert_eq!(camera.dark_frame_policy, DarkFramePolicy::Warn);
+    assert!(!*camera.shutter_closed.read().await);
     assert_eq!(camera.static_name(), "QHYCCD-test_camera");
     assert_eq!(camera.unique_id(), "test_camera");
     assert_eq!(camera.description().await.unwrap(), "QHYCCD camera");
@@ -292,6 +320,16 @@ async fn set_connected_true(
                 Err(eyre!("error"))
             }
         });
+    mock.expect_get_overscan_area()
+        .times(if effective_area { 1 } else { 0 })
+        .returning(|| {
+            Ok(CCDChipArea {
+                start_x: 100,
+                start_y: 0,
+                width: 20,
+                height: 100,
+            })
+        });
     mock.expect_is_control_available()
         .times(if effective_area { 6 } else { 0 })
         .withf(move |control| {
@@ -369,9 +407,44 @@ async fn set_connected_true(
                 Err(eyre!("error"))
             }
         });
+    mock.expect_is_cfw_plugged_in()
+        .times(if expected.is_ok() { 1 } else { 0 })
+        .returning(|| Ok(true));
+    mock.expect_is_control_available()
+        .times(if expected.is_ok() { 2 } else { 0 })
+        .withf(|control| *control == Control::CamHumidity || *control == Control::CamPressure)
+        .returning(|control| (*control == Control::CamHumidity).then_some(0));
+    mock.expect_is_control_available()
+        .times(if expected.is_ok() { 2 } else { 0 })
+        .withf(|control| *control == Control::UsbTraffic || *control == Control::DDR)
+        .returning(|control| (*control == Control::UsbTraffic).then_some(0));
+    mock.expect_get_parameter_min_max_step()
+        .times(if expected.is_ok() { 1 } else { 0 })
+        .withf(|control| *control == Control::UsbTraffic)
+        .returning(|_| Ok((0_f64, 255_f64, 1_f64)));
     let camera = new_camera(mock, MockCameraType::IsOpenFalse { times: 1 });
     //when
     let res = camera.set_connected(true).await;
+    assert_eq!(camera.cfw_detected.load(Ordering::Relaxed), expected.is_ok());
+    let cfw_found = tokio::time::timeout(Duration::from_millis(10), camera.cfw_found.notified());
+    assert_eq!(cfw_found.await.is_ok(), expected.is_ok());
+    assert_eq!(
+        *camera.sensors.read().await,
+        if expected.is_ok() {
+            vec![Sensor::Humidity]
+        } else {
+            Vec::new()
+        }
+    );
+    assert_eq!(
+        *camera.usb_traffic_range.read().await,
+        expected.is_ok().then_some(ControlRange {
+            min: 0_f64,
+            max: 255_f64,
+            step: 1_f64,
+        })
+    );
+    assert_eq!(*camera.ddr_range.read().await, None);
     if expected.is_ok() {
         assert!(res.is_ok())
     } else {
@@ -391,8 +464,10 @@ async fn set_connected_false_success(#[case] close: Result<()>, #[case] expected
     let mut mock = MockCamera::new();
     mock.expect_close().once().return_once(move || close);
     let camera = new_camera(mock, MockCameraType::IsOpenTrue { times: 1 });
+    camera.cfw_detected.store(true, Ordering::Relaxed);
     //when
     let res = camera.set_connected(false).await;
+    assert!(!camera.cfw_detected.load(Ordering::Relaxed));
     if expected.is_ok() {
         assert!(res.is_ok())
     } else {
@@ -402,3 +477,58 @@ async fn set_connected_false_success(#[case] close: Result<()>, #[case] expected
         )
     }
 }
+
+#[rstest]
+#[case(26_i32, 1)]
+#[case(60_i32, 0)]
+#[tokio::test]
+async fn apply_config(#[case] gain: i32, #[case] set_gain_times: usize) {
+    //given
+    let mut mock = MockCamera::new();
+    mock.expect_is_control_available()
+        .once()
+        .withf(|control| *control == qhyccd_rs::Control::Gain)
+        .returning(|_| Some(0));
+    mock.expect_set_parameter()
+        .times(set_gain_times)
+        .withf(move |control, g| {
+            *control == qhyccd_rs::Control::Gain && (*g - gain as f64).abs() < f64::EPSILON
+        })
+        .returning(|_, _| Ok(()));
+    mock.expect_set_parameter()
+        .once()
+        .withf(|control, traffic| {
+            *control == qhyccd_rs::Control::UsbTraffic && (*traffic - 20_f64).abs() < f64::EPSILON
+        })
+        .returning(|_, _| Ok(()));
+    let mut camera = new_camera(
+        mock,
+        MockCameraType::WithGain {
+            times: 1,
+            min_max: Some((0_f64, 51_f64)),
+        },
+    );
+    camera.config = Arc::new(ConfigStore::new(
+        None,
+        crate::config::Config {
+            cameras: [(
+                "test-camera".to_owned(),
+                CameraConfig {
+                    gain: Some(gain),
+                    usb_traffic: Some(20_f64),
+                    ..Default::default()
+                },
+            )]
+            .into(),
+            ..Default::default()
+        },
+    ));
+    *camera.usb_traffic_range.write().await = Some(ControlRange {
+        min: 0_f64,
+        max: 255_f64,
+        step: 1_f64,
+    });
+    //when
+    camera.apply_config().await;
+    //then the rejected gain does not stop the remaining settings from being applied
+}
@@ -83,36 +83,396 @@ async fn percent_completed(
     }
 }
 
+/// Sets `can_stop_exposure` in the configuration of the test camera.
+fn configure_stop(camera: &mut QhyccdCamera, can_stop_exposure: Option<bool>) {
+    camera.config = Arc::new(ConfigStore::new(
+        None,
+        crate::config::Config {
+            cameras: [(
+                "test-camera".to_owned(),
+                CameraConfig {
+                    can_stop_exposure,
+                    ..Default::default()
+                },
+            )]
+            .into(),
+            ..Default::default()
+        },
+    ));
+}
+
 #[tokio::test]
 async fn stop_abort() {
     //given
-    let camera = new_camera(MockCamera::new(), MockCameraType::Untouched);
+    let mut camera = new_camera(MockCamera::new(), MockCameraType::Untouched);
+    configure_stop(&mut camera, Some(true));
     // when / then
-    assert!(!camera.can_stop_exposure().await.unwrap());
+    assert!(camera.can_stop_exposure().await.unwrap());
     assert!(camera.can_abort_exposure().await.unwrap());
 }
 
-#[rustfmt::skip]
 #[rstest]
-#[case(Duration::from_secs_f64(10.0), false, Err(ASCOMError::invalid_operation("dark frames not supported")))]
+#[case(true, None, false)]
+#[case(true, Some(true), true)]
+#[case(false, Some(true), false)]
+#[case(true, Some(false), false)]
 #[tokio::test]
-async fn start_exposure_fail_dark_neg(
-    #[case] duration: Duration,
-    #[case] is_dark: bool,
-    #[case] expected: ASCOMResult<()>,
+async fn can_stop_exposure_unlisted_model(
+    #[case] stop_supported: bool,
+    #[case] configured: Option<bool>,
+    #[case] expected: bool,
 ) {
     //given
-    let mock = MockCamera::new();
-    let camera = new_camera(mock, MockCameraType::Untouched);
+    let mut camera = new_camera(MockCamera::new(), MockCameraType::Untouched);
+    camera.stop_supported = Arc::new(AtomicBool::new(stop_supported));
+    configure_stop(&mut camera, configured);
+    //when
+    let res = camera.can_stop_exposure().await;
+    //then
+    assert_eq!(res.unwrap(), expected);
+}
+
+#[rstest]
+#[case("QHY600M-abc123", true)]
+#[case("QHY268C-abc123", true)]
+#[case("QHY5III462C-abc123", false)]
+#[tokio::test]
+async fn can_stop_exposure_listed_model(#[case] unique_id: &str, #[case] expected: bool) {
+    //given
+    let mut camera = new_camera(MockCamera::new(), MockCameraType::Untouched);
+    camera.unique_id = unique_id.to_owned();
+    camera.stop_supported = Arc::new(AtomicBool::new(!expected));
+    camera.config = Arc::new(ConfigStore::new(
+        None,
+        crate::config::Config {
+            cameras: [(
+                unique_id.to_owned(),
+                CameraConfig {
+                    can_stop_exposure: Some(!expected),
+                    ..Default::default()
+                },
+            )]
+            .into(),
+            ..Default::default()
+        },
+    ));
+    //when
+    let res = camera.can_stop_exposure().await;
+    //then
+    assert_eq!(res.unwrap(), expected);
+}
+
+#[tokio::test]
+async fn stop_exposure_not_supported() {
+    //given
+    let mut camera = new_camera(MockCamera::new(), MockCameraType::Untouched);
+    camera.stop_supported = Arc::new(AtomicBool::new(false));
+    //when
+    let res = camera.stop_exposure().await;
+    //then
+    assert_eq!(
+        res.err().unwrap().to_string(),
+        ASCOMError::NOT_IMPLEMENTED.to_string()
+    );
+}
+
+#[tokio::test]
+async fn stop_exposure_not_connected() {
+    //given
+    let mut camera = new_camera(MockCamera::new(), MockCameraType::IsOpenFalse { times: 1 });
+    configure_stop(&mut camera, Some(true));
+    //when
+    let res = camera.stop_exposure().await;
+    //then
+    assert_eq!(
+        res.err().unwrap().to_string(),
+        ASCOMError::NOT_CONNECTED.to_string()
+    );
+}
+
+#[tokio::test]
+async fn stop_exposure_idle() {
+    //given
+    let mut camera = new_camera(
+        MockCamera::new(),
+        MockCameraType::WithState {
+            times: 1,
+            state: State::Idle,
+        },
+    );
+    configure_stop(&mut camera, Some(true));
+    //when
+    let res = camera.stop_exposure().await;
+    //then
+    assert!(res.is_ok());
+}
+
+#[tokio::test]
+async fn stop_exposure_exposing() {
+    //given
+    let (stop_tx, mut stop_rx) = tokio::sync::oneshot::channel::<StopExposure>();
+    let mut camera = new_camera(
+        MockCamera::new(),
+        MockCameraType::WithState {
+            times: 1,
+            state: State::Exposing {
+                start: SystemTime::UNIX_EPOCH,
+                expected_duration_us: 1000,
+                stop_tx: Some(stop_tx),
+                done_rx: watch::channel(false).1,
+            },
+        },
+    );
+    configure_stop(&mut camera, Some(true));
+    //when
+    let res = camera.stop_exposure().await;
+    //then
+    assert!(res.is_ok());
+    assert!(stop_rx.try_recv().unwrap().want_image);
+}
+
+#[tokio::test]
+async fn stop_exposure_channel_already_used() {
+    //given
+    let mut camera = new_camera(
+        MockCamera::new(),
+        MockCameraType::WithState {
+            times: 1,
+            state: State::Exposing {
+                start: SystemTime::UNIX_EPOCH,
+                expected_duration_us: 1000,
+                stop_tx: None,
+                done_rx: watch::channel(false).1,
+            },
+        },
+    );
+    configure_stop(&mut camera, Some(true));
+    //when
+    let res = camera.stop_exposure().await;
+    //then
+    assert_eq!(
+        res.err().unwrap().to_string(),
+        ASCOMError::INVALID_OPERATION.to_string()
+    );
+}
+
+#[tokio::test]
+#[cfg_attr(miri, ignore)]
+async fn stop_exposure_reads_out_partial_image_no_miri() {
+    //given
+    let mut mock = MockCamera::new();
+    mock.expect_set_parameter()
+        .once()
+        .withf(|control, exposure| {
+            *control == qhyccd_rs::Control::Exposure && *exposure == 10_000_000_f64
+        })
+        .returning(|_, _| Ok(()));
+    mock.expect_set_roi().once().returning(|_| Ok(()));
+    let mut device_mock = MockCamera::new();
+    // get_single_frame blocks until the integration ends
+    device_mock
+        .expect_get_single_frame()
+        .once()
+        .withf(|size| *size == 100_usize)
+        .returning(|_| {
+            std::thread::sleep(std::time::Duration::from_millis(300));
+            Ok(qhyccd_rs::ImageData {
+                data: vec![0, 1, 2, 3, 4, 5],
+                width: 3,
+                height: 2,
+                bits_per_pixel: 8,
+                channels: 1,
+            })
+        });
+    let mut start_clone = MockCamera::new();
+    start_clone
+        .expect_start_single_frame_exposure()
+        .once()
+        .returning(|| Ok(()));
+    let mut size_clone = MockCamera::new();
+    size_clone
+        .expect_get_image_size()
+        .once()
+        .returning(|| Ok(100_usize));
+    let inner_mocks = std::sync::Mutex::new(vec![size_clone, start_clone]);
+    device_mock
+        .expect_clone()
+        .times(2)
+        .returning(move || inner_mocks.lock().unwrap().pop().unwrap());
+    let mut abort_mock = MockCamera::new();
+    abort_mock
+        .expect_stop_exposure()
+        .once()
+        .returning(|| Ok(()));
+    // self.device is cloned twice: device + device_for_abort
+    let outer_mocks = std::sync::Mutex::new(vec![abort_mock, device_mock]);
+    mock.expect_clone()
+        .times(2)
+        .returning(move || outer_mocks.lock().unwrap().pop().unwrap());
+    let mut camera = new_camera(
+        mock,
+        MockCameraType::WithBinningAndRoiAndCCDInfoUnlimited {
+            camera_roi: CCDChipArea {
+                start_x: 0,
+                start_y: 0,
+                width: 3,
+                height: 2,
+            },
+            camera_ccd_info: CCDChipInfo {
+                chip_width: 7_f64,
+                chip_height: 5_f64,
+                image_width: 1920,
+                image_height: 1080,
+                pixel_width: 2.9_f64,
+                pixel_height: 2.9_f64,
+                bits_per_pixel: 16,
+            },
+            camera_binning: 1_u8,
+        },
+    );
+    configure_stop(&mut camera, Some(true));
     //when
-    let res = camera.start_exposure(duration, is_dark).await;
+    let res = camera
+        .start_exposure(Duration::from_secs_f64(10.0), true)
+        .await;
+    tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+    let stop = camera.stop_exposure().await;
+
+    let timeout = tokio::time::Duration::from_secs(2);
+    let start = tokio::time::Instant::now();
+    while start.elapsed() < timeout {
+        if camera.image_ready().await.unwrap() {
+            break;
+        }
+        tokio::time::sleep(tokio::time::Duration::from_millis(10)).await;
+    }
+    //then
+    assert!(res.is_ok());
+    assert!(stop.is_ok());
+    assert!(camera.image_ready().await.unwrap());
+    assert!(camera.last_exposure_duration().await.unwrap() < Duration::from_secs(10));
+}
+
+#[tokio::test]
+async fn start_exposure_fail_dark_rejected() {
+    //given
+    let mut mock = MockCamera::new();
+    mock.expect_set_roi().once().returning(|_| Ok(()));
+    mock.expect_is_control_available()
+        .once()
+        .withf(|control| *control == Control::CamMechanicalShutter)
+        .returning(|_| None);
+    let mut camera = new_camera(
+        mock,
+        MockCameraType::WithBinningAndRoiAndCCDInfo {
+            times: 11,
+            camera_roi: CCDChipArea {
+                start_x: 0,
+                start_y: 0,
+                width: 1920,
+                height: 1080,
+            },
+            camera_ccd_info: CCDChipInfo {
+                chip_width: 7.0,
+                chip_height: 5.0,
+                image_width: 1920,
+                image_height: 1080,
+                pixel_width: 2.9,
+                pixel_height: 2.9,
+                bits_per_pixel: 16,
+            },
+            camera_binning: 1_u8,
+        },
+    );
+    camera.dark_frame_policy = DarkFramePolicy::Reject;
+    //when
+    let res = camera
+        .start_exposure(Duration::from_secs_f64(10.0), false)
+        .await;
     //then
     assert_eq!(
         res.unwrap_err().to_string(),
-        expected.unwrap_err().to_string(),
+        ASCOMError::invalid_operation("dark frames not supported without a mechanical shutter")
+            .to_string(),
     )
 }
 
+#[rustfmt::skip]
+#[rstest]
+#[case(None, DarkFramePolicy::Allow, None, Ok(FrameType::Dark), false)]
+#[case(None, DarkFramePolicy::Warn, None, Ok(FrameType::Dark), false)]
+#[case(None, DarkFramePolicy::Reject, None, Err(ASCOMError::invalid_operation("dark frames not supported without a mechanical shutter")), false)]
+#[case(None, DarkFramePolicy::Warn, Some((10_000_000_f64, 3_600_000_000_f64, 1_f64)), Ok(FrameType::Bias), false)]
+#[case(Some(Ok(())), DarkFramePolicy::Reject, None, Ok(FrameType::Dark), true)]
+#[case(Some(Err(eyre!("error"))), DarkFramePolicy::Allow, None, Err(ASCOMError::INVALID_OPERATION), false)]
+#[tokio::test]
+async fn prepare_frame_dark(
+    #[case] close_shutter: Option<Result<()>>,
+    #[case] policy: DarkFramePolicy,
+    #[case] exposure_min_max_step: Option<(f64, f64, f64)>,
+    #[case] expected: ASCOMResult<FrameType>,
+    #[case] shutter_closed_after: bool,
+) {
+    //given
+    let mut mock = MockCamera::new();
+    let has_shutter = close_shutter.is_some();
+    mock.expect_is_control_available()
+        .once()
+        .withf(|control| *control == Control::CamMechanicalShutter)
+        .returning(move |_| has_shutter.then_some(0));
+    if let Some(close_shutter) = close_shutter {
+        mock.expect_set_parameter()
+            .once()
+            .withf(|control, value| {
+                *control == Control::CamMechanicalShutter && *value == SHUTTER_CLOSED
+            })
+            .return_once(move |_, _| close_shutter);
+    }
+    let mut camera = new_camera(mock, MockCameraType::Untouched);
+    camera.dark_frame_policy = policy;
+    camera.exposure_min_max_step = RwLock::new(exposure_min_max_step);
+    //when
+    let res = camera
+        .prepare_frame(Duration::from_secs_f64(5.0), false)
+        .await;
+    //then
+    if expected.is_ok() {
+        assert_eq!(res.unwrap(), expected.unwrap());
+    } else {
+        assert_eq!(
+            res.unwrap_err().to_string(),
+            expected.unwrap_err().to_string()
+        );
+    }
+    assert_eq!(*camera.shutter_closed.read().await, shutter_closed_after);
+}
+
+#[rstest]
+#[case(false, 0, false)]
+#[case(true, 1, false)]
+#[tokio::test]
+async fn prepare_frame_light(
+    #[case] shutter_closed_before: bool,
+    #[case] open_shutter_times: usize,
+    #[case] shutter_closed_after: bool,
+) {
+    //given
+    let mut mock = MockCamera::new();
+    mock.expect_set_parameter()
+        .times(open_shutter_times)
+        .withf(|control, value| *control == Control::CamMechanicalShutter && *value == SHUTTER_OPEN)
+        .returning(|_, _| Ok(()));
+    let camera = new_camera(mock, MockCameraType::Untouched);
+    *camera.shutter_closed.write().await = shutter_closed_before;
+    //when
+    let res = camera
+        .prepare_frame(Duration::from_secs_f64(5.0), true)
+        .await;
+    //then
+    assert_eq!(res.unwrap(), FrameType::Light);
+    assert_eq!(*camera.shutter_closed.read().await, shutter_closed_after);
+}
+
 #[rstest]
 #[case(3, 100, 0, 10, 10, Err(ASCOMError::invalid_value("StartX > NumX")))]
 #[case(5, 0, 100, 10, 10, Err(ASCOMError::invalid_value("StartY > NumY")))]
@@ -300,7 +660,7 @@ async fn start_exposure_fail_is_exposing_no_miri() {
 #[case(vec![0, 0, 1, 0, 2, 0, 3, 0, 4, 0, 5, 0], 3, 2, 16, 1, Ok(()), array![[[0_u16],[3_u16]],[[1_u16],[4_u16]],[[2_u16],[5_u16]]].into())] //16bpp
 #[case(Vec::new(), 3, 2, 16, 1, Err(ASCOMError::INVALID_OPERATION), Array3::<u16>::zeros((1_usize, 1_usize, 3)).into())] //invalid vector
 #[case(vec![0, 0, 1, 0, 2, 0, 3, 0, 4, 0, 5, 0], 3, 2, 16, 2, Err(ASCOMError::INVALID_OPERATION), Array3::<u16>::zeros((1_usize, 1_usize, 3)).into())] //unsupported channel
-#[case(vec![0, 0, 1, 0, 2, 0, 3, 0, 4, 0, 5, 0], 3, 2, 32, 1, Err(ASCOMError::INVALID_OPERATION), Array3::<u16>::zeros((1_usize, 1_usize, 3)).into())] //unsupported bpp*/
+#[case(vec![0, 0, 1, 0, 2, 0, 3, 0, 4, 0, 5, 0], 3, 2, 32, 1, Err(ASCOMError::INVALID_OPERATION), Array3::<u16>::zeros((1_usize, 1_usize, 3)).into())] //invalid vector for 32bpp
 #[tokio::test]
 #[cfg_attr(miri, ignore)]
 async fn start_exposure_success_no_miri(
@@ -414,6 +774,90 @@ async fn start_exposure_success_no_miri(
     }
 }
 
+#[tokio::test]
+#[cfg_attr(miri, ignore)]
+async fn start_exposure_debayers_on_readout_no_miri() {
+    //given a flat RGGB field of R 200, G 100, B 50
+    let mut mock = MockCamera::new();
+    mock.expect_is_control_available()
+        .times(2)
+        .returning(|control| match control {
+            Control::CamIsColor => Some(0),
+            Control::CamColor => Some(qhyccd_rs::BayerMode::RGGB as u32),
+            _ => None,
+        });
+    mock.expect_set_parameter()
+        .once()
+        .withf(|control, _| *control == qhyccd_rs::Control::Exposure)
+        .returning(|_, _| Ok(()));
+    mock.expect_set_roi().once().returning(|_| Ok(()));
+    let mut clone_mock = MockCamera::new();
+    clone_mock.expect_get_single_frame().once().returning(|_| {
+        Ok(qhyccd_rs::ImageData {
+            data: vec![200_u8, 100, 100, 50],
+            width: 2,
+            height: 2,
+            bits_per_pixel: 8,
+            channels: 1,
+        })
+    });
+    let mut start_clone = MockCamera::new();
+    start_clone
+        .expect_start_single_frame_exposure()
+        .once()
+        .returning(|| Ok(()));
+    let mut size_clone = MockCamera::new();
+    size_clone
+        .expect_get_image_size()
+        .once()
+        .returning(|| Ok(4_usize));
+    let inner_mocks = std::sync::Mutex::new(vec![size_clone, start_clone]);
+    clone_mock
+        .expect_clone()
+        .times(2)
+        .returning(move || inner_mocks.lock().unwrap().pop().unwrap());
+    let clone_mock = std::sync::Mutex::new(Some(clone_mock));
+    mock.expect_clone()
+        .times(2)
+        .returning(move || clone_mock.lock().unwrap().take().unwrap_or_default());
+    let mut camera = new_camera(
+        mock,
+        MockCameraType::WithBinningAndRoiAndCCDInfoUnlimited {
+            camera_roi: CCDChipArea {
+                start_x: 0,
+                start_y: 0,
+                width: 2,
+                height: 2,
+            },
+            camera_ccd_info: CCDChipInfo {
+                chip_width: 7_f64,
+                chip_height: 5_f64,
+                image_width: 2,
+                image_height: 2,
+                pixel_width: 2.9_f64,
+                pixel_height: 2.9_f64,
+                bits_per_pixel: 8,
+            },
+            camera_binning: 1_u8,
+        },
+    );
+    camera.debayer = RwLock::new(Some(DebayerMethod::Bilinear));
+    //when
+    let mut done = camera
+        .begin_exposure(Duration::from_secs(1), true, false)
+        .await
+        .unwrap();
+    tokio::time::timeout(Duration::from_secs(1), done.wait_for(|done| *done))
+        .await
+        .unwrap()
+        .unwrap();
+    //then the stored frame, also served as ImageBytes and written to FITS, is debayered
+    let last_image = camera.last_image.read().await;
+    let image = &last_image.as_ref().unwrap().image;
+    assert_eq!(image.channels, 3);
+    assert_eq!(image.data, [50_u8, 100, 200].repeat(4));
+}
+
 #[tokio::test]
 async fn abort_exposure_idle() {
     //given
@@ -297,3 +297,212 @@ async fn offset_max(
         )
     }
 }
+
+fn qhy600_presets() -> Option<presets::Presets> {
+    presets::for_model("QHY600M", &std::collections::BTreeMap::new())
+}
+
+#[test]
+fn presets_for_model() {
+    //given
+    let overrides = std::collections::BTreeMap::from([(
+        "QHY600M".to_owned(),
+        presets::Presets {
+            gains: vec![presets::GainPreset {
+                name: "Mine".to_owned(),
+                gain: 40,
+                readout_mode: None,
+            }],
+            offsets: vec![],
+        },
+    )]);
+    //when
+    let builtin = qhy600_presets().unwrap();
+    let overridden = presets::for_model("QHY600M", &overrides).unwrap();
+    //then
+    assert_eq!(builtin.gains[1].name, "Unity");
+    assert_eq!(builtin.gains[1].readout_mode, Some(0));
+    assert_eq!(overridden.gains[0].name, "Mine");
+    assert!(overridden.offsets.is_empty());
+    assert!(presets::for_model("QHY5III178M", &std::collections::BTreeMap::new()).is_none());
+}
+
+#[rstest]
+#[case(qhy600_presets(), Ok(vec!["HighDynamicRange", "Unity", "HCG", "LowNoise"]))]
+#[case(None, Err(ASCOMError::NOT_IMPLEMENTED))]
+#[tokio::test]
+async fn gains(
+    #[case] presets: Option<presets::Presets>,
+    #[case] expected: ASCOMResult<Vec<&str>>,
+) {
+    //given
+    let mut camera = new_camera(MockCamera::new(), MockCameraType::IsOpenTrue { times: 1 });
+    camera.presets = presets;
+    //when
+    let res = camera.gains().await;
+    //then
+    match expected {
+        Ok(expected) => assert_eq!(res.unwrap(), expected),
+        Err(expected) => assert_eq!(res.unwrap_err().to_string(), expected.to_string()),
+    }
+}
+
+#[rstest]
+#[case(56_f64, 1, Ok(2_i32))]
+#[case(26_f64, 0, Ok(1_i32))]
+#[case(26_f64, 1, Err(ASCOMError::VALUE_NOT_SET))]
+#[tokio::test]
+async fn gain_presets(
+    #[case] gain: f64,
+    #[case] readout_mode: u32,
+    #[case] expected: ASCOMResult<i32>,
+) {
+    //given
+    let mut mock = MockCamera::new();
+    mock.expect_is_control_available()
+        .once()
+        .withf(|control| *control == qhyccd_rs::Control::Gain)
+        .returning(|_| Some(0));
+    mock.expect_get_parameter()
+        .once()
+        .withf(|control| *control == qhyccd_rs::Control::Gain)
+        .returning(move |_| Ok(gain));
+    mock.expect_get_readout_mode()
+        .once()
+        .returning(move || Ok(readout_mode));
+    let mut camera = new_camera(mock, MockCameraType::IsOpenTrue { times: 1 });
+    camera.presets = qhy600_presets();
+    //when
+    let res = camera.gain().await;
+    //then
+    match expected {
+        Ok(expected) => assert_eq!(res.unwrap(), expected),
+        Err(expected) => assert_eq!(res.unwrap_err().to_string(), expected.to_string()),
+    }
+}
+
+#[tokio::test]
+async fn set_gain_preset_with_readout_mode() {
+    //given
+    let mut mock = MockCamera::new();
+    mock.expect_get_number_of_readout_modes()
+        .once()
+        .returning(|| Ok(3));
+    mock.expect_get_readout_mode_resolution()
+        .once()
+        .withf(|mode| *mode == 1)
+        .returning(|_| Ok((9576, 6388)));
+    mock.expect_get_readout_mode().once().returning(|| Ok(0));
+    mock.expect_set_readout_mode()
+        .once()
+        .withf(|mode| *mode == 1)
+        .returning(|_| Ok(()));
+    mock.expect_is_control_available()
+        .once()
+        .withf(|control| *control == qhyccd_rs::Control::Gain)
+        .returning(|_| Some(0));
+    mock.expect_set_parameter()
+        .once()
+        .withf(|control, gain| {
+            *control == qhyccd_rs::Control::Gain && (*gain - 56_f64).abs() < f64::EPSILON
+        })
+        .returning(|_, _| Ok(()));
+    let mut camera = new_camera(
+        mock,
+        MockCameraType::WithGain {
+            times: 3,
+            min_max: Some((0_f64, 100_f64)),
+        },
+    );
+    camera.presets = qhy600_presets();
+    //when
+    let res = camera.set_gain(2).await;
+    //then
+    assert!(res.is_ok());
+}
+
+#[rstest]
+#[case(-1)]
+#[case(4)]
+#[tokio::test]
+async fn set_gain_preset_invalid_index(#[case] index: i32) {
+    //given
+    let mut camera = new_camera(
+        MockCamera::new(),
+        MockCameraType::WithGain {
+            times: 1,
+            min_max: Some((0_f64, 100_f64)),
+        },
+    );
+    camera.presets = qhy600_presets();
+    //when
+    let res = camera.set_gain(index).await;
+    //then
+    assert_eq!(
+        res.unwrap_err().to_string(),
+        ASCOMError::INVALID_VALUE.to_string()
+    );
+}
+
+#[tokio::test]
+async fn gain_and_offset_min_max_with_presets() {
+    //given
+    let mut camera = new_camera(
+        MockCamera::new(),
+        MockCameraType::WithGain {
+            times: 4,
+            min_max: Some((0_f64, 100_f64)),
+        },
+    );
+    camera.presets = qhy600_presets();
+    //when
+    let results = [
+        camera.gain_min().await,
+        camera.gain_max().await,
+        camera.offset_min().await,
+        camera.offset_max().await,
+    ];
+    //then
+    for res in results {
+        assert_eq!(
+            res.unwrap_err().to_string(),
+            ASCOMError::NOT_IMPLEMENTED.to_string()
+        );
+    }
+}
+
+#[tokio::test]
+async fn offset_presets() {
+    //given
+    let mut mock = MockCamera::new();
+    mock.expect_is_control_available()
+        .times(2)
+        .withf(|control| *control == qhyccd_rs::Control::Offset)
+        .returning(|_| Some(0));
+    mock.expect_get_parameter()
+        .once()
+        .withf(|control| *control == qhyccd_rs::Control::Offset)
+        .returning(|_| Ok(10_f64));
+    mock.expect_set_parameter()
+        .once()
+        .withf(|control, offset| {
+            *control == qhyccd_rs::Control::Offset && (*offset - 30_f64).abs() < f64::EPSILON
+        })
+        .returning(|_, _| Ok(()));
+    let mut camera = new_camera(
+        mock,
+        MockCameraType::WithOffset {
+            times: 4,
+            min_max: Some((0_f64, 255_f64)),
+        },
+    );
+    camera.presets = qhy600_presets();
+    //when
+    let offsets = camera.offsets().await;
+    let offset = camera.offset().await;
+    let set_offset = camera.set_offset(0).await;
+    //then
+    assert_eq!(offsets.unwrap(), vec!["Standard", "Low"]);
+    assert_eq!(offset.unwrap(), 1);
+    assert!(set_offset.is_ok());
+}
@@ -2,21 +2,45 @@
 
 use super::*;
 
+/// A 3 x 2 frame as the SDK delivers it, row by row.
+fn frame() -> qhyccd_rs::ImageData {
+    qhyccd_rs::ImageData {
+        data: [0_u16, 1, 2, 3, 4, 5]
+            .iter()
+            .flat_map(|value| value.to_ne_bytes())
+            .collect(),
+        width: 3,
+        height: 2,
+        bits_per_pixel: 16,
+        channels: 1,
+    }
+}
+
 #[tokio::test]
 async fn image_array_success() {
     //given
     let mock = MockCamera::new();
-    let camera = new_camera(
-        mock,
-        MockCameraType::WithImage {
-            image_array: Array3::<u16>::zeros((10_usize, 10_usize, 3)).into(),
-        },
-    );
+    let camera = new_camera(mock, MockCameraType::WithImage { image: frame() });
     //when
     let res = camera.image_array().await;
     //then
     assert!(res.is_ok());
-    assert_eq!(res.unwrap().shape(), [10, 10, 3]);
+    let expected: ImageArray =
+        array![[[0_u16], [3_u16]], [[1_u16], [4_u16]], [[2_u16], [5_u16]]].into();
+    assert_eq!(res.unwrap(), expected);
+}
+
+#[tokio::test]
+async fn image_array_keeps_stored_frame() {
+    //given
+    let mock = MockCamera::new();
+    let camera = new_camera(mock, MockCameraType::WithImage { image: frame() });
+    //when
+    let res = camera.image_array().await;
+    //then
+    assert!(res.is_ok());
+    let last_image = camera.last_image.read().await;
+    assert_eq!(*last_image.as_ref().unwrap().image, frame());
 }
 
 #[tokio::test]
@@ -55,12 +79,7 @@ async fn image_ready_not_ready_success() {
 async fn image_ready_ready_success() {
     //given
     let mock = MockCamera::new();
-    let camera = new_camera(
-        mock,
-        MockCameraType::WithImage {
-            image_array: Array3::<u16>::zeros((10_usize, 10_usize, 3)).into(),
-        },
-    );
+    let camera = new_camera(mock, MockCameraType::WithImage { image: frame() });
     //when
     let res = camera.image_ready().await;
     //then
@@ -85,3 +104,204 @@ async fn image_ready_ready_success_no_image_taken_yet() {
     assert!(res.is_ok());
     assert!(!res.unwrap());
 }
+
+fn samples<T, const N: usize>(values: &[T], to_ne_bytes: fn(T) -> [u8; N]) -> Vec<u8>
+where
+    T: Copy,
+{
+    values
+        .iter()
+        .flat_map(|value| to_ne_bytes(*value))
+        .collect()
+}
+
+#[rustfmt::skip]
+#[rstest]
+#[case::mono_8bpp(vec![0, 1, 2, 3, 4, 5], 3, 2, 8, 1, array![[[0_u8], [3_u8]], [[1_u8], [4_u8]], [[2_u8], [5_u8]]].into())]
+#[case::mono_16bpp(samples(&[0_u16, 1, 2, 3, 4, 65535], u16::to_ne_bytes), 3, 2, 16, 1, array![[[0_u16], [3_u16]], [[1_u16], [4_u16]], [[2_u16], [65535_u16]]].into())]
+#[case::mono_32bpp(samples(&[0_i32, 1, 2, 3, 4, 100_000], i32::to_ne_bytes), 3, 2, 32, 1, array![[[0_i32], [3_i32]], [[1_i32], [4_i32]], [[2_i32], [100_000_i32]]].into())]
+#[case::rgb_8bpp(vec![1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12], 2, 2, 8, 3, array![[[3_u8, 2, 1], [9, 8, 7]], [[6, 5, 4], [12, 11, 10]]].into())]
+#[case::rgb_16bpp(samples(&[1_u16, 2, 3, 4, 5, 60000], u16::to_ne_bytes), 2, 1, 16, 3, array![[[3_u16, 2, 1]], [[60000, 5, 4]]].into())]
+#[case::rgb_32bpp(samples(&[1_i32, 2, 3, 4, 5, 100_000], i32::to_ne_bytes), 2, 1, 32, 3, array![[[3_i32, 2, 1]], [[100_000, 5, 4]]].into())]
+fn transform_image_static_success(
+    #[case] data: Vec<u8>,
+    #[case] width: u32,
+    #[case] height: u32,
+    #[case] bits_per_pixel: u32,
+    #[case] channels: u32,
+    #[case] expected: ImageArray,
+) {
+    //given
+    let image = qhyccd_rs::ImageData {
+        data,
+        width,
+        height,
+        bits_per_pixel,
+        channels,
+    };
+    //when
+    let res = QhyccdCamera::transform_image_static(&image);
+    //then
+    assert_eq!(res.unwrap(), expected);
+}
+
+#[rstest]
+#[case::two_channels(vec![0; 8], 2, 8, "unsupported number of channels")]
+#[case::bpp_24(vec![0; 12], 1, 24, "unsupported bits_per_pixel")]
+#[case::short_32bpp(vec![0; 15], 1, 32, "does not match")]
+#[case::short_rgb(vec![0; 11], 3, 8, "does not match")]
+fn transform_image_static_fail(
+    #[case] data: Vec<u8>,
+    #[case] channels: u32,
+    #[case] bits_per_pixel: u32,
+    #[case] expected: &str,
+) {
+    //given
+    let image = qhyccd_rs::ImageData {
+        data,
+        width: 2,
+        height: 2,
+        bits_per_pixel,
+        channels,
+    };
+    //when
+    let res = QhyccdCamera::transform_image_static(&image);
+    //then
+    assert!(res.unwrap_err().to_string().contains(expected));
+}
+
+/// A flat field of R 200, G 100, B 50 read from an RGGB sensor, starting at `origin`.
+fn flat_field(origin: (u32, u32)) -> qhyccd_rs::ImageData {
+    let mut data = Vec::new();
+    for y in origin.1..origin.1 + 4 {
+        for x in origin.0..origin.0 + 4 {
+            data.push(match (x % 2, y % 2) {
+                (0, 0) => 200_u8,
+                (1, 1) => 50_u8,
+                _ => 100_u8,
+            });
+        }
+    }
+    qhyccd_rs::ImageData {
+        data,
+        width: 4,
+        height: 4,
+        bits_per_pixel: 8,
+        channels: 1,
+    }
+}
+
+#[rstest]
+#[case::bilinear_even_origin(DebayerMethod::Bilinear, (0, 0), [4, 4, 3])]
+#[case::bilinear_odd_x(DebayerMethod::Bilinear, (1, 0), [4, 4, 3])]
+#[case::gradient_odd_y(DebayerMethod::Gradient, (0, 1), [4, 4, 3])]
+#[case::superpixel_odd_origin(DebayerMethod::Superpixel, (1, 1), [2, 2, 3])]
+#[tokio::test]
+async fn image_array_debayers_live_frames(
+    #[case] method: DebayerMethod,
+    #[case] origin: (u32, u32),
+    #[case] shape: [usize; 3],
+) {
+    //given
+    let mut mock = MockCamera::new();
+    mock.expect_is_control_available()
+        .times(2)
+        .returning(|control| match control {
+            Control::CamIsColor => Some(0),
+            Control::CamColor => Some(qhyccd_rs::BayerMode::RGGB as u32),
+            _ => None,
+        });
+    let mut camera = new_camera(mock, MockCameraType::IsOpenTrue { times: 1 });
+    camera.debayer = RwLock::new(Some(method));
+    camera.binning = RwLock::new(Binning::symmetric(1));
+    camera.intended_roi = RwLock::new(Some(CCDChipArea {
+        start_x: origin.0,
+        start_y: origin.1,
+        width: 4,
+        height: 4,
+    }));
+    *camera.live.write().await = Some(super::live::idle_session(camera.live_feed.clone()));
+    camera.live_feed.push(flat_field(origin)).await;
+    //when
+    let res = camera.image_array().await;
+    //then
+    let expected: ImageArray =
+        Array3::from_shape_fn(shape, |(_, _, plane)| [200_u8, 100, 50][plane]).into();
+    assert_eq!(res.unwrap(), expected);
+}
+
+#[tokio::test]
+async fn image_array_debayer_skips_binned_frames() {
+    //given
+    let mut camera = new_camera(MockCamera::new(), MockCameraType::IsOpenTrue { times: 1 });
+    camera.debayer = RwLock::new(Some(DebayerMethod::Bilinear));
+    *camera.last_image.write().await = Some(LastImage {
+        image: Arc::new(frame()),
+        frame_type: FrameType::Light,
+        origin: (0, 0),
+        binning: Binning::symmetric(2),
+        overscan: None,
+        image_array: Arc::default(),
+    });
+    //when
+    let res = camera.image_array().await;
+    //then
+    let expected: ImageArray =
+        array![[[0_u16], [3_u16]], [[1_u16], [4_u16]], [[2_u16], [5_u16]]].into();
+    assert_eq!(res.unwrap(), expected);
+}
+
+#[tokio::test]
+async fn image_array_debayer_skips_asymmetric_binned_frames() {
+    //given a live frame binned 1x2, which has no Bayer pattern left
+    let mut camera = new_camera(MockCamera::new(), MockCameraType::IsOpenTrue { times: 1 });
+    camera.debayer = RwLock::new(Some(DebayerMethod::Bilinear));
+    camera.binning = RwLock::new(Binning { x: 1, y: 2 });
+    *camera.live.write().await = Some(super::live::idle_session(camera.live_feed.clone()));
+    camera.live_feed.push(flat_field((0, 0))).await;
+    //when
+    let res = camera.image_array().await;
+    //then
+    assert_eq!(res.unwrap().shape(), [4, 4, 1]);
+}
+
+#[tokio::test]
+async fn image_array_serves_the_converted_frame_again() {
+    //given
+    let camera = new_camera(MockCamera::new(), MockCameraType::IsOpenTrue { times: 2 });
+    *camera.last_image.write().await = Some(LastImage {
+        image: Arc::new(frame()),
+        frame_type: FrameType::Light,
+        origin: (0, 0),
+        binning: Binning::symmetric(1),
+        overscan: None,
+        image_array: Arc::default(),
+    });
+    //when
+    let first = camera.image_array().await.unwrap();
+    let second = camera.image_array().await.unwrap();
+    //then
+    assert_eq!(first, second);
+    let last_image = camera.last_image.read().await;
+    let converted = last_image.as_ref().unwrap().image_array.lock().unwrap();
+    assert_eq!(converted.as_ref().unwrap(), &first);
+}
+
+#[tokio::test]
+async fn image_array_serves_stored_frames_as_read() {
+    //given a raw frame stored before debayering was switched on
+    let mut camera = new_camera(MockCamera::new(), MockCameraType::IsOpenTrue { times: 1 });
+    camera.debayer = RwLock::new(Some(DebayerMethod::Bilinear));
+    *camera.last_image.write().await = Some(LastImage {
+        image: Arc::new(flat_field((0, 0))),
+        frame_type: FrameType::Light,
+        origin: (0, 0),
+        binning: Binning::symmetric(1),
+        overscan: None,
+        image_array: Arc::default(),
+    });
+    //when
+    let res = camera.image_array().await;
+    //then
+    assert_eq!(res.unwrap().shape(), [4, 4, 1]);
+}
@@ -0,0 +1,262 @@
+//! Live mode tests
+
+use super::*;
+use crate::live::LiveSession;
+
+fn live_frame() -> qhyccd_rs::ImageData {
+    qhyccd_rs::ImageData {
+        data: vec![1_u8, 2, 3, 4],
+        width: 2,
+        height: 2,
+        bits_per_pixel: 8,
+        channels: 1,
+    }
+}
+
+/// A live session whose camera never delivers a frame.
+pub(super) fn idle_session(feed: Arc<LiveFeed>) -> LiveSession {
+    let mut task_mock = MockCamera::new();
+    task_mock
+        .expect_get_live_frame()
+        .returning(|_| Err(eyre!("no frame")));
+    crate::live::start(task_mock, feed, 4, None, None)
+}
+
+#[tokio::test]
+async fn start_and_stop_live() {
+    //given
+    let mut mock = MockCamera::new();
+    mock.expect_is_open().returning(|| Ok(true));
+    mock.expect_is_control_available()
+        .times(7)
+        .returning(|control| match control {
+            Control::CamLiveVideoMode | Control::Gain => Some(0),
+            _ => None,
+        });
+    mock.expect_get_parameter()
+        .times(2)
+        .withf(|control| *control == Control::Gain)
+        .returning(|_| Ok(26_f64));
+    mock.expect_get_readout_mode().times(2).returning(|| Ok(1));
+    let mut seq = mockall::Sequence::new();
+    mock.expect_set_stream_mode()
+        .once()
+        .in_sequence(&mut seq)
+        .withf(|mode| *mode == qhyccd_rs::StreamMode::LiveMode)
+        .returning(|_| Ok(()));
+    mock.expect_set_stream_mode()
+        .once()
+        .in_sequence(&mut seq)
+        .withf(|mode| *mode == qhyccd_rs::StreamMode::SingleFrameMode)
+        .returning(|_| Ok(()));
+    mock.expect_set_readout_mode()
+        .times(2)
+        .withf(|mode| *mode == 1)
+        .returning(|_| Ok(()));
+    mock.expect_init().times(2).returning(|| Ok(()));
+    mock.expect_set_if_available()
+        .times(2)
+        .withf(|control, bits| {
+            *control == Control::TransferBit && (*bits - 16_f64).abs() < f64::EPSILON
+        })
+        .returning(|_, _| Ok(()));
+    mock.expect_set_bin_mode()
+        .times(2)
+        .withf(|x, y| *x == 1 && *y == 1)
+        .returning(|_, _| Ok(()));
+    mock.expect_set_parameter()
+        .times(2)
+        .withf(|control, gain| *control == Control::Gain && (*gain - 26_f64).abs() < f64::EPSILON)
+        .returning(|_, _| Ok(()));
+    mock.expect_set_roi().once().returning(|_| Ok(()));
+    mock.expect_begin_live().once().returning(|| Ok(()));
+    mock.expect_get_image_size().once().returning(|| Ok(4));
+    mock.expect_end_live().once().returning(|| Ok(()));
+    mock.expect_clone().once().returning(|| {
+        let mut task_mock = MockCamera::new();
+        task_mock
+            .expect_get_live_frame()
+            .withf(|buffer_size| *buffer_size == 4)
+            .returning(|_| Ok(live_frame()));
+        task_mock
+    });
+    let mut camera = new_camera(mock, MockCameraType::Untouched);
+    camera.binning = RwLock::new(Binning::symmetric(1));
+    camera.intended_roi = RwLock::new(Some(CCDChipArea {
+        start_x: 0,
+        start_y: 0,
+        width: 2,
+        height: 2,
+    }));
+    //when
+    let res = camera.start_live(None).await;
+    let timeout = tokio::time::Duration::from_secs(2);
+    let start = tokio::time::Instant::now();
+    while start.elapsed() < timeout {
+        if camera.image_ready().await.unwrap() {
+            break;
+        }
+        tokio::time::sleep(tokio::time::Duration::from_millis(10)).await;
+    }
+    //then
+    assert!(res.is_ok());
+    assert!(camera.image_ready().await.unwrap());
+    assert_eq!(camera.image_array().await.unwrap().shape(), [2, 2, 1]);
+    assert_eq!(
+        camera
+            .start_exposure(Duration::from_secs(1), true)
+            .await
+            .err()
+            .unwrap()
+            .to_string(),
+        ASCOMError::invalid_operation("camera is in live mode, stop it first").to_string()
+    );
+    //when
+    let res = camera.stop_live().await;
+    //then
+    assert!(res.is_ok());
+    assert!(camera.live.read().await.is_none());
+}
+
+#[tokio::test]
+async fn start_live_without_live_mode() {
+    //given
+    let mut mock = MockCamera::new();
+    mock.expect_is_control_available()
+        .once()
+        .withf(|control| *control == Control::CamLiveVideoMode)
+        .returning(|_| None);
+    let camera = new_camera(mock, MockCameraType::IsOpenTrue { times: 1 });
+    //when
+    let res = camera.start_live(None).await;
+    //then
+    assert_eq!(
+        res.err().unwrap().to_string(),
+        ASCOMError::invalid_operation("camera has no live mode").to_string()
+    );
+}
+
+#[tokio::test]
+async fn start_live_during_exposure() {
+    //given
+    let mut mock = MockCamera::new();
+    mock.expect_is_control_available()
+        .once()
+        .withf(|control| *control == Control::CamLiveVideoMode)
+        .returning(|_| Some(0));
+    let camera = new_camera(
+        mock,
+        MockCameraType::WithStateExposing {
+            expected_duration: 1_000_000_f64,
+        },
+    );
+    //when
+    let res = camera.start_live(None).await;
+    //then
+    assert_eq!(
+        res.err().unwrap().to_string(),
+        ASCOMError::invalid_operation("cannot start live mode during an exposure").to_string()
+    );
+    assert!(camera.live.read().await.is_none());
+}
+
+#[tokio::test]
+async fn start_live_begin_live_fails() {
+    //given
+    let mut mock = MockCamera::new();
+    mock.expect_is_control_available()
+        .returning(|control| match control {
+            Control::CamLiveVideoMode => Some(0),
+            _ => None,
+        });
+    mock.expect_get_readout_mode().times(2).returning(|| Ok(0));
+    mock.expect_set_stream_mode().times(2).returning(|_| Ok(()));
+    mock.expect_set_readout_mode()
+        .times(2)
+        .returning(|_| Ok(()));
+    mock.expect_init().times(2).returning(|| Ok(()));
+    mock.expect_set_if_available()
+        .times(2)
+        .returning(|_, _| Ok(()));
+    mock.expect_set_bin_mode().times(2).returning(|_, _| Ok(()));
+    mock.expect_begin_live()
+        .once()
+        .returning(|| Err(eyre!("error")));
+    let camera = new_camera(mock, MockCameraType::IsOpenTrue { times: 1 });
+    //when
+    let res = camera.start_live(None).await;
+    //then the camera is back in single frame mode
+    assert_eq!(
+        res.err().unwrap().to_string(),
+        ASCOMError::INVALID_OPERATION.to_string()
+    );
+    assert!(camera.live.read().await.is_none());
+}
+
+#[tokio::test]
+async fn image_array_in_live_mode() {
+    //given
+    let mock = MockCamera::new();
+    let camera = new_camera(mock, MockCameraType::IsOpenTrue { times: 3 });
+    *camera.live.write().await = Some(idle_session(camera.live_feed.clone()));
+    //when no frame arrived yet
+    let ready = camera.image_ready().await;
+    let res = camera.image_array().await;
+    //then
+    assert!(!ready.unwrap());
+    assert_eq!(
+        res.err().unwrap().to_string(),
+        ASCOMError::VALUE_NOT_SET.to_string()
+    );
+    //when
+    camera.live_feed.push(live_frame()).await;
+    let res = camera.image_array().await;
+    //then
+    assert_eq!(res.unwrap().shape(), [2, 2, 1]);
+}
+
+#[rstest]
+#[case("abc")]
+#[case("-1")]
+#[tokio::test]
+async fn start_live_action_invalid_exposure(#[case] parameters: &str) {
+    //given
+    let camera = new_camera(MockCamera::new(), MockCameraType::Untouched);
+    //when
+    let res = camera
+        .action("StartLive".to_owned(), parameters.to_owned())
+        .await;
+    //then
+    assert_eq!(
+        res.err().unwrap().to_string(),
+        ASCOMError::invalid_value(
+            "StartLive parameter must be the exposure time in seconds or empty"
+        )
+        .to_string()
+    );
+}
+
+#[tokio::test]
+async fn live_status_action() {
+    //given
+    let camera = new_camera(MockCamera::new(), MockCameraType::IsOpenTrue { times: 1 });
+    *camera.live.write().await = Some(idle_session(camera.live_feed.clone()));
+    camera.live_feed.push(live_frame()).await;
+    //when
+    let res = camera.action("LiveStatus".to_owned(), String::new()).await;
+    //then
+    assert_eq!(
+        serde_json::from_str::<serde_json::Value>(&res.unwrap()).unwrap(),
+        serde_json::json!({ "active": true, "sequence": 1, "buffered": 1 })
+    );
+}
+
+#[tokio::test]
+async fn stop_live_when_not_live() {
+    //given
+    let camera = new_camera(MockCamera::new(), MockCameraType::Untouched);
+    //when
+    let res = camera.action("StopLive".to_owned(), String::new()).await;
+    //then
+    assert_eq!(res.unwrap(), "");
+}
@@ -14,16 +14,24 @@ use ndarray::{Array3, array};
 use rstest::*;
 
 // Test modules
+pub mod actions;
 pub mod binning;
 pub mod connection;
 pub mod exposure;
 pub mod gain_offset;
 pub mod image;
+pub mod live;
+pub mod overscan;
 pub mod properties;
 pub mod readout;
 pub mod roi;
+pub mod scaling;
 pub mod sensor;
+pub mod sequence;
+pub mod telemetry;
 pub mod temperature;
+pub mod transfer;
+pub mod tuning;
 
 /// Macro for testing NOT_CONNECTED error responses
 #[macro_export]
@@ -64,7 +72,7 @@ pub enum MockCameraType {
         expected_duration: f64,
     },
     WithImage {
-        image_array: ImageArray,
+        image: qhyccd_rs::ImageData,
     },
     WithExposureMinMaxStep {
         min_max_step: Option<(f64, f64, f64)>,
@@ -109,6 +117,10 @@ pub enum MockCameraType {
         times: usize,
         temperature: Option<f64>,
     },
+    WithCooler {
+        times: usize,
+        cooler: Cooler,
+    },
     WithGain {
         times: usize,
         min_max: Option<(f64, f64)>,
@@ -126,8 +138,8 @@ pub enum MockCameraType {
 /// Creates a new QhyccdCamera with the specified mock configuration
 pub fn new_camera(mut device: MockCamera, variant: MockCameraType) -> QhyccdCamera {
     let mut valid_bins = RwLock::new(None);
-    let mut binning = RwLock::new(0_u8);
-    let mut target_temperature = RwLock::new(None);
+    let mut binning = RwLock::new(Binning::symmetric(0));
+    let mut cooler = Cooler::new(CoolerRamp::default());
     let mut ccd_info = RwLock::new(None);
     let mut intended_roi = RwLock::new(None);
     let mut exposing = RwLock::new(State::Idle);
@@ -173,9 +185,16 @@ pub fn new_camera(mut device: MockCamera, variant: MockCameraType) -> QhyccdCame
                 done_rx: watch::channel(false).1,
             });
         }
-        MockCameraType::WithImage { image_array: image } => {
+        MockCameraType::WithImage { image } => {
             device.expect_is_open().times(1).returning(|| Ok(true));
-            last_image = RwLock::new(Some(image));
+            last_image = RwLock::new(Some(LastImage {
+                image: Arc::new(image),
+                frame_type: FrameType::Light,
+                origin: (0, 0),
+                binning: Binning::symmetric(1),
+                overscan: None,
+                image_array: Arc::default(),
+            }));
         }
         MockCameraType::WithExposureMinMaxStep { min_max_step } => {
             device.expect_is_open().once().returning(|| Ok(true));
@@ -196,7 +215,7 @@ pub fn new_camera(mut device: MockCamera, variant: MockCameraType) -> QhyccdCame
         } => {
             device.expect_is_open().times(times).returning(|| Ok(true));
             valid_bins = RwLock::new(Some(camera_valid_bins));
-            binning = RwLock::new(camera_binning);
+            binning = RwLock::new(Binning::symmetric(camera_binning));
         }
         MockCameraType::WithBinningAndRoiAndCCDInfo {
             times,
@@ -207,7 +226,7 @@ pub fn new_camera(mut device: MockCamera, variant: MockCameraType) -> QhyccdCame
             device.expect_is_open().times(times).returning(|| Ok(true));
             ccd_info = RwLock::new(Some(camera_ccd_info));
             intended_roi = RwLock::new(Some(camera_roi));
-            binning = RwLock::new(camera_binning);
+            binning = RwLock::new(Binning::symmetric(camera_binning));
         }
         MockCameraType::WithBinningAndValidBinsAndRoiAndCCDInfo {
             times,
@@ -220,7 +239,7 @@ pub fn new_camera(mut device: MockCamera, variant: MockCameraType) -> QhyccdCame
             ccd_info = RwLock::new(Some(camera_ccd_info));
             intended_roi = RwLock::new(Some(camera_roi));
             valid_bins = RwLock::new(Some(camera_valid_bins));
-            binning = RwLock::new(camera_binning);
+            binning = RwLock::new(Binning::symmetric(camera_binning));
         }
         MockCameraType::WithBinningAndRoiAndCCDInfoUnlimited {
             camera_roi,
@@ -230,7 +249,7 @@ pub fn new_camera(mut device: MockCamera, variant: MockCameraType) -> QhyccdCame
             device.expect_is_open().returning(|| Ok(true));
             ccd_info = RwLock::new(Some(camera_ccd_info));
             intended_roi = RwLock::new(Some(camera_roi));
-            binning = RwLock::new(camera_binning);
+            binning = RwLock::new(Binning::symmetric(camera_binning));
         }
         MockCameraType::WithBinningAndRoiAndCCDInfoAndExposing {
             times,
@@ -242,7 +261,7 @@ pub fn new_camera(mut device: MockCamera, variant: MockCameraType) -> QhyccdCame
             device.expect_is_open().times(times).returning(|| Ok(true));
             ccd_info = RwLock::new(Some(camera_ccd_info));
             intended_roi = RwLock::new(Some(camera_roi));
-            binning = RwLock::new(camera_binning);
+            binning = RwLock::new(Binning::symmetric(camera_binning));
             exposing = RwLock::new(State::Exposing {
                 start: SystemTime::UNIX_EPOCH,
                 expected_duration_us: expected_duration as u32,
@@ -252,7 +271,14 @@ pub fn new_camera(mut device: MockCamera, variant: MockCameraType) -> QhyccdCame
         }
         MockCameraType::WithTargetTemperature { times, temperature } => {
             device.expect_is_open().times(times).returning(|| Ok(true));
-            target_temperature = RwLock::new(temperature);
+            cooler.target = temperature;
+        }
+        MockCameraType::WithCooler {
+            times,
+            cooler: camera_cooler,
+        } => {
+            device.expect_is_open().times(times).returning(|| Ok(true));
+            cooler = camera_cooler;
         }
         MockCameraType::WithGain { times, min_max } => {
             device.expect_is_open().times(times).returning(|| Ok(true));
@@ -271,22 +297,45 @@ pub fn new_camera(mut device: MockCamera, variant: MockCameraType) -> QhyccdCame
         }
     }
     QhyccdCamera {
+        this: Weak::new(),
         unique_id: "test-camera".to_owned(),
         name: "QHYCCD-test_camera".to_owned(),
         description: "QHYCCD camera".to_owned(),
         device,
         binning,
         valid_bins,
-        target_temperature,
+        cooler: Arc::new(RwLock::new(cooler)),
         ccd_info,
         intended_roi,
+        effective_area: RwLock::new(None),
+        overscan_area: RwLock::new(None),
+        include_overscan: RwLock::new(false),
+        transfer_bits: RwLock::new(16),
         readout_speed_min_max_step,
         exposure_min_max_step,
         last_exposure_start_time,
-        last_exposure_duration_us,
+        last_exposure_duration_us: Arc::new(last_exposure_duration_us),
         last_image: Arc::new(last_image),
         state: Arc::new(exposing),
         gain_min_max,
         offset_min_max,
+        dark_frame_policy: DarkFramePolicy::Warn,
+        shutter_closed: RwLock::new(false),
+        fits_export: None,
+        fits_export_enabled: RwLock::new(false),
+        debayer: RwLock::new(None),
+        config: Arc::new(ConfigStore::default()),
+        cfw_detected: Arc::new(AtomicBool::new(false)),
+        cfw_found: Arc::default(),
+        presets: None,
+        live_feed: Arc::new(LiveFeed::default()),
+        live: RwLock::new(None),
+        sequencer: Arc::new(Sequencer::default()),
+        stop_supported: Arc::new(AtomicBool::new(true)),
+        sensors: RwLock::new(Vec::new()),
+        usb_traffic_range: RwLock::new(None),
+        ddr_range: RwLock::new(None),
+        filter_wheels: Arc::default(),
+        attached: AtomicBool::new(true),
     }
 }
@@ -0,0 +1,181 @@
+//! Overscan readout tests
+
+use super::*;
+
+/// A connected 120x100 camera whose 20 rightmost columns are overscan, at `binning`.
+fn overscan_camera(mock: MockCamera, roi: CCDChipArea, binning: u8) -> QhyccdCamera {
+    let camera = new_camera(
+        mock,
+        MockCameraType::WithBinningAndRoiAndCCDInfoUnlimited {
+            camera_roi: roi,
+            camera_ccd_info: CCDChipInfo {
+                chip_width: 7_f64,
+                chip_height: 5_f64,
+                image_width: 120,
+                image_height: 100,
+                pixel_width: 2.9_f64,
+                pixel_height: 2.9_f64,
+                bits_per_pixel: 16,
+            },
+            camera_binning: binning,
+        },
+    );
+    *camera.effective_area.try_write().unwrap() = Some(CCDChipArea {
+        start_x: 0,
+        start_y: 0,
+        width: 100,
+        height: 100,
+    });
+    *camera.overscan_area.try_write().unwrap() = Some(CCDChipArea {
+        start_x: 100,
+        start_y: 0,
+        width: 20,
+        height: 100,
+    });
+    camera
+}
+
+fn area(start_x: u32, start_y: u32, width: u32, height: u32) -> CCDChipArea {
+    CCDChipArea {
+        start_x,
+        start_y,
+        width,
+        height,
+    }
+}
+
+#[rstest]
+#[case("true", 1, true, area(0, 0, 120, 100))]
+#[case("TRUE", 2, true, area(0, 0, 60, 50))]
+#[case("false", 1, false, area(0, 0, 100, 100))]
+#[case("", 1, false, area(10, 10, 50, 50))]
+#[tokio::test]
+async fn overscan_action(
+    #[case] parameters: &str,
+    #[case] binning: u8,
+    #[case] include_overscan: bool,
+    #[case] expected_roi: CCDChipArea,
+) {
+    //given
+    let camera = overscan_camera(MockCamera::new(), area(10, 10, 50, 50), binning);
+    //when
+    let res = camera
+        .action("Overscan".to_owned(), parameters.to_owned())
+        .await;
+    //then
+    let status: serde_json::Value = serde_json::from_str(&res.unwrap()).unwrap();
+    assert_eq!(status["include_overscan"], include_overscan);
+    assert_eq!(status["overscan_area"]["start_x"], 100);
+    assert_eq!(status["effective_area"]["width"], 100);
+    assert_eq!(status["image_overscan"], serde_json::Value::Null);
+    assert_eq!(*camera.intended_roi.read().await, Some(expected_roi));
+}
+
+#[tokio::test]
+async fn overscan_action_invalid() {
+    //given
+    let camera = overscan_camera(MockCamera::new(), area(0, 0, 100, 100), 1);
+    //when
+    let res = camera
+        .action("Overscan".to_owned(), "maybe".to_owned())
+        .await;
+    //then
+    assert_eq!(
+        res.unwrap_err().to_string(),
+        ASCOMError::invalid_value("Overscan parameter must be true, false or empty").to_string()
+    );
+}
+
+#[tokio::test]
+async fn overscan_action_without_overscan_area() {
+    //given
+    let camera = overscan_camera(MockCamera::new(), area(0, 0, 100, 100), 1);
+    *camera.overscan_area.write().await = None;
+    //when
+    let res = camera
+        .action("Overscan".to_owned(), "true".to_owned())
+        .await;
+    //then
+    assert_eq!(
+        res.unwrap_err().to_string(),
+        ASCOMError::invalid_operation("camera reports no overscan area").to_string()
+    );
+    assert!(!*camera.include_overscan.read().await);
+}
+
+#[tokio::test]
+async fn overscan_action_during_exposure() {
+    //given
+    let camera = overscan_camera(MockCamera::new(), area(0, 0, 100, 100), 1);
+    *camera.state.write().await = State::Exposing {
+        start: SystemTime::UNIX_EPOCH,
+        expected_duration_us: 1_000_000,
+        stop_tx: None,
+        done_rx: watch::channel(false).1,
+    };
+    //when
+    let res = camera
+        .action("Overscan".to_owned(), "true".to_owned())
+        .await;
+    //then
+    assert_eq!(
+        res.unwrap_err().to_string(),
+        ASCOMError::invalid_operation("cannot switch the overscan readout during an exposure")
+            .to_string()
+    );
+}
+
+#[tokio::test]
+async fn overscan_action_not_connected() {
+    not_connected! {action("Overscan".to_owned(), String::new())}
+}
+
+#[rstest]
+#[case::effective_area(false, area(0, 0, 100, 100), 1, true)]
+#[case::overscan_excluded(false, area(0, 0, 120, 100), 1, false)]
+#[case::overscan_excluded_binned(false, area(20, 0, 40, 50), 2, false)]
+#[case::whole_chip(true, area(0, 0, 120, 100), 1, true)]
+#[case::whole_chip_binned(true, area(20, 0, 40, 50), 2, true)]
+#[tokio::test]
+async fn start_exposure_checks_readable_area(
+    #[case] include_overscan: bool,
+    #[case] roi: CCDChipArea,
+    #[case] binning: u8,
+    #[case] readable: bool,
+) {
+    //given a camera that fails right after the ROI was accepted
+    let mut mock = MockCamera::new();
+    mock.expect_set_roi()
+        .times(if readable { 1 } else { 0 })
+        .withf(move |r| *r == roi)
+        .returning(|_| Err(eyre!("error")));
+    let camera = overscan_camera(mock, roi, binning);
+    *camera.include_overscan.write().await = include_overscan;
+    //when
+    let res = camera.start_exposure(Duration::from_secs(1), true).await;
+    //then
+    let expected = match readable {
+        true => ASCOMError::invalid_value("failed to set ROI"),
+        false => ASCOMError::invalid_value("ROI exceeds the readable area of the chip"),
+    };
+    assert_eq!(res.unwrap_err().to_string(), expected.to_string());
+}
+
+#[tokio::test]
+async fn frame_areas_follow_the_roi() {
+    //given
+    let camera = overscan_camera(MockCamera::new(), area(0, 0, 120, 100), 1);
+    //when the overscan is not read out
+    let res = camera
+        .frame_areas(area(80, 0, 40, 100), Binning::symmetric(1))
+        .await;
+    //then
+    assert_eq!(res, (None, None));
+    //when
+    *camera.include_overscan.write().await = true;
+    let res = camera
+        .frame_areas(area(40, 0, 20, 50), Binning::symmetric(2))
+        .await;
+    //then
+    assert_eq!(res, (Some(area(10, 0, 10, 50)), Some(area(0, 0, 10, 50))));
+}
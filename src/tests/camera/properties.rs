@@ -6,16 +6,22 @@ use super::*;
 async fn unimplmented_functions() {
     //given
     let mock = MockCamera::new();
-    let camera = new_camera(mock, MockCameraType::Untouched);
+    let mut camera = new_camera(mock, MockCameraType::Untouched);
+    camera.config = Arc::new(ConfigStore::new(
+        None,
+        crate::config::Config {
+            cameras: [(
+                "test-camera".to_owned(),
+                CameraConfig {
+                    can_stop_exposure: Some(false),
+                    ..Default::default()
+                },
+            )]
+            .into(),
+            ..Default::default()
+        },
+    ));
     //when
-    assert_eq!(
-        camera.electrons_per_adu().await.err().unwrap().to_string(),
-        ASCOMError::NOT_IMPLEMENTED.to_string()
-    );
-    assert_eq!(
-        camera.full_well_capacity().await.err().unwrap().to_string(),
-        ASCOMError::NOT_IMPLEMENTED.to_string()
-    );
     assert_eq!(
         camera.stop_exposure().await.err().unwrap().to_string(),
         ASCOMError::NOT_IMPLEMENTED.to_string()
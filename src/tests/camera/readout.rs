@@ -27,36 +27,415 @@ async fn readout_mode(#[case] get_readout_mode: Result<u32>, #[case] expected: A
     }
 }
 
+/// The geometry and ranges the SDK reports in a readout mode.
+#[derive(Debug, Clone, Copy)]
+struct Mode {
+    resolution: (u32, u32),
+    effective_area: CCDChipArea,
+    overscan_area: Option<CCDChipArea>,
+    bins: &'static [u8],
+    gain: (f64, f64),
+    offset: (f64, f64),
+    exposure: (f64, f64, f64),
+    speed: Option<(f64, f64, f64)>,
+}
+
+fn area(start_x: u32, start_y: u32, width: u32, height: u32) -> CCDChipArea {
+    CCDChipArea {
+        start_x,
+        start_y,
+        width,
+        height,
+    }
+}
+
+// modes with the geometry of a QHY600 and a QHY268, the 2CMS modes read half the resolution
+fn qhy600_photographic() -> Mode {
+    Mode {
+        resolution: (9600, 6422),
+        effective_area: area(24, 34, 9576, 6388),
+        overscan_area: Some(area(0, 0, 24, 6422)),
+        bins: &[1, 2, 3, 4],
+        gain: (0_f64, 200_f64),
+        offset: (0_f64, 255_f64),
+        exposure: (1_f64, 3_600_000_000_f64, 1_f64),
+        speed: None,
+    }
+}
+
+fn qhy600_high_gain() -> Mode {
+    Mode {
+        gain: (0_f64, 100_f64),
+        offset: (0_f64, 128_f64),
+        ..qhy600_photographic()
+    }
+}
+
+fn qhy600_2cms() -> Mode {
+    Mode {
+        resolution: (4800, 3211),
+        effective_area: area(12, 17, 4788, 3194),
+        overscan_area: None,
+        bins: &[1, 2],
+        gain: (0_f64, 60_f64),
+        offset: (0_f64, 100_f64),
+        exposure: (100_f64, 3_600_000_000_f64, 1_f64),
+        speed: Some((0_f64, 2_f64, 1_f64)),
+    }
+}
+
+fn qhy268_photographic() -> Mode {
+    Mode {
+        resolution: (6280, 4210),
+        effective_area: area(24, 0, 6256, 4210),
+        overscan_area: Some(area(0, 0, 24, 4210)),
+        bins: &[1, 2, 3, 4],
+        gain: (0_f64, 100_f64),
+        offset: (0_f64, 255_f64),
+        exposure: (1_f64, 3_600_000_000_f64, 1_f64),
+        speed: None,
+    }
+}
+
+fn qhy268_2cms() -> Mode {
+    Mode {
+        resolution: (3140, 2105),
+        effective_area: area(12, 0, 3128, 2105),
+        overscan_area: Some(area(0, 0, 12, 2105)),
+        bins: &[1, 2],
+        gain: (0_f64, 60_f64),
+        offset: (0_f64, 100_f64),
+        exposure: (100_f64, 3_600_000_000_f64, 1_f64),
+        speed: None,
+    }
+}
+
+fn ccd_info(resolution: (u32, u32)) -> CCDChipInfo {
+    CCDChipInfo {
+        chip_width: 36_f64,
+        chip_height: 24_f64,
+        image_width: resolution.0,
+        image_height: resolution.1,
+        pixel_width: 3.76_f64,
+        pixel_height: 3.76_f64,
+        bits_per_pixel: 16,
+    }
+}
+
+/// Expects the SDK calls of switching to `mode`, read at the hardware binning `read`.
+fn expect_switch(mock: &mut MockCamera, mode: Mode, read: u32) {
+    mock.expect_get_number_of_readout_modes()
+        .once()
+        .returning(|| Ok(4));
+    mock.expect_get_readout_mode_resolution()
+        .once()
+        .withf(|readout_mode| *readout_mode == 3)
+        .returning(move |_| Ok(mode.resolution));
+    mock.expect_get_readout_mode().once().returning(|| Ok(0));
+    mock.expect_set_readout_mode()
+        .once()
+        .withf(|readout_mode| *readout_mode == 3)
+        .returning(|_| Ok(()));
+    // the SDK reports the whole chip, the mode resolution decides the frame
+    mock.expect_get_ccd_info()
+        .once()
+        .returning(|| Ok(ccd_info((9600, 6422))));
+    expect_mode_areas(mock, mode, read);
+}
+
+/// Expects the SDK calls loading the areas and ranges of `mode`, read at the hardware binning `read`.
+fn expect_mode_areas(mock: &mut MockCamera, mode: Mode, read: u32) {
+    mock.expect_get_effective_area()
+        .once()
+        .returning(move || Ok(mode.effective_area));
+    mock.expect_get_overscan_area()
+        .once()
+        .returning(move || Ok(mode.overscan_area.unwrap_or(area(0, 0, 0, 0))));
+    mock.expect_is_control_available()
+        .returning(move |control| {
+            let available = match control {
+                Control::CamBin1x1mode => mode.bins.contains(&1),
+                Control::CamBin2x2mode => mode.bins.contains(&2),
+                Control::CamBin3x3mode => mode.bins.contains(&3),
+                Control::CamBin4x4mode => mode.bins.contains(&4),
+                Control::CamBin6x6mode => mode.bins.contains(&6),
+                Control::CamBin8x8mode => mode.bins.contains(&8),
+                Control::Speed => mode.speed.is_some(),
+                Control::Gain | Control::Offset => true,
+                _ => panic!("unexpected control {control:?}"),
+            };
+            available.then_some(0)
+        });
+    mock.expect_get_parameter_min_max_step()
+        .returning(move |control| match control {
+            Control::Speed => Ok(mode.speed.unwrap()),
+            Control::Exposure => Ok(mode.exposure),
+            Control::Gain => Ok((mode.gain.0, mode.gain.1, 1_f64)),
+            Control::Offset => Ok((mode.offset.0, mode.offset.1, 1_f64)),
+            _ => panic!("unexpected control {control:?}"),
+        });
+    mock.expect_set_bin_mode()
+        .once()
+        .withf(move |bin_x, bin_y| *bin_x == read && *bin_y == read)
+        .returning(|_, _| Ok(()));
+}
+
+/// A connected camera in `mode` at `bin` with the ROI `roi`, the full frame if `None`.
+async fn mode_camera(
+    mut mock: MockCamera,
+    mode: Mode,
+    bin: u8,
+    roi: Option<CCDChipArea>,
+) -> QhyccdCamera {
+    mock.expect_is_open().returning(|| Ok(true));
+    let camera = new_camera(mock, MockCameraType::Untouched);
+    *camera.ccd_info.write().await = Some(ccd_info(mode.resolution));
+    *camera.effective_area.write().await = Some(mode.effective_area);
+    *camera.overscan_area.write().await = mode.overscan_area;
+    *camera.binning.write().await = Binning::symmetric(bin);
+    *camera.valid_bins.write().await = Some(mode.bins.to_vec());
+    *camera.gain_min_max.write().await = Some(mode.gain);
+    *camera.offset_min_max.write().await = Some(mode.offset);
+    *camera.exposure_min_max_step.write().await = Some(mode.exposure);
+    *camera.readout_speed_min_max_step.write().await = mode.speed;
+    *camera.intended_roi.write().await = roi.or(camera.readable_area().await);
+    camera
+}
+
+#[rstest]
+#[case::qhy600_high_gain(
+    qhy600_high_gain(),
+    qhy600_photographic(),
+    1,
+    None,
+    1,
+    area(24, 34, 9576, 6388)
+)]
+#[case::qhy600_2cms_full_frame(
+    qhy600_2cms(),
+    qhy600_photographic(),
+    2,
+    None,
+    2,
+    area(6, 9, 2394, 1596)
+)]
+#[case::qhy600_2cms_subframe_clamped(
+    qhy600_2cms(),
+    qhy600_photographic(),
+    1,
+    Some(area(4000, 3000, 2000, 1000)),
+    1,
+    area(4000, 3000, 800, 211)
+)]
+#[case::qhy600_2cms_without_4x4(
+    qhy600_2cms(),
+    qhy600_photographic(),
+    4,
+    Some(area(100, 100, 500, 400)),
+    1,
+    area(400, 400, 2000, 1600)
+)]
+#[case::qhy600_back_to_photographic(
+    qhy600_photographic(),
+    qhy600_2cms(),
+    2,
+    None,
+    2,
+    area(12, 17, 4788, 3194)
+)]
+#[case::qhy268_2cms_subframe_outside(
+    qhy268_2cms(),
+    qhy268_photographic(),
+    1,
+    Some(area(5000, 3500, 500, 500)),
+    1,
+    area(12, 0, 3128, 2105)
+)]
+#[case::qhy268_back_to_photographic(
+    qhy268_photographic(),
+    qhy268_2cms(),
+    2,
+    None,
+    2,
+    area(12, 0, 3128, 2105)
+)]
+#[case::qhy268_subframe_kept(
+    qhy268_photographic(),
+    qhy268_2cms(),
+    1,
+    Some(area(100, 200, 300, 400)),
+    1,
+    area(100, 200, 300, 400)
+)]
+#[tokio::test]
+async fn set_readout_mode_reloads_mode_state(
+    #[case] to: Mode,
+    #[case] from: Mode,
+    #[case] bin: u8,
+    #[case] roi: Option<CCDChipArea>,
+    #[case] expected_bin: u8,
+    #[case] expected_roi: CCDChipArea,
+) {
+    //given
+    let mut mock = MockCamera::new();
+    expect_switch(&mut mock, to, u32::from(expected_bin));
+    let camera = mode_camera(mock, from, bin, roi).await;
+    //when
+    let res = camera.set_readout_mode(3).await;
+    //then
+    assert!(res.is_ok());
+    assert_eq!(*camera.ccd_info.read().await, Some(ccd_info(to.resolution)));
+    assert_eq!(*camera.effective_area.read().await, Some(to.effective_area));
+    assert_eq!(*camera.overscan_area.read().await, to.overscan_area);
+    assert_eq!(*camera.valid_bins.read().await, Some(to.bins.to_vec()));
+    assert_eq!(
+        *camera.binning.read().await,
+        Binning::symmetric(expected_bin)
+    );
+    assert_eq!(*camera.intended_roi.read().await, Some(expected_roi));
+    assert_eq!(*camera.gain_min_max.read().await, Some(to.gain));
+    assert_eq!(*camera.offset_min_max.read().await, Some(to.offset));
+    assert_eq!(
+        *camera.exposure_min_max_step.read().await,
+        Some(to.exposure)
+    );
+    assert_eq!(*camera.readout_speed_min_max_step.read().await, to.speed);
+}
+
+#[tokio::test]
+async fn set_readout_mode_without_overscan_stops_reading_it() {
+    //given
+    let mut mock = MockCamera::new();
+    expect_switch(&mut mock, qhy600_2cms(), 1);
+    let camera = mode_camera(mock, qhy600_photographic(), 1, None).await;
+    *camera.include_overscan.write().await = true;
+    *camera.intended_roi.write().await = camera.readable_area().await;
+    //when
+    let res = camera.set_readout_mode(3).await;
+    //then the whole chip of the previous mode becomes the effective area of the new one
+    assert!(res.is_ok());
+    assert!(!*camera.include_overscan.read().await);
+    assert_eq!(
+        *camera.intended_roi.read().await,
+        Some(area(12, 17, 4788, 3194))
+    );
+}
+
+#[tokio::test]
+async fn set_readout_mode_fail_reload_rolls_back() {
+    //given the state of the new mode cannot be loaded
+    let mut mock = MockCamera::new();
+    mock.expect_get_number_of_readout_modes()
+        .once()
+        .returning(|| Ok(4));
+    mock.expect_get_readout_mode().once().returning(|| Ok(0));
+    mock.expect_get_readout_mode_resolution()
+        .once()
+        .withf(|readout_mode| *readout_mode == 3)
+        .returning(|_| Ok((4800, 3211)));
+    mock.expect_get_readout_mode_resolution()
+        .once()
+        .withf(|readout_mode| *readout_mode == 0)
+        .returning(|_| Ok((9600, 6422)));
+    mock.expect_set_readout_mode()
+        .once()
+        .withf(|readout_mode| *readout_mode == 3)
+        .returning(|_| Ok(()));
+    mock.expect_set_readout_mode()
+        .once()
+        .withf(|readout_mode| *readout_mode == 0)
+        .returning(|_| Ok(()));
+    let mut loads = 0;
+    mock.expect_get_ccd_info().times(2).returning(move || {
+        loads += 1;
+        match loads {
+            1 => Err(eyre!("error")),
+            _ => Ok(ccd_info((9600, 6422))),
+        }
+    });
+    expect_mode_areas(&mut mock, qhy600_photographic(), 1);
+    let camera = mode_camera(mock, qhy600_photographic(), 1, None).await;
+    //when
+    let res = camera.set_readout_mode(3).await;
+    //then the camera is back in the previous mode
+    assert_eq!(
+        res.unwrap_err().to_string(),
+        ASCOMError::INVALID_OPERATION.to_string()
+    );
+    let from = qhy600_photographic();
+    assert_eq!(
+        *camera.ccd_info.read().await,
+        Some(ccd_info(from.resolution))
+    );
+    assert_eq!(
+        *camera.effective_area.read().await,
+        Some(from.effective_area)
+    );
+    assert_eq!(*camera.binning.read().await, Binning::symmetric(1));
+    assert_eq!(*camera.intended_roi.read().await, Some(from.effective_area));
+}
+
 #[tokio::test]
-async fn set_readout_mode_success() {
+async fn set_readout_mode_fail_roll_back_disconnects() {
+    //given neither the new nor the previous mode can be loaded
+    let mut mock = MockCamera::new();
+    mock.expect_get_number_of_readout_modes()
+        .once()
+        .returning(|| Ok(4));
+    mock.expect_get_readout_mode().once().returning(|| Ok(0));
+    mock.expect_get_readout_mode_resolution()
+        .times(2)
+        .returning(|_| Ok((4800, 3211)));
+    mock.expect_set_readout_mode()
+        .times(2)
+        .returning(|_| Ok(()));
+    mock.expect_get_ccd_info()
+        .times(2)
+        .returning(|| Err(eyre!("error")));
+    let (closed_tx, closed_rx) = oneshot::channel();
+    mock.expect_close().once().return_once(move || {
+        closed_tx.send(()).unwrap();
+        Ok(())
+    });
+    let camera = mode_camera(mock, qhy600_photographic(), 1, None).await;
+    let camera = Arc::new_cyclic(|this| QhyccdCamera {
+        this: this.clone(),
+        ..camera
+    });
+    //when
+    let res = camera.set_readout_mode(3).await;
+    //then the camera has to be connected again
+    assert_eq!(
+        res.unwrap_err().to_string(),
+        ASCOMError::INVALID_OPERATION.to_string()
+    );
+    closed_rx.await.unwrap();
+}
+
+#[tokio::test]
+async fn set_readout_mode_during_exposure() {
     //given
     let mut mock = MockCamera::new();
     mock.expect_get_number_of_readout_modes()
         .once()
-        .returning(|| Ok(3));
+        .returning(|| Ok(4));
     mock.expect_get_readout_mode_resolution()
         .once()
-        .returning(|_| Ok((1920, 1080)));
-    mock.expect_set_readout_mode().once().returning(|_| Ok(()));
+        .returning(|_| Ok((4800, 3211)));
     let camera = new_camera(
         mock,
-        MockCameraType::WithCCDInfo {
-            times: 1,
-            camera_ccd_info: Some(CCDChipInfo {
-                chip_width: 7.0,
-                chip_height: 5.0,
-                image_width: 1920,
-                image_height: 1080,
-                pixel_width: 2.9,
-                pixel_height: 2.9,
-                bits_per_pixel: 16,
-            }),
+        MockCameraType::WithStateExposing {
+            expected_duration: 1_000_000_f64,
         },
     );
     //when
-    let res = camera.set_readout_mode(0).await;
+    let res = camera.set_readout_mode(3).await;
     //then
-    assert!(res.is_ok());
+    assert_eq!(
+        res.unwrap_err().to_string(),
+        ASCOMError::invalid_operation("cannot switch the readout mode during an exposure")
+            .to_string()
+    );
 }
 
 #[tokio::test]
@@ -123,6 +502,7 @@ async fn set_readout_mode_fail_set() {
     mock.expect_get_readout_mode_resolution()
         .once()
         .returning(|_| Ok((1920, 1080)));
+    mock.expect_get_readout_mode().once().returning(|| Ok(1));
     mock.expect_set_readout_mode()
         .once()
         .returning(|_| Err(eyre!("error")));
@@ -0,0 +1,222 @@
+//! ADU scaling tests
+
+use super::*;
+
+fn scaling(adu_scaling: Option<AduScaling>) -> impl FnOnce(&mut CameraConfig) {
+    move |config| config.adu_scaling = adu_scaling
+}
+
+#[rstest]
+#[case::unset(16, None, Some(12_f64), 4096)]
+#[case::native(16, Some(AduScaling::Native), Some(12_f64), 4095)]
+#[case::native_full_range(16, Some(AduScaling::Native), Some(16_f64), 65535)]
+#[case::padded(16, Some(AduScaling::Padded), None, 65535)]
+#[case::unset_8_bit(8, None, None, 255)]
+#[case::native_8_bit(8, Some(AduScaling::Native), None, 255)]
+#[case::padded_8_bit(8, Some(AduScaling::Padded), None, 255)]
+#[tokio::test]
+async fn max_adu_follows_adu_scaling(
+    #[case] transfer_bits: u8,
+    #[case] adu_scaling: Option<AduScaling>,
+    #[case] actual_bits: Option<f64>,
+    #[case] expected: u32,
+) {
+    //given
+    let mut mock = MockCamera::new();
+    mock.expect_get_parameter()
+        .times(usize::from(actual_bits.is_some()))
+        .withf(|control| *control == qhyccd_rs::Control::OutputDataActualBits)
+        .returning(move |_| Ok(actual_bits.unwrap_or_default()));
+    let camera = new_camera(mock, MockCameraType::IsOpenTrue { times: 1 });
+    *camera.transfer_bits.write().await = transfer_bits;
+    camera
+        .config
+        .update_camera("test-camera", scaling(adu_scaling))
+        .await
+        .unwrap();
+    //when
+    let res = camera.max_adu().await;
+    //then
+    assert_eq!(res.unwrap(), expected);
+}
+
+#[rstest]
+#[case::native(Some(AduScaling::Native), Some(12_u32))]
+#[case::padded(Some(AduScaling::Padded), None)]
+#[case::unset(None, None)]
+#[tokio::test]
+async fn native_bits(#[case] adu_scaling: Option<AduScaling>, #[case] expected: Option<u32>) {
+    //given
+    let mut mock = MockCamera::new();
+    mock.expect_get_parameter()
+        .times(usize::from(expected.is_some()))
+        .withf(|control| *control == qhyccd_rs::Control::OutputDataActualBits)
+        .returning(|_| Ok(12_f64));
+    let camera = new_camera(mock, MockCameraType::Untouched);
+    camera
+        .config
+        .update_camera("test-camera", scaling(adu_scaling))
+        .await
+        .unwrap();
+    //when
+    let res = camera.native_bits().await;
+    //then
+    assert_eq!(res, expected);
+}
+
+#[rstest]
+#[case::native(Some(AduScaling::Native), 8_f64)]
+#[case::padded(Some(AduScaling::Padded), 0.50_f64)]
+#[case::unset(None, 0.50_f64)]
+#[tokio::test]
+async fn electrons_per_adu_follows_adu_scaling(
+    #[case] adu_scaling: Option<AduScaling>,
+    #[case] expected: f64,
+) {
+    //given a 12 bit camera whose conversion gain is known for the 16 bit output
+    let mut mock = MockCamera::new();
+    mock.expect_get_readout_mode().once().returning(|| Ok(0));
+    mock.expect_is_control_available()
+        .once()
+        .withf(|control| *control == qhyccd_rs::Control::Gain)
+        .returning(|_| Some(0));
+    mock.expect_get_parameter()
+        .once()
+        .withf(|control| *control == qhyccd_rs::Control::Gain)
+        .returning(|_| Ok(26_f64));
+    mock.expect_get_parameter()
+        .times(usize::from(adu_scaling == Some(AduScaling::Native)))
+        .withf(|control| *control == qhyccd_rs::Control::OutputDataActualBits)
+        .returning(|_| Ok(12_f64));
+    let mut camera = new_camera(mock, MockCameraType::IsOpenTrue { times: 1 });
+    camera.unique_id = "QHY600M-abc".to_owned();
+    camera
+        .config
+        .update_camera("QHY600M-abc", scaling(adu_scaling))
+        .await
+        .unwrap();
+    //when
+    let res = camera.electrons_per_adu().await;
+    //then native values are shifted down by 4 bits
+    assert!((res.unwrap() - expected).abs() < 1e-9);
+}
+
+#[tokio::test]
+async fn native_bits_without_actual_bits() {
+    //given the frames stay padded if the camera cannot tell its ADC depth
+    let mut mock = MockCamera::new();
+    mock.expect_get_parameter()
+        .once()
+        .returning(|_| Err(eyre!("error")));
+    let camera = new_camera(mock, MockCameraType::Untouched);
+    camera
+        .config
+        .update_camera("test-camera", scaling(Some(AduScaling::Native)))
+        .await
+        .unwrap();
+    //when
+    let res = camera.native_bits().await;
+    //then
+    assert_eq!(res, None);
+}
+
+#[tokio::test]
+async fn native_bits_with_8_bit_transfer() {
+    //given
+    let camera = new_camera(MockCamera::new(), MockCameraType::Untouched);
+    *camera.transfer_bits.write().await = 8;
+    camera
+        .config
+        .update_camera("test-camera", scaling(Some(AduScaling::Native)))
+        .await
+        .unwrap();
+    //when
+    let res = camera.native_bits().await;
+    //then
+    assert_eq!(res, None);
+}
+
+#[tokio::test]
+#[cfg_attr(miri, ignore)]
+async fn start_exposure_stores_native_values_no_miri() {
+    //given a 12 bit camera delivering MSB-aligned samples
+    let mut mock = MockCamera::new();
+    mock.expect_get_parameter()
+        .once()
+        .withf(|control| *control == qhyccd_rs::Control::OutputDataActualBits)
+        .returning(|_| Ok(12_f64));
+    mock.expect_set_parameter()
+        .once()
+        .withf(|control, _| *control == qhyccd_rs::Control::Exposure)
+        .returning(|_, _| Ok(()));
+    mock.expect_set_roi().once().returning(|_| Ok(()));
+    let mut clone_mock = MockCamera::new();
+    clone_mock.expect_get_single_frame().once().returning(|_| {
+        Ok(qhyccd_rs::ImageData {
+            data: [0x0010_u16, 0xfff0]
+                .iter()
+                .flat_map(|value| value.to_ne_bytes())
+                .collect(),
+            width: 2,
+            height: 1,
+            bits_per_pixel: 16,
+            channels: 1,
+        })
+    });
+    let mut start_clone = MockCamera::new();
+    start_clone
+        .expect_start_single_frame_exposure()
+        .once()
+        .returning(|| Ok(()));
+    let mut size_clone = MockCamera::new();
+    size_clone
+        .expect_get_image_size()
+        .once()
+        .returning(|| Ok(4_usize));
+    let inner_mocks = std::sync::Mutex::new(vec![size_clone, start_clone]);
+    clone_mock
+        .expect_clone()
+        .times(2)
+        .returning(move || inner_mocks.lock().unwrap().pop().unwrap());
+    let clone_mock = std::sync::Mutex::new(Some(clone_mock));
+    mock.expect_clone()
+        .times(2)
+        .returning(move || clone_mock.lock().unwrap().take().unwrap_or_default());
+    let camera = new_camera(
+        mock,
+        MockCameraType::WithBinningAndRoiAndCCDInfoUnlimited {
+            camera_roi: CCDChipArea {
+                start_x: 0,
+                start_y: 0,
+                width: 2,
+                height: 1,
+            },
+            camera_ccd_info: CCDChipInfo {
+                chip_width: 7_f64,
+                chip_height: 5_f64,
+                image_width: 2,
+                image_height: 1,
+                pixel_width: 2.9_f64,
+                pixel_height: 2.9_f64,
+                bits_per_pixel: 16,
+            },
+            camera_binning: 1_u8,
+        },
+    );
+    camera
+        .config
+        .update_camera("test-camera", scaling(Some(AduScaling::Native)))
+        .await
+        .unwrap();
+    //when
+    let res = camera.start_exposure(Duration::from_secs(1), true).await;
+    let timeout = tokio::time::Duration::from_secs(1);
+    let start = tokio::time::Instant::now();
+    while start.elapsed() < timeout && *camera.state.read().await != State::Idle {
+        tokio::time::sleep(tokio::time::Duration::from_millis(10)).await;
+    }
+    //then
+    assert!(res.is_ok());
+    let image: ImageArray = array![[[1_u16]], [[4095_u16]]].into();
+    assert_eq!(camera.image_array().await.unwrap(), image);
+}
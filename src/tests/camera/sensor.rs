@@ -1,6 +1,7 @@
 //! Sensor properties and readout mode tests
 
 use super::*;
+use crate::sensor::{SensorCurve, SensorPoint};
 
 #[rstest]
 #[case(Some(0), Some(qhyccd_rs::BayerMode::GBRG as u32), 2, Ok(0_u8), Ok(1_u8))]
@@ -95,6 +96,70 @@ async fn sensor_type_success_color(
     }
 }
 
+#[rstest]
+#[case::unbinned(Binning::symmetric(1), SensorType::Color)]
+#[case::binned_frames_stay_raw(Binning::symmetric(2), SensorType::RGGB)]
+#[tokio::test]
+async fn sensor_type_debayered(#[case] binning: Binning, #[case] expected: SensorType) {
+    //given
+    let mut mock = MockCamera::new();
+    mock.expect_is_control_available()
+        .once()
+        .withf(|control| *control == qhyccd_rs::Control::CamIsColor)
+        .return_once(|_| Some(0));
+    mock.expect_is_control_available()
+        .once()
+        .withf(|control| *control == qhyccd_rs::Control::CamColor)
+        .return_once(|_| Some(qhyccd_rs::BayerMode::RGGB as u32));
+    let mut camera = new_camera(mock, MockCameraType::IsOpenTrue { times: 1 });
+    camera.debayer = RwLock::new(Some(DebayerMethod::Gradient));
+    camera.binning = RwLock::new(binning);
+    //when
+    let res = camera.sensor_type().await;
+    //then
+    assert_eq!(res.unwrap(), expected);
+}
+
+#[tokio::test]
+async fn bayer_offset_debayered() {
+    //given
+    let mut camera = new_camera(MockCamera::new(), MockCameraType::IsOpenTrue { times: 2 });
+    camera.debayer = RwLock::new(Some(DebayerMethod::Bilinear));
+    camera.binning = RwLock::new(Binning::symmetric(1));
+    //when
+    let res_x = camera.bayer_offset_x().await;
+    let res_y = camera.bayer_offset_y().await;
+    //then
+    assert_eq!(
+        res_x.unwrap_err().to_string(),
+        ASCOMError::NOT_IMPLEMENTED.to_string()
+    );
+    assert_eq!(
+        res_y.unwrap_err().to_string(),
+        ASCOMError::NOT_IMPLEMENTED.to_string()
+    );
+}
+
+#[tokio::test]
+async fn bayer_offset_debayer_binned() {
+    //given binned frames, which are not debayered
+    let mut mock = MockCamera::new();
+    mock.expect_is_control_available()
+        .times(2)
+        .returning(|control| match control {
+            Control::CamIsColor => Some(0),
+            Control::CamColor => Some(qhyccd_rs::BayerMode::GBRG as u32),
+            _ => None,
+        });
+    let mut camera = new_camera(mock, MockCameraType::IsOpenTrue { times: 1 });
+    camera.debayer = RwLock::new(Some(DebayerMethod::Bilinear));
+    camera.binning = RwLock::new(Binning::symmetric(2));
+    //when
+    let res = camera.bayer_offset_x().await;
+    //then
+    assert_eq!(res.unwrap(), 0);
+}
+
 #[rstest]
 #[case(Ok(2), Ok(2_usize))]
 #[case(Err(eyre!("error")), Err(ASCOMError::INVALID_OPERATION))]
@@ -149,6 +214,9 @@ async fn set_readout_mode(
         .times(resolution_times)
         .withf(move |readout_mode| *readout_mode == 3)
         .return_once(move |_| resolution);
+    mock.expect_get_readout_mode()
+        .times(set_mode_times)
+        .returning(|| Ok(0));
     mock.expect_set_readout_mode()
         .times(set_mode_times)
         .withf(move |readout_mode| *readout_mode == 3)
@@ -346,3 +414,124 @@ async fn set_fast_readout(
         )
     }
 }
+
+#[rstest]
+#[case(qhyccd_rs::BayerMode::RGGB, 0, 0, qhyccd_rs::BayerMode::RGGB)]
+#[case(qhyccd_rs::BayerMode::RGGB, 1, 0, qhyccd_rs::BayerMode::GRBG)]
+#[case(qhyccd_rs::BayerMode::RGGB, 0, 1, qhyccd_rs::BayerMode::GBRG)]
+#[case(qhyccd_rs::BayerMode::RGGB, 1, 1, qhyccd_rs::BayerMode::BGGR)]
+#[case(qhyccd_rs::BayerMode::GBRG, 3, 0, qhyccd_rs::BayerMode::BGGR)]
+#[case(qhyccd_rs::BayerMode::GRBG, 2, 5, qhyccd_rs::BayerMode::BGGR)]
+#[case(qhyccd_rs::BayerMode::BGGR, 7, 9, qhyccd_rs::BayerMode::RGGB)]
+fn bayer_pattern_at_origin(
+    #[case] pattern: qhyccd_rs::BayerMode,
+    #[case] start_x: u32,
+    #[case] start_y: u32,
+    #[case] expected: qhyccd_rs::BayerMode,
+) {
+    assert_eq!(bayer_pattern_at(pattern, start_x, start_y), expected);
+}
+
+#[rstest]
+#[case("QHY600M-abc", 1, Ok(0), 26_f64, Ok(0.50_f64))]
+#[case("QHY600M-abc", 1, Ok(0), 13_f64, Ok(0.65_f64))]
+#[case("QHY600M-abc", 1, Ok(1), 0_f64, Ok(0.33_f64))]
+#[case("QHY294C-abc", 1, Ok(3), 800_f64, Ok(0.70_f64))]
+#[case("QHY600M-abc", 1, Ok(4), 0_f64, Err(ASCOMError::NOT_IMPLEMENTED))]
+#[case("test-camera", 0, Ok(0), 0_f64, Err(ASCOMError::NOT_IMPLEMENTED))]
+#[tokio::test]
+async fn electrons_per_adu(
+    #[case] unique_id: &str,
+    #[case] times: usize,
+    #[case] readout_mode: Result<u32>,
+    #[case] gain: f64,
+    #[case] expected: ASCOMResult<f64>,
+) {
+    //given
+    let mut mock = MockCamera::new();
+    mock.expect_get_readout_mode()
+        .times(times)
+        .return_once(move || readout_mode);
+    mock.expect_is_control_available()
+        .times(times)
+        .withf(|control| *control == qhyccd_rs::Control::Gain)
+        .returning(|_| Some(0));
+    mock.expect_get_parameter()
+        .times(times)
+        .withf(|control| *control == qhyccd_rs::Control::Gain)
+        .returning(move |_| Ok(gain));
+    let mut camera = new_camera(mock, MockCameraType::IsOpenTrue { times: 1 });
+    camera.unique_id = unique_id.to_owned();
+    //when
+    let res = camera.electrons_per_adu().await;
+    //then
+    match expected {
+        Ok(expected) => assert!((res.unwrap() - expected).abs() < 1e-9),
+        Err(expected) => assert_eq!(res.unwrap_err().to_string(), expected.to_string()),
+    }
+}
+
+#[tokio::test]
+async fn electrons_per_adu_with_8_bit_transfer() {
+    //given
+    let mut mock = MockCamera::new();
+    mock.expect_get_readout_mode().once().returning(|| Ok(0));
+    mock.expect_is_control_available()
+        .once()
+        .withf(|control| *control == qhyccd_rs::Control::Gain)
+        .returning(|_| Some(0));
+    mock.expect_get_parameter()
+        .once()
+        .withf(|control| *control == qhyccd_rs::Control::Gain)
+        .returning(|_| Ok(26_f64));
+    let mut camera = new_camera(mock, MockCameraType::IsOpenTrue { times: 1 });
+    camera.unique_id = "QHY600M-abc".to_owned();
+    *camera.transfer_bits.write().await = 8;
+    //when
+    let res = camera.electrons_per_adu().await;
+    //then each 8 bit ADU holds 256 ADU of the 16 bit output
+    assert!((res.unwrap() - 128_f64).abs() < 1e-9);
+}
+
+#[rstest]
+#[case(None, 41_500_f64)]
+#[case(
+    Some(vec![SensorCurve {
+        readout_mode: None,
+        points: vec![SensorPoint {
+            gain: 0_f64,
+            electrons_per_adu: 0.9_f64,
+            full_well_capacity: 58_000_f64,
+        }],
+    }]),
+    58_000_f64
+)]
+#[tokio::test]
+async fn full_well_capacity(#[case] measured: Option<Vec<SensorCurve>>, #[case] expected: f64) {
+    //given
+    let mut mock = MockCamera::new();
+    mock.expect_get_readout_mode().once().returning(|| Ok(0));
+    mock.expect_is_control_available()
+        .once()
+        .withf(|control| *control == qhyccd_rs::Control::Gain)
+        .returning(|_| Some(0));
+    mock.expect_get_parameter()
+        .once()
+        .withf(|control| *control == qhyccd_rs::Control::Gain)
+        .returning(|_| Ok(13_f64));
+    let mut camera = new_camera(mock, MockCameraType::IsOpenTrue { times: 1 });
+    camera.unique_id = "QHY600M-abc".to_owned();
+    if let Some(measured) = measured {
+        camera.config = Arc::new(ConfigStore::new(
+            None,
+            crate::config::Config {
+                sensors: [("QHY600M".to_owned(), measured)].into(),
+                ..Default::default()
+            },
+        ));
+    }
+    //when
+    let res = camera.full_well_capacity().await;
+    //then
+    assert!((res.unwrap() - expected).abs() < 1e-9);
+}
@@ -0,0 +1,336 @@
+//! Exposure sequence tests
+
+use super::*;
+use crate::sequence::{Progress, Sequence, SequenceFrameType, SequenceState};
+
+fn dark_frame() -> qhyccd_rs::ImageData {
+    qhyccd_rs::ImageData {
+        data: vec![1, 2, 3, 4, 5, 6],
+        width: 3,
+        height: 2,
+        bits_per_pixel: 8,
+        channels: 1,
+    }
+}
+
+/// The handle the exposure task works with, every clone reads out `dark_frame` after `readout`.
+fn exposing_device(readout: std::time::Duration) -> MockCamera {
+    let mut device = MockCamera::new();
+    device
+        .expect_start_single_frame_exposure()
+        .returning(|| Ok(()));
+    device.expect_get_image_size().returning(|| Ok(6_usize));
+    device.expect_get_single_frame().returning(move |_| {
+        std::thread::sleep(readout);
+        Ok(dark_frame())
+    });
+    device
+        .expect_abort_exposure_and_readout()
+        .returning(|| Ok(()));
+    device
+        .expect_clone()
+        .returning(move || exposing_device(readout));
+    device
+}
+
+/// A connected camera without shutter, gain or cooler that takes 3x2 frames.
+fn sequence_camera(directory: &str, readout: std::time::Duration) -> Arc<QhyccdCamera> {
+    let mut mock = MockCamera::new();
+    mock.expect_is_control_available().returning(|_| None);
+    mock.expect_get_readout_mode()
+        .returning(|| Err(eyre!("no readout mode")));
+    mock.expect_set_roi().returning(|_| Ok(()));
+    mock.expect_set_parameter()
+        .withf(|control, _| *control == Control::Exposure)
+        .returning(|_, _| Ok(()));
+    mock.expect_clone()
+        .returning(move || exposing_device(readout));
+    let directory = std::env::temp_dir().join(directory);
+    let _ = std::fs::remove_dir_all(&directory);
+    std::fs::create_dir_all(&directory).unwrap();
+    Arc::new_cyclic(|this| QhyccdCamera {
+        this: this.clone(),
+        fits_export: Some(FitsExport::new(directory)),
+        ..new_camera(
+            mock,
+            MockCameraType::WithBinningAndRoiAndCCDInfoUnlimited {
+                camera_roi: CCDChipArea {
+                    start_x: 0,
+                    start_y: 0,
+                    width: 3,
+                    height: 2,
+                },
+                camera_ccd_info: CCDChipInfo {
+                    chip_width: 7_f64,
+                    chip_height: 5_f64,
+                    image_width: 3,
+                    image_height: 2,
+                    pixel_width: 2.9_f64,
+                    pixel_height: 2.9_f64,
+                    bits_per_pixel: 8,
+                },
+                camera_binning: 1_u8,
+            },
+        )
+    })
+}
+
+/// Waits up to 2s for the sequence to leave the running state.
+async fn finished(camera: &QhyccdCamera) -> Progress {
+    let start = tokio::time::Instant::now();
+    while start.elapsed() < tokio::time::Duration::from_secs(2) {
+        let progress = camera.sequencer.progress().await;
+        if progress.state != SequenceState::Running {
+            return progress;
+        }
+        tokio::time::sleep(tokio::time::Duration::from_millis(10)).await;
+    }
+    camera.sequencer.progress().await
+}
+
+#[test]
+fn parse_sequence() {
+    //when
+    let sequence: Sequence = serde_json::from_str(
+        r#"{"entries": [
+            {"count": 10, "duration": 120, "gain": 26, "offset": 30, "bin": 2,
+             "roi": {"start_x": 10, "start_y": 20, "num_x": 100, "num_y": 50},
+             "filter": {"wheel": "CFW=QHY600M-abc", "position": 2}},
+            {"count": 20, "frame_type": "bias"}
+        ]}"#,
+    )
+    .unwrap();
+    //then
+    assert_eq!(sequence.entries.len(), 2);
+    let light = &sequence.entries[0];
+    assert_eq!(light.frame_type, SequenceFrameType::Light);
+    assert_eq!(light.duration, 120_f64);
+    assert_eq!(light.roi.unwrap().num_x, 100);
+    assert_eq!(light.filter.as_ref().unwrap().wheel, "CFW=QHY600M-abc");
+    let bias = &sequence.entries[1];
+    assert_eq!(bias.frame_type, SequenceFrameType::Bias);
+    assert_eq!(
+        (bias.gain, bias.bin, bias.filter.as_ref()),
+        (None, None, None)
+    );
+    assert!(
+        serde_json::from_str::<Sequence>(r#"{"entries": [{"count": 1, "exposure": 5}]}"#).is_err()
+    );
+}
+
+#[rstest]
+#[case(r#"{"entries": []}"#, true, "sequence has no entries")]
+#[case(
+    r#"{"entries": [{"count": 0}]}"#,
+    true,
+    "entry 0: count must be at least 1"
+)]
+#[case(
+    r#"{"entries": [{"count": 1}, {"count": 1, "duration": -1}]}"#,
+    true,
+    "entry 1: duration must be a number of seconds"
+)]
+#[case(
+    r#"{"entries": [{"count": 1, "filter": {"wheel": "CFW=QHY600M-def", "position": 1}}]}"#,
+    true,
+    "entry 0: unknown filter wheel CFW=QHY600M-def"
+)]
+#[case(
+    r#"{"entries": [{"count": 1}]}"#,
+    false,
+    "no FITS output directory configured"
+)]
+#[case(r#"{"entries": [{"count": 1}"#, true, "not a valid sequence")]
+#[tokio::test]
+async fn start_sequence_invalid(
+    #[case] parameters: &str,
+    #[case] fits_export: bool,
+    #[case] expected: &str,
+) {
+    //given
+    let mut camera = new_camera(MockCamera::new(), MockCameraType::IsOpenTrue { times: 1 });
+    camera.fits_export = fits_export.then(|| FitsExport::new(std::env::temp_dir()));
+    //when
+    let res = camera
+        .action("StartSequence".to_owned(), parameters.to_owned())
+        .await;
+    //then
+    assert!(res.unwrap_err().to_string().contains(expected));
+    assert_eq!(camera.sequencer.progress().await.state, SequenceState::Idle);
+}
+
+#[tokio::test]
+async fn start_sequence_not_connected() {
+    //given
+    let camera = new_camera(MockCamera::new(), MockCameraType::IsOpenFalse { times: 1 });
+    //when
+    let res = camera
+        .action(
+            "StartSequence".to_owned(),
+            r#"{"entries": [{"count": 1}]}"#.to_owned(),
+        )
+        .await;
+    //then
+    assert_eq!(
+        res.unwrap_err().to_string(),
+        ASCOMError::NOT_CONNECTED.to_string()
+    );
+}
+
+#[tokio::test]
+async fn start_exposure_refused_during_sequence() {
+    //given
+    let camera = new_camera(MockCamera::new(), MockCameraType::Untouched);
+    camera.sequencer.progress.write().await.state = SequenceState::Running;
+    //when
+    let res = camera.start_exposure(Duration::from_secs(1), true).await;
+    //then
+    assert_eq!(
+        res.unwrap_err().to_string(),
+        ASCOMError::invalid_operation("a sequence is running, abort it first").to_string()
+    );
+}
+
+#[tokio::test]
+#[cfg_attr(miri, ignore)]
+async fn run_dark_sequence_no_miri() {
+    //given
+    let camera = sequence_camera(
+        "qhyccd-alpaca-sequence-darks",
+        std::time::Duration::from_millis(5),
+    );
+    //when
+    let res = camera
+        .action(
+            "StartSequence".to_owned(),
+            r#"{"entries": [{"count": 2, "duration": 0.01, "frame_type": "dark"}]}"#.to_owned(),
+        )
+        .await;
+    let progress = finished(&camera).await;
+    //then
+    assert_eq!(res.unwrap(), "");
+    assert_eq!(progress.state, SequenceState::Completed, "{progress:?}");
+    assert_eq!((progress.entry, progress.frame), (Some(0), 2));
+    assert_eq!((progress.frames_done, progress.frames_total), (2, 2));
+    let last_file = progress.last_file.unwrap();
+    assert!(last_file.exists());
+    assert!(
+        last_file
+            .file_name()
+            .unwrap()
+            .to_string_lossy()
+            .contains("_Dark_")
+    );
+    let status: serde_json::Value = serde_json::from_str(
+        &camera
+            .action("SequenceStatus".to_owned(), String::new())
+            .await
+            .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(status["state"], "completed");
+    assert_eq!(status["frames_done"], 2);
+}
+
+#[tokio::test]
+#[cfg_attr(miri, ignore)]
+async fn abort_sequence_no_miri() {
+    //given a sequence whose frames take 200ms to read out
+    let camera = sequence_camera(
+        "qhyccd-alpaca-sequence-abort",
+        std::time::Duration::from_millis(200),
+    );
+    camera
+        .action(
+            "StartSequence".to_owned(),
+            r#"{"entries": [{"count": 5, "duration": 0.01}]}"#.to_owned(),
+        )
+        .await
+        .unwrap();
+    tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+    //when another sequence is started
+    let res = camera
+        .action(
+            "StartSequence".to_owned(),
+            r#"{"entries": [{"count": 1}]}"#.to_owned(),
+        )
+        .await;
+    //then
+    assert_eq!(
+        res.unwrap_err().to_string(),
+        ASCOMError::invalid_operation("a sequence is already running").to_string()
+    );
+    //when
+    let res = camera
+        .action("AbortSequence".to_owned(), String::new())
+        .await;
+    //then
+    assert!(res.is_ok());
+    let progress = camera.sequencer.progress().await;
+    assert_eq!(progress.state, SequenceState::Aborted);
+    assert_eq!(progress.frames_done, 0);
+    assert_eq!(*camera.state.read().await, State::Idle);
+}
+
+#[tokio::test]
+#[cfg_attr(miri, ignore)]
+async fn sequence_fails_on_unknown_bin_no_miri() {
+    //given
+    let camera = sequence_camera(
+        "qhyccd-alpaca-sequence-bin",
+        std::time::Duration::from_millis(5),
+    );
+    *camera.valid_bins.write().await = Some(vec![1]);
+    //when
+    camera
+        .action(
+            "StartSequence".to_owned(),
+            r#"{"entries": [{"count": 1, "duration": 0.01}, {"count": 1, "bin": 3}]}"#.to_owned(),
+        )
+        .await
+        .unwrap();
+    let progress = finished(&camera).await;
+    //then the first entry is done, the second fails before taking a frame
+    assert_eq!(progress.state, SequenceState::Failed);
+    assert_eq!((progress.entry, progress.frames_done), (Some(1), 1));
+    let error = progress.error.unwrap();
+    assert!(error.starts_with("entry 1: "));
+    assert!(error.contains("bin value must be one of the valid bins"));
+}
+
+#[tokio::test]
+#[cfg_attr(miri, ignore)]
+async fn sequence_gain_picks_a_preset_no_miri() {
+    //given a camera with a single gain preset
+    let camera = sequence_camera(
+        "qhyccd-alpaca-sequence-preset",
+        std::time::Duration::from_millis(5),
+    );
+    let camera = Arc::new_cyclic(|this| QhyccdCamera {
+        this: this.clone(),
+        presets: Some(presets::Presets {
+            gains: vec![presets::GainPreset {
+                name: "Unity".to_owned(),
+                gain: 26,
+                readout_mode: None,
+            }],
+            offsets: vec![],
+        }),
+        ..Arc::into_inner(camera).unwrap()
+    });
+    //when the entry asks for a preset that does not exist
+    camera
+        .action(
+            "StartSequence".to_owned(),
+            r#"{"entries": [{"count": 1, "duration": 0.01, "gain": 26}]}"#.to_owned(),
+        )
+        .await
+        .unwrap();
+    let progress = finished(&camera).await;
+    //then
+    assert_eq!(progress.state, SequenceState::Failed);
+    assert_eq!(
+        progress.error.unwrap(),
+        format!("entry 0: {}", ASCOMError::INVALID_VALUE)
+    );
+}
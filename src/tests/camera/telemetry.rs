@@ -0,0 +1,124 @@
+//! Environment sensor tests
+
+use super::*;
+
+/// A connected camera with `sensors` that read `readings` as (control, value), `None` fails.
+fn telemetry_camera(sensors: Vec<Sensor>, readings: Vec<(Control, Option<f64>)>) -> QhyccdCamera {
+    let mut mock = MockCamera::new();
+    for (control, reading) in readings {
+        mock.expect_get_parameter()
+            .once()
+            .withf(move |c| *c == control)
+            .returning(move |_| reading.ok_or_else(|| eyre!("error")));
+    }
+    let camera = new_camera(mock, MockCameraType::IsOpenTrue { times: 1 });
+    *camera.sensors.try_write().unwrap() = sensors;
+    camera
+}
+
+#[test]
+fn sensor_from_action() {
+    assert_eq!(Sensor::from_action("humidity"), Some(Sensor::Humidity));
+    assert_eq!(Sensor::from_action("PRESSURE"), Some(Sensor::Pressure));
+    assert_eq!(Sensor::from_action("ChamberTemperature"), None);
+    assert_eq!(Sensor::from_action("Telemetry"), None);
+}
+
+#[tokio::test]
+async fn supported_actions_list_found_sensors() {
+    //given
+    let camera = new_camera(MockCamera::new(), MockCameraType::Untouched);
+    *camera.sensors.write().await = vec![Sensor::Humidity, Sensor::Pressure];
+    //when
+    let res = camera.supported_actions().await.unwrap();
+    //then
+    assert!(res.contains(&"Telemetry".to_owned()));
+    assert!(res.contains(&"Humidity".to_owned()));
+    assert!(res.contains(&"Pressure".to_owned()));
+}
+
+#[rstest]
+#[case("Humidity", Control::CamHumidity, 41.5_f64, r#"{"humidity":41.5}"#)]
+#[case("pressure", Control::CamPressure, 1012.5_f64, r#"{"pressure":1012.5}"#)]
+#[tokio::test]
+async fn sensor_action(
+    #[case] action: &str,
+    #[case] control: Control,
+    #[case] value: f64,
+    #[case] expected: &str,
+) {
+    //given
+    let camera = telemetry_camera(Sensor::ALL.to_vec(), vec![(control, Some(value))]);
+    //when
+    let res = camera.action(action.to_owned(), String::new()).await;
+    //then
+    assert_eq!(res.unwrap(), expected);
+}
+
+#[tokio::test]
+async fn telemetry_action() {
+    //given
+    let camera = telemetry_camera(
+        vec![Sensor::Humidity, Sensor::Pressure],
+        vec![
+            (Control::CamHumidity, Some(41.5_f64)),
+            (Control::CamPressure, Some(1012.5_f64)),
+        ],
+    );
+    //when
+    let res = camera.action("Telemetry".to_owned(), String::new()).await;
+    //then
+    let telemetry: serde_json::Value = serde_json::from_str(&res.unwrap()).unwrap();
+    assert_eq!(
+        telemetry,
+        serde_json::json!({"humidity": 41.5, "pressure": 1012.5})
+    );
+}
+
+#[tokio::test]
+async fn telemetry_action_without_sensors() {
+    //given
+    let camera = telemetry_camera(Vec::new(), Vec::new());
+    //when
+    let res = camera.action("Telemetry".to_owned(), String::new()).await;
+    //then
+    assert_eq!(res.unwrap(), "{}");
+}
+
+#[tokio::test]
+async fn sensor_action_not_available() {
+    //given
+    let camera = telemetry_camera(vec![Sensor::Humidity], Vec::new());
+    //when
+    let res = camera.action("Pressure".to_owned(), String::new()).await;
+    //then
+    assert_eq!(
+        res.unwrap_err().to_string(),
+        ASCOMError::ACTION_NOT_IMPLEMENTED.to_string()
+    );
+}
+
+#[tokio::test]
+async fn telemetry_action_fail() {
+    //given
+    let camera = telemetry_camera(
+        vec![Sensor::Humidity, Sensor::Pressure],
+        vec![
+            (Control::CamHumidity, Some(41.5_f64)),
+            (Control::CamPressure, None),
+        ],
+    );
+    //when
+    let res = camera.action("Telemetry".to_owned(), String::new()).await;
+    //then
+    assert_eq!(
+        res.unwrap_err().to_string(),
+        ASCOMError::INVALID_OPERATION.to_string()
+    );
+}
+
+#[tokio::test]
+async fn telemetry_not_connected() {
+    not_connected! {action("Telemetry".to_owned(), String::new())}
+    not_connected! {action("Humidity".to_owned(), String::new())}
+}
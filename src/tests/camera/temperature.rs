@@ -2,16 +2,25 @@
 
 use super::*;
 
+/// A cooler controller in `state` regulating to `target`, with an ambient of 20°C.
+fn cooler(state: CoolerState, target: Option<f64>, ramp: CoolerRamp) -> Cooler {
+    let mut cooler = Cooler::new(ramp);
+    cooler.state = state;
+    cooler.target = target;
+    cooler.ambient = 20_f64;
+    cooler
+}
+
 #[rstest]
-#[case(true, Ok(1_f64), 1, Ok(true))]
-#[case(true, Ok(0_f64), 1, Ok(false))]
-#[case(true, Err(eyre!("error")), 1, Err(ASCOMError::INVALID_VALUE))]
-#[case(false, Ok(1_f64), 0, Err(ASCOMError::NOT_IMPLEMENTED))]
+#[case(true, CoolerState::Regulating, Ok(true))]
+#[case(true, CoolerState::Ramping { set_point: 0_f64 }, Ok(true))]
+#[case(true, CoolerState::Off, Ok(false))]
+#[case(true, CoolerState::WarmingUp { set_point: 0_f64 }, Ok(false))]
+#[case(false, CoolerState::Off, Err(ASCOMError::NOT_IMPLEMENTED))]
 #[tokio::test]
 async fn cooler_on(
     #[case] is_control_available: bool,
-    #[case] get_parameter: Result<f64>,
-    #[case] get_parameter_times: usize,
+    #[case] state: CoolerState,
     #[case] expected: ASCOMResult<bool>,
 ) {
     //given
@@ -20,16 +29,18 @@ async fn cooler_on(
         .once()
         .withf(|control| *control == qhyccd_rs::Control::Cooler)
         .returning(move |_| if is_control_available { Some(0) } else { None });
-    mock.expect_get_parameter()
-        .times(get_parameter_times)
-        .withf(|control| *control == qhyccd_rs::Control::CurPWM)
-        .return_once(move |_| get_parameter);
-    let camera = new_camera(mock, MockCameraType::IsOpenTrue { times: 1 });
+    let camera = new_camera(
+        mock,
+        MockCameraType::WithCooler {
+            times: 1,
+            cooler: cooler(state, None, CoolerRamp::default()),
+        },
+    );
     //when
     let res = camera.cooler_on().await;
     //then
     if res.is_ok() {
-        assert!(expected.is_ok())
+        assert_eq!(res.unwrap(), expected.unwrap())
     } else {
         assert_eq!(
             res.err().unwrap().to_string(),
@@ -39,19 +50,54 @@ async fn cooler_on(
 }
 
 #[rstest]
-#[case(true, true, true, 0, Ok(()))]
-#[case(false, false, true, 0, Ok(()))]
-#[case(true, false, true, 1, Ok(()))]
-#[case(false, true, true, 1, Ok(()))]
-#[case(true, false, false, 1, Err(ASCOMError::INVALID_OPERATION))]
-#[case(false, true, false, 1, Err(ASCOMError::INVALID_OPERATION))]
+#[case::already_on(CoolerState::Regulating, None, true, 0, Ok(()), 0, 0, Ok(()), CoolerState::Regulating)]
+#[case::already_off(CoolerState::Off, None, false, 0, Ok(()), 0, 0, Ok(()), CoolerState::Off)]
+#[case::on(CoolerState::Off, None, true, 1, Ok(()), 1, 1, Ok(()), CoolerState::Regulating)]
+#[case::on_ramp(
+    CoolerState::Off,
+    Some(1_f64),
+    true,
+    1,
+    Ok(()),
+    1,
+    1,
+    Ok(()),
+    CoolerState::Ramping { set_point: 20_f64 }
+)]
+#[case::on_fails(
+    CoolerState::Off,
+    None,
+    true,
+    1,
+    Err(eyre!("error")),
+    1,
+    0,
+    Err(ASCOMError::INVALID_OPERATION),
+    CoolerState::Off
+)]
+#[case::off(CoolerState::Regulating, None, false, 0, Ok(()), 1, 0, Ok(()), CoolerState::Off)]
+#[case::off_warm_up(
+    CoolerState::Regulating,
+    Some(1_f64),
+    false,
+    0,
+    Ok(()),
+    0,
+    1,
+    Ok(()),
+    CoolerState::WarmingUp { set_point: -10_f64 }
+)]
 #[tokio::test]
 async fn set_cooler_on(
-    #[case] is_cooler_on: bool,
+    #[case] state: CoolerState,
+    #[case] rate: Option<f64>,
     #[case] cooler_on: bool,
-    #[case] set_manualpwm_ok: bool,
-    #[case] set_manualpwm_times: usize,
+    #[case] cur_temp_times: usize,
+    #[case] set_parameter: Result<()>,
+    #[case] set_parameter_times: usize,
+    #[case] clone_times: usize,
     #[case] expected: ASCOMResult<()>,
+    #[case] expected_state: CoolerState,
 ) {
     //given
     let mut mock = MockCamera::new();
@@ -60,24 +106,32 @@ async fn set_cooler_on(
         .withf(|control| *control == qhyccd_rs::Control::Cooler)
         .returning(move |_| Some(0));
     mock.expect_get_parameter()
-        .once()
-        .withf(|control| *control == qhyccd_rs::Control::CurPWM)
-        .returning(move |_| if is_cooler_on { Ok(1_f64) } else { Ok(0_f64) });
+        .times(cur_temp_times)
+        .withf(|control| *control == qhyccd_rs::Control::CurTemp)
+        .returning(|_| Ok(20_f64));
+    let (control, value) = match (cooler_on, rate) {
+        (true, None) => (qhyccd_rs::Control::Cooler, -10_f64),
+        (true, Some(_)) => (qhyccd_rs::Control::Cooler, 20_f64),
+        (false, _) => (qhyccd_rs::Control::ManualPWM, 0_f64),
+    };
     mock.expect_set_parameter()
-        .times(set_manualpwm_times)
-        .withf(move |control, temp| {
-            *control == qhyccd_rs::Control::ManualPWM
-                && (*temp - if cooler_on { 1_f64 } else { 0_f64 } / 100_f64 * 255_f64).abs()
-                    < f64::EPSILON
-        })
-        .returning(move |_, _| {
-            if set_manualpwm_ok {
-                Ok(())
-            } else {
-                Err(eyre!("error"))
-            }
-        });
-    let camera = new_camera(mock, MockCameraType::IsOpenTrue { times: 1 });
+        .times(set_parameter_times)
+        .withf(move |c, v| *c == control && (*v - value).abs() < f64::EPSILON)
+        .return_once(move |_, _| set_parameter);
+    mock.expect_clone()
+        .times(clone_times)
+        .returning(MockCamera::new);
+    let ramp = CoolerRamp {
+        cool_down: rate,
+        warm_up: rate,
+    };
+    let camera = new_camera(
+        mock,
+        MockCameraType::WithCooler {
+            times: 1,
+            cooler: cooler(state, Some(-10_f64), ramp),
+        },
+    );
     //when
     let res = camera.set_cooler_on(cooler_on).await;
     //then
@@ -89,6 +143,117 @@ async fn set_cooler_on(
             expected.err().unwrap().to_string()
         )
     }
+    assert_eq!(camera.cooler.read().await.state, expected_state);
+}
+
+#[tokio::test(start_paused = true)]
+async fn cooler_ramps_down_in_background() {
+    //given 12°C/min moves the set-point 1°C per tick
+    let mut mock = MockCamera::new();
+    mock.expect_is_control_available()
+        .once()
+        .withf(|control| *control == qhyccd_rs::Control::Cooler)
+        .returning(|_| Some(0));
+    mock.expect_get_parameter()
+        .once()
+        .withf(|control| *control == qhyccd_rs::Control::CurTemp)
+        .returning(|_| Ok(20_f64));
+    mock.expect_set_parameter()
+        .once()
+        .withf(|control, v| {
+            *control == qhyccd_rs::Control::Cooler && (*v - 20_f64).abs() < f64::EPSILON
+        })
+        .returning(|_, _| Ok(()));
+    mock.expect_clone().once().returning(|| {
+        let mut task_mock = MockCamera::new();
+        let mut seq = mockall::Sequence::new();
+        for set_point in [19_f64, 18_f64, 17_f64] {
+            task_mock
+                .expect_set_parameter()
+                .once()
+                .in_sequence(&mut seq)
+                .withf(move |control, v| {
+                    *control == qhyccd_rs::Control::Cooler && (*v - set_point).abs() < f64::EPSILON
+                })
+                .returning(|_, _| Ok(()));
+        }
+        task_mock
+    });
+    let ramp = CoolerRamp {
+        cool_down: Some(12_f64),
+        warm_up: None,
+    };
+    let camera = new_camera(
+        mock,
+        MockCameraType::WithCooler {
+            times: 1,
+            cooler: cooler(CoolerState::Off, Some(17_f64), ramp),
+        },
+    );
+    //when
+    camera.set_cooler_on(true).await.unwrap();
+    tokio::time::sleep(crate::cooler::TICK * 5 / 2).await;
+    //then
+    assert_eq!(
+        camera.cooler.read().await.state,
+        CoolerState::Ramping { set_point: 18_f64 }
+    );
+    tokio::time::sleep(crate::cooler::TICK * 2).await;
+    assert_eq!(camera.cooler.read().await.state, CoolerState::Regulating);
+}
+
+#[tokio::test(start_paused = true)]
+async fn disconnect_warms_up_before_closing() {
+    //given a sensor at 18°C that warms up 1°C per tick to its ambient of 20°C
+    let mock = MockCamera::new();
+    let mut task_mock = MockCamera::new();
+    let mut seq = mockall::Sequence::new();
+    task_mock
+        .expect_set_parameter()
+        .once()
+        .in_sequence(&mut seq)
+        .withf(|control, v| {
+            *control == qhyccd_rs::Control::Cooler && (*v - 19_f64).abs() < f64::EPSILON
+        })
+        .returning(|_, _| Ok(()));
+    task_mock
+        .expect_set_parameter()
+        .once()
+        .in_sequence(&mut seq)
+        .withf(|control, v| *control == qhyccd_rs::Control::ManualPWM && v.abs() < f64::EPSILON)
+        .returning(|_, _| Ok(()));
+    task_mock
+        .expect_close()
+        .once()
+        .in_sequence(&mut seq)
+        .returning(|| Ok(()));
+    let ramp = CoolerRamp {
+        cool_down: None,
+        warm_up: Some(12_f64),
+    };
+    let camera = new_camera(
+        mock,
+        MockCameraType::WithCooler {
+            times: 1,
+            cooler: cooler(CoolerState::Regulating, Some(18_f64), ramp),
+        },
+    );
+    camera.cooler.write().await.task = Some(tokio::spawn(crate::cooler::run(
+        task_mock,
+        camera.cooler.clone(),
+    )));
+    //when
+    camera.set_connected(false).await.unwrap();
+    //then the camera reports disconnected right away, but stays open until warmed up
+    assert!(!camera.connected().await.unwrap());
+    assert_eq!(
+        camera.cooler.read().await.state,
+        CoolerState::WarmingUp { set_point: 18_f64 }
+    );
+    let task = camera.cooler.write().await.task.take().unwrap();
+    task.await.unwrap();
+    assert_eq!(camera.cooler.read().await.state, CoolerState::Off);
+    assert!(!camera.cooler.read().await.close_when_off);
 }
 
 #[rstest]
@@ -232,14 +397,16 @@ async fn set_ccd_temperature(
 }
 
 #[rstest]
-#[case(true, 1, 1, -2_f64, Ok(()), 1, Ok(()))]
-#[case(true, 0, 0, -300_f64, Ok(()), 0, Err(ASCOMError::INVALID_VALUE))]
-#[case(true, 0, 0, 81_f64, Ok(()), 0, Err(ASCOMError::INVALID_VALUE))]
-#[case(true, 1, 1, -2_f64, Err(eyre!("error")), 1, Err(ASCOMError::INVALID_OPERATION))]
-#[case(false, 1, 1, -2_f64, Ok(()), 0, Err(ASCOMError::NOT_IMPLEMENTED))]
+#[case(true, CoolerState::Regulating, 1, 1, -2_f64, Ok(()), 1, Ok(()))]
+#[case(true, CoolerState::Off, 1, 1, -2_f64, Ok(()), 0, Ok(()))]
+#[case(true, CoolerState::Regulating, 0, 0, -300_f64, Ok(()), 0, Err(ASCOMError::INVALID_VALUE))]
+#[case(true, CoolerState::Regulating, 0, 0, 81_f64, Ok(()), 0, Err(ASCOMError::INVALID_VALUE))]
+#[case(true, CoolerState::Regulating, 1, 1, -2_f64, Err(eyre!("error")), 1, Err(ASCOMError::INVALID_OPERATION))]
+#[case(false, CoolerState::Off, 1, 1, -2_f64, Ok(()), 0, Err(ASCOMError::NOT_IMPLEMENTED))]
 #[tokio::test]
 async fn set_set_ccd_temperature(
     #[case] has_cooler: bool,
+    #[case] state: CoolerState,
     #[case] is_control_avaiable_times: usize,
     #[case] is_open_times: usize,
     #[case] temperature: f64,
@@ -259,10 +426,12 @@ async fn set_set_ccd_temperature(
             *control == qhyccd_rs::Control::Cooler && (*temp - temperature).abs() < f64::EPSILON
         })
         .return_once(move |_, _| set_parameter);
+    mock.expect_clone().returning(MockCamera::new);
     let camera = new_camera(
         mock,
-        MockCameraType::IsOpenTrue {
+        MockCameraType::WithCooler {
             times: is_open_times,
+            cooler: cooler(state, Some(-10_f64), CoolerRamp::default()),
         },
     );
     //when
@@ -270,10 +439,12 @@ async fn set_set_ccd_temperature(
     //then
     if res.is_ok() {
         assert!(expected.is_ok());
+        assert_eq!(camera.cooler.read().await.target, Some(temperature));
     } else {
         assert_eq!(
             res.err().unwrap().to_string(),
             expected.err().unwrap().to_string()
-        )
+        );
+        assert_eq!(camera.cooler.read().await.target, Some(-10_f64));
     }
 }
@@ -0,0 +1,179 @@
+//! Transfer bit depth tests
+
+use super::*;
+
+/// Expects 8 bit transfer to be offered and `transfer_bits` to be set once.
+fn expect_transfer_bits(mock: &mut MockCamera, transfer_bits: f64) {
+    mock.expect_is_control_available()
+        .withf(|control| *control == qhyccd_rs::Control::Cam8bits)
+        .returning(|_| Some(0));
+    mock.expect_set_if_available()
+        .once()
+        .withf(move |control, bits| {
+            *control == qhyccd_rs::Control::TransferBit
+                && (*bits - transfer_bits).abs() < f64::EPSILON
+        })
+        .returning(|_, _| Ok(()));
+}
+
+#[rstest]
+#[case("8", 8_f64, "8")]
+#[case(" 16 ", 16_f64, "16")]
+#[tokio::test]
+async fn transfer_bits_action(
+    #[case] parameters: &str,
+    #[case] transfer_bits: f64,
+    #[case] expected: &str,
+) {
+    //given
+    let mut mock = MockCamera::new();
+    expect_transfer_bits(&mut mock, transfer_bits);
+    let camera = new_camera(mock, MockCameraType::IsOpenTrue { times: 1 });
+    //when
+    let res = camera
+        .action("TransferBits".to_owned(), parameters.to_owned())
+        .await;
+    //then
+    assert_eq!(res.unwrap(), expected);
+    assert_eq!(*camera.transfer_bits.read().await, transfer_bits as u8);
+}
+
+#[tokio::test]
+async fn transfer_bits_action_query() {
+    //given
+    let camera = new_camera(MockCamera::new(), MockCameraType::IsOpenTrue { times: 1 });
+    *camera.transfer_bits.write().await = 8;
+    //when
+    let res = camera
+        .action("TransferBits".to_owned(), String::new())
+        .await;
+    //then
+    assert_eq!(res.unwrap(), "8");
+}
+
+#[rstest]
+#[case("12")]
+#[case("eight")]
+#[tokio::test]
+async fn transfer_bits_action_invalid(#[case] parameters: &str) {
+    //given
+    let camera = new_camera(MockCamera::new(), MockCameraType::IsOpenTrue { times: 1 });
+    //when
+    let res = camera
+        .action("TransferBits".to_owned(), parameters.to_owned())
+        .await;
+    //then
+    assert_eq!(
+        res.unwrap_err().to_string(),
+        ASCOMError::invalid_value("TransferBits parameter must be 8, 16 or empty").to_string()
+    );
+}
+
+#[tokio::test]
+async fn transfer_bits_action_without_8_bit_transfer() {
+    //given
+    let mut mock = MockCamera::new();
+    mock.expect_is_control_available()
+        .once()
+        .withf(|control| *control == qhyccd_rs::Control::Cam8bits)
+        .returning(|_| None);
+    let camera = new_camera(mock, MockCameraType::IsOpenTrue { times: 1 });
+    //when
+    let res = camera
+        .action("TransferBits".to_owned(), "8".to_owned())
+        .await;
+    //then
+    assert_eq!(
+        res.unwrap_err().to_string(),
+        ASCOMError::invalid_operation("camera does not transfer 8 bit frames").to_string()
+    );
+    assert_eq!(*camera.transfer_bits.read().await, 16);
+}
+
+#[tokio::test]
+async fn transfer_bits_action_fail() {
+    //given
+    let mut mock = MockCamera::new();
+    mock.expect_is_control_available()
+        .once()
+        .withf(|control| *control == qhyccd_rs::Control::Cam8bits)
+        .returning(|_| Some(0));
+    mock.expect_set_if_available()
+        .once()
+        .returning(|_, _| Err(eyre!("error")));
+    let camera = new_camera(mock, MockCameraType::IsOpenTrue { times: 1 });
+    //when
+    let res = camera
+        .action("TransferBits".to_owned(), "8".to_owned())
+        .await;
+    //then
+    assert_eq!(
+        res.unwrap_err().to_string(),
+        ASCOMError::INVALID_OPERATION.to_string()
+    );
+    assert_eq!(*camera.transfer_bits.read().await, 16);
+}
+
+#[tokio::test]
+async fn transfer_bits_action_during_exposure() {
+    //given
+    let camera = new_camera(MockCamera::new(), MockCameraType::IsOpenTrue { times: 1 });
+    *camera.state.write().await = State::Exposing {
+        start: SystemTime::UNIX_EPOCH,
+        expected_duration_us: 1_000_000,
+        stop_tx: None,
+        done_rx: watch::channel(false).1,
+    };
+    //when
+    let res = camera
+        .action("TransferBits".to_owned(), "8".to_owned())
+        .await;
+    //then
+    assert_eq!(
+        res.unwrap_err().to_string(),
+        ASCOMError::invalid_operation("cannot switch the transfer bits during an exposure")
+            .to_string()
+    );
+}
+
+#[tokio::test]
+async fn transfer_bits_action_not_connected() {
+    not_connected! {action("TransferBits".to_owned(), String::new())}
+}
+
+#[tokio::test]
+async fn max_adu_with_8_bit_transfer() {
+    //given the camera is not asked for its ADC depth
+    let camera = new_camera(MockCamera::new(), MockCameraType::IsOpenTrue { times: 1 });
+    *camera.transfer_bits.write().await = 8;
+    //when
+    let res = camera.max_adu().await;
+    //then
+    assert_eq!(res.unwrap(), 255);
+}
+
+#[tokio::test]
+async fn apply_config_transfer_bits() {
+    //given
+    let mut mock = MockCamera::new();
+    expect_transfer_bits(&mut mock, 8_f64);
+    let mut camera = new_camera(mock, MockCameraType::Untouched);
+    camera.config = Arc::new(ConfigStore::new(
+        None,
+        crate::config::Config {
+            cameras: [(
+                "test-camera".to_owned(),
+                CameraConfig {
+                    transfer_bits: Some(8),
+                    ..Default::default()
+                },
+            )]
+            .into(),
+            ..Default::default()
+        },
+    ));
+    //when
+    camera.apply_config().await;
+    //then
+    assert_eq!(*camera.transfer_bits.read().await, 8);
+}
@@ -0,0 +1,210 @@
+//! USB traffic, DDR buffer and readout speed tests
+
+use super::*;
+
+fn usb_traffic_range() -> ControlRange {
+    ControlRange {
+        min: 0_f64,
+        max: 255_f64,
+        step: 1_f64,
+    }
+}
+
+#[rstest]
+#[case("UsbTraffic", Tuning::UsbTraffic, Control::UsbTraffic)]
+#[case("DdrBuffer", Tuning::DdrBuffer, Control::DDR)]
+#[case("READOUTSPEED", Tuning::ReadoutSpeed, Control::Speed)]
+#[tokio::test]
+async fn tuning_action_sets_and_stores(
+    #[case] action: &str,
+    #[case] tuning: Tuning,
+    #[case] control: Control,
+) {
+    //given
+    let mut mock = MockCamera::new();
+    mock.expect_set_parameter()
+        .once()
+        .withf(move |c, value| *c == control && (*value - 1_f64).abs() < f64::EPSILON)
+        .returning(|_, _| Ok(()));
+    mock.expect_get_parameter()
+        .once()
+        .withf(move |c| *c == control)
+        .returning(|_| Ok(1_f64));
+    let camera = new_camera(mock, MockCameraType::IsOpenTrue { times: 1 });
+    match tuning {
+        Tuning::UsbTraffic => *camera.usb_traffic_range.write().await = Some(usb_traffic_range()),
+        Tuning::DdrBuffer => {
+            *camera.ddr_range.write().await = Some(ControlRange {
+                min: 0_f64,
+                max: 1_f64,
+                step: 1_f64,
+            })
+        }
+        Tuning::ReadoutSpeed => {
+            *camera.readout_speed_min_max_step.write().await = Some((0_f64, 2_f64, 1_f64))
+        }
+    }
+    //when
+    let res = camera.action(action.to_owned(), " 1 ".to_owned()).await;
+    //then
+    let status: serde_json::Value = serde_json::from_str(&res.unwrap()).unwrap();
+    assert_eq!(status["value"], 1_f64);
+    assert_eq!(status["min"], 0_f64);
+    let stored = camera.config.camera("test-camera").await;
+    let stored = match tuning {
+        Tuning::UsbTraffic => stored.usb_traffic,
+        Tuning::DdrBuffer => stored.ddr_buffer,
+        Tuning::ReadoutSpeed => stored.readout_speed,
+    };
+    assert_eq!(stored, Some(1_f64));
+}
+
+#[tokio::test]
+async fn tuning_action_query() {
+    //given
+    let mut mock = MockCamera::new();
+    mock.expect_get_parameter()
+        .once()
+        .withf(|control| *control == Control::UsbTraffic)
+        .returning(|_| Ok(30_f64));
+    let camera = new_camera(mock, MockCameraType::IsOpenTrue { times: 1 });
+    *camera.usb_traffic_range.write().await = Some(usb_traffic_range());
+    //when
+    let res = camera.action("UsbTraffic".to_owned(), String::new()).await;
+    //then
+    assert_eq!(
+        serde_json::from_str::<serde_json::Value>(&res.unwrap()).unwrap(),
+        serde_json::json!({"value": 30.0, "min": 0.0, "max": 255.0, "step": 1.0})
+    );
+    assert_eq!(camera.config.camera("test-camera").await.usb_traffic, None);
+}
+
+#[rstest]
+#[case(
+    "256",
+    ASCOMError::invalid_value("UsbTraffic must be between 0 and 255 in steps of 1")
+)]
+#[case(
+    "12.5",
+    ASCOMError::invalid_value("UsbTraffic must be between 0 and 255 in steps of 1")
+)]
+#[case(
+    "fast",
+    ASCOMError::invalid_value("UsbTraffic parameter must be a number or empty")
+)]
+#[tokio::test]
+async fn tuning_action_invalid(#[case] parameters: &str, #[case] expected: ASCOMError) {
+    //given
+    let camera = new_camera(MockCamera::new(), MockCameraType::IsOpenTrue { times: 1 });
+    *camera.usb_traffic_range.write().await = Some(usb_traffic_range());
+    //when
+    let res = camera
+        .action("UsbTraffic".to_owned(), parameters.to_owned())
+        .await;
+    //then
+    assert_eq!(res.unwrap_err().to_string(), expected.to_string());
+    assert_eq!(camera.config.camera("test-camera").await.usb_traffic, None);
+}
+
+#[tokio::test]
+async fn tuning_action_not_available() {
+    //given
+    let camera = new_camera(MockCamera::new(), MockCameraType::IsOpenTrue { times: 1 });
+    //when
+    let res = camera.action("DdrBuffer".to_owned(), "1".to_owned()).await;
+    //then
+    assert_eq!(
+        res.unwrap_err().to_string(),
+        ASCOMError::NOT_IMPLEMENTED.to_string()
+    );
+}
+
+#[tokio::test]
+async fn tuning_action_during_exposure() {
+    //given
+    let camera = new_camera(MockCamera::new(), MockCameraType::IsOpenTrue { times: 1 });
+    *camera.usb_traffic_range.write().await = Some(usb_traffic_range());
+    *camera.state.write().await = State::Exposing {
+        start: SystemTime::UNIX_EPOCH,
+        expected_duration_us: 1_000_000,
+        stop_tx: None,
+        done_rx: watch::channel(false).1,
+    };
+    //when
+    let res = camera
+        .action("UsbTraffic".to_owned(), "40".to_owned())
+        .await;
+    //then
+    assert_eq!(
+        res.unwrap_err().to_string(),
+        ASCOMError::invalid_operation("cannot change UsbTraffic during an exposure").to_string()
+    );
+}
+
+#[tokio::test]
+async fn tuning_action_not_connected() {
+    not_connected! {action("UsbTraffic".to_owned(), String::new())}
+}
+
+#[rstest]
+#[case::raised(30_f64, Some(40_f64))]
+#[case::capped(250_f64, Some(255_f64))]
+#[case::at_max(255_f64, None)]
+#[tokio::test]
+async fn back_off_usb_traffic(#[case] current: f64, #[case] expected: Option<f64>) {
+    //given
+    let mut mock = MockCamera::new();
+    mock.expect_get_parameter()
+        .once()
+        .withf(|control| *control == Control::UsbTraffic)
+        .returning(move |_| Ok(current));
+    mock.expect_set_parameter()
+        .times(usize::from(expected.is_some()))
+        .withf(move |control, value| *control == Control::UsbTraffic && Some(*value) == expected)
+        .returning(|_, _| Ok(()));
+    let config = Arc::new(ConfigStore::default());
+    let usb_backoff = UsbBackoff {
+        config: config.clone(),
+        unique_id: "test-camera".to_owned(),
+        by: 10_f64,
+        range: usb_traffic_range(),
+    };
+    //when
+    usb_backoff.apply(&mock).await;
+    //then
+    assert_eq!(config.camera("test-camera").await.usb_traffic, expected);
+}
+
+#[tokio::test]
+async fn back_off_usb_traffic_fail() {
+    //given the camera keeps its stored value if it rejects the new one
+    let mut mock = MockCamera::new();
+    mock.expect_get_parameter().once().returning(|_| Ok(30_f64));
+    mock.expect_set_parameter()
+        .once()
+        .returning(|_, _| Err(eyre!("error")));
+    let config = Arc::new(ConfigStore::default());
+    let usb_backoff = UsbBackoff {
+        config: config.clone(),
+        unique_id: "test-camera".to_owned(),
+        by: 10_f64,
+        range: usb_traffic_range(),
+    };
+    //when
+    usb_backoff.apply(&mock).await;
+    //then
+    assert_eq!(config.camera("test-camera").await.usb_traffic, None);
+}
+
+#[tokio::test]
+async fn supported_actions_list_found_tuning_controls() {
+    //given a camera with a USB traffic control only
+    let camera = new_camera(MockCamera::new(), MockCameraType::Untouched);
+    *camera.usb_traffic_range.write().await = Some(usb_traffic_range());
+    //when
+    let res = camera.supported_actions().await.unwrap();
+    //then
+    assert!(res.contains(&"UsbTraffic".to_owned()));
+    assert!(!res.contains(&"DdrBuffer".to_owned()));
+    assert!(!res.contains(&"ReadoutSpeed".to_owned()));
+}
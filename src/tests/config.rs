@@ -0,0 +1,134 @@
+//! Configuration file tests
+
+use crate::config::*;
+
+fn temp_path(name: &str) -> std::path::PathBuf {
+    std::env::temp_dir().join(format!("qhyccd-alpaca-{}.toml", name))
+}
+
+#[tokio::test]
+async fn load_missing_file() {
+    //given
+    let path = temp_path("missing");
+    let _ = std::fs::remove_file(&path);
+    //when
+    let store = ConfigStore::load(path).unwrap();
+    //then
+    assert_eq!(store.camera("QHY600M-abc").await, CameraConfig::default());
+}
+
+#[tokio::test]
+async fn load_file() {
+    //given
+    let path = temp_path("load");
+    std::fs::write(
+        &path,
+        "[cameras.QHY600M-abc]\nreadout_mode = 1\ngain = 26\ntarget_temperature = -10.0\n",
+    )
+    .unwrap();
+    //when
+    let store = ConfigStore::load(path.clone()).unwrap();
+    //then
+    assert_eq!(
+        store.camera("QHY600M-abc").await,
+        CameraConfig {
+            readout_mode: Some(1),
+            gain: Some(26),
+            target_temperature: Some(-10_f64),
+            ..Default::default()
+        }
+    );
+    assert_eq!(store.camera("QHY268C-def").await, CameraConfig::default());
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn load_invalid_file() {
+    //given
+    let path = temp_path("invalid");
+    std::fs::write(&path, "[cameras.QHY600M-abc]\ngain = \"high\"\n").unwrap();
+    //when
+    let res = ConfigStore::load(path.clone());
+    //then
+    assert!(res.is_err());
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[tokio::test]
+async fn save_camera_roundtrip() {
+    //given
+    let path = temp_path("roundtrip");
+    let _ = std::fs::remove_file(&path);
+    let store = ConfigStore::load(path.clone()).unwrap();
+    let camera = CameraConfig {
+        binning: Some(2),
+        offset: Some(30),
+        usb_traffic: Some(20_f64),
+        fits_export: Some(false),
+        ..Default::default()
+    };
+    //when
+    store
+        .save_camera("QHY600M-abc", camera.clone())
+        .await
+        .unwrap();
+    //then
+    let reloaded = ConfigStore::load(path.clone()).unwrap();
+    assert_eq!(reloaded.camera("QHY600M-abc").await, camera);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[tokio::test]
+async fn save_camera_without_file() {
+    //given
+    let store = ConfigStore::default();
+    let camera = CameraConfig {
+        gain: Some(26),
+        ..Default::default()
+    };
+    //when
+    let res = store.save_camera("QHY600M-abc", camera.clone()).await;
+    //then
+    assert!(res.is_ok());
+    assert_eq!(store.camera("QHY600M-abc").await, camera);
+}
+
+#[tokio::test]
+async fn failed_save_keeps_settings() {
+    //given
+    let path = std::env::temp_dir()
+        .join("qhyccd-alpaca-missing-directory")
+        .join("config.toml");
+    let store = ConfigStore::new(Some(path), Config::default());
+    //when
+    let saved = store
+        .save_camera(
+            "QHY600M-abc",
+            CameraConfig {
+                gain: Some(26),
+                ..Default::default()
+            },
+        )
+        .await;
+    let updated = store
+        .update_camera("QHY600M-abc", |camera| camera.offset = Some(30))
+        .await;
+    let wheel = store
+        .set_filter_wheel(
+            "CFW-abc",
+            FilterWheelConfig {
+                names: Some(vec!["L".to_owned()]),
+                ..Default::default()
+            },
+        )
+        .await;
+    //then
+    assert!(saved.is_err());
+    assert!(updated.is_err());
+    assert!(wheel.is_err());
+    assert_eq!(store.camera("QHY600M-abc").await, CameraConfig::default());
+    assert_eq!(
+        store.filter_wheel("CFW-abc").await,
+        FilterWheelConfig::default()
+    );
+}
@@ -0,0 +1,174 @@
+//! Cooler controller tests
+
+use std::time::Duration;
+
+use crate::cooler::*;
+use rstest::*;
+
+fn cooler(
+    state: CoolerState,
+    target: Option<f64>,
+    cool_down: Option<f64>,
+    warm_up: Option<f64>,
+) -> Cooler {
+    let mut cooler = Cooler::new(CoolerRamp { cool_down, warm_up });
+    cooler.state = state;
+    cooler.target = target;
+    cooler.ambient = 20_f64;
+    cooler
+}
+
+#[rstest]
+#[case::no_ramp(None, CoolerCommand::SetPoint(-10_f64), CoolerState::Regulating)]
+#[case::ramp(
+    Some(1_f64),
+    CoolerCommand::SetPoint(15_f64),
+    CoolerState::Ramping { set_point: 15_f64 }
+)]
+fn switch_on(
+    #[case] cool_down: Option<f64>,
+    #[case] expected: CoolerCommand,
+    #[case] expected_state: CoolerState,
+) {
+    //given
+    let mut cooler = cooler(CoolerState::Off, Some(-10_f64), cool_down, None);
+    //when
+    let command = cooler.switch_on(15_f64);
+    //then
+    assert_eq!(command, expected);
+    assert_eq!(cooler.state, expected_state);
+    assert_eq!(cooler.ambient, 15_f64);
+}
+
+#[test]
+fn switch_on_without_target_holds_temperature() {
+    //given
+    let mut cooler = cooler(CoolerState::Off, None, None, None);
+    //when
+    let command = cooler.switch_on(15_f64);
+    //then
+    assert_eq!(command, CoolerCommand::SetPoint(15_f64));
+    assert_eq!(cooler.target, Some(15_f64));
+}
+
+#[test]
+fn switch_on_during_warm_up_ramps_from_set_point() {
+    //given
+    let mut cooler = cooler(
+        CoolerState::WarmingUp { set_point: 0_f64 },
+        Some(-10_f64),
+        Some(1_f64),
+        Some(1_f64),
+    );
+    //when
+    let command = cooler.switch_on(5_f64);
+    //then
+    assert_eq!(command, CoolerCommand::SetPoint(0_f64));
+    assert_eq!(cooler.state, CoolerState::Ramping { set_point: 0_f64 });
+    assert_eq!(cooler.ambient, 20_f64);
+}
+
+#[rstest]
+#[case::no_ramp(
+    CoolerState::Regulating,
+    None,
+    CoolerCommand::SwitchOff,
+    CoolerState::Off
+)]
+#[case::ramp(
+    CoolerState::Regulating,
+    Some(1_f64),
+    CoolerCommand::None,
+    CoolerState::WarmingUp { set_point: -10_f64 }
+)]
+#[case::ramping(
+    CoolerState::Ramping { set_point: 5_f64 },
+    Some(1_f64),
+    CoolerCommand::None,
+    CoolerState::WarmingUp { set_point: 5_f64 }
+)]
+#[case::already_off(CoolerState::Off, Some(1_f64), CoolerCommand::None, CoolerState::Off)]
+fn switch_off(
+    #[case] state: CoolerState,
+    #[case] warm_up: Option<f64>,
+    #[case] expected: CoolerCommand,
+    #[case] expected_state: CoolerState,
+) {
+    //given
+    let mut cooler = cooler(state, Some(-10_f64), None, warm_up);
+    //when
+    let command = cooler.switch_off();
+    //then
+    assert_eq!(command, expected);
+    assert_eq!(cooler.state, expected_state);
+}
+
+#[rstest]
+#[case::off(CoolerState::Off, CoolerCommand::None, CoolerState::Off)]
+#[case::regulating(
+    CoolerState::Regulating,
+    CoolerCommand::SetPoint(-10_f64),
+    CoolerState::Ramping { set_point: -10_f64 }
+)]
+#[case::ramping(
+    CoolerState::Ramping { set_point: 0_f64 },
+    CoolerCommand::None,
+    CoolerState::Ramping { set_point: 0_f64 }
+)]
+fn set_target(
+    #[case] state: CoolerState,
+    #[case] expected: CoolerCommand,
+    #[case] expected_state: CoolerState,
+) {
+    //given
+    let mut cooler = cooler(state, Some(-10_f64), Some(1_f64), Some(1_f64));
+    //when
+    let command = cooler.set_target(-20_f64);
+    //then
+    assert_eq!(command, expected);
+    assert_eq!(cooler.state, expected_state);
+    assert_eq!(cooler.target, Some(-20_f64));
+}
+
+#[rstest]
+// 6°C/min is 3°C in 30s
+#[case::cooling(
+    CoolerState::Ramping { set_point: 0_f64 },
+    CoolerCommand::SetPoint(-3_f64),
+    CoolerState::Ramping { set_point: -3_f64 }
+)]
+#[case::cooling_done(
+    CoolerState::Ramping { set_point: -8_f64 },
+    CoolerCommand::SetPoint(-10_f64),
+    CoolerState::Regulating
+)]
+#[case::towards_higher_target(
+    CoolerState::Ramping { set_point: -20_f64 },
+    CoolerCommand::SetPoint(-19_f64),
+    CoolerState::Ramping { set_point: -19_f64 }
+)]
+#[case::warming(
+    CoolerState::WarmingUp { set_point: 0_f64 },
+    CoolerCommand::SetPoint(1_f64),
+    CoolerState::WarmingUp { set_point: 1_f64 }
+)]
+#[case::warming_done(
+    CoolerState::WarmingUp { set_point: 19.5_f64 },
+    CoolerCommand::SwitchOff,
+    CoolerState::Off
+)]
+#[case::regulating(CoolerState::Regulating, CoolerCommand::None, CoolerState::Regulating)]
+#[case::off(CoolerState::Off, CoolerCommand::None, CoolerState::Off)]
+fn step(
+    #[case] state: CoolerState,
+    #[case] expected: CoolerCommand,
+    #[case] expected_state: CoolerState,
+) {
+    //given cooling at 6°C/min, warming at 2°C/min
+    let mut cooler = cooler(state, Some(-10_f64), Some(6_f64), Some(2_f64));
+    //when
+    let command = cooler.step(Duration::from_secs(30));
+    //then
+    assert_eq!(command, expected);
+    assert_eq!(cooler.state, expected_state);
+}
@@ -0,0 +1,149 @@
+//! Software debayering tests
+
+use qhyccd_rs::BayerMode;
+use rstest::rstest;
+
+use crate::debayer::*;
+
+fn mono(data: Vec<u8>, width: u32, height: u32) -> qhyccd_rs::ImageData {
+    qhyccd_rs::ImageData {
+        data,
+        width,
+        height,
+        bits_per_pixel: 8,
+        channels: 1,
+    }
+}
+
+/// BGR samples of every pixel.
+fn pixels(image: &qhyccd_rs::ImageData) -> Vec<[u8; 3]> {
+    image
+        .data
+        .chunks_exact(3)
+        .map(|bgr| [bgr[0], bgr[1], bgr[2]])
+        .collect()
+}
+
+#[rstest]
+#[case(BayerMode::RGGB, [40, 25, 10])]
+#[case(BayerMode::BGGR, [10, 25, 40])]
+#[case(BayerMode::GRBG, [30, 25, 20])]
+#[case(BayerMode::GBRG, [20, 25, 30])]
+fn superpixel(#[case] pattern: BayerMode, #[case] expected_bgr: [u8; 3]) {
+    //given a single 2x2 cell
+    let image = mono(vec![10, 20, 30, 40], 2, 2);
+    //when
+    let res = debayer(&image, pattern, DebayerMethod::Superpixel);
+    //then
+    let res = res.unwrap();
+    assert_eq!((res.width, res.height, res.channels), (1, 1, 3));
+    assert_eq!(pixels(&res), vec![expected_bgr]);
+}
+
+#[rstest]
+#[case(DebayerMethod::Bilinear)]
+#[case(DebayerMethod::Gradient)]
+fn flat_field_keeps_colours(#[case] method: DebayerMethod) {
+    //given a flat field of R 200, G 100, B 50 on a GBRG sensor
+    let mut data = Vec::new();
+    for y in 0..6 {
+        for x in 0..6 {
+            data.push(match (x % 2, y % 2) {
+                (0, 0) | (1, 1) => 100_u8,
+                (1, 0) => 50_u8,
+                _ => 200_u8,
+            });
+        }
+    }
+    let image = mono(data, 6, 6);
+    //when
+    let res = debayer(&image, BayerMode::GBRG, method);
+    //then
+    let res = res.unwrap();
+    assert_eq!((res.width, res.height, res.channels), (6, 6, 3));
+    assert!(pixels(&res).iter().all(|bgr| *bgr == [50, 100, 200]));
+}
+
+#[test]
+fn bilinear_interpolates_neighbours() {
+    //given a 4x4 RGGB frame with a single bright green pixel next to the red one at 2, 2
+    let mut data = vec![0_u8; 16];
+    data[2 * 4 + 1] = 80;
+    let image = mono(data, 4, 4);
+    //when
+    let res = debayer(&image, BayerMode::RGGB, DebayerMethod::Bilinear);
+    //then green at the red pixel is the average of its four green neighbours
+    let res = res.unwrap();
+    assert_eq!(pixels(&res)[2 * 4 + 2], [0, 20, 0]);
+}
+
+#[test]
+fn debayer_keeps_16_bit() {
+    //given
+    let image = qhyccd_rs::ImageData {
+        data: [1000_u16, 2000, 3000, 4000]
+            .iter()
+            .flat_map(|value| value.to_ne_bytes())
+            .collect(),
+        width: 2,
+        height: 2,
+        bits_per_pixel: 16,
+        channels: 1,
+    };
+    //when
+    let res = debayer(&image, BayerMode::RGGB, DebayerMethod::Superpixel);
+    //then
+    let res = res.unwrap();
+    assert_eq!(res.bits_per_pixel, 16);
+    let samples: Vec<u16> = res
+        .data
+        .chunks_exact(2)
+        .map(|value| u16::from_ne_bytes([value[0], value[1]]))
+        .collect();
+    assert_eq!(samples, vec![4000, 2500, 1000]);
+}
+
+#[rstest]
+#[case(mono(vec![0; 4], 4, 1), "too small")]
+#[case(
+    qhyccd_rs::ImageData {
+        data: vec![0; 16],
+        width: 2,
+        height: 2,
+        bits_per_pixel: 32,
+        channels: 1,
+    },
+    "cannot debayer"
+)]
+#[case(
+    qhyccd_rs::ImageData {
+        data: vec![0; 12],
+        width: 2,
+        height: 2,
+        bits_per_pixel: 8,
+        channels: 3,
+    },
+    "cannot debayer"
+)]
+fn debayer_fail(#[case] image: qhyccd_rs::ImageData, #[case] expected: &str) {
+    //when
+    let res = debayer(&image, BayerMode::RGGB, DebayerMethod::Bilinear);
+    //then
+    assert!(res.unwrap_err().to_string().contains(expected));
+}
+
+#[test]
+fn method_names() {
+    for method in [
+        DebayerMethod::Bilinear,
+        DebayerMethod::Gradient,
+        DebayerMethod::Superpixel,
+    ] {
+        assert_eq!(DebayerMethod::from_name(method.name()), Some(method));
+    }
+    assert_eq!(
+        DebayerMethod::from_name("Bilinear"),
+        Some(DebayerMethod::Bilinear)
+    );
+    assert_eq!(DebayerMethod::from_name("ahd"), None);
+}
@@ -0,0 +1,250 @@
+//! Frame endpoint tests
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::RwLock;
+
+use crate::binning::Binning;
+use crate::endpoint::*;
+use crate::live::LiveFeed;
+use crate::sequence::Sequencer;
+use crate::{FrameType, LastImage};
+
+fn frame(value: u8) -> qhyccd_rs::ImageData {
+    qhyccd_rs::ImageData {
+        data: vec![value; 4],
+        width: 2,
+        height: 2,
+        bits_per_pixel: 8,
+        channels: 1,
+    }
+}
+
+fn no_image() -> Arc<RwLock<Option<LastImage>>> {
+    Arc::new(RwLock::new(None))
+}
+
+/// Serves camera `QHY178M-abc` on a free port and returns the base URL.
+async fn serve_camera(
+    live_feed: Arc<LiveFeed>,
+    last_image: Arc<RwLock<Option<LastImage>>>,
+) -> String {
+    serve_frames(CameraFrames {
+        live_feed,
+        last_image,
+        sequencer: Arc::default(),
+    })
+    .await
+}
+
+async fn serve_frames(frames: CameraFrames) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let cameras = Arc::new(RwLock::new(HashMap::from([(
+        "QHY178M-abc".to_owned(),
+        frames,
+    )])));
+    tokio::spawn(serve(listener, cameras));
+    format!("http://{addr}")
+}
+
+#[tokio::test]
+#[cfg_attr(miri, ignore)]
+async fn latest_frame_endpoint() {
+    //given
+    let feed = Arc::new(LiveFeed::default());
+    let url = format!(
+        "{}/live/QHY178M-abc",
+        serve_camera(feed.clone(), no_image()).await
+    );
+    //when no frame was read yet
+    let res = reqwest::get(format!("{url}/latest")).await.unwrap();
+    //then
+    assert_eq!(res.status(), reqwest::StatusCode::NO_CONTENT);
+    //when
+    feed.push(frame(7)).await;
+    let res = reqwest::get(format!("{url}/latest")).await.unwrap();
+    //then
+    assert_eq!(res.status(), reqwest::StatusCode::OK);
+    assert_eq!(res.headers()["x-sequence"], "1");
+    assert_eq!(res.headers()["x-width"], "2");
+    assert_eq!(res.headers()["x-bits-per-pixel"], "8");
+    assert_eq!(res.bytes().await.unwrap().to_vec(), vec![7_u8; 4]);
+}
+
+#[tokio::test]
+#[cfg_attr(miri, ignore)]
+async fn unknown_camera_not_found() {
+    //given
+    let url = format!(
+        "{}/live/QHY178M-abc",
+        serve_camera(Arc::new(LiveFeed::default()), no_image()).await
+    );
+    //when
+    let res = reqwest::get(url.replace("QHY178M-abc", "QHY600M-def"))
+        .await
+        .unwrap();
+    //then
+    assert_eq!(res.status(), reqwest::StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+#[cfg_attr(miri, ignore)]
+async fn stream_endpoint_sends_new_frames() {
+    //given
+    let feed = Arc::new(LiveFeed::default());
+    let url = format!(
+        "{}/live/QHY178M-abc",
+        serve_camera(feed.clone(), no_image()).await
+    );
+    let mut res = reqwest::get(url).await.unwrap();
+    assert_eq!(
+        res.headers()["content-type"],
+        "multipart/x-mixed-replace; boundary=frame"
+    );
+    //when
+    feed.push(frame(9)).await;
+    //then
+    let mut received = Vec::new();
+    while !received.ends_with(&[9_u8, 9, 9, 9, b'\r', b'\n']) {
+        let chunk = tokio::time::timeout(std::time::Duration::from_secs(2), res.chunk())
+            .await
+            .expect("no frame received")
+            .unwrap()
+            .expect("stream closed");
+        received.extend_from_slice(&chunk);
+    }
+    let received = String::from_utf8_lossy(&received);
+    assert!(received.starts_with("--frame\r\n"));
+    assert!(received.contains("X-Sequence: 1\r\n"));
+    assert!(received.contains("Content-Length: 4\r\n"));
+}
+
+#[tokio::test]
+#[cfg_attr(miri, ignore)]
+async fn image_bytes_endpoint() {
+    //given
+    let last_image = no_image();
+    let url = format!(
+        "{}/image/QHY178M-abc?ClientTransactionID=42",
+        serve_camera(Arc::new(LiveFeed::default()), last_image.clone()).await
+    );
+    //when no exposure was taken yet
+    let res = reqwest::get(&url).await.unwrap();
+    //then
+    assert_eq!(res.status(), reqwest::StatusCode::NO_CONTENT);
+    //when
+    *last_image.write().await = Some(LastImage {
+        image: Arc::new(qhyccd_rs::ImageData {
+            data: vec![1, 2, 3, 4, 5, 6],
+            width: 3,
+            height: 2,
+            bits_per_pixel: 8,
+            channels: 1,
+        }),
+        frame_type: FrameType::Light,
+        origin: (0, 0),
+        binning: Binning::symmetric(1),
+        overscan: None,
+        image_array: Arc::default(),
+    });
+    let res = reqwest::get(&url).await.unwrap();
+    //then
+    assert_eq!(res.status(), reqwest::StatusCode::OK);
+    assert_eq!(res.headers()["content-type"], "application/imagebytes");
+    let bytes = res.bytes().await.unwrap();
+    assert_eq!(&bytes[8..12], &42_i32.to_le_bytes());
+    assert_eq!(&bytes[44..], &[1, 4, 2, 5, 3, 6]);
+}
+
+#[tokio::test]
+#[cfg_attr(miri, ignore)]
+async fn unknown_path_not_found() {
+    //given
+    let url = serve_camera(Arc::new(LiveFeed::default()), no_image()).await;
+    //when
+    let res = reqwest::get(format!("{url}/api/v1/camera/0/imagearray"))
+        .await
+        .unwrap();
+    //then
+    assert_eq!(res.status(), reqwest::StatusCode::NOT_FOUND);
+}
+
+/// Sends `request` as is and returns the status line of the response.
+async fn raw_request(url: &str, request: &[u8]) -> String {
+    let mut stream = TcpStream::connect(url.trim_start_matches("http://"))
+        .await
+        .unwrap();
+    // the endpoint may answer before it read everything
+    let _ = stream.write_all(request).await;
+    // the connection may be reset after the response, keep what was read
+    let mut response = Vec::new();
+    let _ = stream.read_to_end(&mut response).await;
+    String::from_utf8_lossy(&response)
+        .lines()
+        .next()
+        .unwrap_or_default()
+        .to_owned()
+}
+
+#[tokio::test]
+#[cfg_attr(miri, ignore)]
+async fn request_line_too_long() {
+    //given
+    let url = serve_camera(Arc::new(LiveFeed::default()), no_image()).await;
+    let request = format!("GET /{} HTTP/1.1\r\n\r\n", "a".repeat(10_000));
+    //when
+    let res = raw_request(&url, request.as_bytes()).await;
+    //then
+    assert_eq!(res, "HTTP/1.1 414 URI Too Long");
+}
+
+#[rstest::rstest]
+#[case(1, 10_000)]
+#[case(101, 10)]
+#[tokio::test]
+#[cfg_attr(miri, ignore)]
+async fn headers_too_large(#[case] count: usize, #[case] length: usize) {
+    //given
+    let url = serve_camera(Arc::new(LiveFeed::default()), no_image()).await;
+    let header = format!("X-Padding: {}\r\n", "a".repeat(length));
+    let request = format!(
+        "GET /live/QHY178M-abc/latest HTTP/1.1\r\n{}\r\n",
+        header.repeat(count)
+    );
+    //when
+    let res = raw_request(&url, request.as_bytes()).await;
+    //then
+    assert_eq!(res, "HTTP/1.1 431 Request Header Fields Too Large");
+}
+
+#[tokio::test]
+#[cfg_attr(miri, ignore)]
+async fn sequence_endpoint() {
+    //given no sequence was run
+    let url = serve_frames(CameraFrames {
+        live_feed: Arc::default(),
+        last_image: no_image(),
+        sequencer: Arc::new(Sequencer::default()),
+    })
+    .await;
+    //when
+    let res = reqwest::get(format!("{url}/sequence/QHY178M-abc"))
+        .await
+        .unwrap();
+    //then
+    assert_eq!(res.status(), reqwest::StatusCode::OK);
+    assert_eq!(res.headers()["content-type"], "application/json");
+    let progress: serde_json::Value = serde_json::from_slice(&res.bytes().await.unwrap()).unwrap();
+    assert_eq!(progress["state"], "idle");
+    assert_eq!(progress["frames_done"], 0);
+    //when
+    let res = reqwest::get(format!("{url}/sequence/QHY600M-def"))
+        .await
+        .unwrap();
+    //then
+    assert_eq!(res.status(), reqwest::StatusCode::NOT_FOUND);
+}
@@ -0,0 +1,215 @@
+//! Tests for filter wheels in the CFW port of a camera
+
+use super::*;
+use crate::mocks::MockCamera;
+use qhyccd_rs::Control;
+
+/// Creates a filter wheel driven through `camera`
+fn camera_port_filter_wheel(
+    camera: MockCamera,
+    cfw_detected: bool,
+    connected: bool,
+) -> QhyccdFilterWheel {
+    let mut filter_wheel = new_filter_wheel(MockFilterWheel::new(), MockFilterWheelType::Untouched);
+    filter_wheel.device = FilterWheelDevice::CameraPort {
+        camera,
+        cfw_detected: Arc::new(AtomicBool::new(cfw_detected)),
+        connected: AtomicBool::new(connected),
+    };
+    filter_wheel
+}
+
+/// Expects `control` to be looked up `times` times, `available` or not
+fn expect_control(camera: &mut MockCamera, control: Control, available: bool, times: usize) {
+    camera
+        .expect_is_control_available()
+        .times(times)
+        .withf(move |c| *c == control)
+        .returning(move |_| available.then_some(0));
+}
+
+/// Expects `control` to be read `times` times, answering `value`
+fn expect_read(camera: &mut MockCamera, control: Control, value: f64, times: usize) {
+    camera
+        .expect_get_parameter()
+        .times(times)
+        .withf(move |c| *c == control)
+        .returning(move |_| Ok(value));
+}
+
+#[rstest]
+#[case(false, true, Err(ASCOMError::NOT_CONNECTED))]
+#[case(true, false, Err(ASCOMError::NOT_CONNECTED))]
+#[case(true, true, Ok(()))]
+#[tokio::test]
+async fn set_connected_true(
+    #[case] camera_open: bool,
+    #[case] cfw_detected: bool,
+    #[case] expected: ASCOMResult,
+) {
+    //given
+    let connects = camera_open && cfw_detected;
+    let mut camera = MockCamera::new();
+    camera
+        .expect_is_open()
+        .once()
+        .returning(move || Ok(camera_open));
+    let times = usize::from(connects);
+    expect_control(&mut camera, Control::CfwSlotsNum, true, times);
+    expect_read(&mut camera, Control::CfwSlotsNum, 7_f64, times);
+    // positions are ASCII digits
+    expect_control(&mut camera, Control::CfwPort, true, times);
+    expect_read(&mut camera, Control::CfwPort, 50_f64, times);
+    let filter_wheel = camera_port_filter_wheel(camera, cfw_detected, false);
+    //when
+    let res = filter_wheel.set_connected(true).await;
+    //then
+    match expected {
+        Ok(_) => {
+            assert!(res.is_ok());
+            assert_eq!(*filter_wheel.number_of_filters.read().await, Some(7));
+            assert_eq!(*filter_wheel.target_position.read().await, Some(2));
+        }
+        Err(expected) => assert_eq!(res.unwrap_err().to_string(), expected.to_string()),
+    }
+}
+
+#[tokio::test]
+async fn connected_false_after_camera_disconnect() {
+    //given
+    let filter_wheel = camera_port_filter_wheel(MockCamera::new(), false, true);
+    //when
+    let res = filter_wheel.connected().await;
+    //then
+    assert!(!res.unwrap());
+}
+
+#[tokio::test]
+async fn set_connected_false_keeps_camera_open() {
+    //given
+    let mut camera = MockCamera::new();
+    camera.expect_is_open().once().returning(|| Ok(true));
+    let filter_wheel = camera_port_filter_wheel(camera, true, true);
+    //when
+    let res = filter_wheel.set_connected(false).await;
+    //then
+    assert!(res.is_ok());
+    assert!(!filter_wheel.connected().await.unwrap());
+}
+
+#[tokio::test]
+async fn set_position() {
+    //given
+    let mut camera = MockCamera::new();
+    camera.expect_is_open().once().returning(|| Ok(true));
+    expect_control(&mut camera, Control::CfwPort, true, 1);
+    camera
+        .expect_set_parameter()
+        .once()
+        .withf(|control, position| {
+            *control == Control::CfwPort && (*position - 51_f64).abs() < f64::EPSILON
+        })
+        .returning(|_, _| Ok(()));
+    let mut filter_wheel = camera_port_filter_wheel(camera, true, true);
+    filter_wheel.number_of_filters = RwLock::new(Some(7));
+    filter_wheel.target_position = RwLock::new(Some(0));
+    //when
+    let res = filter_wheel.set_position(3).await;
+    //then
+    assert!(res.is_ok());
+    assert_eq!(*filter_wheel.target_position.read().await, Some(3));
+}
+
+#[tokio::test]
+async fn set_connected_true_without_filters() {
+    //given
+    let mut camera = MockCamera::new();
+    camera.expect_is_open().once().returning(|| Ok(true));
+    expect_control(&mut camera, Control::CfwSlotsNum, false, 1);
+    let filter_wheel = camera_port_filter_wheel(camera, true, false);
+    //when
+    let res = filter_wheel.set_connected(true).await;
+    //then
+    assert_eq!(
+        res.unwrap_err().to_string(),
+        ASCOMError::NOT_CONNECTED.to_string()
+    );
+}
+
+#[rstest]
+#[case(State::Idle, 1, Some(2))]
+#[case(
+    State::Exposing {
+        start: SystemTime::UNIX_EPOCH,
+        expected_duration_us: 1_000_u32,
+        stop_tx: None,
+        done_rx: watch::channel(false).1,
+    },
+    0,
+    Some(3)
+)]
+#[tokio::test]
+async fn position_camera_state(
+    #[case] state: State,
+    #[case] get_fw_position_times: usize,
+    #[case] expected: Option<usize>,
+) {
+    //given
+    let mut camera = MockCamera::new();
+    camera.expect_is_open().once().returning(|| Ok(true));
+    expect_control(&mut camera, Control::CfwPort, true, get_fw_position_times);
+    expect_read(&mut camera, Control::CfwPort, 50_f64, get_fw_position_times);
+    let mut filter_wheel = camera_port_filter_wheel(camera, true, true);
+    filter_wheel.number_of_filters = RwLock::new(Some(7));
+    filter_wheel.target_position = RwLock::new(Some(3));
+    filter_wheel.camera_state = Some(Arc::new(RwLock::new(state)));
+    //when
+    let res = filter_wheel.position().await;
+    //then
+    assert_eq!(res.unwrap(), expected);
+}
+
+#[rstest]
+#[case(State::Idle, 1, Ok(()))]
+#[case(
+    State::Exposing {
+        start: SystemTime::UNIX_EPOCH,
+        expected_duration_us: 1_000_u32,
+        stop_tx: None,
+        done_rx: watch::channel(false).1,
+    },
+    0,
+    Err(ASCOMError::invalid_operation("cannot move the filter wheel during an exposure"))
+)]
+#[tokio::test]
+async fn set_position_camera_state(
+    #[case] state: State,
+    #[case] set_fw_position_times: usize,
+    #[case] expected: ASCOMResult,
+) {
+    //given
+    let mut mock = MockFilterWheel::new();
+    mock.expect_set_fw_position()
+        .times(set_fw_position_times)
+        .withf(|position| *position == 3)
+        .returning(|_| Ok(()));
+    let mut filter_wheel = new_filter_wheel(
+        mock,
+        MockFilterWheelType::WithFiltersAndTargetPosition {
+            times: 1,
+            filters: 5,
+            target: 0,
+        },
+    );
+    filter_wheel.camera_state = Some(Arc::new(RwLock::new(state)));
+    //when
+    let res = filter_wheel.set_position(3).await;
+    //then
+    match expected {
+        Ok(_) => assert!(res.is_ok()),
+        Err(expected) => {
+            assert_eq!(res.unwrap_err().to_string(), expected.to_string());
+            assert_eq!(*filter_wheel.target_position.read().await, Some(0));
+        }
+    }
+}
@@ -63,3 +63,134 @@ async fn names_fail() {
         ASCOMError::NOT_CONNECTED.to_string()
     );
 }
+
+#[tokio::test]
+async fn focus_offsets_configured() {
+    //given
+    let mock = MockFilterWheel::new();
+    let filter_wheel = with_config(
+        new_filter_wheel(
+            mock,
+            MockFilterWheelType::WithFilters {
+                times: 1,
+                filters: 3,
+            },
+        ),
+        None,
+        Some(vec![0, 12, -40]),
+    );
+    //when
+    let res = filter_wheel.focus_offsets().await;
+    //then
+    assert_eq!(res.unwrap(), vec![0, 12, -40]);
+}
+
+#[tokio::test]
+async fn names_configured() {
+    //given
+    let mock = MockFilterWheel::new();
+    let filter_wheel = with_config(
+        new_filter_wheel(
+            mock,
+            MockFilterWheelType::WithFilters {
+                times: 1,
+                filters: 3,
+            },
+        ),
+        Some(vec!["L", "Ha", "OIII"]),
+        None,
+    );
+    //when
+    let res = filter_wheel.names().await;
+    //then
+    assert_eq!(res.unwrap(), vec!["L", "Ha", "OIII"]);
+}
+
+#[tokio::test]
+async fn supported_actions() {
+    //given
+    let filter_wheel = new_filter_wheel(MockFilterWheel::new(), MockFilterWheelType::Untouched);
+    //when
+    let res = filter_wheel.supported_actions().await;
+    //then
+    assert_eq!(res.unwrap(), vec!["FilterNames", "FocusOffsets"]);
+}
+
+#[rstest]
+#[case("FilterNames", "", 1, Ok(r#"["Filter0","Filter1","Filter2"]"#))]
+#[case("FilterNames", r#"["L","Ha","OIII"]"#, 2, Ok(r#"["L","Ha","OIII"]"#))]
+#[case(
+    "FilterNames",
+    r#"["L","Ha"]"#,
+    1,
+    Err(ASCOMError::invalid_value(
+        "2 filter names configured, but the filter wheel has 3 filters"
+    ))
+)]
+#[case(
+    "FilterNames",
+    "L,Ha,OIII",
+    0,
+    Err(ASCOMError::invalid_value("FilterNames parameter must be a JSON array of strings"))
+)]
+#[case("FocusOffsets", "", 1, Ok("[0,0,0]"))]
+#[case("focusoffsets", "[0, 12, -40]", 2, Ok("[0,12,-40]"))]
+#[case(
+    "FocusOffsets",
+    "[0.5, 1, 2]",
+    0,
+    Err(ASCOMError::invalid_value("FocusOffsets parameter must be a JSON array of integers"))
+)]
+#[tokio::test]
+async fn filter_wheel_actions(
+    #[case] action: &str,
+    #[case] parameters: &str,
+    #[case] is_open_times: usize,
+    #[case] expected: ASCOMResult<&str>,
+) {
+    //given
+    let mock = MockFilterWheel::new();
+    let filter_wheel = new_filter_wheel(
+        mock,
+        MockFilterWheelType::WithFilters {
+            times: is_open_times,
+            filters: 3,
+        },
+    );
+    //when
+    let res = filter_wheel
+        .action(action.to_owned(), parameters.to_owned())
+        .await;
+    //then
+    match expected {
+        Ok(expected) => assert_eq!(res.unwrap(), expected),
+        Err(expected) => assert_eq!(res.unwrap_err().to_string(), expected.to_string()),
+    }
+}
+
+#[tokio::test]
+async fn filter_names_action_persisted() {
+    //given
+    let path = std::env::temp_dir().join("qhyccd-alpaca-filter-names.toml");
+    let _ = std::fs::remove_file(&path);
+    let mut filter_wheel = new_filter_wheel(
+        MockFilterWheel::new(),
+        MockFilterWheelType::WithFilters {
+            times: 2,
+            filters: 2,
+        },
+    );
+    filter_wheel.config = Arc::new(ConfigStore::load(path.clone()).unwrap());
+    //when
+    let res = filter_wheel
+        .action("FilterNames".to_owned(), r#"["Ha","SII"]"#.to_owned())
+        .await;
+    //then
+    assert!(res.is_ok());
+    let saved = ConfigStore::load(path.clone()).unwrap();
+    assert_eq!(
+        saved.filter_wheel("test-filter_wheel").await.names,
+        Some(vec!["Ha".to_owned(), "SII".to_owned()])
+    );
+    std::fs::remove_file(&path).unwrap();
+}
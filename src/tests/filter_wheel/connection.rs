@@ -142,6 +142,45 @@ async fn set_connected_fail_get_fw_position() {
     );
 }
 
+#[rstest]
+#[case(
+    Some(vec!["L", "R", "G"]),
+    None,
+    "3 filter names configured, but the filter wheel has 7 filters"
+)]
+#[case(
+    None,
+    Some(vec![0, 10, 20, 30, 40, 50, 60, 70]),
+    "8 focus offsets configured, but the filter wheel has 7 filters"
+)]
+#[tokio::test]
+async fn set_connected_fail_config_mismatch(
+    #[case] names: Option<Vec<&str>>,
+    #[case] focus_offsets: Option<Vec<i32>>,
+    #[case] expected: &str,
+) {
+    //given
+    let mut mock = MockFilterWheel::new();
+    mock.expect_open().once().returning(|| Ok(()));
+    mock.expect_get_number_of_filters()
+        .once()
+        .returning(|| Ok(7));
+    mock.expect_close().once().returning(|| Ok(()));
+    let filter_wheel = with_config(
+        new_filter_wheel(mock, MockFilterWheelType::IsOpenFalse { times: 1 }),
+        names,
+        focus_offsets,
+    );
+    //when
+    let res = filter_wheel.set_connected(true).await;
+    //then
+    assert_eq!(
+        res.err().unwrap().to_string(),
+        ASCOMError::invalid_value(expected).to_string()
+    );
+    assert!(filter_wheel.number_of_filters.read().await.is_none());
+}
+
 #[tokio::test]
 async fn set_connected_false_success() {
     //given
@@ -3,8 +3,10 @@
 use crate::mocks::MockFilterWheel;
 use crate::*;
 use eyre::eyre;
+use rstest::*;
 
 // Test modules
+pub mod camera_port;
 pub mod configuration;
 pub mod connection;
 pub mod position;
@@ -46,6 +48,29 @@ macro_rules! not_connected_fw {
     };
 }
 
+/// Stores `names` and `focus_offsets` as the configuration of the test filter wheel
+pub fn with_config(
+    mut filter_wheel: QhyccdFilterWheel,
+    names: Option<Vec<&str>>,
+    focus_offsets: Option<Vec<i32>>,
+) -> QhyccdFilterWheel {
+    filter_wheel.config = Arc::new(ConfigStore::new(
+        None,
+        crate::config::Config {
+            filter_wheels: [(
+                "test-filter_wheel".to_owned(),
+                FilterWheelConfig {
+                    names: names.map(|names| names.into_iter().map(str::to_owned).collect()),
+                    focus_offsets,
+                },
+            )]
+            .into(),
+            ..Default::default()
+        },
+    ));
+    filter_wheel
+}
+
 /// Creates a new QhyccdFilterWheel with the specified mock configuration
 pub fn new_filter_wheel(
     mut device: MockFilterWheel,
@@ -83,8 +108,11 @@ pub fn new_filter_wheel(
         unique_id: "test-filter_wheel".to_owned(),
         name: "QHYCCD-test_filter_wheel".to_owned(),
         description: "QHYCCD filter wheel".to_owned(),
-        device,
+        device: FilterWheelDevice::Sdk(device),
         number_of_filters,
         target_position,
+        config: Arc::new(ConfigStore::default()),
+        camera_state: None,
+        attached: AtomicBool::new(true),
     }
 }
@@ -0,0 +1,336 @@
+//! FITS export tests
+
+use std::time::{Duration, SystemTime};
+
+use crate::fits::{FitsMetadata, write, write_to};
+use crate::mocks::MockCamera;
+use crate::tests::camera::{MockCameraType, new_camera};
+use crate::*;
+use qhyccd_rs::Control;
+
+fn metadata() -> FitsMetadata {
+    FitsMetadata {
+        camera: "QHY600M".to_owned(),
+        frame_type: FrameType::Dark,
+        date_obs: SystemTime::UNIX_EPOCH + Duration::from_millis(1_700_000_000_123),
+        exposure_us: 1_500_000,
+        gain: Some(26_f64),
+        offset: Some(30_f64),
+        bin_x: 2,
+        bin_y: 2,
+        start_x: 10,
+        start_y: 20,
+        ccd_temperature: Some(-9.5_f64),
+        set_temperature: Some(-10_f64),
+        readout_mode: Some("High Gain Mode".to_owned()),
+        bayer_pattern: None,
+        pixel_width: 3.76_f64,
+        pixel_height: 3.76_f64,
+        overscan: None,
+        data_area: None,
+        data_max: None,
+    }
+}
+
+fn header_value<'a>(header: &'a str, keyword: &str) -> Option<&'a str> {
+    header
+        .as_bytes()
+        .chunks(80)
+        .map(|card| std::str::from_utf8(card).unwrap())
+        .find(|card| card[0..8].trim_end() == keyword)
+        .map(|card| card[10..].split(" / ").next().unwrap().trim())
+}
+
+#[test]
+fn path_from_template() {
+    //given
+    let export = FitsExport::new("/data")
+        .with_filename_template("{camera}-{frame_type}-{exposure}s-{date_obs}.fits");
+    //when
+    let path = export.path(&metadata());
+    //then
+    assert_eq!(
+        path,
+        std::path::PathBuf::from("/data/QHY600M-Dark-1.5s-2023-11-14T22-13-20.123.fits")
+    );
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn write_16bit() {
+    //given
+    let path = std::env::temp_dir().join("qhyccd-alpaca-write_16bit.fits");
+    let image = qhyccd_rs::ImageData {
+        data: [0_u16, 1, 32768, 65535]
+            .iter()
+            .flat_map(|value| value.to_ne_bytes())
+            .collect(),
+        width: 2,
+        height: 2,
+        bits_per_pixel: 16,
+        channels: 1,
+    };
+    //when
+    let res = write_to(&path, &metadata(), &image);
+    //then
+    assert!(res.is_ok());
+    let bytes = std::fs::read(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(bytes.len(), 2 * 2880);
+    let header = std::str::from_utf8(&bytes[0..2880]).unwrap();
+    assert!(header.starts_with("SIMPLE  =                    T"));
+    assert_eq!(header_value(header, "BITPIX"), Some("16"));
+    assert_eq!(header_value(header, "NAXIS1"), Some("2"));
+    assert_eq!(header_value(header, "NAXIS2"), Some("2"));
+    assert_eq!(header_value(header, "BZERO"), Some("32768"));
+    assert_eq!(header_value(header, "IMAGETYP"), Some("'Dark Frame'"));
+    assert_eq!(
+        header_value(header, "DATE-OBS"),
+        Some("'2023-11-14T22:13:20.123'")
+    );
+    assert_eq!(header_value(header, "EXPTIME"), Some("1.5"));
+    assert_eq!(header_value(header, "GAIN"), Some("26"));
+    assert_eq!(header_value(header, "OFFSET"), Some("30"));
+    assert_eq!(header_value(header, "XBINNING"), Some("2"));
+    assert_eq!(header_value(header, "XORGSUBF"), Some("10"));
+    assert_eq!(header_value(header, "YORGSUBF"), Some("20"));
+    assert_eq!(header_value(header, "CCD-TEMP"), Some("-9.5"));
+    assert_eq!(header_value(header, "SET-TEMP"), Some("-10"));
+    assert_eq!(header_value(header, "READOUTM"), Some("'High Gain Mode'"));
+    assert_eq!(header_value(header, "XPIXSZ"), Some("7.52"));
+    assert_eq!(header_value(header, "BAYERPAT"), None);
+    assert_eq!(header_value(header, "BIASSEC"), None);
+    assert_eq!(header_value(header, "DATAMAX"), None);
+    assert!(header.contains(&format!("{:<80}", "END")));
+    assert_eq!(
+        &bytes[2880..2888],
+        &[0x80, 0x00, 0x80, 0x01, 0x00, 0x00, 0x7f, 0xff]
+    );
+    assert!(bytes[2888..].iter().all(|byte| *byte == 0));
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn write_8bit_with_bayer_pattern_and_overscan() {
+    //given
+    let path = std::env::temp_dir().join("qhyccd-alpaca-write_8bit.fits");
+    let image = qhyccd_rs::ImageData {
+        data: vec![1, 2, 3, 4, 5, 6],
+        width: 3,
+        height: 2,
+        bits_per_pixel: 8,
+        channels: 1,
+    };
+    let metadata = FitsMetadata {
+        bayer_pattern: Some(qhyccd_rs::BayerMode::RGGB),
+        overscan: Some(CCDChipArea {
+            start_x: 2,
+            start_y: 0,
+            width: 1,
+            height: 2,
+        }),
+        data_area: Some(CCDChipArea {
+            start_x: 0,
+            start_y: 0,
+            width: 2,
+            height: 2,
+        }),
+        data_max: Some(255),
+        ..metadata()
+    };
+    //when
+    let res = write_to(&path, &metadata, &image);
+    //then
+    assert!(res.is_ok());
+    let bytes = std::fs::read(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    let header = std::str::from_utf8(&bytes[0..2880]).unwrap();
+    assert_eq!(header_value(header, "BITPIX"), Some("8"));
+    assert_eq!(header_value(header, "BZERO"), None);
+    assert_eq!(header_value(header, "BAYERPAT"), Some("'RGGB    '"));
+    assert_eq!(header_value(header, "BIASSEC"), Some("'[3:3,1:2]'"));
+    assert_eq!(header_value(header, "DATASEC"), Some("'[1:2,1:2]'"));
+    assert_eq!(header_value(header, "DATAMAX"), Some("255"));
+    assert_eq!(&bytes[2880..2886], &[1, 2, 3, 4, 5, 6]);
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn write_32bit_summed_frame() {
+    //given
+    let path = std::env::temp_dir().join("qhyccd-alpaca-write_32bit.fits");
+    let image = qhyccd_rs::ImageData {
+        data: [0_u32, 1, 65536, 589_815]
+            .iter()
+            .flat_map(|value| value.to_ne_bytes())
+            .collect(),
+        width: 2,
+        height: 2,
+        bits_per_pixel: 32,
+        channels: 1,
+    };
+    //when
+    let res = write_to(&path, &metadata(), &image);
+    //then
+    assert!(res.is_ok());
+    let bytes = std::fs::read(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    let header = std::str::from_utf8(&bytes[0..2880]).unwrap();
+    assert_eq!(header_value(header, "BITPIX"), Some("32"));
+    assert_eq!(header_value(header, "BZERO"), None);
+    assert_eq!(
+        &bytes[2880..2896],
+        &[0, 0, 0, 0, 0, 0, 0, 1, 0, 1, 0, 0, 0, 0x08, 0xff, 0xf7]
+    );
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn write_8bit_colour_planes() {
+    //given
+    let path = std::env::temp_dir().join("qhyccd-alpaca-write_colour.fits");
+    let image = qhyccd_rs::ImageData {
+        // two pixels, interleaved BGR
+        data: vec![1, 2, 3, 4, 5, 6],
+        width: 2,
+        height: 1,
+        bits_per_pixel: 8,
+        channels: 3,
+    };
+    //when
+    let res = write_to(&path, &metadata(), &image);
+    //then
+    assert!(res.is_ok());
+    let bytes = std::fs::read(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    let header = std::str::from_utf8(&bytes[0..2880]).unwrap();
+    assert_eq!(header_value(header, "BITPIX"), Some("8"));
+    assert_eq!(header_value(header, "NAXIS"), Some("3"));
+    assert_eq!(header_value(header, "NAXIS3"), Some("3"));
+    assert_eq!(&bytes[2880..2886], &[3, 6, 2, 5, 1, 4]);
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn write_creates_directory() {
+    //given
+    let directory = std::env::temp_dir().join("qhyccd-alpaca-write_creates_directory");
+    let _ = std::fs::remove_dir_all(&directory);
+    let export = FitsExport::new(directory.join("night"));
+    let image = qhyccd_rs::ImageData {
+        data: vec![1, 2, 3, 4],
+        width: 2,
+        height: 2,
+        bits_per_pixel: 8,
+        channels: 1,
+    };
+    //when
+    let res = write(&export, &metadata(), &image);
+    //then
+    let path = res.unwrap();
+    assert!(path.starts_with(directory.join("night")));
+    assert!(path.is_file());
+    std::fs::remove_dir_all(&directory).unwrap();
+}
+
+#[rstest::rstest]
+#[case(2, 16, 1, "unsupported number of channels")]
+#[case(1, 12, 1, "unsupported bits_per_pixel")]
+#[case(1, 16, 0, "does not match")]
+fn write_fail(
+    #[case] channels: u32,
+    #[case] bits_per_pixel: u32,
+    #[case] data_len: usize,
+    #[case] expected: &str,
+) {
+    //given
+    let path = std::env::temp_dir().join("qhyccd-alpaca-write_fail.fits");
+    let image = qhyccd_rs::ImageData {
+        data: vec![0; data_len],
+        width: 2,
+        height: 2,
+        bits_per_pixel,
+        channels,
+    };
+    //when
+    let res = write_to(&path, &metadata(), &image);
+    //then
+    assert!(res.unwrap_err().to_string().contains(expected));
+}
+
+#[tokio::test]
+async fn fits_metadata_from_camera() {
+    //given
+    let mut mock = MockCamera::new();
+    mock.expect_is_control_available()
+        .returning(|control| match control {
+            Control::Gain | Control::Offset | Control::Cooler | Control::CamIsColor => Some(0),
+            Control::CamColor => Some(qhyccd_rs::BayerMode::RGGB as u32),
+            _ => None,
+        });
+    mock.expect_get_parameter()
+        .returning(|control| match control {
+            Control::Gain => Ok(26_f64),
+            Control::Offset => Ok(30_f64),
+            Control::CurTemp => Ok(-9.5_f64),
+            _ => panic!("unexpected control"),
+        });
+    mock.expect_get_readout_mode().once().returning(|| Ok(1));
+    mock.expect_get_readout_mode_name()
+        .once()
+        .withf(|mode| *mode == 1)
+        .returning(|_| Ok("High Gain Mode".to_owned()));
+    let mut camera = new_camera(
+        mock,
+        MockCameraType::WithBinningAndRoiAndCCDInfo {
+            times: 0,
+            camera_roi: CCDChipArea {
+                start_x: 1,
+                start_y: 0,
+                width: 100,
+                height: 100,
+            },
+            camera_ccd_info: CCDChipInfo {
+                chip_width: 7_f64,
+                chip_height: 5_f64,
+                image_width: 1920,
+                image_height: 1080,
+                pixel_width: 2.9_f64,
+                pixel_height: 2.9_f64,
+                bits_per_pixel: 16,
+            },
+            camera_binning: 1_u8,
+        },
+    );
+    camera.unique_id = "QHY268C-abc123".to_owned();
+    camera.cooler.write().await.target = Some(-10_f64);
+    //when
+    let res = camera
+        .fits_metadata(FrameType::Light, SystemTime::UNIX_EPOCH, 2_000_000)
+        .await;
+    //then
+    assert_eq!(
+        res,
+        FitsMetadata {
+            camera: "QHY268C".to_owned(),
+            frame_type: FrameType::Light,
+            date_obs: SystemTime::UNIX_EPOCH,
+            exposure_us: 2_000_000,
+            gain: Some(26_f64),
+            offset: Some(30_f64),
+            bin_x: 1,
+            bin_y: 1,
+            start_x: 1,
+            start_y: 0,
+            ccd_temperature: Some(-9.5_f64),
+            set_temperature: Some(-10_f64),
+            readout_mode: Some("High Gain Mode".to_owned()),
+            bayer_pattern: Some(qhyccd_rs::BayerMode::GRBG),
+            pixel_width: 2.9_f64,
+            pixel_height: 2.9_f64,
+            overscan: None,
+            data_area: None,
+            data_max: None,
+        }
+    );
+}
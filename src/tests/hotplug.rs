@@ -0,0 +1,305 @@
+//! Tests for devices plugged in or unplugged while the server runs
+
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
+use std::time::{Duration, SystemTime};
+
+use ascom_alpaca::ASCOMError;
+use ascom_alpaca::api::Device;
+
+use crate::hotplug::{DeviceRegistry, watch};
+use crate::mocks::{MockCamera, MockFilterWheel, MockSdk};
+use crate::{ConfigStore, CoolerRamp, DarkFramePolicy, FilterWheelDevice, State};
+
+fn new_registry() -> DeviceRegistry {
+    DeviceRegistry::new(
+        Arc::new(ConfigStore::default()),
+        DarkFramePolicy::default(),
+        None,
+        CoolerRamp::default(),
+    )
+}
+
+fn camera(id: &str) -> MockCamera {
+    let mut camera = MockCamera::new();
+    camera.expect_id().return_const(id.to_owned());
+    camera.expect_clone().returning(camera_clone);
+    camera
+}
+
+/// The handle the registry keeps, and hands on to a wheel in its CFW port
+fn camera_clone() -> MockCamera {
+    let mut clone = MockCamera::new();
+    clone.expect_close().returning(|| Ok(()));
+    clone.expect_is_open().returning(|| Ok(false));
+    clone.expect_clone().returning(camera_clone);
+    clone
+}
+
+fn filter_wheel(id: &str) -> MockFilterWheel {
+    let mut filter_wheel = MockFilterWheel::new();
+    filter_wheel.expect_id().return_const(id.to_owned());
+    filter_wheel.expect_close().returning(|| Ok(()));
+    filter_wheel
+}
+
+/// An SDK reporting the cameras and filter wheels with these ids
+fn sdk(cameras: &[&'static str], filter_wheels: &[&'static str]) -> MockSdk {
+    let (cameras, filter_wheels) = (cameras.to_vec(), filter_wheels.to_vec());
+    let mut sdk = MockSdk::default();
+    sdk.expect_cameras().once().returning(move || {
+        Box::new(
+            cameras
+                .iter()
+                .copied()
+                .map(camera)
+                .collect::<Vec<_>>()
+                .into_iter(),
+        )
+    });
+    sdk.expect_filter_wheels().once().returning(move || {
+        Box::new(
+            filter_wheels
+                .iter()
+                .copied()
+                .map(filter_wheel)
+                .collect::<Vec<_>>()
+                .into_iter(),
+        )
+    });
+    sdk
+}
+
+fn camera_ids(registry: &DeviceRegistry) -> Vec<&str> {
+    registry
+        .cameras
+        .iter()
+        .map(|camera| camera.unique_id.as_str())
+        .collect()
+}
+
+fn filter_wheel_ids(registry: &DeviceRegistry) -> Vec<&str> {
+    registry
+        .filter_wheels
+        .iter()
+        .map(|(_, filter_wheel)| filter_wheel.unique_id.as_str())
+        .collect()
+}
+
+#[tokio::test]
+async fn rescan_adds_new_devices_after_known_ones() {
+    //given
+    let mut registry = new_registry();
+    assert!(registry.rescan(&sdk(&["QHY600M-a"], &["CFW3-w"])).await);
+    //when
+    let res = registry
+        .rescan(&sdk(&["QHY268C-b", "QHY600M-a"], &["CFW3-w"]))
+        .await;
+    //then
+    assert!(res);
+    assert_eq!(camera_ids(&registry), ["QHY600M-a", "QHY268C-b"]);
+    assert_eq!(filter_wheel_ids(&registry), ["CFW=CFW3-w"]);
+    let frames = registry.frames();
+    let frames = frames.read().await;
+    assert!(frames.contains_key("QHY600M-a"));
+    assert!(frames.contains_key("QHY268C-b"));
+}
+
+#[tokio::test]
+async fn rescan_without_changes() {
+    //given
+    let mut registry = new_registry();
+    registry.rescan(&sdk(&["QHY600M-a"], &["CFW3-w"])).await;
+    //when
+    let res = registry.rescan(&sdk(&["QHY600M-a"], &["CFW3-w"])).await;
+    //then
+    assert!(!res);
+    assert_eq!(camera_ids(&registry), ["QHY600M-a"]);
+    assert_eq!(filter_wheel_ids(&registry), ["CFW=CFW3-w"]);
+}
+
+#[tokio::test]
+async fn rescan_wheel_reported_with_camera() {
+    //given the SDK reports the wheel in the camera CFW port
+    let mut registry = new_registry();
+    //when
+    registry.rescan(&sdk(&["QHY600M-a"], &["QHY600M-a"])).await;
+    //then
+    let [(_, filter_wheel)] = registry.filter_wheels.as_slice() else {
+        panic!("expected one filter wheel");
+    };
+    assert!(matches!(filter_wheel.device, FilterWheelDevice::Sdk(_)));
+    assert!(filter_wheel.camera_state.is_some());
+}
+
+#[tokio::test]
+async fn rescan_adds_port_wheel_once_detected() {
+    //given
+    let mut registry = new_registry();
+    registry.rescan(&sdk(&["QHY600M-a"], &[])).await;
+    assert!(registry.filter_wheels.is_empty());
+    registry.cameras[0]
+        .cfw_detected
+        .store(true, Ordering::Relaxed);
+    //when
+    let res = registry.rescan(&sdk(&["QHY600M-a"], &[])).await;
+    //then
+    assert!(res);
+    let [(id, filter_wheel)] = registry.filter_wheels.as_slice() else {
+        panic!("expected one filter wheel");
+    };
+    assert_eq!(id, "QHY600M-a");
+    assert_eq!(filter_wheel.unique_id, "CFW=QHY600M-a");
+    assert!(matches!(
+        filter_wheel.device,
+        FilterWheelDevice::CameraPort { .. }
+    ));
+    assert!(filter_wheel.camera_state.is_some());
+    assert!(!registry.rescan(&sdk(&["QHY600M-a"], &[])).await);
+}
+
+#[tokio::test]
+async fn watch_adds_port_wheel_when_camera_finds_it() {
+    //given
+    let mut registry = new_registry();
+    registry.rescan(&sdk(&["QHY600M-a"], &[])).await;
+    registry.cameras[0]
+        .cfw_detected
+        .store(true, Ordering::Relaxed);
+    registry.cameras[0].cfw_found.notify_one();
+    //when
+    watch(&mut registry, None).await;
+    //then
+    assert_eq!(filter_wheel_ids(&registry), ["CFW=QHY600M-a"]);
+}
+
+/// A registry with a camera exposing
+async fn exposing_registry() -> DeviceRegistry {
+    let mut registry = new_registry();
+    registry.rescan(&sdk(&["QHY600M-a"], &[])).await;
+    *registry.cameras[0].state.write().await = State::Exposing {
+        start: SystemTime::UNIX_EPOCH,
+        expected_duration_us: 1_000_000,
+        stop_tx: None,
+        done_rx: tokio::sync::watch::channel(false).1,
+    };
+    registry
+}
+
+/// A registry with a connected filter wheel
+async fn connected_wheel_registry() -> DeviceRegistry {
+    let mut registry = new_registry();
+    let mut found = MockSdk::default();
+    found
+        .expect_cameras()
+        .once()
+        .returning(|| Box::new(Vec::new().into_iter()));
+    found.expect_filter_wheels().once().returning(|| {
+        let mut filter_wheel = filter_wheel("CFW3-w");
+        filter_wheel.expect_is_open().returning(|| Ok(true));
+        Box::new(vec![filter_wheel].into_iter())
+    });
+    registry.rescan(&found).await;
+    registry
+}
+
+#[tokio::test(start_paused = true)]
+async fn watch_skips_rescan_while_devices_in_use() {
+    // a single test, the expectation on the static `Sdk::new` is shared by all tests
+    let new_sdk = MockSdk::new_context();
+    new_sdk.expect().never();
+    for mut registry in [exposing_registry().await, connected_wheel_registry().await] {
+        //when
+        let res = tokio::time::timeout(
+            Duration::from_secs(10),
+            watch(&mut registry, Some(Duration::from_secs(1))),
+        )
+        .await;
+        //then the SDK was not initialised again
+        assert!(res.is_err());
+    }
+}
+
+#[tokio::test]
+async fn rescan_unplugged_camera_detaches_port_wheel() {
+    //given
+    let mut registry = new_registry();
+    registry.rescan(&sdk(&["QHY600M-a"], &[])).await;
+    registry.cameras[0]
+        .cfw_detected
+        .store(true, Ordering::Relaxed);
+    registry.rescan(&sdk(&["QHY600M-a"], &[])).await;
+    //when
+    let res = registry.rescan(&sdk(&[], &[])).await;
+    //then
+    assert!(!res);
+    assert!(!registry.filter_wheels[0].1.attached.load(Ordering::Relaxed));
+}
+
+#[tokio::test]
+async fn rescan_unplugged_camera() {
+    //given
+    let mut registry = new_registry();
+    registry.rescan(&sdk(&["QHY600M-a"], &["CFW3-w"])).await;
+    //when
+    let res = registry.rescan(&sdk(&[], &["CFW3-w"])).await;
+    //then
+    assert!(!res);
+    let camera = registry.cameras[0].clone();
+    assert!(!camera.attached.load(Ordering::Relaxed));
+    assert!(!camera.connected().await.unwrap());
+    let attached: Vec<bool> = registry
+        .filter_wheels
+        .iter()
+        .map(|(_, filter_wheel)| filter_wheel.attached.load(Ordering::Relaxed))
+        .collect();
+    assert_eq!(attached, [true]);
+}
+
+#[tokio::test]
+async fn rescan_camera_plugged_in_again() {
+    //given
+    let mut registry = new_registry();
+    registry.rescan(&sdk(&["QHY600M-a"], &[])).await;
+    registry.rescan(&sdk(&[], &[])).await;
+    //when
+    let res = registry.rescan(&sdk(&["QHY600M-a"], &[])).await;
+    //then
+    assert!(!res);
+    assert_eq!(camera_ids(&registry), ["QHY600M-a"]);
+    assert!(registry.cameras[0].attached.load(Ordering::Relaxed));
+}
+
+#[tokio::test]
+async fn rescan_unplugged_filter_wheel() {
+    //given
+    let mut registry = new_registry();
+    registry.rescan(&sdk(&[], &["CFW3-w"])).await;
+    //when
+    let res = registry.rescan(&sdk(&[], &[])).await;
+    //then
+    assert!(!res);
+    let filter_wheel = registry.filter_wheels[0].1.clone();
+    assert!(!filter_wheel.attached.load(Ordering::Relaxed));
+    assert!(!filter_wheel.connected().await.unwrap());
+}
+
+#[tokio::test]
+async fn set_connected_unplugged() {
+    //given
+    let mut registry = new_registry();
+    registry.rescan(&sdk(&["QHY600M-a"], &["CFW3-w"])).await;
+    registry.rescan(&sdk(&[], &[])).await;
+    //when
+    let camera = registry.cameras[0].set_connected(true).await;
+    let filter_wheel = registry.filter_wheels[0].1.set_connected(true).await;
+    //then
+    assert_eq!(
+        camera.unwrap_err().to_string(),
+        ASCOMError::NOT_CONNECTED.to_string()
+    );
+    assert_eq!(
+        filter_wheel.unwrap_err().to_string(),
+        ASCOMError::NOT_CONNECTED.to_string()
+    );
+}
@@ -0,0 +1,166 @@
+//! ImageBytes encoding tests
+
+use crate::image::*;
+
+fn header(bytes: &[u8]) -> Vec<i32> {
+    bytes[..HEADER_LEN]
+        .chunks_exact(4)
+        .map(|value| i32::from_le_bytes([value[0], value[1], value[2], value[3]]))
+        .collect()
+}
+
+#[test]
+fn image_bytes_8bpp() {
+    //given
+    let image = qhyccd_rs::ImageData {
+        data: vec![0, 1, 2, 3, 4, 5],
+        width: 3,
+        height: 2,
+        bits_per_pixel: 8,
+        channels: 1,
+    };
+    //when
+    let res = image_bytes(&image, 7, 8);
+    //then
+    let bytes = res.unwrap();
+    assert_eq!(header(&bytes), vec![1, 0, 7, 8, 44, 2, 6, 2, 3, 2, 0]);
+    assert_eq!(&bytes[HEADER_LEN..], &[0, 3, 1, 4, 2, 5]);
+}
+
+#[test]
+fn image_bytes_16bpp() {
+    //given
+    let image = qhyccd_rs::ImageData {
+        data: [0_u16, 1, 2, 3, 4, 0xABCD]
+            .iter()
+            .flat_map(|value| value.to_ne_bytes())
+            .collect(),
+        width: 3,
+        height: 2,
+        bits_per_pixel: 16,
+        channels: 1,
+    };
+    //when
+    let res = image_bytes(&image, 0, 1);
+    //then
+    let bytes = res.unwrap();
+    assert_eq!(header(&bytes), vec![1, 0, 0, 1, 44, 2, 8, 2, 3, 2, 0]);
+    let pixels: Vec<u16> = bytes[HEADER_LEN..]
+        .chunks_exact(2)
+        .map(|value| u16::from_le_bytes([value[0], value[1]]))
+        .collect();
+    assert_eq!(pixels, vec![0, 3, 1, 4, 2, 0xABCD]);
+}
+
+#[test]
+fn image_bytes_rgb() {
+    //given BGR samples of a 2 x 1 frame
+    let image = qhyccd_rs::ImageData {
+        data: vec![1, 2, 3, 4, 5, 6],
+        width: 2,
+        height: 1,
+        bits_per_pixel: 8,
+        channels: 3,
+    };
+    //when
+    let res = image_bytes(&image, 0, 0);
+    //then
+    let bytes = res.unwrap();
+    assert_eq!(header(&bytes), vec![1, 0, 0, 0, 44, 2, 6, 3, 2, 1, 3]);
+    assert_eq!(&bytes[HEADER_LEN..], &[3, 2, 1, 6, 5, 4]);
+}
+
+#[test]
+fn image_bytes_32bpp() {
+    //given
+    let image = qhyccd_rs::ImageData {
+        data: [1_i32, 100_000]
+            .iter()
+            .flat_map(|value| value.to_ne_bytes())
+            .collect(),
+        width: 1,
+        height: 2,
+        bits_per_pixel: 32,
+        channels: 1,
+    };
+    //when
+    let res = image_bytes(&image, 0, 0);
+    //then
+    let bytes = res.unwrap();
+    assert_eq!(header(&bytes), vec![1, 0, 0, 0, 44, 2, 2, 2, 1, 2, 0]);
+    assert_eq!(&bytes[HEADER_LEN..HEADER_LEN + 4], &1_i32.to_le_bytes());
+    assert_eq!(&bytes[HEADER_LEN + 4..], &100_000_i32.to_le_bytes());
+}
+
+#[test]
+fn image_bytes_ignores_padding() {
+    //given
+    let image = qhyccd_rs::ImageData {
+        data: vec![1, 2, 3, 4, 9, 9],
+        width: 2,
+        height: 2,
+        bits_per_pixel: 8,
+        channels: 1,
+    };
+    //when
+    let res = image_bytes(&image, 0, 0);
+    //then
+    assert_eq!(&res.unwrap()[HEADER_LEN..], &[1, 3, 2, 4]);
+}
+
+#[rstest::rstest]
+#[case(2, 16, 16, "unsupported number of channels")]
+#[case(1, 24, 12, "unsupported bits_per_pixel")]
+#[case(1, 16, 7, "does not match")]
+fn image_bytes_fail(
+    #[case] channels: u32,
+    #[case] bits_per_pixel: u32,
+    #[case] data_len: usize,
+    #[case] expected: &str,
+) {
+    //given
+    let image = qhyccd_rs::ImageData {
+        data: vec![0; data_len],
+        width: 2,
+        height: 2,
+        bits_per_pixel,
+        channels,
+    };
+    //when
+    let res = image_bytes(&image, 0, 0);
+    //then
+    assert!(res.unwrap_err().to_string().contains(expected));
+}
+
+#[rstest::rstest]
+#[case::twelve_bits(16, 12, vec![0x0010, 0xfff0, 0x8000], vec![0x0001, 0x0fff, 0x0800])]
+#[case::fourteen_bits(16, 14, vec![0x0004, 0xfffc], vec![0x0001, 0x3fff])]
+#[case::full_range(16, 16, vec![0x0001, 0xffff], vec![0x0001, 0xffff])]
+#[case::unknown_depth(16, 0, vec![0x0010], vec![0x0010])]
+#[case::eight_bits(8, 6, vec![0x0404], vec![0x0404])]
+fn to_native_shifts_padded_samples(
+    #[case] bits_per_pixel: u32,
+    #[case] actual_bits: u32,
+    #[case] samples: Vec<u16>,
+    #[case] expected: Vec<u16>,
+) {
+    //given
+    let bytes = |samples: &[u16]| -> Vec<u8> {
+        samples
+            .iter()
+            .flat_map(|sample| sample.to_ne_bytes())
+            .collect()
+    };
+    let mut image = qhyccd_rs::ImageData {
+        data: bytes(&samples),
+        width: samples.len() as u32,
+        height: 1,
+        bits_per_pixel,
+        channels: 1,
+    };
+    //when
+    to_native(&mut image, actual_bits);
+    //then
+    assert_eq!(image.data, bytes(&expected));
+    assert_eq!(image.bits_per_pixel, bits_per_pixel);
+}
@@ -0,0 +1,114 @@
+//! Live feed tests
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use eyre::eyre;
+
+use qhyccd_rs::Control;
+
+use crate::config::ConfigStore;
+use crate::live::*;
+use crate::mocks::MockCamera;
+use crate::tuning::{ControlRange, UsbBackoff};
+
+fn frame(value: u8) -> qhyccd_rs::ImageData {
+    qhyccd_rs::ImageData {
+        data: vec![value; 4],
+        width: 2,
+        height: 2,
+        bits_per_pixel: 8,
+        channels: 1,
+    }
+}
+
+#[tokio::test]
+async fn push_drops_oldest_frame() {
+    //given
+    let feed = LiveFeed::default();
+    //when
+    for value in 0..RING_SIZE + 2 {
+        feed.push(frame(value as u8)).await;
+    }
+    //then
+    assert_eq!(feed.len().await, RING_SIZE);
+    assert_eq!(feed.sequence(), (RING_SIZE + 2) as u64);
+    let latest = feed.latest().await.unwrap();
+    assert_eq!(latest.sequence, (RING_SIZE + 2) as u64);
+    assert_eq!(latest.image.data, vec![(RING_SIZE + 1) as u8; 4]);
+}
+
+#[tokio::test]
+async fn clear_keeps_sequence() {
+    //given
+    let feed = LiveFeed::default();
+    feed.push(frame(1)).await;
+    //when
+    feed.clear().await;
+    //then
+    assert!(feed.latest().await.is_none());
+    assert_eq!(feed.push(frame(2)).await, 2);
+}
+
+#[tokio::test(start_paused = true)]
+async fn capture_gives_up_without_frames() {
+    //given
+    let mut camera = MockCamera::new();
+    camera
+        .expect_get_live_frame()
+        .returning(|_| Err(eyre!("no frame")));
+    let feed = Arc::new(LiveFeed::default());
+    //when
+    let session = start(camera, feed.clone(), 4, None, None);
+    let stopped = tokio::time::timeout(FRAME_TIMEOUT * 2, async {
+        while session.is_running() {
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        }
+    })
+    .await;
+    //then
+    assert!(stopped.is_ok());
+    assert_eq!(feed.sequence(), 0);
+    session.stop().await;
+}
+
+#[tokio::test(start_paused = true)]
+async fn capture_backs_off_usb_traffic_without_frames() {
+    //given
+    let mut camera = MockCamera::new();
+    camera
+        .expect_get_live_frame()
+        .returning(|_| Err(eyre!("no frame")));
+    camera
+        .expect_get_parameter()
+        .times(1..)
+        .withf(|control| *control == Control::UsbTraffic)
+        .returning(|_| Ok(30_f64));
+    camera
+        .expect_set_parameter()
+        .times(1..)
+        .withf(|control, value| {
+            *control == Control::UsbTraffic && (*value - 40_f64).abs() < f64::EPSILON
+        })
+        .returning(|_, _| Ok(()));
+    let config = Arc::new(ConfigStore::default());
+    let usb_backoff = UsbBackoff {
+        config: config.clone(),
+        unique_id: "test-camera".to_owned(),
+        by: 10_f64,
+        range: ControlRange {
+            min: 0_f64,
+            max: 255_f64,
+            step: 1_f64,
+        },
+    };
+    let feed = Arc::new(LiveFeed::default());
+    //when
+    let session = start(camera, feed, 4, None, Some(usb_backoff));
+    while session.is_running() {
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    }
+    //then
+    assert_eq!(config.camera("test-camera").await.usb_traffic, Some(40_f64));
+    session.stop().await;
+}
@@ -1,5 +1,17 @@
 //! Test modules for qhyccd-alpaca
 
+pub mod binning;
 pub mod camera;
+pub mod config;
+pub mod cooler;
+pub mod debayer;
+pub mod endpoint;
 pub mod filter_wheel;
+pub mod fits;
+pub mod hotplug;
+pub mod image;
+pub mod live;
+pub mod overscan;
+pub mod sensor;
 pub mod server;
+pub mod tuning;
@@ -0,0 +1,107 @@
+//! Overscan geometry tests
+
+use qhyccd_rs::{CCDChipArea, CCDChipInfo};
+use rstest::*;
+
+use crate::binning::Binning;
+use crate::overscan::*;
+
+fn area(start_x: u32, start_y: u32, width: u32, height: u32) -> CCDChipArea {
+    CCDChipArea {
+        start_x,
+        start_y,
+        width,
+        height,
+    }
+}
+
+/// A QHY268 like chip with 24 overscan columns on the left.
+fn ccd_info() -> CCDChipInfo {
+    CCDChipInfo {
+        chip_width: 23.5_f64,
+        chip_height: 15.7_f64,
+        image_width: 6304,
+        image_height: 4210,
+        pixel_width: 3.76_f64,
+        pixel_height: 3.76_f64,
+        bits_per_pixel: 16,
+    }
+}
+
+#[rstest]
+#[case(false, area(24, 0, 6280, 4210))]
+#[case(true, area(0, 0, 6304, 4210))]
+fn full_frame_with_and_without_overscan(
+    #[case] include_overscan: bool,
+    #[case] expected: CCDChipArea,
+) {
+    assert_eq!(
+        full_frame(include_overscan, area(24, 0, 6280, 4210), Some(ccd_info())),
+        expected
+    );
+    assert_eq!(
+        full_frame(include_overscan, area(24, 0, 6280, 4210), None),
+        area(24, 0, 6280, 4210)
+    );
+}
+
+#[rstest]
+#[case(area(24, 0, 6280, 4210), 1, area(24, 0, 6280, 4210))]
+#[case(area(24, 0, 6280, 4210), 2, area(12, 0, 3140, 2105))]
+#[case(area(24, 0, 6280, 4210), 5, area(5, 0, 1255, 842))]
+#[case(area(0, 0, 24, 4210), 4, area(0, 0, 6, 1052))]
+fn binned_areas(#[case] chip_area: CCDChipArea, #[case] bin: u8, #[case] expected: CCDChipArea) {
+    assert_eq!(binned(chip_area, Binning::symmetric(bin)), expected);
+}
+
+#[test]
+fn binned_areas_asymmetric() {
+    assert_eq!(
+        binned(area(24, 0, 6280, 4210), Binning { x: 1, y: 4 }),
+        area(24, 0, 6280, 1052)
+    );
+    assert_eq!(
+        in_frame(
+            area(0, 0, 24, 4210),
+            area(0, 0, 6304, 1052),
+            Binning { x: 1, y: 4 }
+        ),
+        Some(area(0, 0, 24, 1052))
+    );
+}
+
+#[rstest]
+#[case(area(0, 0, 6304, 4210), 1, Some(area(0, 0, 24, 4210)))]
+#[case(area(0, 0, 3152, 2105), 2, Some(area(0, 0, 12, 2105)))]
+#[case(area(10, 100, 50, 50), 1, Some(area(0, 0, 14, 50)))]
+#[case(area(24, 0, 6280, 4210), 1, None)]
+fn overscan_in_frame(
+    #[case] roi: CCDChipArea,
+    #[case] bin: u8,
+    #[case] expected: Option<CCDChipArea>,
+) {
+    assert_eq!(
+        in_frame(area(0, 0, 24, 4210), roi, Binning::symmetric(bin)),
+        expected
+    );
+}
+
+#[rstest]
+#[case(area(24, 0, 6280, 4210), true)]
+#[case(area(100, 100, 10, 10), true)]
+#[case(area(0, 0, 6280, 4210), false)]
+#[case(area(24, 0, 6281, 4210), false)]
+fn contains_roi(#[case] roi: CCDChipArea, #[case] expected: bool) {
+    assert_eq!(contains(area(24, 0, 6280, 4210), roi), expected);
+}
+
+#[test]
+fn areas_as_json_and_fits_section() {
+    assert_eq!(
+        to_json(Some(area(0, 0, 24, 4210))),
+        serde_json::json!({"start_x": 0, "start_y": 0, "width": 24, "height": 4210})
+    );
+    assert_eq!(to_json(None), serde_json::Value::Null);
+    assert_eq!(fits_section(area(0, 0, 24, 4210)), "[1:24,1:4210]");
+    assert_eq!(fits_section(area(24, 0, 6280, 4210)), "[25:6304,1:4210]");
+}
@@ -0,0 +1,86 @@
+//! Sensor characteristics table tests
+
+use std::collections::BTreeMap;
+
+use crate::sensor::*;
+use rstest::*;
+
+fn curve(readout_mode: Option<u32>, points: &[(f64, f64, f64)]) -> SensorCurve {
+    SensorCurve {
+        readout_mode,
+        points: points
+            .iter()
+            .map(
+                |&(gain, electrons_per_adu, full_well_capacity)| SensorPoint {
+                    gain,
+                    electrons_per_adu,
+                    full_well_capacity,
+                },
+            )
+            .collect(),
+    }
+}
+
+#[rstest]
+#[case("QHY600M", true)]
+#[case("QHY600PH", true)]
+#[case("QHY5III290C", true)]
+#[case("QHY5III178M", false)]
+#[case("QHY42PRO", false)]
+fn builtin_curves(#[case] model: &str, #[case] known: bool) {
+    assert_eq!(curves(model, &BTreeMap::new()).is_some(), known);
+}
+
+#[test]
+fn overrides_use_longest_prefix() {
+    //given
+    let overrides = BTreeMap::from([
+        (
+            "QHY".to_owned(),
+            vec![curve(None, &[(0_f64, 1_f64, 1_f64)])],
+        ),
+        (
+            "QHY600M".to_owned(),
+            vec![curve(None, &[(0_f64, 2_f64, 2_f64)])],
+        ),
+    ]);
+    //when
+    let qhy600 = curves("QHY600M", &overrides).unwrap();
+    let qhy42 = curves("QHY42PRO", &overrides).unwrap();
+    //then
+    assert!((qhy600[0].points[0].electrons_per_adu - 2_f64).abs() < f64::EPSILON);
+    assert!((qhy42[0].points[0].electrons_per_adu - 1_f64).abs() < f64::EPSILON);
+}
+
+#[rstest]
+#[case(Some(1), 5_f64, Some((1.5_f64, 150_f64)))]
+#[case(Some(1), -5_f64, Some((2_f64, 200_f64)))]
+#[case(Some(1), 50_f64, Some((1_f64, 100_f64)))]
+#[case(Some(2), 5_f64, Some((5_f64, 500_f64)))]
+#[case(None, 5_f64, Some((5_f64, 500_f64)))]
+fn interpolate_gain_and_readout_mode(
+    #[case] readout_mode: Option<u32>,
+    #[case] gain: f64,
+    #[case] expected: Option<(f64, f64)>,
+) {
+    //given
+    let curves = vec![
+        curve(None, &[(0_f64, 5_f64, 500_f64)]),
+        curve(
+            Some(1),
+            &[(10_f64, 1_f64, 100_f64), (0_f64, 2_f64, 200_f64)],
+        ),
+    ];
+    //when
+    let res = interpolate(&curves, readout_mode, gain)
+        .map(|point| (point.electrons_per_adu, point.full_well_capacity));
+    //then
+    assert_eq!(res, expected);
+}
+
+#[test]
+fn interpolate_without_matching_curve() {
+    let curves = vec![curve(Some(0), &[(0_f64, 1_f64, 100_f64)])];
+    assert!(interpolate(&curves, Some(1), 0_f64).is_none());
+    assert!(interpolate(&[curve(None, &[])], None, 0_f64).is_none());
+}
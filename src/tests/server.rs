@@ -1,19 +1,22 @@
 //! ServerBuilder tests
 
-use crate::ServerBuilder;
 use crate::mocks::{MockCamera, MockFilterWheel, MockSdk};
+use crate::{DEFAULT_WARM_UP_RATE, ServerBuilder};
 use eyre::eyre;
 
 #[tokio::test]
 async fn server_builder_default() {
     let builder = ServerBuilder::default();
     assert_eq!(builder.port, 0);
+    assert_eq!(builder.cooler_ramp.cool_down, None);
+    assert_eq!(builder.cooler_ramp.warm_up, Some(DEFAULT_WARM_UP_RATE));
 }
 
 #[tokio::test]
 async fn server_builder_new() {
     let builder = ServerBuilder::new();
     assert_eq!(builder.port, 0);
+    assert_eq!(builder.cooler_ramp.warm_up, Some(DEFAULT_WARM_UP_RATE));
 }
 
 #[tokio::test]
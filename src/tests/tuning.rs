@@ -0,0 +1,32 @@
+//! Tuning control range tests
+
+use rstest::*;
+
+use crate::tuning::*;
+
+fn range(min: f64, max: f64, step: f64) -> ControlRange {
+    ControlRange { min, max, step }
+}
+
+#[rstest]
+#[case::min(range(0_f64, 255_f64, 1_f64), 0_f64, true)]
+#[case::max(range(0_f64, 255_f64, 1_f64), 255_f64, true)]
+#[case::below(range(0_f64, 255_f64, 1_f64), -1_f64, false)]
+#[case::above(range(0_f64, 255_f64, 1_f64), 256_f64, false)]
+#[case::off_step(range(0_f64, 255_f64, 1_f64), 12.5_f64, false)]
+#[case::on_step(range(10_f64, 60_f64, 5_f64), 35_f64, true)]
+#[case::between_steps(range(10_f64, 60_f64, 5_f64), 32_f64, false)]
+#[case::fractional_step(range(0_f64, 1_f64, 0.1_f64), 0.3_f64, true)]
+#[case::no_step(range(0_f64, 2_f64, 0_f64), 1.5_f64, true)]
+fn contains(#[case] range: ControlRange, #[case] value: f64, #[case] expected: bool) {
+    assert_eq!(range.contains(value), expected);
+}
+
+#[rstest]
+#[case::raised(range(0_f64, 255_f64, 1_f64), 30_f64, 10_f64, 40_f64)]
+#[case::capped(range(0_f64, 255_f64, 1_f64), 250_f64, 10_f64, 255_f64)]
+#[case::on_step(range(0_f64, 100_f64, 5_f64), 30_f64, 7_f64, 35_f64)]
+#[case::capped_on_step(range(0_f64, 98_f64, 5_f64), 90_f64, 10_f64, 95_f64)]
+fn raise(#[case] range: ControlRange, #[case] value: f64, #[case] by: f64, #[case] expected: f64) {
+    assert!((range.raise(value, by) - expected).abs() < 1e-9);
+}
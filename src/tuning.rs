@@ -0,0 +1,147 @@
+//! USB traffic, DDR buffer and readout speed
+//!
+//! Cameras on marginal USB hubs drop or corrupt frames unless the transfer is tuned. The ranges
+//! of these controls are read on connect, the readout speed again with every readout mode. Each
+//! one is set through an action, validated against its min, max and step and stored per
+//! camera. The actions take a number, or nothing to query, and answer with the range:
+//!
+//! ```text
+//! UsbTraffic   {"value": 30.0, "min": 0.0, "max": 255.0, "step": 1.0}
+//! DdrBuffer    {"value": 1.0, "min": 0.0, "max": 1.0, "step": 1.0}
+//! ReadoutSpeed {"value": 2.0, "min": 0.0, "max": 2.0, "step": 1.0}
+//! ```
+//!
+//! With `usb_traffic_backoff` a failed frame raises the USB traffic by that amount, up to its
+//! maximum, and stores the new value. This applies to single frames, aborted ones included, and
+//! to live mode, where every [`crate::live`] warning about missing frames counts as a failure:
+//!
+//! ```toml
+//! [cameras.QHY600M-abc123]
+//! usb_traffic = 30
+//! ddr_buffer = 1
+//! readout_speed = 2
+//! usb_traffic_backoff = 10
+//! ```
+use std::sync::Arc;
+
+use qhyccd_rs::Control;
+use tracing::{debug, error, warn};
+
+use crate::QhyCamera;
+use crate::config::ConfigStore;
+
+/// A control tuning the readout and transfer of frames.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Tuning {
+    /// USB bandwidth, higher values transfer more slowly
+    UsbTraffic,
+    /// buffering of frames in the camera memory
+    DdrBuffer,
+    /// readout speed of the sensor
+    ReadoutSpeed,
+}
+
+impl Tuning {
+    pub(crate) const ALL: [Tuning; 3] =
+        [Tuning::UsbTraffic, Tuning::DdrBuffer, Tuning::ReadoutSpeed];
+
+    pub(crate) fn control(self) -> Control {
+        match self {
+            Tuning::UsbTraffic => Control::UsbTraffic,
+            Tuning::DdrBuffer => Control::DDR,
+            Tuning::ReadoutSpeed => Control::Speed,
+        }
+    }
+
+    /// The Alpaca action querying and setting this control.
+    pub(crate) fn action(self) -> &'static str {
+        match self {
+            Tuning::UsbTraffic => "UsbTraffic",
+            Tuning::DdrBuffer => "DdrBuffer",
+            Tuning::ReadoutSpeed => "ReadoutSpeed",
+        }
+    }
+}
+
+/// The values a control accepts, as reported by the SDK.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct ControlRange {
+    pub(crate) min: f64,
+    pub(crate) max: f64,
+    pub(crate) step: f64,
+}
+
+impl From<(f64, f64, f64)> for ControlRange {
+    fn from((min, max, step): (f64, f64, f64)) -> Self {
+        Self { min, max, step }
+    }
+}
+
+impl ControlRange {
+    /// Whether `value` lies within the range, a whole number of steps above `min`.
+    pub(crate) fn contains(self, value: f64) -> bool {
+        if !(self.min..=self.max).contains(&value) {
+            return false;
+        }
+        if self.step <= 0_f64 {
+            return true;
+        }
+        let steps = (value - self.min) / self.step;
+        (steps - steps.round()).abs() < 1e-6
+    }
+
+    /// `value` raised by `by`, kept on a step and capped at `max`.
+    pub(crate) fn raise(self, value: f64, by: f64) -> f64 {
+        let raised = (value + by).clamp(self.min, self.max);
+        if self.step <= 0_f64 {
+            return raised;
+        }
+        self.min + ((raised - self.min) / self.step + 1e-6).floor() * self.step
+    }
+}
+
+/// Raises the USB traffic after failed frames, set up from `usb_traffic_backoff`.
+#[derive(Debug, Clone)]
+pub(crate) struct UsbBackoff {
+    pub(crate) config: Arc<ConfigStore>,
+    pub(crate) unique_id: String,
+    /// how much to raise the USB traffic by
+    pub(crate) by: f64,
+    pub(crate) range: ControlRange,
+}
+
+impl UsbBackoff {
+    /// Raises the USB traffic of `device` and stores the new value, slowing the transfer down
+    /// on hubs that cannot keep up.
+    pub(crate) async fn apply(&self, device: &QhyCamera) {
+        let current = match device.get_parameter(Control::UsbTraffic) {
+            Ok(current) => current,
+            Err(e) => {
+                error!(?e, "could not read the USB traffic to back off");
+                return;
+            }
+        };
+        let usb_traffic = self.range.raise(current, self.by);
+        if usb_traffic <= current {
+            debug!(current, "USB traffic already at its maximum");
+            return;
+        }
+        if let Err(e) = device.set_parameter(Control::UsbTraffic, usb_traffic) {
+            error!(?e, usb_traffic, "could not back off the USB traffic");
+            return;
+        }
+        warn!(
+            current,
+            usb_traffic, "USB traffic raised after a failed frame"
+        );
+        if let Err(e) = self
+            .config
+            .update_camera(&self.unique_id, |config| {
+                config.usb_traffic = Some(usb_traffic)
+            })
+            .await
+        {
+            error!(?e, "saving the backed off USB traffic failed");
+        }
+    }
+}